@@ -0,0 +1,71 @@
+use criterion::BenchmarkId;
+use criterion::Criterion;
+use criterion::Throughput;
+use criterion::{criterion_group, criterion_main};
+use preprocessing_mpsi_with_vole::channel_utils::{read_vec_f, read_vec_f_bulk, write_vec_f};
+use rand::Rng;
+use scuttlebutt::field::F128b;
+use scuttlebutt::{AesRng, Channel};
+use std::io::{BufReader, BufWriter};
+use std::os::unix::net::UnixStream;
+use std::time::{Duration, Instant};
+
+fn read_routine(v: &[F128b], bulk: bool) -> Duration {
+    let (sender, receiver) = UnixStream::pair().unwrap();
+    let w = v.to_vec();
+    let handle = std::thread::spawn(move || {
+        let mut channel = Channel::new(
+            BufReader::new(sender.try_clone().unwrap()),
+            BufWriter::new(sender),
+        );
+        write_vec_f(&mut channel, &w).unwrap();
+    });
+
+    let mut channel = Channel::new(
+        BufReader::new(receiver.try_clone().unwrap()),
+        BufWriter::new(receiver),
+    );
+
+    let start = Instant::now();
+    let res: Vec<F128b> = if bulk {
+        read_vec_f_bulk(&mut channel).unwrap()
+    } else {
+        read_vec_f(&mut channel).unwrap()
+    };
+    let d = start.elapsed();
+
+    handle.join().unwrap();
+    assert_eq!(res.len(), v.len());
+
+    d
+}
+
+fn bench_read_vec_f(c: &mut Criterion) {
+    let min_e = 10;
+    let max_e = 20;
+
+    let mut group = c.benchmark_group("read_vec_f_time");
+    for e in (min_e..=max_e).step_by(2) {
+        let size: usize = 1 << e;
+        let mut rng = AesRng::new();
+        let v = (0..size).map(|_| rng.gen::<F128b>()).collect::<Vec<_>>();
+
+        group.throughput(Throughput::Elements(size as u64));
+        group.bench_with_input(BenchmarkId::new("Elementwise", size), &v, |b, v| {
+            b.iter_custom(|iter| (0..iter).map(|_| read_routine(v, false)).sum());
+        });
+        group.bench_with_input(BenchmarkId::new("Bulk", size), &v, |b, v| {
+            b.iter_custom(|iter| (0..iter).map(|_| read_routine(v, true)).sum());
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    name = read_vec_benches;
+    config = Criterion::default().sample_size(10);
+    targets = bench_read_vec_f
+);
+criterion_main!(read_vec_benches);
+
+// cargo bench read_vec_f_time
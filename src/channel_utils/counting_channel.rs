@@ -0,0 +1,192 @@
+//! Channel wrapper counting the number of bytes sent and received, and the number of messages sent.
+//!
+//! [CountingChannel] implements [AbstractChannel], so it can wrap any channel of this library.
+//! A message is a flush after some bytes are written, so the count tells the round complexity of a protocol
+//! (channels of this library flush at the end of each protocol message).
+//! The counters are shared by clones of the channel and can be read from other threads (e.g. [metrics](crate::metrics)).
+//! A budget of bytes can be enforced live by [CountingChannel::with_budget].
+//!
+//! # Example
+//!
+//! ```
+//! use preprocessing_mpsi_with_vole::channel_utils::channel_from;
+//! use preprocessing_mpsi_with_vole::channel_utils::counting_channel::{ByteCounters, CountingChannel};
+//! use scuttlebutt::AbstractChannel;
+//! use std::os::unix::net::UnixStream;
+//! use std::sync::Arc;
+//! use anyhow::Result;
+//!
+//! # fn try_main() -> Result<()> {
+//! let (s, r) = UnixStream::pair()?;
+//!
+//! let counters = Arc::new(ByteCounters::default());
+//! let mut ch1 = CountingChannel::new(channel_from(s)?, Arc::clone(&counters));
+//! let mut ch2 = channel_from(r)?;
+//!
+//! ch1.write_u64(10)?;
+//! ch1.flush()?;
+//! assert_eq!(ch2.read_u64()?, 10);
+//!
+//! assert_eq!(counters.sent(), 8);
+//! assert_eq!(counters.received(), 0);
+//! assert_eq!(counters.message_count(), 1);
+//! # Ok(())
+//! # }
+//! # fn main() {
+//! #    try_main().unwrap();
+//! # }
+//! ```
+
+use crate::channel_utils::FinishChannel;
+use crate::error::PsiError;
+use scuttlebutt::AbstractChannel;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Numbers of bytes sent and received, and number of messages sent.
+#[derive(Debug, Default)]
+pub struct ByteCounters {
+    sent: AtomicU64,
+    received: AtomicU64,
+    messages: AtomicU64,
+    budget_exceeded: AtomicBool,
+}
+
+impl ByteCounters {
+    /// Number of bytes written so far.
+    pub fn sent(&self) -> u64 {
+        self.sent.load(Ordering::Relaxed)
+    }
+
+    /// Number of bytes read so far.
+    pub fn received(&self) -> u64 {
+        self.received.load(Ordering::Relaxed)
+    }
+
+    /// Number of messages sent so far, i.e. flushes with written bytes. Flushes without any written byte are not counted.
+    pub fn message_count(&self) -> u64 {
+        self.messages.load(Ordering::Relaxed)
+    }
+
+    /// Number of bytes written and read so far.
+    pub fn total(&self) -> u64 {
+        self.sent() + self.received()
+    }
+
+    /// Whether a channel sharing the counters refused bytes over its budget (See [CountingChannel::with_budget]).
+    pub fn budget_exceeded(&self) -> bool {
+        self.budget_exceeded.load(Ordering::Relaxed)
+    }
+}
+
+/// Channel counting bytes passed through `inner`.
+///
+/// Please look the parent document ( [crate::channel_utils::counting_channel] ) for usage example.
+pub struct CountingChannel<C: AbstractChannel> {
+    inner: C,
+    counters: Arc<ByteCounters>,
+    // whether bytes are written since the last flush.
+    pending: bool,
+    budget: Option<u64>,
+}
+
+impl<C: AbstractChannel> CountingChannel<C> {
+    /// Wrap `inner`. Bytes are added to `counters`.
+    pub fn new(inner: C, counters: Arc<ByteCounters>) -> Self {
+        Self {
+            inner,
+            counters,
+            pending: false,
+            budget: None,
+        }
+    }
+
+    /// Refuse to write or read bytes which would make [ByteCounters::total] exceed `budget`.
+    ///
+    /// The refused operation fails with an I/O error carrying [PsiError::CommBudgetExceeded], and nothing is passed to `inner`.
+    /// The budget is on the shared counters, so it bounds the bytes of all the channels sharing them.
+    pub fn with_budget(mut self, budget: u64) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    fn check_budget(&self, len: usize) -> std::io::Result<()> {
+        let Some(budget) = self.budget else {
+            return Ok(());
+        };
+        let used = self.counters.total();
+        if used + len as u64 > budget {
+            self.counters.budget_exceeded.store(true, Ordering::Relaxed);
+            return Err(std::io::Error::other(PsiError::CommBudgetExceeded {
+                used,
+                budget,
+            }));
+        }
+        Ok(())
+    }
+
+    /// Counters of this channel.
+    pub fn counters(&self) -> &Arc<ByteCounters> {
+        &self.counters
+    }
+
+    /// Number of messages sent by the channels sharing the counters. See [ByteCounters::message_count].
+    pub fn message_count(&self) -> u64 {
+        self.counters.message_count()
+    }
+
+    /// Unwrap the channel.
+    pub fn into_inner(self) -> C {
+        self.inner
+    }
+}
+
+impl<C: AbstractChannel> AbstractChannel for CountingChannel<C> {
+    #[inline(always)]
+    fn write_bytes(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        self.check_budget(bytes.len())?;
+        self.inner.write_bytes(bytes)?;
+        self.counters
+            .sent
+            .fetch_add(bytes.len() as u64, Ordering::Relaxed);
+        self.pending |= !bytes.is_empty();
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn read_bytes(&mut self, bytes: &mut [u8]) -> std::io::Result<()> {
+        self.check_budget(bytes.len())?;
+        self.inner.read_bytes(bytes)?;
+        self.counters
+            .received
+            .fetch_add(bytes.len() as u64, Ordering::Relaxed);
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()?;
+        if self.pending {
+            self.counters.messages.fetch_add(1, Ordering::Relaxed);
+            self.pending = false;
+        }
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            counters: Arc::clone(&self.counters),
+            pending: false,
+            budget: self.budget,
+        }
+    }
+}
+
+impl<C: FinishChannel> FinishChannel for CountingChannel<C> {
+    fn finish(&mut self) -> std::io::Result<()> {
+        self.flush()?;
+        self.inner.finish()
+    }
+}
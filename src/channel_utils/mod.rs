@@ -5,11 +5,10 @@
 //! Here, we show an example using channels. (Not much to do with this module.)
 //!
 //! ```
-//! use preprocessing_mpsi_with_vole::channel_utils::{write_vec_f, read_vec_f};
+//! use preprocessing_mpsi_with_vole::channel_utils::{channel_from, write_vec_f, read_vec_f};
 //! use preprocessing_mpsi_with_vole::set_utils::FromU128;
-//! use scuttlebutt::{Channel, AbstractChannel};
+//! use scuttlebutt::AbstractChannel;
 //! use scuttlebutt::field::F128b;
-//! use std::io::{BufReader, BufWriter};
 //! use std::os::unix::net::UnixStream;
 //! use anyhow::Result;
 //!
@@ -17,9 +16,7 @@
 //! let (sender, receiver) = UnixStream::pair().unwrap();
 //!
 //! let handle = std::thread::spawn(move || -> Result<()> {
-//!     let reader = BufReader::new(sender.try_clone().unwrap());
-//!     let writer = BufWriter::new(sender);
-//!     let mut channel = Channel::new(reader, writer);
+//!     let mut channel = channel_from(sender)?;
 //!
 //!     channel.write_u8(10)?;
 //!
@@ -29,9 +26,7 @@
 //!     Ok(())
 //! });
 //!
-//! let reader = BufReader::new(receiver.try_clone().unwrap());
-//! let writer = BufWriter::new(receiver);
-//! let mut channel = Channel::new(reader, writer);
+//! let mut channel = channel_from(receiver)?;
 //!
 //! let n = channel.read_u8()?;
 //!
@@ -51,15 +46,141 @@
 //!
 //! For more information, the document of [scuttlebutt::AbstractChannel] will help you.
 
-use anyhow::{Context, Result};
+use crate::error::PsiError;
+use anyhow::{bail, Context, Result};
+use generic_array::GenericArray;
 use scuttlebutt::field::FiniteField as FF;
-use scuttlebutt::AbstractChannel;
+use scuttlebutt::{AbstractChannel, Channel};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::os::unix::net::UnixStream;
 use std::sync::{Arc, Mutex};
 use typenum::marker_traits::Unsigned;
 
+pub mod counting_channel;
+pub mod multiplexed_channel;
 pub mod sync_channel;
 pub mod sync_channel_by_cb;
 pub mod tcp_channel;
+pub mod throttled_channel;
+pub mod transcript_channel;
+
+/// Stream which can be duplicated into a reading half and a writing half (e.g. [TcpStream], [UnixStream]).
+pub trait TryCloneStream: Sized {
+    /// Create another handle of the same stream.
+    fn try_clone_stream(&self) -> std::io::Result<Self>;
+}
+
+impl TryCloneStream for TcpStream {
+    fn try_clone_stream(&self) -> std::io::Result<Self> {
+        self.try_clone()
+    }
+}
+
+impl TryCloneStream for UnixStream {
+    fn try_clone_stream(&self) -> std::io::Result<Self> {
+        self.try_clone()
+    }
+}
+
+/// Create a buffered [Channel] from a stream. Both parties should use this so that buffering matches on the two sides.
+pub fn channel_from<RW>(stream: RW) -> Result<Channel<BufReader<RW>, BufWriter<RW>>>
+where
+    RW: Read + Write + TryCloneStream,
+{
+    let reader = stream
+        .try_clone_stream()
+        .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+    Ok(Channel::new(BufReader::new(reader), BufWriter::new(stream)))
+}
+
+/// Adapter making any bidirectional byte stream usable by [channel_from], for transports without a `try_clone`
+/// (e.g. a QUIC stream, a WebSocket bridge or a pipe pair wrapped in one type).
+///
+/// The handles share `stream` behind a mutex, so the reading and the writing half lock it in turn.
+/// It is enough for the protocols of this library, which use a channel from one thread at a time.
+/// For a transport which is already split into a reading and a writing half, use [Channel::new] with them directly instead.
+pub struct SharedStream<RW>(Arc<Mutex<RW>>);
+
+impl<RW> SharedStream<RW> {
+    /// Wrap `stream`.
+    pub fn new(stream: RW) -> Self {
+        Self(Arc::new(Mutex::new(stream)))
+    }
+}
+
+impl<RW: Read> Read for SharedStream<RW> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().read(buf)
+    }
+}
+
+impl<RW: Write> Write for SharedStream<RW> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.lock().unwrap().flush()
+    }
+}
+
+impl<RW> TryCloneStream for SharedStream<RW> {
+    fn try_clone_stream(&self) -> std::io::Result<Self> {
+        Ok(Self(Arc::clone(&self.0)))
+    }
+}
+
+/// Create a buffered [Channel] from any `Read + Write` stream through [SharedStream]. The peer may use [channel_from] or this.
+pub fn channel_from_stream<RW>(
+    stream: RW,
+) -> Result<Channel<BufReader<SharedStream<RW>>, BufWriter<SharedStream<RW>>>>
+where
+    RW: Read + Write,
+{
+    channel_from(SharedStream::new(stream))
+}
+
+/// Stream whose writing half can be closed while the reading half is kept (e.g. [TcpStream], [UnixStream]).
+pub trait ShutdownStream {
+    /// Close the writing half, so that the peer reads EOF.
+    fn shutdown_write(&self) -> std::io::Result<()>;
+}
+
+impl ShutdownStream for TcpStream {
+    fn shutdown_write(&self) -> std::io::Result<()> {
+        self.shutdown(Shutdown::Write)
+    }
+}
+
+impl ShutdownStream for UnixStream {
+    fn shutdown_write(&self) -> std::io::Result<()> {
+        self.shutdown(Shutdown::Write)
+    }
+}
+
+/// Channel which can be finished explicitly.
+///
+/// Dropping a channel made by [channel_from] flushes its buffer and closes the stream, but not while a clone of it is alive.
+/// [finish](FinishChannel::finish) closes the writing half at once, so the peer reliably reads either complete data or a clean EOF,
+/// e.g. when a protocol is aborted by an error on this side. The channel wrappers of this module forward it.
+pub trait FinishChannel: AbstractChannel {
+    /// Flush the pending writes and close the writing half. Reading is still possible, but writing after it fails.
+    fn finish(&mut self) -> std::io::Result<()>;
+}
+
+impl<RW> FinishChannel for Channel<BufReader<RW>, BufWriter<RW>>
+where
+    RW: Read + Write + ShutdownStream,
+{
+    fn finish(&mut self) -> std::io::Result<()> {
+        self.flush()?;
+        let writer = AbstractChannel::clone(self).writer();
+        let writer = writer.borrow();
+        writer.get_ref().shutdown_write()
+    }
+}
 
 /// Write a vector of field elements to a channel.
 pub fn write_vec_f<F, C>(channel: &mut C, v: &[F]) -> Result<usize>
@@ -89,31 +210,243 @@ where
     Ok(len)
 }
 
-/// Read a vector of field elements from a channel.
-pub fn read_vec_f<F, C>(channel: &mut C) -> Result<Vec<F>>
+// byte length of an element of `F` truncated to its lowest `bits` bits, and the mask of the last byte.
+fn truncated_len<F: FF>(bits: usize) -> Result<(usize, u8)> {
+    let f_len = F::ByteReprLen::to_usize();
+    if bits == 0 || bits > f_len * 8 {
+        bail!(
+            "bits (={}) must be in 1..={}. @{}:{}",
+            bits,
+            f_len * 8,
+            file!(),
+            line!()
+        );
+    }
+
+    let mask = match bits % 8 {
+        0 => u8::MAX,
+        rest => (1 << rest) - 1,
+    };
+    Ok((bits.div_ceil(8), mask))
+}
+
+/// Write a vector of field elements to a channel, each truncated to its lowest `bits` bits: the first $`\lceil bits / 8 \rceil`$ bytes
+/// of its canonical byte representation, with the bits above `bits` cleared. It is read by [read_vec_f_truncated] with the same `bits`.
+pub fn write_vec_f_truncated<F, C>(channel: &mut C, v: &[F], bits: usize) -> Result<usize>
+where
+    F: FF,
+    C: AbstractChannel,
+{
+    let (t_len, mask) =
+        truncated_len::<F>(bits).with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+    let mut bytes = Vec::with_capacity(v.len() * t_len);
+    for x in v {
+        bytes.extend_from_slice(&x.to_bytes()[..t_len]);
+        *bytes.last_mut().unwrap() &= mask;
+    }
+
+    let len = bytes.len();
+
+    channel
+        .write_usize(len)
+        .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+    channel
+        .write_bytes(&bytes)
+        .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+    channel
+        .flush()
+        .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+    Ok(len)
+}
+
+/// The number of elements buffered at once by [write_vec_f_mapped].
+pub const WRITE_CHUNK_LEN: usize = 1 << 12;
+
+/// Evaluate `f` over `inputs` and write the results to a channel without holding the full output vector.
+///
+/// The results are written every [WRITE_CHUNK_LEN] elements. The wire format is the same as [write_vec_f],
+/// so the results can be read by [read_vec_f] or [read_vec_f_bulk].
+pub fn write_vec_f_mapped<F, C, I, G>(channel: &mut C, inputs: I, f: G) -> Result<usize>
 where
     F: FF,
     C: AbstractChannel,
+    I: ExactSizeIterator<Item = F>,
+    G: Fn(F) -> Result<F>,
 {
+    let f_len = F::ByteReprLen::to_usize();
+    let len = inputs.len() * f_len;
+
+    channel
+        .write_usize(len)
+        .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+    let mut buf = Vec::with_capacity(WRITE_CHUNK_LEN.min(inputs.len()) * f_len);
+    for x in inputs {
+        let y = f(x).with_context(|| format!("@{}:{}", file!(), line!()))?;
+        buf.extend_from_slice(&y.to_bytes());
+
+        if buf.len() == WRITE_CHUNK_LEN * f_len {
+            channel
+                .write_bytes(&buf)
+                .with_context(|| format!("@{}:{}", file!(), line!()))?;
+            buf.clear();
+        }
+    }
+
+    channel
+        .write_bytes(&buf)
+        .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+    channel
+        .flush()
+        .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+    Ok(len)
+}
+
+/// Maximum number of elements [read_vec_f] and [read_vec_f_bulk] accept from a length prefix: $`2^{24}`$ elements
+/// (256 MiB of [F128b](scuttlebutt::field::F128b)), eight times the code vector of $`2^{21}`$ elements.
+///
+/// A longer vector is rejected before allocating its buffer, so that a malicious peer can't exhaust the memory by a huge length prefix.
+/// Use [read_vec_f_capped] for another cap of a call.
+pub const DEFAULT_MAX_VEC_ELEMENTS: usize = 1 << 24;
+
+// read the length prefix in bytes, and reject it with PsiError::MessageTooLarge if it declares more than `max` elements of `f_len` bytes.
+fn read_vec_len<C: AbstractChannel>(channel: &mut C, f_len: usize, max: usize) -> Result<usize> {
     let bytes_len = channel
         .read_usize()
         .with_context(|| format!("@{}:{}", file!(), line!()))?;
 
-    let mut res = vec![0u8; bytes_len];
+    let elements = bytes_len.div_ceil(f_len);
+    if elements > max {
+        return Err(PsiError::MessageTooLarge { elements, max }.into());
+    }
 
-    channel.read_bytes(&mut res)?;
+    Ok(bytes_len)
+}
+
+// read the payload of a vector of `F` into one buffer, after checking its length prefix by read_vec_len
+// and that it is a multiple of the byte length of `F`.
+fn read_vec_bytes<F: FF, C: AbstractChannel>(channel: &mut C, max: usize) -> Result<Vec<u8>> {
+    let f_len = F::ByteReprLen::to_usize();
+    let bytes_len =
+        read_vec_len(channel, f_len, max).with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+    if bytes_len % f_len != 0 {
+        bail!(
+            "bytes_len (={}) is not a multiple of field byte length (={}) @{}:{}",
+            bytes_len,
+            f_len,
+            file!(),
+            line!()
+        );
+    }
+
+    let mut buf = vec![0u8; bytes_len];
+
+    channel
+        .read_bytes(&mut buf)
+        .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+    Ok(buf)
+}
+
+/// Read a vector of field elements from a channel.
+///
+/// The length of the payload must be a multiple of the byte length of `F`,
+/// and a vector longer than [DEFAULT_MAX_VEC_ELEMENTS] is rejected with [PsiError::MessageTooLarge].
+pub fn read_vec_f<F, C>(channel: &mut C) -> Result<Vec<F>>
+where
+    F: FF,
+    C: AbstractChannel,
+{
+    read_vec_f_capped(channel, DEFAULT_MAX_VEC_ELEMENTS)
+}
+
+/// Same as [read_vec_f], but with the cap `max` instead of [DEFAULT_MAX_VEC_ELEMENTS].
+pub fn read_vec_f_capped<F, C>(channel: &mut C, max: usize) -> Result<Vec<F>>
+where
+    F: FF,
+    C: AbstractChannel,
+{
+    let res = read_vec_bytes::<F, _>(channel, max)
+        .with_context(|| format!("@{}:{}", file!(), line!()))?;
 
     let res = res
         .chunks(F::ByteReprLen::to_usize())
-        .into_iter()
-        .map(|x| {
-            F::from_bytes(x.as_ref().into()).with_context(|| format!("@{}:{}", file!(), line!()))
-        })
+        .map(|x| F::from_bytes(x.into()).with_context(|| format!("@{}:{}", file!(), line!())))
         .collect::<Result<Vec<_>>>()?;
 
     Ok(res)
 }
 
+/// Read a vector of field elements from a channel in bulk.
+///
+/// The wire format and the checks are the same as [read_vec_f]. The whole payload is read into a single buffer
+/// and parsed in place into a vector allocated once for the declared length.
+pub fn read_vec_f_bulk<F, C>(channel: &mut C) -> Result<Vec<F>>
+where
+    F: FF,
+    C: AbstractChannel,
+{
+    let buf = read_vec_bytes::<F, _>(channel, DEFAULT_MAX_VEC_ELEMENTS)
+        .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+    let f_len = F::ByteReprLen::to_usize();
+    let mut res = Vec::with_capacity(buf.len() / f_len);
+    for chunk in buf.chunks_exact(f_len) {
+        let f = F::from_bytes(GenericArray::from_slice(chunk))
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+        res.push(f);
+    }
+
+    Ok(res)
+}
+
+/// Read a vector written by [write_vec_f_truncated] with the same `bits`. Each element is restored with the bits above `bits` cleared.
+///
+/// The length of the payload must be a multiple of the truncated length,
+/// and a vector longer than [DEFAULT_MAX_VEC_ELEMENTS] is rejected with [PsiError::MessageTooLarge].
+pub fn read_vec_f_truncated<F, C>(channel: &mut C, bits: usize) -> Result<Vec<F>>
+where
+    F: FF,
+    C: AbstractChannel,
+{
+    let (t_len, mask) =
+        truncated_len::<F>(bits).with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+    let bytes_len = read_vec_len(channel, t_len, DEFAULT_MAX_VEC_ELEMENTS)
+        .with_context(|| format!("@{}:{}", file!(), line!()))?;
+    if bytes_len % t_len != 0 {
+        bail!(
+            "bytes_len (={}) is not a multiple of truncated byte length (={}) @{}:{}",
+            bytes_len,
+            t_len,
+            file!(),
+            line!()
+        );
+    }
+
+    let mut buf = vec![0u8; bytes_len];
+
+    channel
+        .read_bytes(&mut buf)
+        .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+    let mut repr = GenericArray::<u8, F::ByteReprLen>::default();
+    buf.chunks_exact(t_len)
+        .map(|chunk| {
+            repr[..t_len].copy_from_slice(chunk);
+            repr[t_len - 1] &= mask;
+            F::from_bytes(&repr).with_context(|| format!("@{}:{}", file!(), line!()))
+        })
+        .collect()
+}
+
 /// Wrap channels with Arc<Mutex<_>>.
 pub fn ch_arcnize<C>(channels: Vec<(usize, C)>) -> Vec<(usize, Arc<Mutex<C>>)>
 where
@@ -158,9 +491,166 @@ where
 mod tests {
     use super::*;
     use rand::Rng;
-    use scuttlebutt::{field::F128b, AesRng, Channel};
-    use std::io::{BufReader, BufWriter};
-    use std::os::unix::net::UnixStream;
+    use scuttlebutt::{field::F128b, AesRng};
+
+    #[test]
+    fn test_channel_from() {
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut channel = channel_from(sender).unwrap();
+
+            let x = channel.read_u64().unwrap();
+            channel.write_u64(x + 1).unwrap();
+            channel.flush().unwrap();
+        });
+
+        let mut channel = channel_from(receiver).unwrap();
+        channel.write_u64(41).unwrap();
+        channel.flush().unwrap();
+
+        assert_eq!(channel.read_u64().unwrap(), 42);
+
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_finish_channel() {
+        use counting_channel::{ByteCounters, CountingChannel};
+        use throttled_channel::{Throttle, ThrottledChannel};
+
+        // dropped after a partial write without flush: the peer reads the bytes, then EOF.
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut channel = CountingChannel::new(
+                ThrottledChannel::new(channel_from(sender).unwrap(), Throttle::default()),
+                Arc::new(ByteCounters::default()),
+            );
+            channel.write_u64(7).unwrap();
+        });
+        let mut channel = channel_from(receiver).unwrap();
+        assert_eq!(channel.read_u64().unwrap(), 7);
+        let err = channel.read_u8().unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+        handle.join().unwrap();
+
+        // finished while a clone is still alive.
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let (done_tx, done_rx) = std::sync::mpsc::channel();
+        let handle = std::thread::spawn(move || {
+            let mut channel = CountingChannel::new(
+                channel_from(sender).unwrap(),
+                Arc::new(ByteCounters::default()),
+            );
+            let _clone = channel.clone();
+            channel.write_u64(8).unwrap();
+            channel.finish().unwrap();
+            assert!(channel.write_u64(9).and_then(|_| channel.flush()).is_err());
+            done_rx.recv().unwrap();
+        });
+        let mut channel = channel_from(receiver).unwrap();
+        assert_eq!(channel.read_u64().unwrap(), 8);
+        let err = channel.read_u8().unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+        done_tx.send(()).unwrap();
+        handle.join().unwrap();
+    }
+
+    // in-memory duplex stream: bytes written to one end are read from the other.
+    struct MemoryStream {
+        tx: std::sync::mpsc::Sender<Vec<u8>>,
+        rx: std::sync::mpsc::Receiver<Vec<u8>>,
+        pending: Vec<u8>,
+    }
+
+    fn memory_stream_pair() -> (MemoryStream, MemoryStream) {
+        let (tx1, rx1) = std::sync::mpsc::channel();
+        let (tx2, rx2) = std::sync::mpsc::channel();
+        let stream = |tx, rx| MemoryStream {
+            tx,
+            rx,
+            pending: Vec::new(),
+        };
+        (stream(tx1, rx2), stream(tx2, rx1))
+    }
+
+    impl Read for MemoryStream {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.pending.is_empty() {
+                match self.rx.recv() {
+                    Ok(bytes) => self.pending = bytes,
+                    // the peer is dropped.
+                    Err(_) => return Ok(0),
+                }
+            }
+            let n = buf.len().min(self.pending.len());
+            buf[..n].copy_from_slice(&self.pending[..n]);
+            self.pending.drain(..n);
+            Ok(n)
+        }
+    }
+
+    impl Write for MemoryStream {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.tx
+                .send(buf.to_vec())
+                .map_err(|_| std::io::Error::from(std::io::ErrorKind::BrokenPipe))?;
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_opprf_over_memory_stream() {
+        use crate::preprocessed::opprf::{
+            SepOpprfReceiver, SepOpprfReceiverWithVole, SepOpprfSender, SepOpprfSenderWithVole,
+        };
+        use crate::solver::PaxosSolver;
+        use crate::vole::{LPNVoleReceiver, LPNVoleSender, LPN_EXTEND_SMALL, LPN_SETUP_SMALL};
+        use std::collections::HashMap;
+
+        let mut rng = AesRng::new();
+        let set = (0..30).map(|_| rng.gen()).collect::<Vec<F128b>>();
+        let points = set[..20]
+            .iter()
+            .map(|&x| (x, rng.gen()))
+            .collect::<Vec<(F128b, F128b)>>();
+        let programmed: HashMap<F128b, F128b> = HashMap::from_iter(points.iter().cloned());
+
+        let (s, r) = memory_stream_pair();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let mut channel = channel_from_stream(s).unwrap();
+            let sender = SepOpprfSenderWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
+                &mut channel,
+                &mut rng,
+                30,
+                LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+            )
+            .unwrap();
+            sender.send(&mut channel, &points, 30, &mut rng).unwrap();
+        });
+
+        let mut channel = channel_from_stream(r).unwrap();
+        let receiver = SepOpprfReceiverWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
+            &mut channel,
+            &mut rng,
+            set.len(),
+            LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+        )
+        .unwrap();
+        let received = receiver.receive(&mut channel, &set, &mut rng).unwrap();
+        handle.join().unwrap();
+
+        assert_eq!(received.len(), set.len());
+        for (x, y) in received.iter() {
+            if let Some(v) = programmed.get(x) {
+                assert_eq!(y, v);
+            }
+        }
+    }
 
     #[test]
     fn test_write_read_vec_f() {
@@ -171,10 +661,7 @@ mod tests {
 
         let (sender, receiver) = UnixStream::pair().unwrap();
         let handle = std::thread::spawn(move || {
-            let mut channel = Channel::new(
-                BufReader::new(sender.try_clone().unwrap()),
-                BufWriter::new(sender),
-            );
+            let mut channel = channel_from(sender).unwrap();
 
             channel.write_bytes(b"hello").unwrap();
             channel.flush().unwrap();
@@ -182,10 +669,7 @@ mod tests {
             let _len = write_vec_f(&mut channel, &w).unwrap();
         });
 
-        let mut channel = Channel::new(
-            BufReader::new(receiver.try_clone().unwrap()),
-            BufWriter::new(receiver),
-        );
+        let mut channel = channel_from(receiver).unwrap();
 
         let mut buf = [0u8; 5];
         channel.read_bytes(&mut buf).unwrap();
@@ -197,4 +681,155 @@ mod tests {
 
         assert_eq!(v, res);
     }
+
+    #[test]
+    fn test_write_read_vec_f_truncated() {
+        let mut rng = AesRng::new();
+
+        let v = (0..10).map(|_| rng.gen::<F128b>()).collect::<Vec<_>>();
+        let w = v.clone();
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut channel = channel_from(sender).unwrap();
+
+            assert_eq!(write_vec_f_truncated(&mut channel, &w, 12).unwrap(), 20);
+            assert_eq!(write_vec_f_truncated(&mut channel, &w, 128).unwrap(), 160);
+            assert!(write_vec_f_truncated(&mut channel, &w, 0).is_err());
+        });
+
+        let mut channel = channel_from(receiver).unwrap();
+
+        // the lowest 12 bits are kept and the others are cleared.
+        let res = read_vec_f_truncated::<F128b, _>(&mut channel, 12).unwrap();
+        for (x, y) in v.iter().zip(res.iter()) {
+            let (x, y) = (x.to_bytes(), y.to_bytes());
+            assert_eq!(x[0], y[0]);
+            assert_eq!(x[1] & 0x0f, y[1]);
+            assert!(y[2..].iter().all(|&b| b == 0));
+        }
+        assert_eq!(
+            read_vec_f_truncated::<F128b, _>(&mut channel, 128).unwrap(),
+            v
+        );
+
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_write_vec_f_mapped() {
+        let mut rng = AesRng::new();
+
+        let v = (0..50_000).map(|_| rng.gen::<F128b>()).collect::<Vec<_>>();
+        let w = v.clone();
+        let f = |x: F128b| Ok(x * x + x);
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut channel = channel_from(sender).unwrap();
+
+            let buffered = w.iter().map(|&x| f(x)).collect::<Result<Vec<_>>>().unwrap();
+            let len = write_vec_f(&mut channel, &buffered).unwrap();
+            let len_streamed = write_vec_f_mapped(&mut channel, w.into_iter(), f).unwrap();
+
+            assert_eq!(len, len_streamed);
+        });
+
+        let mut channel = channel_from(receiver).unwrap();
+
+        let buffered = read_vec_f::<F128b, _>(&mut channel).unwrap();
+        let streamed = read_vec_f::<F128b, _>(&mut channel).unwrap();
+
+        handle.join().unwrap();
+
+        assert_eq!(buffered, streamed);
+        assert_eq!(streamed.len(), v.len());
+        assert_eq!(streamed[0], f(v[0]).unwrap());
+    }
+
+    #[test]
+    fn test_read_vec_f_bulk() {
+        let mut rng = AesRng::new();
+
+        let v = (0..100).map(|_| rng.gen::<F128b>()).collect::<Vec<_>>();
+        let w = v.clone();
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut channel = channel_from(sender).unwrap();
+
+            write_vec_f(&mut channel, &w).unwrap();
+            write_vec_f(&mut channel, &w).unwrap();
+        });
+
+        let mut channel = channel_from(receiver).unwrap();
+
+        let res = read_vec_f::<F128b, _>(&mut channel).unwrap();
+        let res_bulk = read_vec_f_bulk::<F128b, _>(&mut channel).unwrap();
+
+        handle.join().unwrap();
+
+        assert_eq!(res, res_bulk);
+        assert_eq!(v, res_bulk);
+    }
+
+    #[test]
+    fn test_read_vec_f_too_large() {
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut channel = channel_from(sender).unwrap();
+
+            // a huge length prefix without the payload.
+            channel.write_usize(usize::MAX / 2).unwrap();
+            channel.flush().unwrap();
+
+            // within the cap of 2 elements, but not of 1.
+            write_vec_f(&mut channel, &[F128b::one(), F128b::one()]).unwrap();
+        });
+
+        let mut channel = channel_from(receiver).unwrap();
+
+        let err = read_vec_f::<F128b, _>(&mut channel).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<PsiError>(),
+            Some(&PsiError::MessageTooLarge {
+                elements: (usize::MAX / 2).div_ceil(16),
+                max: DEFAULT_MAX_VEC_ELEMENTS
+            })
+        );
+
+        let err = read_vec_f_capped::<F128b, _>(&mut channel, 1).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<PsiError>(),
+            Some(&PsiError::MessageTooLarge {
+                elements: 2,
+                max: 1
+            })
+        );
+
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_read_vec_f_invalid_length() {
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut channel = channel_from(sender).unwrap();
+
+            // the length prefixes are rejected before their payloads are read, so no payload is sent.
+            channel.write_usize(17).unwrap();
+            channel.write_usize(17).unwrap();
+            channel.flush().unwrap();
+        });
+
+        let mut channel = channel_from(receiver).unwrap();
+
+        let res = read_vec_f::<F128b, _>(&mut channel);
+        let res_bulk = read_vec_f_bulk::<F128b, _>(&mut channel);
+
+        handle.join().unwrap();
+
+        assert!(res.is_err());
+        assert!(res_bulk.is_err());
+    }
 }
@@ -0,0 +1,580 @@
+//! Multiplexed channel to run multiple independent sessions over one connection.
+//!
+//! Every message is framed as follows (little endian).
+//!
+//! | Size | Content                |
+//! | :--- | :--------------------- |
+//! | 4    | Session id (`u32`)     |
+//! | 4    | Payload length (`u32`) |
+//! | ...  | Payload                |
+//!
+//! [MultiplexedChannel] owns the reader of the connection in a background thread, which dispatches frames to [SessionChannel]s by session id.
+//! A [SessionChannel] implements [AbstractChannel], so it can be used for any protocol in this library (e.g. OPPRF).
+//! Written bytes are buffered in the session channel and sent when it is flushed, in frames of at most [MAX_FRAME_LEN] bytes.
+//! The number of sessions open at once is limited by [MultiplexedChannel::with_max_concurrent_sessions],
+//! and the bytes received and not read yet in each session by [MultiplexedChannel::with_max_buffered_len].
+//!
+//! The reader thread closes the connection (and so disconnects all sessions) on a frame longer than [MAX_FRAME_LEN],
+//! on a frame opening a new session beyond the limit, or on a frame beyond the buffered bytes of its session.
+//! Frames for a session closed here are dropped. So a peer can make it buffer at most the two limits multiplied.
+//!
+//! # Example
+//!
+//! ```
+//! use preprocessing_mpsi_with_vole::channel_utils::multiplexed_channel::MultiplexedChannel;
+//! use scuttlebutt::AbstractChannel;
+//! use std::io::{BufReader, BufWriter};
+//! use std::os::unix::net::UnixStream;
+//! use anyhow::Result;
+//!
+//! # fn try_main() -> Result<()> {
+//! let (s, r) = UnixStream::pair()?;
+//!
+//! let mux1 = MultiplexedChannel::new(BufReader::new(s.try_clone()?), BufWriter::new(s));
+//! let mux2 = MultiplexedChannel::new(BufReader::new(r.try_clone()?), BufWriter::new(r));
+//!
+//! let mut ch1_a = mux1.session(0)?;
+//! let mut ch1_b = mux1.session(1)?;
+//! let mut ch2_a = mux2.session(0)?;
+//! let mut ch2_b = mux2.session(1)?;
+//!
+//! ch1_a.write_u8(10)?;
+//! ch1_a.flush()?;
+//! ch1_b.write_u8(20)?;
+//! ch1_b.flush()?;
+//!
+//! assert_eq!(ch2_b.read_u8()?, 20);
+//! assert_eq!(ch2_a.read_u8()?, 10);
+//! # Ok(())
+//! # }
+//! # fn main() {
+//! #    try_main().unwrap();
+//! # }
+//! ```
+
+use crate::error::PsiError;
+use anyhow::{bail, Result};
+use crossbeam::channel::{unbounded, Receiver, Sender};
+use scuttlebutt::AbstractChannel;
+use std::collections::{HashMap, HashSet};
+use std::io::{ErrorKind, Read, Write};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Maximum payload length of one frame. Longer flushes are split into several frames.
+pub const MAX_FRAME_LEN: usize = 1 << 20;
+
+/// Default limit of [MultiplexedChannel::with_max_concurrent_sessions].
+pub const DEFAULT_MAX_CONCURRENT_SESSIONS: usize = 1 << 10;
+
+/// Default limit of [MultiplexedChannel::with_max_buffered_len]: 256 MiB, as much as the longest vector of [read_vec_f](crate::channel_utils::read_vec_f).
+pub const DEFAULT_MAX_BUFFERED_LEN: usize = 1 << 28;
+
+// frames of a session received and not read yet.
+struct SessionQueue {
+    tx: Sender<Vec<u8>>,
+    // taken by MultiplexedChannel::session.
+    rx: Option<Receiver<Vec<u8>>>,
+    // bytes sent to `tx` and not received from it yet.
+    buffered: Arc<AtomicUsize>,
+}
+
+impl SessionQueue {
+    fn new() -> Self {
+        let (tx, rx) = unbounded();
+        Self {
+            tx,
+            rx: Some(rx),
+            buffered: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Sessions {
+    queues: HashMap<u32, SessionQueue>,
+    // ids of the sessions closed here and not taken again. Their frames are dropped.
+    closed: HashSet<u32>,
+}
+
+// state shared with the reader thread.
+struct Shared {
+    sessions: Mutex<Sessions>,
+    // number of sessions taken and not closed yet.
+    open_sessions: AtomicUsize,
+    max_sessions: AtomicUsize,
+    max_buffered_len: AtomicUsize,
+    closed: AtomicBool,
+}
+
+/// Connection shared by multiple sessions.
+///
+/// Please look the parent document ( [crate::channel_utils::multiplexed_channel] ) for usage example.
+pub struct MultiplexedChannel<W: Write + Send + 'static> {
+    writer: Arc<Mutex<W>>,
+    shared: Arc<Shared>,
+}
+
+impl<W: Write + Send + 'static> MultiplexedChannel<W> {
+    /// Create a multiplexed channel. A thread reading frames from `reader` is spawned.
+    pub fn new<R: Read + Send + 'static>(reader: R, writer: W) -> Self {
+        let shared = Arc::new(Shared {
+            sessions: Mutex::new(Sessions::default()),
+            open_sessions: AtomicUsize::new(0),
+            max_sessions: AtomicUsize::new(DEFAULT_MAX_CONCURRENT_SESSIONS),
+            max_buffered_len: AtomicUsize::new(DEFAULT_MAX_BUFFERED_LEN),
+            closed: AtomicBool::new(false),
+        });
+
+        let shared_for_demux = Arc::clone(&shared);
+        std::thread::spawn(move || demux(reader, shared_for_demux));
+
+        Self {
+            writer: Arc::new(Mutex::new(writer)),
+            shared,
+        }
+    }
+
+    /// Limit the number of sessions open at once to `max`. Default: [DEFAULT_MAX_CONCURRENT_SESSIONS].
+    /// A session is closed when all the clones of its channel are dropped.
+    ///
+    /// Sessions opened by the peer and not taken yet count too, since their frames are buffered.
+    /// A frame of the peer opening a session beyond the limit closes the connection.
+    pub fn with_max_concurrent_sessions(self, max: usize) -> Self {
+        self.shared.max_sessions.store(max, Ordering::SeqCst);
+        self
+    }
+
+    /// Limit the bytes received and not read yet in each session to `max`. Default: [DEFAULT_MAX_BUFFERED_LEN].
+    ///
+    /// A frame of the peer beyond the limit closes the connection, instead of blocking the frames of the other sessions.
+    pub fn with_max_buffered_len(self, max: usize) -> Self {
+        self.shared.max_buffered_len.store(max, Ordering::SeqCst);
+        self
+    }
+
+    /// Number of sessions open now.
+    pub fn open_sessions(&self) -> usize {
+        self.shared.open_sessions.load(Ordering::SeqCst)
+    }
+
+    /// Create the sub-channel for session `id`. Each open session can be taken only once.
+    ///
+    /// If as many sessions as the limit are open, [PsiError::TooManySessions] is returned instead of waiting for a free slot.
+    /// It fails after the connection is closed. A closed session can be taken again, and then the frames for it are received again.
+    pub fn session(&self, id: u32) -> Result<SessionChannel<W>> {
+        let mut sessions = self.shared.sessions.lock().unwrap();
+        if self.shared.closed.load(Ordering::SeqCst) {
+            bail!("the connection is closed. @{}:{}", file!(), line!());
+        }
+        let max = self.shared.max_sessions.load(Ordering::SeqCst);
+        if self.open_sessions() >= max
+            || (!sessions.queues.contains_key(&id) && sessions.queues.len() >= max)
+        {
+            return Err(PsiError::TooManySessions { max }.into());
+        }
+
+        let queue = sessions.queues.entry(id).or_insert_with(SessionQueue::new);
+        let Some(rx) = queue.rx.take() else {
+            bail!("session {} is already taken. @{}:{}", id, file!(), line!());
+        };
+        let buffered = Arc::clone(&queue.buffered);
+        sessions.closed.remove(&id);
+        self.shared.open_sessions.fetch_add(1, Ordering::SeqCst);
+
+        Ok(SessionChannel {
+            id,
+            slot: Arc::new(SessionSlot {
+                id,
+                shared: Arc::clone(&self.shared),
+            }),
+            writer: Arc::clone(&self.writer),
+            write_buf: Arc::new(Mutex::new(Vec::new())),
+            read_state: Arc::new(Mutex::new(ReadState {
+                rx,
+                buffered,
+                buf: Vec::new(),
+                pos: 0,
+            })),
+        })
+    }
+}
+
+fn demux<R: Read>(mut reader: R, shared: Arc<Shared>) {
+    let mut head = [0u8; 8];
+    while reader.read_exact(&mut head).is_ok() {
+        let id = u32::from_le_bytes(head[0..4].try_into().unwrap());
+        let len = u32::from_le_bytes(head[4..8].try_into().unwrap()) as usize;
+        if len > MAX_FRAME_LEN {
+            break;
+        }
+
+        let mut payload = vec![0u8; len];
+        if reader.read_exact(&mut payload).is_err() {
+            break;
+        }
+
+        let mut sessions = shared.sessions.lock().unwrap();
+        // nobody reads the frames of a session closed here.
+        if sessions.closed.contains(&id) {
+            continue;
+        }
+        if !sessions.queues.contains_key(&id)
+            && sessions.queues.len() >= shared.max_sessions.load(Ordering::SeqCst)
+        {
+            break;
+        }
+        let queue = sessions.queues.entry(id).or_insert_with(SessionQueue::new);
+        if queue.buffered.load(Ordering::SeqCst) + len
+            > shared.max_buffered_len.load(Ordering::SeqCst)
+        {
+            break;
+        }
+        queue.buffered.fetch_add(len, Ordering::SeqCst);
+        let _ = queue.tx.send(payload);
+    }
+
+    // connection closed. disconnect all sessions.
+    let mut sessions = shared.sessions.lock().unwrap();
+    shared.closed.store(true, Ordering::SeqCst);
+    sessions.queues.clear();
+}
+
+struct ReadState {
+    rx: Receiver<Vec<u8>>,
+    // See SessionQueue::buffered.
+    buffered: Arc<AtomicUsize>,
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+// slot of an open session, shared by the clones of its channel. The session is closed and its id is freed when it is dropped.
+struct SessionSlot {
+    id: u32,
+    shared: Arc<Shared>,
+}
+
+impl Drop for SessionSlot {
+    fn drop(&mut self) {
+        let mut sessions = self.shared.sessions.lock().unwrap();
+        sessions.queues.remove(&self.id);
+        sessions.closed.insert(self.id);
+        self.shared.open_sessions.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Sub-channel of [MultiplexedChannel] for one session.
+pub struct SessionChannel<W: Write + Send + 'static> {
+    id: u32,
+    slot: Arc<SessionSlot>,
+    writer: Arc<Mutex<W>>,
+    write_buf: Arc<Mutex<Vec<u8>>>,
+    read_state: Arc<Mutex<ReadState>>,
+}
+
+impl<W: Write + Send + 'static> SessionChannel<W> {
+    /// Session id of this channel.
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+}
+
+impl<W: Write + Send + 'static> AbstractChannel for SessionChannel<W> {
+    fn write_bytes(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        self.write_buf.lock().unwrap().extend_from_slice(bytes);
+        Ok(())
+    }
+
+    fn read_bytes(&mut self, bytes: &mut [u8]) -> std::io::Result<()> {
+        let mut state = self.read_state.lock().unwrap();
+        let mut filled = 0;
+        while filled < bytes.len() {
+            if state.pos == state.buf.len() {
+                state.buf = state.rx.recv().map_err(|_| {
+                    std::io::Error::new(
+                        ErrorKind::UnexpectedEof,
+                        format!("session {} is disconnected.", self.id),
+                    )
+                })?;
+                state.buffered.fetch_sub(state.buf.len(), Ordering::SeqCst);
+                state.pos = 0;
+            }
+
+            let n = (bytes.len() - filled).min(state.buf.len() - state.pos);
+            let pos = state.pos;
+            bytes[filled..filled + n].copy_from_slice(&state.buf[pos..pos + n]);
+            state.pos += n;
+            filled += n;
+        }
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        let payload = std::mem::take(&mut *self.write_buf.lock().unwrap());
+
+        let mut writer = self.writer.lock().unwrap();
+        for frame in payload.chunks(MAX_FRAME_LEN) {
+            writer.write_all(&self.id.to_le_bytes())?;
+            writer.write_all(&(frame.len() as u32).to_le_bytes())?;
+            writer.write_all(frame)?;
+        }
+        writer.flush()
+    }
+
+    fn clone(&self) -> Self {
+        Self {
+            id: self.id,
+            slot: Arc::clone(&self.slot),
+            writer: Arc::clone(&self.writer),
+            write_buf: Arc::clone(&self.write_buf),
+            read_state: Arc::clone(&self.read_state),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::preprocessed::opprf::{
+        SepOpprfReceiver, SepOpprfReceiverWithVole, SepOpprfSender, SepOpprfSenderWithVole,
+    };
+    use crate::set_utils::FromU128;
+    use crate::solver::PaxosSolver;
+    use crate::vole::{LPNVoleReceiver, LPNVoleSender, LPN_EXTEND_SMALL, LPN_SETUP_SMALL};
+    use scuttlebutt::{field::F128b, AesRng};
+    use std::io::{BufReader, BufWriter};
+    use std::os::unix::net::UnixStream;
+
+    fn opprf_session<W: Write + Send + 'static>(
+        mut sender_channel: SessionChannel<W>,
+        mut receiver_channel: SessionChannel<W>,
+        offset: u128,
+    ) -> std::thread::JoinHandle<()> {
+        std::thread::spawn(move || {
+            let program = (0..10)
+                .map(|i| {
+                    (
+                        F128b::from_u128(offset + i),
+                        F128b::from_u128(offset + i * 100),
+                    )
+                })
+                .collect::<Vec<_>>();
+            let queries = (5..15)
+                .map(|i| F128b::from_u128(offset + i))
+                .collect::<Vec<_>>();
+
+            let handle = std::thread::spawn(move || {
+                let mut rng = AesRng::new();
+                let vole_share_for_s = LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL);
+                let opprf_sender = SepOpprfSenderWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
+                    &mut sender_channel,
+                    &mut rng,
+                    10,
+                    vole_share_for_s,
+                )
+                .unwrap();
+                opprf_sender
+                    .send(&mut sender_channel, &program, 10, &mut rng)
+                    .unwrap();
+            });
+
+            let mut rng = AesRng::new();
+            let vole_share_for_r = LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL);
+            let opprf_receiver = SepOpprfReceiverWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
+                &mut receiver_channel,
+                &mut rng,
+                queries.len(),
+                vole_share_for_r,
+            )
+            .unwrap();
+            let received = opprf_receiver
+                .receive(&mut receiver_channel, &queries, &mut rng)
+                .unwrap();
+
+            handle.join().unwrap();
+
+            for (i, (x, y)) in received.into_iter().enumerate() {
+                let i = i as u128 + 5;
+                assert_eq!(x, F128b::from_u128(offset + i));
+                if i < 10 {
+                    assert_eq!(y, F128b::from_u128(offset + i * 100));
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn test_session_channel() {
+        let (s, r) = UnixStream::pair().unwrap();
+        let mux1 =
+            MultiplexedChannel::new(BufReader::new(s.try_clone().unwrap()), BufWriter::new(s));
+        let mux2 =
+            MultiplexedChannel::new(BufReader::new(r.try_clone().unwrap()), BufWriter::new(r));
+
+        let mut ch1 = mux1.session(7).unwrap();
+        let mut ch2 = mux2.session(7).unwrap();
+
+        ch1.write_u32(123).unwrap();
+        ch1.write_u64(456).unwrap();
+        ch1.flush().unwrap();
+
+        assert_eq!(ch2.read_u32().unwrap(), 123);
+        assert_eq!(ch2.read_u64().unwrap(), 456);
+
+        assert!(mux1.session(7).is_err());
+    }
+
+    #[test]
+    fn test_max_concurrent_sessions() {
+        let (s, _r) = UnixStream::pair().unwrap();
+        let mux =
+            MultiplexedChannel::new(BufReader::new(s.try_clone().unwrap()), BufWriter::new(s))
+                .with_max_concurrent_sessions(2);
+
+        let ch0 = mux.session(0).unwrap();
+        let ch1 = mux.session(1).unwrap();
+        assert_eq!(mux.open_sessions(), 2);
+
+        let err = mux.session(2).err().unwrap();
+        assert_eq!(
+            err.downcast_ref::<PsiError>(),
+            Some(&PsiError::TooManySessions { max: 2 })
+        );
+
+        // a session is closed when all of its clones are dropped.
+        let ch0_clone = ch0.clone();
+        drop(ch0);
+        assert!(mux.session(2).is_err());
+        drop(ch0_clone);
+        assert_eq!(mux.open_sessions(), 1);
+
+        let ch2 = mux.session(2).unwrap();
+        assert_eq!(ch2.id(), 2);
+        assert!(mux.session(3).is_err());
+        drop(ch1);
+        drop(ch2);
+        assert_eq!(mux.open_sessions(), 0);
+    }
+
+    fn write_frame<W: Write>(writer: &mut W, id: u32, len: u32, payload: &[u8]) {
+        writer.write_all(&id.to_le_bytes()).unwrap();
+        writer.write_all(&len.to_le_bytes()).unwrap();
+        writer.write_all(payload).unwrap();
+        writer.flush().unwrap();
+    }
+
+    #[test]
+    fn test_peer_exceeds_max_concurrent_sessions() {
+        let (s, mut r) = UnixStream::pair().unwrap();
+        let mux =
+            MultiplexedChannel::new(BufReader::new(s.try_clone().unwrap()), BufWriter::new(s))
+                .with_max_concurrent_sessions(2);
+        let mut ch0 = mux.session(0).unwrap();
+
+        // session 1 is buffered until taken, and session 2 is beyond the limit.
+        write_frame(&mut r, 0, 1, &[10]);
+        write_frame(&mut r, 1, 1, &[20]);
+        write_frame(&mut r, 2, 1, &[30]);
+
+        // frames before the violation are delivered, and then the session is disconnected instead of waiting forever.
+        assert_eq!(ch0.read_u8().unwrap(), 10);
+        let err = ch0.read_u8().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+        assert!(mux.session(1).is_err());
+    }
+
+    #[test]
+    fn test_frames_of_closed_session() {
+        let (s, mut r) = UnixStream::pair().unwrap();
+        let mux =
+            MultiplexedChannel::new(BufReader::new(s.try_clone().unwrap()), BufWriter::new(s))
+                .with_max_concurrent_sessions(2);
+        let mut ch0 = mux.session(0).unwrap();
+        drop(mux.session(1).unwrap());
+
+        // the frame of the closed session 1 is dropped, so session 2 is within the limit.
+        write_frame(&mut r, 1, 1, &[20]);
+        write_frame(&mut r, 2, 1, &[30]);
+        write_frame(&mut r, 0, 1, &[10]);
+        assert_eq!(ch0.read_u8().unwrap(), 10);
+        assert!(!mux.shared.sessions.lock().unwrap().queues.contains_key(&1));
+
+        // taken again, session 1 receives its frames again.
+        drop(ch0);
+        let mut ch1 = mux.session(1).unwrap();
+        write_frame(&mut r, 1, 1, &[21]);
+        assert_eq!(ch1.read_u8().unwrap(), 21);
+    }
+
+    #[test]
+    fn test_max_buffered_len() {
+        // bytes buffered and not read in session 0, then a byte of session 1.
+        let read_after = |max: usize| {
+            let (s, mut r) = UnixStream::pair().unwrap();
+            let mux =
+                MultiplexedChannel::new(BufReader::new(s.try_clone().unwrap()), BufWriter::new(s))
+                    .with_max_buffered_len(max);
+            let _ch0 = mux.session(0).unwrap();
+            let mut ch1 = mux.session(1).unwrap();
+
+            write_frame(&mut r, 0, 3, &[1, 2, 3]);
+            write_frame(&mut r, 0, 2, &[4, 5]);
+            write_frame(&mut r, 1, 1, &[10]);
+            ch1.read_u8()
+        };
+
+        assert_eq!(read_after(5).unwrap(), 10);
+        let err = read_after(4).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_frame_too_long() {
+        let (s, mut r) = UnixStream::pair().unwrap();
+        let mux =
+            MultiplexedChannel::new(BufReader::new(s.try_clone().unwrap()), BufWriter::new(s));
+        let mut ch0 = mux.session(0).unwrap();
+
+        // the header alone closes the connection, before the payload is allocated.
+        write_frame(&mut r, 0, MAX_FRAME_LEN as u32 + 1, &[]);
+        let err = ch0.read_u8().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+
+        // a long flush is split into frames within the limit.
+        let (s, r) = UnixStream::pair().unwrap();
+        let mux1 =
+            MultiplexedChannel::new(BufReader::new(s.try_clone().unwrap()), BufWriter::new(s));
+        let mux2 =
+            MultiplexedChannel::new(BufReader::new(r.try_clone().unwrap()), BufWriter::new(r));
+        let mut ch1 = mux1.session(0).unwrap();
+        let mut ch2 = mux2.session(0).unwrap();
+        let data = (0..2 * MAX_FRAME_LEN + 3)
+            .map(|i| i as u8)
+            .collect::<Vec<_>>();
+        let data_for_ch1 = data.clone();
+        let handle = std::thread::spawn(move || {
+            ch1.write_bytes(&data_for_ch1).unwrap();
+            ch1.flush().unwrap();
+        });
+        let mut received = vec![0u8; data.len()];
+        ch2.read_bytes(&mut received).unwrap();
+        handle.join().unwrap();
+        assert_eq!(received, data);
+    }
+
+    #[test]
+    fn test_two_opprf_sessions_over_one_connection() {
+        let (s, r) = UnixStream::pair().unwrap();
+        let mux1 =
+            MultiplexedChannel::new(BufReader::new(s.try_clone().unwrap()), BufWriter::new(s));
+        let mux2 =
+            MultiplexedChannel::new(BufReader::new(r.try_clone().unwrap()), BufWriter::new(r));
+
+        let handle_a = opprf_session(mux1.session(0).unwrap(), mux2.session(0).unwrap(), 0);
+        let handle_b = opprf_session(mux1.session(1).unwrap(), mux2.session(1).unwrap(), 1000);
+
+        handle_a.join().unwrap();
+        handle_b.join().unwrap();
+    }
+}
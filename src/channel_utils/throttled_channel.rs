@@ -0,0 +1,214 @@
+//! Channel wrapper limiting the throughput and adding a latency, to reproduce constrained links locally (`prep_psi --throttle-kbps --latency-ms`).
+//!
+//! Written bytes are paced to [Throttle::bytes_per_sec]: a write waits while the bytes written so far are ahead of the rate.
+//! Each flush (the end of a protocol message) waits until all written bytes are on the link, and then for [Throttle::latency].
+//! Reads pass through, so wrap the channels of every party to throttle both directions.
+//!
+//! # Example
+//!
+//! ```
+//! use preprocessing_mpsi_with_vole::channel_utils::channel_from;
+//! use preprocessing_mpsi_with_vole::channel_utils::throttled_channel::{Throttle, ThrottledChannel};
+//! use scuttlebutt::AbstractChannel;
+//! use std::os::unix::net::UnixStream;
+//! use std::time::Duration;
+//! use anyhow::Result;
+//!
+//! # fn try_main() -> Result<()> {
+//! let (s, r) = UnixStream::pair()?;
+//!
+//! let throttle = Throttle::new(Some(1_000_000), Duration::from_millis(10));
+//! let mut ch1 = ThrottledChannel::new(channel_from(s)?, throttle);
+//! let mut ch2 = channel_from(r)?;
+//!
+//! ch1.write_u64(10)?;
+//! ch1.flush()?;
+//! assert_eq!(ch2.read_u64()?, 10);
+//! # Ok(())
+//! # }
+//! # fn main() {
+//! #    try_main().unwrap();
+//! # }
+//! ```
+
+use crate::channel_utils::FinishChannel;
+use scuttlebutt::AbstractChannel;
+use std::time::{Duration, Instant};
+
+// a write sleeps only when it is ahead of the rate by more than this, so that small writes don't sleep one by one.
+const MAX_AHEAD: Duration = Duration::from_millis(1);
+
+/// Limits of a [ThrottledChannel].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Throttle {
+    /// Throughput in bytes per second. `None` means unlimited.
+    pub bytes_per_sec: Option<u64>,
+    /// Latency added to each flush.
+    pub latency: Duration,
+}
+
+impl Throttle {
+    /// Throughput of `bytes_per_sec` (unlimited if `None`) and `latency` per flush.
+    pub fn new(bytes_per_sec: Option<u64>, latency: Duration) -> Self {
+        Self {
+            bytes_per_sec,
+            latency,
+        }
+    }
+
+    /// Limits of `--throttle-kbps` (kilobits per second) and `--latency-ms`.
+    pub fn from_kbps(kbps: Option<u64>, latency_ms: u64) -> Self {
+        Self::new(
+            kbps.map(|kbps| kbps * 1000 / 8),
+            Duration::from_millis(latency_ms),
+        )
+    }
+
+    /// Whether the channel passes bytes through without waiting.
+    pub fn is_unlimited(&self) -> bool {
+        self.bytes_per_sec.is_none() && self.latency.is_zero()
+    }
+
+    /// Time to transfer `len` bytes at the rate.
+    pub fn transfer_time(&self, len: usize) -> Duration {
+        match self.bytes_per_sec {
+            Some(rate) if rate > 0 => Duration::from_secs_f64(len as f64 / rate as f64),
+            _ => Duration::ZERO,
+        }
+    }
+}
+
+/// Channel throttling bytes written to `inner`.
+///
+/// An unlimited [Throttle] only passes bytes through, so that throttling can be switched at runtime without changing the channel type.
+/// A clone is paced separately.
+///
+/// Please look the parent document ( [crate::channel_utils::throttled_channel] ) for usage example.
+pub struct ThrottledChannel<C: AbstractChannel> {
+    inner: C,
+    throttle: Throttle,
+    // time when the bytes written so far are on the link.
+    free_at: Option<Instant>,
+}
+
+impl<C: AbstractChannel> ThrottledChannel<C> {
+    /// Wrap `inner` with the limits of `throttle`.
+    pub fn new(inner: C, throttle: Throttle) -> Self {
+        Self {
+            inner,
+            throttle,
+            free_at: None,
+        }
+    }
+
+    /// Limits of this channel.
+    pub fn throttle(&self) -> Throttle {
+        self.throttle
+    }
+
+    /// Unwrap the channel.
+    pub fn into_inner(self) -> C {
+        self.inner
+    }
+
+    fn sleep_until(at: Instant) {
+        let now = Instant::now();
+        if at > now {
+            std::thread::sleep(at - now);
+        }
+    }
+}
+
+impl<C: AbstractChannel> AbstractChannel for ThrottledChannel<C> {
+    #[inline(always)]
+    fn write_bytes(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        if self.throttle.bytes_per_sec.is_some() {
+            let now = Instant::now();
+            let start = self.free_at.map_or(now, |at| at.max(now));
+            let free_at = start + self.throttle.transfer_time(bytes.len());
+            if free_at > now + MAX_AHEAD {
+                Self::sleep_until(free_at);
+            }
+            self.free_at = Some(free_at);
+        }
+        self.inner.write_bytes(bytes)
+    }
+
+    #[inline(always)]
+    fn read_bytes(&mut self, bytes: &mut [u8]) -> std::io::Result<()> {
+        self.inner.read_bytes(bytes)
+    }
+
+    #[inline(always)]
+    fn flush(&mut self) -> std::io::Result<()> {
+        if let Some(at) = self.free_at.take() {
+            Self::sleep_until(at);
+        }
+        if !self.throttle.latency.is_zero() {
+            std::thread::sleep(self.throttle.latency);
+        }
+        self.inner.flush()
+    }
+
+    #[inline(always)]
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            throttle: self.throttle,
+            free_at: None,
+        }
+    }
+}
+
+impl<C: FinishChannel> FinishChannel for ThrottledChannel<C> {
+    fn finish(&mut self) -> std::io::Result<()> {
+        self.flush()?;
+        self.inner.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channel_utils::channel_from;
+    use rand::Rng;
+    use scuttlebutt::AesRng;
+    use std::os::unix::net::UnixStream;
+
+    #[test]
+    fn test_throttled_channel() {
+        let mut rng = AesRng::new();
+        let data = (0..200_000).map(|_| rng.gen()).collect::<Vec<u8>>();
+        let throttle = Throttle::new(Some(1_000_000), Duration::from_millis(20));
+        let expected = throttle.transfer_time(data.len()) + throttle.latency * 5;
+
+        let (s, r) = UnixStream::pair().unwrap();
+        let data_for_s = data.clone();
+        let handle = std::thread::spawn(move || {
+            let mut channel = ThrottledChannel::new(channel_from(s).unwrap(), throttle);
+            let start = Instant::now();
+            for chunk in data_for_s.chunks(data_for_s.len() / 5) {
+                channel.write_bytes(chunk).unwrap();
+                channel.flush().unwrap();
+            }
+            start.elapsed()
+        });
+
+        let mut channel = channel_from(r).unwrap();
+        let mut received = vec![0u8; data.len()];
+        channel.read_bytes(&mut received).unwrap();
+        let elapsed = handle.join().unwrap();
+
+        assert_eq!(received, data);
+        assert!(elapsed >= expected, "{:?} < {:?}", elapsed, expected);
+        assert!(
+            elapsed < expected * 3,
+            "{:?} >= 3 * {:?}",
+            elapsed,
+            expected
+        );
+
+        assert!(Throttle::from_kbps(None, 0).is_unlimited());
+        assert_eq!(Throttle::from_kbps(Some(8), 0).bytes_per_sec, Some(1000));
+    }
+}
@@ -0,0 +1,124 @@
+//! Channel wrapper recording the bytes written to it, for the auditable transcript of `prep_psi --deterministic`.
+//!
+//! Only written bytes are recorded, so wrapping a channel of every party records each message exactly once.
+//! The transcript is shared by clones of the channel.
+//!
+//! # Example
+//!
+//! ```
+//! use preprocessing_mpsi_with_vole::channel_utils::channel_from;
+//! use preprocessing_mpsi_with_vole::channel_utils::transcript_channel::{Transcript, TranscriptChannel};
+//! use scuttlebutt::AbstractChannel;
+//! use std::os::unix::net::UnixStream;
+//! use std::sync::Arc;
+//! use anyhow::Result;
+//!
+//! # fn try_main() -> Result<()> {
+//! let (s, r) = UnixStream::pair()?;
+//!
+//! let transcript = Arc::new(Transcript::default());
+//! let mut ch1 = TranscriptChannel::new(channel_from(s)?, Some(Arc::clone(&transcript)));
+//! let mut ch2 = channel_from(r)?;
+//!
+//! ch1.write_u64(10)?;
+//! ch1.flush()?;
+//! assert_eq!(ch2.read_u64()?, 10);
+//!
+//! assert_eq!(transcript.bytes(), 10u64.to_le_bytes());
+//! # Ok(())
+//! # }
+//! # fn main() {
+//! #    try_main().unwrap();
+//! # }
+//! ```
+
+use crate::channel_utils::FinishChannel;
+use scuttlebutt::AbstractChannel;
+use std::sync::{Arc, Mutex};
+
+/// Bytes written to a channel.
+#[derive(Debug, Default)]
+pub struct Transcript {
+    bytes: Mutex<Vec<u8>>,
+}
+
+impl Transcript {
+    /// Copy of the bytes written so far.
+    pub fn bytes(&self) -> Vec<u8> {
+        self.bytes.lock().unwrap().clone()
+    }
+
+    /// Number of bytes written so far.
+    pub fn len(&self) -> usize {
+        self.bytes.lock().unwrap().len()
+    }
+
+    /// Whether nothing has been written.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Channel recording bytes written to `inner`.
+///
+/// With `None`, nothing is recorded and the channel only passes bytes through,
+/// so that recording can be switched at runtime without changing the channel type.
+///
+/// Please look the parent document ( [crate::channel_utils::transcript_channel] ) for usage example.
+pub struct TranscriptChannel<C: AbstractChannel> {
+    inner: C,
+    transcript: Option<Arc<Transcript>>,
+}
+
+impl<C: AbstractChannel> TranscriptChannel<C> {
+    /// Wrap `inner`. Written bytes are appended to `transcript`.
+    pub fn new(inner: C, transcript: Option<Arc<Transcript>>) -> Self {
+        Self { inner, transcript }
+    }
+
+    /// Transcript of this channel.
+    pub fn transcript(&self) -> Option<&Arc<Transcript>> {
+        self.transcript.as_ref()
+    }
+
+    /// Unwrap the channel.
+    pub fn into_inner(self) -> C {
+        self.inner
+    }
+}
+
+impl<C: AbstractChannel> AbstractChannel for TranscriptChannel<C> {
+    #[inline(always)]
+    fn write_bytes(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        self.inner.write_bytes(bytes)?;
+        if let Some(transcript) = &self.transcript {
+            transcript.bytes.lock().unwrap().extend_from_slice(bytes);
+        }
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn read_bytes(&mut self, bytes: &mut [u8]) -> std::io::Result<()> {
+        self.inner.read_bytes(bytes)
+    }
+
+    #[inline(always)]
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+
+    #[inline(always)]
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            transcript: self.transcript.clone(),
+        }
+    }
+}
+
+impl<C: FinishChannel> FinishChannel for TranscriptChannel<C> {
+    fn finish(&mut self) -> std::io::Result<()> {
+        self.flush()?;
+        self.inner.finish()
+    }
+}
@@ -7,25 +7,29 @@ use crate::channel_utils::sync_channel::create_unix_channels;
 use crate::channel_utils::sync_channel_by_cb::create_crossbeam_channels;
 use crate::channel_utils::sync_channel_by_cb::{CrossbeamReceiver, CrossbeamSender};
 use crate::channel_utils::tcp_channel::create_tcp_channels;
+use crate::preprocessed::psi::field_bits;
+use crate::set_utils::is_stdin;
+pub use crate::set_utils::{InputFormat, Normalize, OnDuplicate};
 use crate::solver::{Solver, SolverParams};
 use crate::vole::{
-    LPNVoleReceiver, LPNVoleSender, OtVoleReceiver, OtVoleSender, VoleShareForReceiver,
-    VoleShareForSender, LPN_EXTEND_MEDIUM, LPN_EXTEND_SMALL, LPN_SETUP_MEDIUM, LPN_SETUP_SMALL,
+    lpn_tier::measure_capacity, select_tier, LPNVoleReceiver, LPNVoleSender, LpnTier,
+    OtVoleReceiver, OtVoleSender, VoleShareForReceiver, VoleShareForSender,
 };
-use anyhow::Result;
-use clap::{Parser, ValueEnum};
+use anyhow::{bail, Context, Result};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use ocelot::ot::{AlszReceiver as OtReceiver, AlszSender as OtSender};
 use scuttlebutt::field::F128b;
 use scuttlebutt::{AbstractChannel, SyncChannel};
 use std::fmt::Display;
 use std::net::TcpStream;
+use std::path::PathBuf;
 use std::{
     io::{BufReader, BufWriter},
     os::unix::net::UnixStream,
 };
 
 /// How to share VOLE (a kind of corelated randomness). More details: [vole](crate::vole).
-#[derive(Clone, Copy, ValueEnum, Debug)]
+#[derive(Clone, Copy, ValueEnum, Debug, PartialEq, Eq)]
 pub enum VoleType {
     /// Use Oblivious Transfer. See [OtVoleSender] or [OtVoleReceiver].
     Ot,
@@ -62,6 +66,33 @@ impl Display for SolverType {
     }
 }
 
+/// Hash families of the rows of [PaxosSolver](crate::solver::PaxosSolver) ([SolverType::Paxos] only).
+/// See [row_hash](crate::solver::paxos::row_hash).
+///
+/// All parties must use the same family. It is checked in the handshake as a part of the solver.
+#[derive(Clone, Copy, ValueEnum, Debug, PartialEq, Eq)]
+pub enum OkvsHash {
+    /// Keyed SHA-256. See [Sha256RowHash](crate::solver::paxos::Sha256RowHash).
+    Sha256,
+    /// CBC-MAC by AES-128. See [AesRowHash](crate::solver::paxos::AesRowHash).
+    Aes,
+    /// Keyed BLAKE3 (`okvs-hash` feature only). See [Blake3RowHash](crate::solver::paxos::Blake3RowHash).
+    Blake3,
+    /// SipHash-2-4 (`okvs-hash` feature only). See [SipRowHash](crate::solver::paxos::SipRowHash).
+    Siphash,
+}
+
+impl Display for OkvsHash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OkvsHash::Sha256 => write!(f, "sha256"),
+            OkvsHash::Aes => write!(f, "aes"),
+            OkvsHash::Blake3 => write!(f, "blake3"),
+            OkvsHash::Siphash => write!(f, "siphash"),
+        }
+    }
+}
+
 /// Channel types. Channels are used to communicate between parties. More details: [channel_utils](crate::channel_utils).
 #[derive(Clone, Copy, ValueEnum, Debug)]
 pub enum ChannelType {
@@ -103,6 +134,63 @@ impl Display for MultiThreadOptimization {
     }
 }
 
+/// Which phases to run in this invocation. See [state](crate::preprocessed::state).
+#[derive(Clone, Copy, ValueEnum, Debug, PartialEq, Eq)]
+pub enum PhaseType {
+    /// Run only the offline phase and save the state to `--state`.
+    Offline,
+    /// Load the state from `--state` and run only the online phase.
+    Online,
+    /// Run both phases in this invocation.
+    Both,
+}
+
+impl Display for PhaseType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PhaseType::Offline => write!(f, "offline"),
+            PhaseType::Online => write!(f, "online"),
+            PhaseType::Both => write!(f, "both"),
+        }
+    }
+}
+
+/// Role of the party that learns the intersection.
+#[derive(Clone, Copy, ValueEnum, Debug, PartialEq, Eq)]
+pub enum Role {
+    /// The receiver (PID 0) learns the intersection.
+    Receiver,
+    /// The sender of PID `--result-party`, which must be given, learns the intersection instead of the receiver.
+    Sender,
+}
+
+impl Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Role::Receiver => write!(f, "receiver"),
+            Role::Sender => write!(f, "sender"),
+        }
+    }
+}
+
+/// What the receiver does when a sender drops out of the run.
+#[derive(Clone, Copy, ValueEnum, Debug, PartialEq, Eq)]
+pub enum OnPartyDrop {
+    /// Abort the run with the PIDs of the dropped parties.
+    Abort,
+    /// Run the protocol again among the remaining parties, and output the intersection of their sets.
+    Continue,
+}
+
+impl Display for OnPartyDrop {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OnPartyDrop::Abort => write!(f, "abort"),
+            OnPartyDrop::Continue => write!(f, "continue"),
+        }
+    }
+}
+
 /// Arguments for Preprocessing MPSI protocol.
 /// This struct implements [clap::Parser] to make that this binary has CommandLine Arguments.
 #[derive(Parser, Debug)]
@@ -128,6 +216,12 @@ pub struct PrePSIArgs {
     #[arg(short = 's', long = "solver", default_value_t = SolverType::Paxos)]
     pub solver_type: SolverType,
 
+    /// Hash family of the rows of the Paxos solver.
+    ///
+    /// All parties must use the same one. Only with `--solver paxos`.
+    #[arg(long = "okvs-hash", default_value_t = OkvsHash::Sha256)]
+    pub okvs_hash: OkvsHash,
+
     /// Channel Types.
     #[arg(short = 'c', long = "channel", default_value_t = ChannelType::Unix)]
     pub channel_type: ChannelType,
@@ -149,6 +243,483 @@ pub struct PrePSIArgs {
     /// If specified, print the sets and the intersection.
     #[arg(long = "verbose", default_value_t = false)]
     pub verbose: bool,
+
+    /// Memory budget in MB.
+    ///
+    /// If specified, the run is aborted before starting when the estimated peak memory exceeds it.
+    #[arg(long = "max-memory-mb")]
+    pub max_memory_mb: Option<usize>,
+
+    /// Communication budget in MB.
+    ///
+    /// If specified, the run is aborted as soon as the bytes sent and received by the receiver would exceed it.
+    /// It is checked live on the channels, not estimated.
+    #[arg(long = "max-comm-mb")]
+    pub max_comm_mb: Option<f64>,
+
+    /// Input files of the parties' sets.
+    ///
+    /// Specify it as many times as the number of parties. The last one is the receiver's set.
+    /// `-` means the standard input, and can be given only once.
+    /// If not specified, random sets are used.
+    #[arg(long = "input")]
+    pub input: Vec<PathBuf>,
+
+    /// Format of input files.
+    #[arg(long = "input-format", default_value_t = InputFormat::Decimal)]
+    pub input_format: InputFormat,
+
+    /// Normalizations of string inputs (`--input-format hash` only).
+    ///
+    /// Comma separated list applied in the given order. All parties must use the same normalizations.
+    #[arg(long = "normalize", value_delimiter = ',', default_values_t = [Normalize::Trim])]
+    pub normalize: Vec<Normalize>,
+
+    /// What to do with duplicated elements in input files.
+    ///
+    /// Duplicates are compared after parsing (and normalization), so e.g. `7` and `07` are duplicates in decimal.
+    #[arg(long = "on-duplicate", default_value_t = OnDuplicate::Dedup)]
+    pub on_duplicate: OnDuplicate,
+
+    /// Validate-only mode.
+    ///
+    /// If specified, only load and parse the input files, report the number of elements and parse errors, and exit.
+    #[arg(long = "validate-only", default_value_t = false)]
+    pub validate_only: bool,
+
+    /// Comparison width in bits.
+    ///
+    /// The receiver compares only the lowest bits of the reconstructed sums with zero, and the senders send only them in the tables of
+    /// conditional reconstruction (paxos only). An element not in the intersection is output with probability 2^-bits,
+    /// so the false match probability is at most set_size * 2^-bits.
+    #[arg(long = "compare-bits", default_value_t = 128)]
+    pub compare_bits: usize,
+
+    /// Sort output.
+    ///
+    /// If specified, print the intersection sorted by the canonical byte representation of elements (one rawhex per line).
+    #[arg(long = "sort-output", default_value_t = false)]
+    pub sort_output: bool,
+
+    /// Output file of the intersection.
+    ///
+    /// If specified, the intersection is written to the file, one rawhex per line, so that it can be read back by `--input-format rawhex`.
+    /// If the file name ends with `.gz`, it is compressed by gzip.
+    #[arg(long = "output")]
+    pub output: Option<PathBuf>,
+
+    /// Compress `--output` by gzip even if the file name doesn't end with `.gz`.
+    #[arg(long = "output-compress", default_value_t = false)]
+    pub output_compress: bool,
+
+    /// Print commitments to the input sets.
+    ///
+    /// If specified, an order-independent commitment to each party's set is printed before the protocol, so that the inputs can be audited later. See [commit_set](crate::set_utils::commit_set).
+    #[arg(long = "print-commitments", default_value_t = false)]
+    pub print_commitments: bool,
+
+    /// Safety margin of the LPN tier selection.
+    ///
+    /// The smallest LPN parameter tier whose capacity is at least `m * (1 + margin)` is used, where `m` is the required length of VOLE. Only used with `--vole lpn`.
+    #[arg(long = "lpn-safety-margin", default_value_t = 0.1)]
+    pub lpn_safety_margin: f64,
+
+    /// Set size below which `--vole lpn` falls back to `--vole ot`.
+    ///
+    /// For tiny sets, the setup of LPN VOLE dominates the offline phase, and the OT-based VOLE is faster. `0` disables the fallback.
+    #[arg(long = "small-set-threshold", default_value_t = DEFAULT_SMALL_SET_THRESHOLD)]
+    pub small_set_threshold: usize,
+
+    /// Address of the metrics endpoint (e.g. `127.0.0.1:9100`).
+    ///
+    /// If specified, bytes sent and received by the receiver, the current phase and the progress are served in Prometheus text format. See [metrics](crate::metrics).
+    #[arg(long = "metrics-addr")]
+    pub metrics_addr: Option<String>,
+
+    /// Phases to run.
+    ///
+    /// `offline` saves the precomputed state of all parties to `--state`, and `online` loads it. The state is secret and must be used only once.
+    #[arg(long = "phase", default_value_t = PhaseType::Both)]
+    pub phase: PhaseType,
+
+    /// State file for `--phase offline` and `--phase online`.
+    #[arg(long = "state")]
+    pub state: Option<PathBuf>,
+
+    /// CSV file to append the timing and the communication of the run to.
+    ///
+    /// The header is written if the file is new. See [append_csv](crate::metrics::append_csv) for the columns.
+    #[arg(long = "metrics-csv")]
+    pub metrics_csv: Option<PathBuf>,
+
+    /// Run deterministically from `SEED` for certification.
+    ///
+    /// The RNGs of all parties and of the random sets (or the padding of `--input`) are derived from `SEED` instead of the OS randomness,
+    /// so two runs with the same arguments exchange the same bytes. Never use it in production: the seed reveals all the secrets.
+    #[arg(long = "deterministic", value_name = "SEED")]
+    pub deterministic: Option<u64>,
+
+    /// File to dump the transcript (all bytes sent between the parties) to.
+    ///
+    /// For each pair of PIDs `(from, to)` in ascending order, `from`, `to` and the length (u64 each, little endian) are followed by the bytes sent from `from` to `to`.
+    /// Use with `--deterministic` to get a byte-identical archive.
+    #[arg(long = "transcript")]
+    pub transcript: Option<PathBuf>,
+
+    /// Unix domain socket accepting `pause`, `resume` and `status` commands, one per line.
+    ///
+    /// The parties pause only at the boundaries of the phases. See [control](crate::control) for the replies.
+    #[arg(long = "control-socket")]
+    pub control_socket: Option<PathBuf>,
+
+    /// Load `--input` on a separate thread while the offline phase runs, and give the sets to the parties before the online phase.
+    ///
+    /// The offline phase is made for `--set-size`, so every input must have at most `--set-size` elements (they are padded to it).
+    #[arg(long = "overlap-input", default_value_t = false)]
+    pub overlap_input: bool,
+
+    /// Before the offline phase, exchange the version, field, solver, set size and solver params hash with every peer and report the result.
+    ///
+    /// See [warmup](crate::preprocessed::psi::warmup) for the checks.
+    #[arg(long = "warmup", default_value_t = false)]
+    pub warmup: bool,
+
+    /// Run only the warmup (implies `--warmup`), report it, and exit before the offline phase.
+    #[arg(long = "warmup-only", default_value_t = false)]
+    pub warmup_only: bool,
+
+    /// Limit the throughput of each party's outgoing link to `KBPS` kilobits per second, to reproduce a WAN locally.
+    ///
+    /// See [throttled_channel](crate::channel_utils::throttled_channel). Not specified means unlimited.
+    #[arg(long = "throttle-kbps", value_name = "KBPS")]
+    pub throttle_kbps: Option<u64>,
+
+    /// Latency in milliseconds added to each message (flush) sent by each party.
+    #[arg(long = "latency-ms", default_value_t = 0)]
+    pub latency_ms: u64,
+
+    /// Before sending each programmed table, decode all the programmed points locally and abort on any mismatch.
+    ///
+    /// It catches solver bugs or silent corruption before a bad table is sent, at the cost of one more decode per point.
+    #[arg(long = "verify-encode")]
+    pub verify_encode: bool,
+
+    /// Role of the party that learns the intersection.
+    #[arg(long = "role", default_value_t = Role::Receiver)]
+    pub role: Role,
+
+    /// PID of the party that learns the intersection: 0 (the receiver) with `--role receiver`,
+    /// or a sender (1 to `num_parties - 1`) with `--role sender`. Any other combination is rejected.
+    ///
+    /// With `--role sender`, the receiver computes the intersection as usual and sends it only to this sender, so the other senders get nothing.
+    /// With `--input`, party `i` has the `(num_parties - 1 - i)`-th input, so 0 is the last one.
+    #[arg(long = "result-party", default_value_t = 0)]
+    pub result_party: usize,
+
+    /// What to do when a sender drops out of the run.
+    ///
+    /// A sender whose own error is not a disconnection of a peer is dropped, and the others failed only because of it.
+    /// With `continue`, the protocol is run again from the offline phase among the remaining parties.
+    #[arg(long = "on-party-drop", default_value_t = OnPartyDrop::Abort)]
+    pub on_party_drop: OnPartyDrop,
+
+    /// Print the version and the optional features compiled in, and exit.
+    #[arg(long = "features", default_value_t = false)]
+    pub features: bool,
+
+    /// Subcommand run instead of the protocol.
+    #[command(subcommand)]
+    pub command: Option<PsiCommand>,
+}
+
+/// Subcommands of `prep_psi`.
+#[derive(Subcommand, Debug, Clone)]
+pub enum PsiCommand {
+    /// Open a TCP connection, exchange a ping and a pong, and print the round-trip time. See [ping](crate::preprocessed::psi::ping).
+    Ping(PingArgs),
+}
+
+/// Arguments of `prep_psi ping`. Run `--listen` on one end and `--connect` on the other.
+#[derive(Args, Debug, Clone)]
+#[group(required = true, multiple = false)]
+pub struct PingArgs {
+    /// Address to connect to, e.g. `192.0.2.1:10000`.
+    #[arg(long = "connect")]
+    pub connect: Option<String>,
+
+    /// Address to listen on for one connection, e.g. `0.0.0.0:10000`.
+    #[arg(long = "listen")]
+    pub listen: Option<String>,
+}
+
+/// Arguments of `prep_psi_sweep`, the benchmark sweep. See [sweep](crate::preprocessed::psi::sweep).
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None, next_line_help = true)]
+pub struct SweepArgs {
+    /// Number of participants in the protocol.
+    #[arg(short = 'N', long, default_value_t = 3)]
+    pub num_parties: usize,
+
+    /// The smallest set size is 2^min_log_size.
+    #[arg(long = "min-log-size", default_value_t = 10)]
+    pub min_log_size: u32,
+
+    /// The largest set size is 2^max_log_size.
+    #[arg(long = "max-log-size", default_value_t = 18)]
+    pub max_log_size: u32,
+
+    /// Solvers to sweep, comma separated.
+    #[arg(long = "solver", value_delimiter = ',', default_values_t = [SolverType::Vandelmonde, SolverType::Paxos])]
+    pub solvers: Vec<SolverType>,
+
+    /// LPN parameter tiers to sweep, comma separated. Points whose tier is too small for the code length are skipped.
+    #[arg(long = "lpn-tier", value_delimiter = ',', default_values_t = [LpnTier::Small, LpnTier::Medium])]
+    pub tiers: Vec<LpnTier>,
+
+    /// Output CSV file. If not specified, the CSV is printed to the standard output.
+    #[arg(long = "output")]
+    pub output: Option<PathBuf>,
+
+    /// Number of runs of each point, each over freshly generated sets. With more than 1, the mean, median and standard deviation of each measurement are reported instead.
+    #[arg(long = "trials", default_value_t = 1)]
+    pub trials: usize,
+}
+
+impl PrePSIArgs {
+    /// Reject contradictory combinations of flags. [run](crate::preprocessed::psi::run) calls it before creating any channel.
+    ///
+    /// The error message names the conflicting flags.
+    pub fn validate(&self) -> Result<()> {
+        if self.validate_only {
+            if self.input.is_empty() {
+                bail!("--validate-only requires at least one --input.");
+            }
+            if self.metrics_addr.is_some() {
+                bail!("--metrics-addr cannot be used with --validate-only: no protocol is run.");
+            }
+            if self.sort_output {
+                bail!("--sort-output cannot be used with --validate-only: no intersection is computed.");
+            }
+            if self.output.is_some() {
+                bail!("--output cannot be used with --validate-only: no intersection is computed.");
+            }
+            if self.print_commitments {
+                bail!(
+                    "--print-commitments cannot be used with --validate-only: no protocol is run."
+                );
+            }
+            if self.max_memory_mb.is_some() {
+                bail!("--max-memory-mb cannot be used with --validate-only: no protocol is run.");
+            }
+            if self.max_comm_mb.is_some() {
+                bail!("--max-comm-mb cannot be used with --validate-only: no protocol is run.");
+            }
+            if self.phase != PhaseType::Both {
+                bail!("--phase cannot be used with --validate-only: no protocol is run.");
+            }
+            if self.role != Role::Receiver {
+                bail!("--role cannot be used with --validate-only: no intersection is computed.");
+            }
+            if self.result_party != 0 {
+                bail!("--result-party cannot be used with --validate-only: no intersection is computed.");
+            }
+            if self.metrics_csv.is_some() {
+                bail!("--metrics-csv cannot be used with --validate-only: no protocol is run.");
+            }
+            if self.deterministic.is_some() {
+                bail!("--deterministic cannot be used with --validate-only: no protocol is run.");
+            }
+            if self.transcript.is_some() {
+                bail!("--transcript cannot be used with --validate-only: no protocol is run.");
+            }
+            if self.control_socket.is_some() {
+                bail!("--control-socket cannot be used with --validate-only: no protocol is run.");
+            }
+            if self.overlap_input {
+                bail!("--overlap-input cannot be used with --validate-only: no protocol is run.");
+            }
+            if self.warmup {
+                bail!("--warmup cannot be used with --validate-only: no protocol is run.");
+            }
+            if self.warmup_only {
+                bail!("--warmup-only cannot be used with --validate-only: no protocol is run.");
+            }
+            if self.throttle_kbps.is_some() {
+                bail!("--throttle-kbps cannot be used with --validate-only: no protocol is run.");
+            }
+            if self.latency_ms != 0 {
+                bail!("--latency-ms cannot be used with --validate-only: no protocol is run.");
+            }
+            if self.verify_encode {
+                bail!("--verify-encode cannot be used with --validate-only: no protocol is run.");
+            }
+        } else if !self.input.is_empty() && self.input.len() != self.num_parties {
+            bail!(
+                "--input is given {} times but --num-parties is {}. Give one --input per party.",
+                self.input.len(),
+                self.num_parties
+            );
+        }
+
+        let stdin_count = self.input.iter().filter(|path| is_stdin(path)).count();
+        if stdin_count > 1 {
+            bail!(
+                "--input - (the standard input) is given {} times, but it can be read only once.",
+                stdin_count
+            );
+        }
+
+        match (self.phase, &self.state) {
+            (PhaseType::Both, Some(_)) => {
+                bail!("--state is only used with --phase offline or --phase online.")
+            }
+            (PhaseType::Offline | PhaseType::Online, None) => {
+                bail!("--phase {} requires --state.", self.phase)
+            }
+            _ => {}
+        }
+
+        match (self.solver_type, self.okvs_hash) {
+            (SolverType::Vandelmonde, OkvsHash::Sha256) | (SolverType::Paxos, _) => {}
+            (SolverType::Vandelmonde, hash) => bail!(
+                "--okvs-hash {} cannot be used with --solver vandelmonde: it hashes no rows.",
+                hash
+            ),
+        }
+
+        if !cfg!(feature = "okvs-hash")
+            && matches!(self.okvs_hash, OkvsHash::Blake3 | OkvsHash::Siphash)
+        {
+            bail!(
+                "--okvs-hash {} requires the okvs-hash feature.",
+                self.okvs_hash
+            );
+        }
+
+        if self.output_compress && self.output.is_none() {
+            bail!("--output-compress requires --output.");
+        }
+
+        if let Some(mb) = self.max_comm_mb {
+            if !(mb > 0.0 && mb.is_finite()) {
+                bail!("--max-comm-mb (={}) must be positive.", mb);
+            }
+        }
+
+        if self.throttle_kbps == Some(0) {
+            bail!("--throttle-kbps must be positive. Omit it for an unlimited link.");
+        }
+
+        if self.overlap_input {
+            if self.input.is_empty() {
+                bail!("--overlap-input requires --input.");
+            }
+            if self.phase != PhaseType::Both {
+                bail!(
+                    "--overlap-input cannot be used with --phase {}: the inputs are loaded during the offline phase of the same run.",
+                    self.phase
+                );
+            }
+        }
+
+        if self.phase == PhaseType::Offline {
+            if !self.input.is_empty() {
+                bail!("--input cannot be used with --phase offline: sets are used only in the online phase.");
+            }
+            if self.sort_output {
+                bail!("--sort-output cannot be used with --phase offline: no intersection is computed.");
+            }
+            if self.output.is_some() {
+                bail!("--output cannot be used with --phase offline: no intersection is computed.");
+            }
+            if self.print_commitments {
+                bail!("--print-commitments cannot be used with --phase offline: sets are used only in the online phase.");
+            }
+            if self.role != Role::Receiver {
+                bail!("--role cannot be used with --phase offline: no intersection is computed.");
+            }
+            if self.result_party != 0 {
+                bail!("--result-party cannot be used with --phase offline: no intersection is computed.");
+            }
+            if self.verify_encode {
+                bail!("--verify-encode cannot be used with --phase offline: tables are encoded only in the online phase.");
+            }
+        }
+
+        if self.result_party >= self.num_parties {
+            bail!(
+                "--result-party (={}) must be less than --num-parties (={}).",
+                self.result_party,
+                self.num_parties
+            );
+        }
+        match self.role {
+            Role::Receiver if self.result_party != 0 => bail!(
+                "--result-party (={}) is a sender, but --role is receiver. Use --role sender.",
+                self.result_party
+            ),
+            Role::Sender if self.result_party == 0 => bail!(
+                "--role sender requires --result-party of a sender (1 to {}): PID 0 is the receiver.",
+                self.num_parties - 1
+            ),
+            Role::Receiver | Role::Sender => {}
+        }
+
+        if self.on_party_drop == OnPartyDrop::Continue {
+            if self.phase != PhaseType::Both {
+                bail!(
+                    "--on-party-drop continue cannot be used with --phase {}: the remaining parties run again from the offline phase.",
+                    self.phase
+                );
+            }
+            if self.overlap_input {
+                bail!("--on-party-drop continue cannot be used with --overlap-input: the sets must be kept for the remaining parties.");
+            }
+            if self.role != Role::Receiver {
+                bail!("--on-party-drop continue requires --role receiver: the receiver decides to continue.");
+            }
+        }
+
+        if self.input.is_empty() && !self.validate_only && self.common_size > self.set_size {
+            bail!(
+                "--common-size (={}) must not exceed --set-size (={}).",
+                self.common_size,
+                self.set_size
+            );
+        }
+
+        if !matches!(self.input_format, InputFormat::Hash) && self.normalize != [Normalize::Trim] {
+            bail!(
+                "--normalize is only applied with --input-format hash, but --input-format is {}.",
+                self.input_format
+            );
+        }
+
+        if self.compare_bits == 0 || self.compare_bits > field_bits::<F128b>() {
+            bail!(
+                "--compare-bits (={}) must be in 1..={}.",
+                self.compare_bits,
+                field_bits::<F128b>()
+            );
+        }
+
+        if self.lpn_safety_margin.is_nan() || self.lpn_safety_margin < 0.0 {
+            bail!(
+                "--lpn-safety-margin (={}) must be non-negative.",
+                self.lpn_safety_margin
+            );
+        }
+
+        if matches!(self.channel_type, ChannelType::Tcp) && self.port + self.num_parties > 1 << 16 {
+            bail!(
+                "--port (={}) is too large for --channel tcp with --num-parties {}: ports up to port + num_parties are used.",
+                self.port,
+                self.num_parties
+            );
+        }
+
+        Ok(())
+    }
 }
 
 /// Arguments for Kmprt protocol.
@@ -296,6 +867,13 @@ impl VoleShareForReceiver<F128b> for VoleShareForReceiverUnion {
             VoleShareForReceiverUnion::Lpn(v) => v.receive(channel, rng, m),
         }
     }
+
+    fn describe(&self) -> String {
+        match self {
+            VoleShareForReceiverUnion::Ot(v) => v.describe(),
+            VoleShareForReceiverUnion::Lpn(v) => v.describe(),
+        }
+    }
 }
 
 impl VoleShareForSender<F128b> for VoleShareForSenderUnion {
@@ -310,41 +888,70 @@ impl VoleShareForSender<F128b> for VoleShareForSenderUnion {
             VoleShareForSenderUnion::Lpn(v) => v.receive(channel, rng, m),
         }
     }
+
+    fn describe(&self) -> String {
+        match self {
+            VoleShareForSenderUnion::Ot(v) => v.describe(),
+            VoleShareForSenderUnion::Lpn(v) => v.describe(),
+        }
+    }
 }
 
 fn create_lpn_vole_sr<S: Solver<F128b>>(
     set_size: usize,
-) -> (LPNVoleSender<F128b>, LPNVoleReceiver<F128b>) {
+    safety_margin: f64,
+) -> Result<(LPNVoleSender<F128b>, LPNVoleReceiver<F128b>)> {
     let m_size = S::calc_params(set_size).code_length();
-    let (setup_param, extend_param) = if m_size < (1 << 17) {
-        println!("Small parameters are used.");
-        (LPN_SETUP_SMALL, LPN_EXTEND_SMALL)
-    } else {
-        println!("Medium parameters are used.");
-        (LPN_SETUP_MEDIUM, LPN_EXTEND_MEDIUM)
-    };
-    (
+    let tier = select_tier::<F128b>(m_size, safety_margin)
+        .with_context(|| format!("@{}:{}", file!(), line!()))?;
+    let capacity =
+        measure_capacity::<F128b>(tier).with_context(|| format!("@{}:{}", file!(), line!()))?;
+    println!(
+        "LPN tier: {} (capacity {} >= required {})",
+        tier, capacity, m_size
+    );
+    let (setup_param, extend_param) = tier.params();
+    Ok((
         LPNVoleSender::new(setup_param, extend_param),
         LPNVoleReceiver::new(setup_param, extend_param),
-    )
+    ))
+}
+
+/// Default of `--small-set-threshold`. See [select_vole_type].
+pub const DEFAULT_SMALL_SET_THRESHOLD: usize = 64;
+
+/// VOLE actually used for `set_size`: [VoleType::Lpn] is replaced by [VoleType::Ot] if `set_size < small_set_threshold`.
+///
+/// Both sides select the same VOLE since all parties agree on the set size.
+pub fn select_vole_type(
+    vole_type: VoleType,
+    set_size: usize,
+    small_set_threshold: usize,
+) -> VoleType {
+    match vole_type {
+        VoleType::Lpn if set_size < small_set_threshold => VoleType::Ot,
+        _ => vole_type,
+    }
 }
 
 /// Create vole sender and receiver for the protocol. Runtime utility.
 pub fn create_vole_sr<S: Solver<F128b>>(
     vole_type: VoleType,
     set_size: usize,
-) -> (VoleShareForSenderUnion, VoleShareForReceiverUnion) {
+    lpn_safety_margin: f64,
+) -> Result<(VoleShareForSenderUnion, VoleShareForReceiverUnion)> {
     match vole_type {
-        VoleType::Ot => (
+        VoleType::Ot => Ok((
             VoleShareForSenderUnion::Ot(OtVoleSender::new()),
             VoleShareForReceiverUnion::Ot(OtVoleReceiver::new()),
-        ),
+        )),
         VoleType::Lpn => {
-            let (s, r) = create_lpn_vole_sr::<S>(set_size);
-            (
+            let (s, r) = create_lpn_vole_sr::<S>(set_size, lpn_safety_margin)
+                .with_context(|| format!("@{}:{}", file!(), line!()))?;
+            Ok((
                 VoleShareForSenderUnion::Lpn(s),
                 VoleShareForReceiverUnion::Lpn(r),
-            )
+            ))
         }
     }
 }
@@ -0,0 +1,214 @@
+//! Control socket to pause and resume a running protocol, e.g. to free CPU for higher-priority work (`prep_psi --control-socket`).
+//!
+//! [serve] listens on a Unix domain socket and answers each command line with one line:
+//!
+//! | Command  | Reply                  | Effect                                               |
+//! | :------- | :--------------------- | :--------------------------------------------------- |
+//! | `pause`  | `ok paused`            | Parties stop at their next boundary                  |
+//! | `resume` | `ok running`           | Paused parties continue                              |
+//! | `status` | `paused phase=offline` | Whether the run is paused, and the current [Phase]   |
+//!
+//! The parties check the flag only at the boundaries of the phases ([Control::wait_if_paused]),
+//! i.e. before the offline phase and between the offline and the online phase, so that no message is cut and the cryptographic state is kept as it is.
+//! A party already in a phase finishes it, and its peers may wait for a paused party.
+
+use crate::metrics::{Metrics, Phase};
+use anyhow::{Context, Result};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::{Arc, Condvar, Mutex};
+
+/// Pause flag shared by the parties and the control socket.
+#[derive(Debug, Default)]
+pub struct Control {
+    paused: Mutex<bool>,
+    resumed: Condvar,
+}
+
+impl Control {
+    /// Create a control which is not paused.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pause the parties at their next boundary.
+    pub fn pause(&self) {
+        *self.paused.lock().unwrap() = true;
+    }
+
+    /// Resume the paused parties.
+    pub fn resume(&self) {
+        *self.paused.lock().unwrap() = false;
+        self.resumed.notify_all();
+    }
+
+    /// Whether the parties are paused.
+    pub fn is_paused(&self) -> bool {
+        *self.paused.lock().unwrap()
+    }
+
+    /// Block until resumed if paused. Called by the parties at the boundaries of the phases.
+    pub fn wait_if_paused(&self) {
+        let paused = self.paused.lock().unwrap();
+        let _paused = self.resumed.wait_while(paused, |paused| *paused).unwrap();
+    }
+
+    /// Reply to one command line. See the [module document](crate::control) for the commands.
+    pub fn command(&self, command: &str, phase: Phase) -> String {
+        match command.trim() {
+            "pause" => {
+                self.pause();
+                "ok paused".to_string()
+            }
+            "resume" => {
+                self.resume();
+                "ok running".to_string()
+            }
+            "status" => {
+                let state = if self.is_paused() {
+                    "paused"
+                } else {
+                    "running"
+                };
+                format!("{} phase={}", state, phase)
+            }
+            other => format!("error unknown command {:?}", other),
+        }
+    }
+}
+
+fn respond(stream: UnixStream, control: &Control, metrics: &Metrics) -> std::io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let reply = control.command(&line?, metrics.phase());
+        writeln!(writer, "{}", reply)?;
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+/// Serve `control` on a Unix domain socket at `path` in a background thread. `metrics` gives the phase reported by `status`.
+///
+/// The thread runs until the process exits. The socket file is not removed, so remove it after the run.
+pub fn serve<P: AsRef<Path>>(path: P, control: Arc<Control>, metrics: Arc<Metrics>) -> Result<()> {
+    let path = path.as_ref();
+    let listener = UnixListener::bind(path).with_context(|| {
+        format!(
+            "Failed to bind {}. @{}:{}",
+            path.display(),
+            file!(),
+            line!()
+        )
+    })?;
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let control = Arc::clone(&control);
+            let metrics = Arc::clone(&metrics);
+            // a client may keep its connection open, so that each one is served by its own thread.
+            std::thread::spawn(move || {
+                // a broken client must not stop the socket.
+                let _ = respond(stream, &control, &metrics);
+            });
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channel_utils::channel_from;
+    use crate::preprocessed::psi::{Receiver, Sender};
+    use crate::set_utils::create_sets_without_check;
+    use crate::solver::PaxosSolver;
+    use crate::vole::{LPNVoleReceiver, LPNVoleSender, LPN_EXTEND_SMALL, LPN_SETUP_SMALL};
+    use scuttlebutt::field::F128b;
+    use scuttlebutt::AesRng;
+    use std::collections::HashSet;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_control_pause_resume() {
+        let path =
+            std::env::temp_dir().join(format!("prep_psi_control_{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let control = Arc::new(Control::new());
+        let metrics = Arc::new(Metrics::new());
+        serve(&path, Arc::clone(&control), Arc::clone(&metrics)).unwrap();
+
+        let stream = UnixStream::connect(&path).unwrap();
+        let mut writer = stream.try_clone().unwrap();
+        let mut lines = BufReader::new(stream).lines();
+        let mut send = |command: &str| {
+            writeln!(writer, "{}", command).unwrap();
+            lines.next().unwrap().unwrap()
+        };
+
+        assert_eq!(send("status"), "running phase=preparing");
+        assert_eq!(send("pause"), "ok paused");
+        assert_eq!(send("status"), "paused phase=preparing");
+        assert!(send("stop").starts_with("error"));
+
+        // the parties stop at the boundary before the offline phase.
+        let mut rng = AesRng::new();
+        let (intersection, mut sets) =
+            create_sets_without_check::<F128b, _>(2, 20, 5, &mut rng).unwrap();
+        let r_set = sets.pop().unwrap();
+        let s_set = sets.pop().unwrap();
+        let (s, r) = UnixStream::pair().unwrap();
+
+        let control_for_s = Arc::clone(&control);
+        let handle = std::thread::spawn(move || {
+            control_for_s.wait_if_paused();
+            let mut rng = AesRng::new();
+            let mut channels = vec![(0, channel_from(s).unwrap())];
+            let sender = Sender::<F128b, PaxosSolver<F128b>, _, _>::precomp(
+                1,
+                &mut channels,
+                &mut rng,
+                LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+                LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+                s_set.len(),
+            )
+            .unwrap();
+            control_for_s.wait_if_paused();
+            sender.send(&s_set, &mut channels, &mut rng).unwrap();
+        });
+
+        let (tx, rx) = mpsc::channel();
+        let control_for_r = Arc::clone(&control);
+        let receiver_handle = std::thread::spawn(move || {
+            control_for_r.wait_if_paused();
+            let mut rng = AesRng::new();
+            let mut channels = vec![(1, channel_from(r).unwrap())];
+            let receiver = Receiver::<F128b, PaxosSolver<F128b>, _, _>::precomp(
+                &mut channels,
+                &mut rng,
+                LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+                LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+                r_set.len(),
+            )
+            .unwrap();
+            control_for_r.wait_if_paused();
+            tx.send(receiver.receive(&r_set, &mut channels, &mut rng).unwrap())
+                .unwrap();
+        });
+
+        assert!(rx.recv_timeout(Duration::from_millis(300)).is_err());
+
+        assert_eq!(send("resume"), "ok running");
+        let res = rx.recv_timeout(Duration::from_secs(60)).unwrap();
+        handle.join().unwrap();
+        receiver_handle.join().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let res: HashSet<F128b> = HashSet::from_iter(res);
+        let intersection: HashSet<F128b> = HashSet::from_iter(intersection);
+        assert_eq!(res, intersection);
+    }
+}
@@ -0,0 +1,152 @@
+//! Typed errors of this library.
+//!
+//! Functions in this library return [anyhow::Error]. The errors that callers may want to handle are made from [PsiError],
+//! so you can check them by `err.downcast_ref::<PsiError>()`.
+
+use thiserror::Error;
+
+/// Errors which callers may want to distinguish.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum PsiError {
+    /// The estimated peak memory of the run exceeds the given budget.
+    #[error("memory budget exceeded: the run requires about {required_mb} MB, but the budget is {budget_mb} MB")]
+    MemoryBudgetExceeded {
+        /// Estimated peak memory in MB.
+        required_mb: usize,
+        /// Given budget in MB.
+        budget_mb: usize,
+    },
+
+    /// The bytes sent and received in the run crossed the given budget, checked live by
+    /// [CountingChannel::with_budget](crate::channel_utils::counting_channel::CountingChannel::with_budget).
+    #[error("communication budget exceeded: {used} bytes are sent and received, but the budget is {budget} bytes")]
+    CommBudgetExceeded {
+        /// Bytes sent and received before the channel refused to go on.
+        used: u64,
+        /// Given budget in bytes.
+        budget: u64,
+    },
+
+    /// Some keys hash to identical positions of the solver, so the points can't be encoded.
+    #[error("hard key-hash collision: the keys {keys:?} hash to identical OKVS positions")]
+    KeyHashCollision {
+        /// Pairs of colliding keys (formatted by `Debug`).
+        keys: Vec<(String, String)>,
+    },
+
+    /// Some programmed points don't decode to their values under the freshly encoded table, found by the local check of `--verify-encode`.
+    ///
+    /// It means a solver bug or corruption, so the table is not sent.
+    #[error("encode verification failed: {mismatches} of {points} programmed points don't decode to their values")]
+    EncodeVerificationFailed {
+        /// Number of points decoding to wrong values.
+        mismatches: usize,
+        /// Number of programmed points.
+        points: usize,
+    },
+
+    /// The OPPRF sender was asked to program no points.
+    ///
+    /// An empty table would make the outputs meaningless, so the sender refuses to send it instead of defining them.
+    #[error("empty program: the OPPRF sender has no points to program")]
+    EmptyProgram,
+
+    /// A serialized artifact or an input set was made for a different field from the compile-time one.
+    #[error("field mismatch: expected {expected} ({expected_len} bytes), but found {found} ({found_len} bytes)")]
+    FieldMismatch {
+        /// Name of the compile-time field.
+        expected: String,
+        /// Byte length of the compile-time field.
+        expected_len: usize,
+        /// Name of the field recorded in the artifact.
+        found: String,
+        /// Byte length recorded in the artifact.
+        found_len: usize,
+    },
+
+    /// A peer declared a vector longer than the cap of [read_vec_f](crate::channel_utils::read_vec_f), so it is not read.
+    ///
+    /// The cap is [DEFAULT_MAX_VEC_ELEMENTS](crate::channel_utils::DEFAULT_MAX_VEC_ELEMENTS), or the one given to
+    /// [read_vec_f_capped](crate::channel_utils::read_vec_f_capped).
+    #[error(
+        "message too large: the peer declared {elements} elements, but at most {max} are accepted"
+    )]
+    MessageTooLarge {
+        /// Number of elements declared by the length prefix.
+        elements: usize,
+        /// The cap.
+        max: usize,
+    },
+
+    /// The intersection is larger than the cap on revealed elements, so it is not output.
+    #[error(
+        "reveal cap exceeded: the intersection is larger than the cap ({cap}) and is not revealed"
+    )]
+    RevealCapExceeded {
+        /// Maximum number of elements allowed to be revealed.
+        cap: usize,
+    },
+
+    /// A peer declared a different set size from ours in the handshake of precomp.
+    #[error("set size mismatch: party {peer} declared set size {theirs}, but ours is {ours}")]
+    SetSizeMismatch {
+        /// ID of the peer.
+        peer: usize,
+        /// Our declared set size.
+        ours: usize,
+        /// Set size declared by the peer.
+        theirs: usize,
+    },
+
+    /// A peer uses a different solver from ours, declared in the handshake of precomp.
+    ///
+    /// The ids are [Solver::ID](crate::solver::Solver::ID).
+    #[error("solver mismatch: party {peer} uses solver id {theirs}, but ours is {ours}")]
+    SolverMismatch {
+        /// ID of the peer.
+        peer: usize,
+        /// Our solver id.
+        ours: u8,
+        /// Solver id declared by the peer.
+        theirs: u8,
+    },
+
+    /// The Merkle root of a cached programmed table differs from the one recorded when it was cached.
+    #[error(
+        "table tampered: the Merkle root of the programmed table doesn't match the recorded one"
+    )]
+    TableTampered,
+
+    /// A saved offline state (VOLE correlations) is made for a different number of queries from the requested one.
+    #[error(
+        "VOLE size mismatch: the state is made for {stored} queries, but {requested} are requested"
+    )]
+    VoleSizeMismatch {
+        /// Number of queries recorded in the state.
+        stored: usize,
+        /// Number of queries requested on load.
+        requested: usize,
+    },
+
+    /// The peer supplied different hash seeds of the solver from ours (See
+    /// [OpprfConfig::with_hash_seed](crate::preprocessed::opprf::OpprfConfig::with_hash_seed)).
+    #[error("hash seed mismatch: the peer supplied different hash seeds of the solver from ours")]
+    HashSeedMismatch,
+
+    /// A [MultiplexedChannel](crate::channel_utils::multiplexed_channel::MultiplexedChannel) was asked for a new session
+    /// while as many sessions as its limit are open.
+    #[error("too many sessions: at most {max} sessions can be open at once")]
+    TooManySessions {
+        /// Limit of concurrent sessions.
+        max: usize,
+    },
+
+    /// Senders dropped out of the run, and the others failed only because of them.
+    ///
+    /// The handshake of precomp returns it with the peers which are disconnected in it.
+    #[error("party dropped: the parties {pids:?} dropped out of the run")]
+    PartyDropped {
+        /// PIDs of the dropped parties.
+        pids: Vec<usize>,
+    },
+}
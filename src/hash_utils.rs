@@ -5,9 +5,20 @@ use typenum::marker_traits::Unsigned;
 
 /// Hash Function s.t.
 /// H: F x F -> F
+#[allow(dead_code)]
 #[inline]
 pub fn hash<F: FF>(x: F, y: F) -> Result<F> {
+    hash_with_tag(&[], x, y)
+}
+
+/// Hash Function with domain separation tag s.t.
+/// H_tag: F x F -> F
+///
+/// If `tag` is empty, it is same as [hash].
+#[inline]
+pub fn hash_with_tag<F: FF>(tag: &[u8], x: F, y: F) -> Result<F> {
     let mut hasher = Sha256::new();
+    hasher.update(tag);
     hasher.update(x.to_bytes());
     hasher.update(y.to_bytes());
     let res = hasher.finalize();
@@ -60,6 +71,19 @@ mod tests {
         assert_eq!(h, h2);
     }
 
+    #[test]
+    fn test_hash_with_tag() {
+        let mut rng = AesRng::new();
+        let x: F128b = rng.gen();
+        let y: F128b = rng.gen();
+
+        assert_eq!(hash_with_tag(&[], x, y).unwrap(), hash(x, y).unwrap());
+        assert_ne!(
+            hash_with_tag(b"tag1", x, y).unwrap(),
+            hash_with_tag(b"tag2", x, y).unwrap()
+        );
+    }
+
     #[test]
     fn test_hash_f() {
         let mut rng = AesRng::new();
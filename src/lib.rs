@@ -7,10 +7,16 @@
 
 pub mod channel_utils;
 pub mod cli_utils;
+pub mod control;
+pub mod error;
 mod hash_utils;
 pub mod kmprt17;
+pub mod metrics;
 pub mod preprocessed;
 pub mod rs21;
 pub mod set_utils;
 pub mod solver;
+mod version;
 pub mod vole;
+
+pub use version::{version_info, VersionInfo};
@@ -0,0 +1,341 @@
+//! Prometheus-style metrics of a running protocol.
+//!
+//! [Metrics] holds the byte counters of [CountingChannel](crate::channel_utils::counting_channel::CountingChannel)s and the current [Phase].
+//! [serve] spawns a background thread answering every HTTP request with [Metrics::render], i.e. the [Prometheus text format](https://prometheus.io/docs/instrumenting/exposition_formats/).
+//!
+//! | Metric                           | Type    | Content                                     |
+//! | :------------------------------- | :------ | :------------------------------------------ |
+//! | `prep_psi_bytes_sent_total`      | counter | Bytes sent by the counted channels          |
+//! | `prep_psi_bytes_received_total`  | counter | Bytes received by the counted channels      |
+//! | `prep_psi_phase{phase="..."}`    | gauge   | 1 for the current phase, 0 for the others   |
+//! | `prep_psi_progress_percent`      | gauge   | Percentage of the phases already finished   |
+//!
+//! [append_csv] appends a [RunRecord] of a finished run to a CSV file (See [CSV_HEADER]), so that the runs can be collected across invocations.
+
+use crate::channel_utils::counting_channel::ByteCounters;
+use anyhow::{Context, Result};
+use std::fmt::{Display, Write as _};
+use std::fs::OpenOptions;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Phases of a run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    /// Creating sets, channels and VOLE shares.
+    Preparing,
+    /// Offline phase (VOLE sharing).
+    Offline,
+    /// Online phase.
+    Online,
+    /// The run is finished.
+    Finished,
+}
+
+impl Phase {
+    const ALL: [Phase; 4] = [
+        Phase::Preparing,
+        Phase::Offline,
+        Phase::Online,
+        Phase::Finished,
+    ];
+
+    fn index(self) -> usize {
+        Self::ALL.iter().position(|p| *p == self).unwrap()
+    }
+}
+
+impl Display for Phase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Phase::Preparing => write!(f, "preparing"),
+            Phase::Offline => write!(f, "offline"),
+            Phase::Online => write!(f, "online"),
+            Phase::Finished => write!(f, "finished"),
+        }
+    }
+}
+
+/// Metrics shared by the protocol and the HTTP endpoint.
+#[derive(Debug)]
+pub struct Metrics {
+    counters: Arc<ByteCounters>,
+    phase: AtomicUsize,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    /// Create metrics in [Phase::Preparing] with zero counters.
+    pub fn new() -> Self {
+        Self {
+            counters: Arc::new(ByteCounters::default()),
+            phase: AtomicUsize::new(Phase::Preparing.index()),
+        }
+    }
+
+    /// Counters to pass to [CountingChannel::new](crate::channel_utils::counting_channel::CountingChannel::new).
+    pub fn counters(&self) -> Arc<ByteCounters> {
+        Arc::clone(&self.counters)
+    }
+
+    /// Set the current phase.
+    pub fn set_phase(&self, phase: Phase) {
+        self.phase.store(phase.index(), Ordering::Relaxed);
+    }
+
+    /// Current phase.
+    pub fn phase(&self) -> Phase {
+        Phase::ALL[self.phase.load(Ordering::Relaxed)]
+    }
+
+    /// Percentage of the phases already finished.
+    pub fn progress_percent(&self) -> f64 {
+        self.phase().index() as f64 * 100.0 / (Phase::ALL.len() - 1) as f64
+    }
+
+    /// Render the metrics in Prometheus text format.
+    pub fn render(&self) -> String {
+        let mut s = String::new();
+        let current = self.phase();
+
+        writeln!(s, "# HELP prep_psi_bytes_sent_total Bytes sent.").unwrap();
+        writeln!(s, "# TYPE prep_psi_bytes_sent_total counter").unwrap();
+        writeln!(s, "prep_psi_bytes_sent_total {}", self.counters.sent()).unwrap();
+        writeln!(s, "# HELP prep_psi_bytes_received_total Bytes received.").unwrap();
+        writeln!(s, "# TYPE prep_psi_bytes_received_total counter").unwrap();
+        writeln!(
+            s,
+            "prep_psi_bytes_received_total {}",
+            self.counters.received()
+        )
+        .unwrap();
+        writeln!(s, "# HELP prep_psi_phase Current phase.").unwrap();
+        writeln!(s, "# TYPE prep_psi_phase gauge").unwrap();
+        for phase in Phase::ALL {
+            writeln!(
+                s,
+                "prep_psi_phase{{phase=\"{}\"}} {}",
+                phase,
+                (phase == current) as u8
+            )
+            .unwrap();
+        }
+        writeln!(
+            s,
+            "# HELP prep_psi_progress_percent Percentage of finished phases."
+        )
+        .unwrap();
+        writeln!(s, "# TYPE prep_psi_progress_percent gauge").unwrap();
+        writeln!(s, "prep_psi_progress_percent {}", self.progress_percent()).unwrap();
+
+        s
+    }
+}
+
+/// Header line of the CSV written by [append_csv].
+pub const CSV_HEADER: &str =
+    "num_parties,set_size,solver,vole,phase,offline_ms,online_ms,offline_bytes,online_bytes";
+
+/// Timing and communication of one run, measured on the receiver.
+#[derive(Clone, Debug)]
+pub struct RunRecord {
+    /// Number of parties including the receiver.
+    pub num_parties: usize,
+    /// Set size of each party.
+    pub set_size: usize,
+    /// Solver name. e.g. `paxos`
+    pub solver: String,
+    /// VOLE description. e.g. `LPN(small)`
+    pub vole: String,
+    /// Phases run. e.g. `both`
+    pub phase: String,
+    /// Time of the offline phase (importing the state with `--phase online`).
+    pub offline: Duration,
+    /// Time of the online phase. `None` if it was not run.
+    pub online: Option<Duration>,
+    /// Bytes sent and received in the offline phase.
+    pub offline_bytes: u64,
+    /// Bytes sent and received in the online phase.
+    pub online_bytes: u64,
+}
+
+impl RunRecord {
+    /// Format as a CSV row in the order of [CSV_HEADER]. `online_ms` is empty if the online phase was not run.
+    pub fn to_csv(&self) -> String {
+        format!(
+            "{},{},{},{},{},{:.3},{},{},{}",
+            self.num_parties,
+            self.set_size,
+            self.solver,
+            self.vole,
+            self.phase,
+            self.offline.as_secs_f64() * 1000.0,
+            self.online
+                .map(|d| format!("{:.3}", d.as_secs_f64() * 1000.0))
+                .unwrap_or_default(),
+            self.offline_bytes,
+            self.online_bytes
+        )
+    }
+}
+
+/// Append `record` to the CSV file at `path`. [CSV_HEADER] is written first if the file is new or empty.
+pub fn append_csv<P: AsRef<Path>>(path: P, record: &RunRecord) -> Result<()> {
+    let path = path.as_ref();
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| {
+            format!(
+                "Failed to open {}. @{}:{}",
+                path.display(),
+                file!(),
+                line!()
+            )
+        })?;
+
+    let is_empty = file
+        .metadata()
+        .with_context(|| format!("@{}:{}", file!(), line!()))?
+        .len()
+        == 0;
+    let mut rows = String::new();
+    if is_empty {
+        writeln!(rows, "{}", CSV_HEADER).unwrap();
+    }
+    writeln!(rows, "{}", record.to_csv()).unwrap();
+
+    // one write per run, so that rows of concurrent runs are not interleaved.
+    file.write_all(rows.as_bytes()).with_context(|| {
+        format!(
+            "Failed to write {}. @{}:{}",
+            path.display(),
+            file!(),
+            line!()
+        )
+    })
+}
+
+fn respond(mut stream: TcpStream, metrics: &Metrics) -> std::io::Result<()> {
+    // the request itself is ignored. read until the end of the header.
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = stream.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..n]);
+    }
+
+    let body = metrics.render();
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+/// Serve `metrics` over HTTP at `addr` in a background thread.
+///
+/// Returns the bound address (useful when the port of `addr` is 0). The thread runs until the process exits.
+pub fn serve(addr: &str, metrics: Arc<Metrics>) -> Result<SocketAddr> {
+    let listener = TcpListener::bind(addr)
+        .with_context(|| format!("Failed to bind {}. @{}:{}", addr, file!(), line!()))?;
+    let local_addr = listener
+        .local_addr()
+        .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            // a broken scrape must not stop the endpoint.
+            let _ = respond(stream, &metrics);
+        }
+    });
+
+    Ok(local_addr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channel_utils::counting_channel::CountingChannel;
+    use crate::channel_utils::{channel_from, read_vec_f, write_vec_f};
+    use rand::Rng;
+    use scuttlebutt::field::F128b;
+    use scuttlebutt::AesRng;
+    use std::os::unix::net::UnixStream;
+
+    fn scrape(addr: SocketAddr) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    fn metric_value(response: &str, name: &str) -> f64 {
+        response
+            .lines()
+            .find_map(|l| l.strip_prefix(name)?.strip_prefix(' '))
+            .unwrap()
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_serve_metrics() {
+        let metrics = Arc::new(Metrics::new());
+        let addr = serve("127.0.0.1:0", Arc::clone(&metrics)).unwrap();
+
+        let response = scrape(addr);
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert_eq!(metric_value(&response, "prep_psi_bytes_sent_total"), 0.0);
+        assert_eq!(
+            metric_value(&response, "prep_psi_phase{phase=\"preparing\"}"),
+            1.0
+        );
+
+        metrics.set_phase(Phase::Online);
+
+        let (s, r) = UnixStream::pair().unwrap();
+        let mut rng = AesRng::new();
+        let v: Vec<F128b> = (0..100).map(|_| rng.gen()).collect();
+        let v_len = v.len();
+        let handle = std::thread::spawn(move || {
+            let mut channel = channel_from(r).unwrap();
+            let w: Vec<F128b> = read_vec_f(&mut channel).unwrap();
+            w.len()
+        });
+
+        let mut channel = CountingChannel::new(channel_from(s).unwrap(), metrics.counters());
+        write_vec_f(&mut channel, &v).unwrap();
+        assert_eq!(handle.join().unwrap(), v_len);
+
+        let response = scrape(addr);
+        let sent = metric_value(&response, "prep_psi_bytes_sent_total");
+        assert_eq!(sent, channel.counters().sent() as f64);
+        assert!(sent >= (v_len * 16) as f64);
+        assert_eq!(
+            metric_value(&response, "prep_psi_phase{phase=\"online\"}"),
+            1.0
+        );
+        assert_eq!(
+            metric_value(&response, "prep_psi_phase{phase=\"offline\"}"),
+            0.0
+        );
+        assert!((metric_value(&response, "prep_psi_progress_percent") - 200.0 / 3.0).abs() < 1e-9);
+    }
+}
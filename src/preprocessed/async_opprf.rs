@@ -0,0 +1,258 @@
+//! Cancel-safe async OPPRF over a socket, for tokio applications. Enabled by the `tokio` feature.
+//!
+//! The protocols of this library are blocking, so each step runs on the blocking pool of tokio (`spawn_blocking`)
+//! with the channel moved into the task. If the future is dropped at an await point (e.g. by `tokio::time::timeout` or `select!`),
+//! the socket is shut down in both directions:
+//!
+//! - the blocking task fails at its next read or write and exits, so no task is leaked;
+//! - the peer observes a reset (an end of stream or a broken pipe) instead of waiting forever.
+//!
+//! After a cancellation the [CancellableChannel] is closed, and later calls fail.
+//! Each step uses its own `AesRng::new()` since an RNG can't be borrowed across the await.
+
+use crate::channel_utils::{channel_from, TryCloneStream};
+use crate::preprocessed::opprf::{
+    SepOpprfReceiver, SepOpprfReceiverWithVole, SepOpprfSender, SepOpprfSenderWithVole,
+};
+use crate::solver::Solver;
+use crate::vole::{VoleShareForReceiver, VoleShareForSender};
+use anyhow::{bail, Context, Error};
+use scuttlebutt::field::FiniteField as FF;
+use scuttlebutt::{AesRng, Channel};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::os::unix::net::UnixStream;
+
+/// Stream which can be shut down from another handle, so that a blocked read or write on it returns (e.g. [TcpStream], [UnixStream]).
+pub trait ShutdownStream: Read + Write + TryCloneStream + Send + Sync + 'static {
+    /// Shut down both directions of the stream.
+    fn shutdown_both(&self) -> std::io::Result<()>;
+}
+
+impl ShutdownStream for TcpStream {
+    fn shutdown_both(&self) -> std::io::Result<()> {
+        self.shutdown(Shutdown::Both)
+    }
+}
+
+impl ShutdownStream for UnixStream {
+    fn shutdown_both(&self) -> std::io::Result<()> {
+        self.shutdown(Shutdown::Both)
+    }
+}
+
+// shut down the stream when dropped, unless disarmed. It is dropped without being disarmed only if the future is cancelled.
+struct ShutdownOnDrop<'a, RW: ShutdownStream>(Option<&'a RW>);
+
+impl<'a, RW: ShutdownStream> ShutdownOnDrop<'a, RW> {
+    fn disarm(mut self) {
+        self.0 = None;
+    }
+}
+
+impl<'a, RW: ShutdownStream> Drop for ShutdownOnDrop<'a, RW> {
+    fn drop(&mut self) {
+        if let Some(stream) = self.0 {
+            // the peer may have closed it already.
+            let _ = stream.shutdown_both();
+        }
+    }
+}
+
+/// Buffered channel over a socket whose protocol steps are cancel-safe futures.
+///
+/// Please look the parent document ( [crate::preprocessed::async_opprf] ) for the cancellation behavior.
+pub struct CancellableChannel<RW: ShutdownStream> {
+    channel: Option<Channel<BufReader<RW>, BufWriter<RW>>>,
+    stream: RW,
+}
+
+impl<RW: ShutdownStream> CancellableChannel<RW> {
+    /// Wrap `stream`. The peer may use [channel_from] on its end since the buffering is the same.
+    pub fn new(stream: RW) -> Result<Self, Error> {
+        let handle = stream
+            .try_clone_stream()
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+        let channel = channel_from(stream).with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        Ok(Self {
+            channel: Some(channel),
+            stream: handle,
+        })
+    }
+
+    /// Whether a previous step was cancelled, so the channel can't be used any more.
+    pub fn is_closed(&self) -> bool {
+        self.channel.is_none()
+    }
+
+    // run `f` on the blocking pool with the channel. The channel is given back unless the future is dropped.
+    async fn run<T, G>(&mut self, f: G) -> Result<T, Error>
+    where
+        T: Send + 'static,
+        G: FnOnce(&mut Channel<BufReader<RW>, BufWriter<RW>>, &mut AesRng) -> Result<T, Error>
+            + Send
+            + 'static,
+    {
+        let Some(mut channel) = self.channel.take() else {
+            bail!(
+                "the channel is closed by a cancelled step. @{}:{}",
+                file!(),
+                line!()
+            );
+        };
+
+        let guard = ShutdownOnDrop(Some(&self.stream));
+        let joined = tokio::task::spawn_blocking(move || {
+            let mut rng = AesRng::new();
+            let res = f(&mut channel, &mut rng);
+            (channel, res)
+        })
+        .await;
+        guard.disarm();
+
+        let (channel, res) = joined.with_context(|| format!("@{}:{}", file!(), line!()))?;
+        self.channel = Some(channel);
+        res
+    }
+
+    /// Async [precomp](SepOpprfSender::precomp) of the OPPRF sender.
+    pub async fn opprf_sender_precomp<F, S, V>(
+        &mut self,
+        query_num: usize,
+        system: V,
+    ) -> Result<SepOpprfSenderWithVole<F, S, V>, Error>
+    where
+        F: FF + Send + 'static,
+        S: Solver<F> + Send + 'static,
+        V: VoleShareForSender<F> + Send + 'static,
+        SepOpprfSenderWithVole<F, S, V>: Send,
+    {
+        self.run(move |channel, rng| {
+            SepOpprfSenderWithVole::precomp(channel, rng, query_num, system)
+                .with_context(|| format!("@{}:{}", file!(), line!()))
+        })
+        .await
+    }
+
+    /// Async [send](SepOpprfSender::send) of the OPPRF sender.
+    pub async fn opprf_send<F, S, V>(
+        &mut self,
+        sender: SepOpprfSenderWithVole<F, S, V>,
+        points: Vec<(F, F)>,
+        query_num: usize,
+    ) -> Result<(), Error>
+    where
+        F: FF + Send + 'static,
+        S: Solver<F> + Send + 'static,
+        V: VoleShareForSender<F> + Send + 'static,
+        SepOpprfSenderWithVole<F, S, V>: Send,
+    {
+        self.run(move |channel, rng| {
+            sender
+                .send(channel, &points, query_num, rng)
+                .with_context(|| format!("@{}:{}", file!(), line!()))?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Async [precomp](SepOpprfReceiver::precomp) of the OPPRF receiver.
+    pub async fn opprf_receiver_precomp<F, S, V>(
+        &mut self,
+        query_num: usize,
+        system: V,
+    ) -> Result<SepOpprfReceiverWithVole<F, S, V>, Error>
+    where
+        F: FF + Send + 'static,
+        S: Solver<F> + Send + 'static,
+        V: VoleShareForReceiver<F> + Send + 'static,
+        SepOpprfReceiverWithVole<F, S, V>: Send,
+    {
+        self.run(move |channel, rng| {
+            SepOpprfReceiverWithVole::precomp(channel, rng, query_num, system)
+                .with_context(|| format!("@{}:{}", file!(), line!()))
+        })
+        .await
+    }
+
+    /// Async [receive](SepOpprfReceiver::receive) of the OPPRF receiver.
+    pub async fn opprf_receive<F, S, V>(
+        &mut self,
+        receiver: SepOpprfReceiverWithVole<F, S, V>,
+        queries: Vec<F>,
+    ) -> Result<Vec<(F, F)>, Error>
+    where
+        F: FF + Send + 'static,
+        S: Solver<F> + Send + 'static,
+        V: VoleShareForReceiver<F> + Send + 'static,
+        SepOpprfReceiverWithVole<F, S, V>: Send,
+    {
+        self.run(move |channel, rng| {
+            receiver
+                .receive(channel, &queries, rng)
+                .with_context(|| format!("@{}:{}", file!(), line!()))
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::PaxosSolver;
+    use crate::vole::{LPNVoleReceiver, LPNVoleSender, LPN_EXTEND_SMALL, LPN_SETUP_SMALL};
+    use rand::Rng;
+    use scuttlebutt::field::F128b;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_async_receive_cancelled() {
+        let query_num = 100;
+        let (s, r) = UnixStream::pair().unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let mut channel = channel_from(s).unwrap();
+            let vole_share_for_s = LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL);
+            let sender = SepOpprfSenderWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
+                &mut channel,
+                &mut rng,
+                query_num,
+                vole_share_for_s,
+            )
+            .unwrap();
+
+            // slow sender: the receiver is cancelled while waiting for the table.
+            std::thread::sleep(Duration::from_millis(500));
+            let points = (0..query_num)
+                .map(|_| (rng.gen(), rng.gen()))
+                .collect::<Vec<(F128b, F128b)>>();
+            let res = sender.send(&mut channel, &points, query_num, &mut rng);
+            tx.send(res.map(|_| ())).unwrap();
+        });
+
+        let mut channel = CancellableChannel::new(r).unwrap();
+        let vole_share_for_r = LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL);
+        let receiver = channel
+            .opprf_receiver_precomp::<F128b, PaxosSolver<F128b>, _>(query_num, vole_share_for_r)
+            .await
+            .unwrap();
+
+        let mut rng = AesRng::new();
+        let queries = (0..query_num).map(|_| rng.gen()).collect::<Vec<F128b>>();
+        let res = tokio::time::timeout(
+            Duration::from_millis(100),
+            channel.opprf_receive(receiver, queries),
+        )
+        .await;
+        assert!(res.is_err(), "the receive should be cancelled.");
+        assert!(channel.is_closed());
+
+        // the sender observes the reset instead of hanging.
+        let sender_res = rx.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert!(sender_res.is_err());
+    }
+}
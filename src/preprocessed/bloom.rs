@@ -0,0 +1,166 @@
+//! Bloom filter of a set, used as the prefilter of [OPPRF membership](crate::preprocessed::opprf::SepOpprfReceiverWithVole::receive_membership_with_prefilter).
+//!
+//! **Privacy tradeoff:** a Bloom filter is not oblivious. The holder of the filter can test any element offline
+//! and learns approximate membership of the set (false positive rate given by [BloomFilter::new]), and the size of the filter reveals the set size roughly.
+//! Use it only when the set owner accepts to reveal it.
+//!
+//! The positions of an element are derived from SHA-256 of a random key (chosen by the owner of the filter) and the element, by double hashing.
+
+use anyhow::{bail, Context, Error};
+use rand::{CryptoRng, Rng};
+use scuttlebutt::field::FiniteField as FF;
+use scuttlebutt::AbstractChannel;
+use sha2::{Digest, Sha256};
+
+/// Bloom filter of field elements.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BloomFilter {
+    key: [u8; 16],
+    num_hashes: usize,
+    bits: Vec<u64>,
+    num_bits: usize,
+}
+
+impl BloomFilter {
+    /// Empty filter sized for `expected_items` elements with false positive rate about `fp_rate` (in `(0, 1)`).
+    pub fn new<RNG: CryptoRng + Rng>(
+        expected_items: usize,
+        fp_rate: f64,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        if !(fp_rate > 0.0 && fp_rate < 1.0) {
+            bail!(
+                "fp_rate (={}) must be in (0, 1). @{}:{}",
+                fp_rate,
+                file!(),
+                line!()
+            );
+        }
+
+        let n = expected_items.max(1) as f64;
+        let ln2 = std::f64::consts::LN_2;
+        let num_bits = ((-n * fp_rate.ln() / (ln2 * ln2)).ceil() as usize).max(64);
+        let num_hashes = ((num_bits as f64 / n * ln2).round() as usize).max(1);
+
+        Ok(Self {
+            key: rng.gen(),
+            num_hashes,
+            bits: vec![0; (num_bits + 63) / 64],
+            num_bits,
+        })
+    }
+
+    /// Filter of `set` sized with `fp_rate`. See [BloomFilter::new].
+    pub fn from_set<F: FF, RNG: CryptoRng + Rng>(
+        set: &[F],
+        fp_rate: f64,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        let mut filter = Self::new(set.len(), fp_rate, rng)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+        for x in set.iter() {
+            filter.insert(x);
+        }
+        Ok(filter)
+    }
+
+    /// The number of bits of the filter.
+    pub fn num_bits(&self) -> usize {
+        self.num_bits
+    }
+
+    /// The number of positions set for each element.
+    pub fn num_hashes(&self) -> usize {
+        self.num_hashes
+    }
+
+    fn positions<F: FF>(&self, x: &F) -> impl Iterator<Item = usize> {
+        let mut hasher = Sha256::new();
+        hasher.update(self.key);
+        hasher.update(x.to_bytes());
+        let digest = hasher.finalize();
+        let h1 = u64::from_le_bytes(digest[0..8].try_into().unwrap());
+        let h2 = u64::from_le_bytes(digest[8..16].try_into().unwrap()) | 1;
+        let num_bits = self.num_bits as u64;
+
+        (0..self.num_hashes as u64)
+            .map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % num_bits) as usize)
+    }
+
+    /// Add `x` to the filter.
+    pub fn insert<F: FF>(&mut self, x: &F) {
+        for i in self.positions(x).collect::<Vec<_>>() {
+            self.bits[i / 64] |= 1 << (i % 64);
+        }
+    }
+
+    /// Whether `x` may be in the set. `false` means that `x` is surely not in the set.
+    pub fn contains<F: FF>(&self, x: &F) -> bool {
+        self.positions(x)
+            .all(|i| self.bits[i / 64] & (1 << (i % 64)) != 0)
+    }
+
+    /// Send the filter.
+    pub fn send<C: AbstractChannel>(&self, channel: &mut C) -> Result<(), Error> {
+        channel.write_bytes(&self.key)?;
+        channel.write_u64(self.num_hashes as u64)?;
+        channel.write_u64(self.num_bits as u64)?;
+        for &word in self.bits.iter() {
+            channel.write_u64(word)?;
+        }
+        channel.flush()?;
+        Ok(())
+    }
+
+    /// Receive the filter sent by [BloomFilter::send].
+    pub fn receive<C: AbstractChannel>(channel: &mut C) -> Result<Self, Error> {
+        let mut key = [0u8; 16];
+        channel.read_bytes(&mut key)?;
+        let num_hashes = channel.read_u64()? as usize;
+        let num_bits = channel.read_u64()? as usize;
+        if num_bits == 0 || num_hashes == 0 {
+            bail!(
+                "invalid Bloom filter: num_bits (={}), num_hashes (={}). @{}:{}",
+                num_bits,
+                num_hashes,
+                file!(),
+                line!()
+            );
+        }
+        let bits = (0..(num_bits + 63) / 64)
+            .map(|_| channel.read_u64())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            key,
+            num_hashes,
+            bits,
+            num_bits,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use scuttlebutt::field::F128b;
+    use scuttlebutt::AesRng;
+
+    #[test]
+    fn test_bloom_filter() {
+        let mut rng = AesRng::new();
+        let set = (0..1000).map(|_| rng.gen::<F128b>()).collect::<Vec<_>>();
+        let filter = BloomFilter::from_set(&set, 0.01, &mut rng).unwrap();
+
+        // no false negatives.
+        assert!(set.iter().all(|x| filter.contains(x)));
+
+        let false_positives = (0..10000)
+            .filter(|_| filter.contains(&rng.gen::<F128b>()))
+            .count();
+        assert!(false_positives < 300, "{}", false_positives);
+
+        assert!(BloomFilter::new(10, 0.0, &mut rng).is_err());
+        assert!(BloomFilter::new(10, 1.0, &mut rng).is_err());
+    }
+}
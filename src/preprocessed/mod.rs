@@ -1,6 +1,12 @@
 //! The main module of the Preprocessing Multi-party PSI project
 
+#[cfg(feature = "tokio")]
+pub mod async_opprf;
+pub mod bloom;
 pub mod opprf;
 pub mod oprf;
+pub mod packing;
 /// Preprocessed MPSI module using OPPRF and OPRF.
 pub mod psi;
+pub mod state;
+pub mod table;
@@ -104,17 +104,323 @@
 //! # }
 //! ```
 
-use crate::channel_utils::{read_vec_f, write_vec_f};
+use crate::channel_utils::{read_vec_f, read_vec_f_truncated, write_vec_f, write_vec_f_truncated};
+use crate::error::PsiError;
+use crate::hash_utils::hash_with_tag;
+use crate::preprocessed::bloom::BloomFilter;
 use crate::preprocessed::oprf::{
-    SepOprfReceiver, SepOprfReceiverWithVole, SepOprfSender, SepOprfSenderWithVole,
+    OprfEvaluator, SepOprfReceiver, SepOprfReceiverWithVole, SepOprfSender, SepOprfSenderWithVole,
 };
-use crate::solver::Solver;
+use crate::preprocessed::state::{read_params, read_u64, write_params, write_u64};
+use crate::preprocessed::table::ProgrammedTable;
+use crate::set_utils::{decompose_range, FromU128, PrefixKey};
+#[cfg(feature = "rayon")]
+use crate::solver::parallel::encode_with_retry_in;
+use crate::solver::{encode_with_retry_hook, Decoder, Solver, SolverParams};
 use crate::vole::{VoleShareForReceiver, VoleShareForSender};
-use anyhow::{anyhow, Context, Error};
+use anyhow::{bail, Context, Error};
+use rand::distributions::{Distribution, Standard};
 use rand::{CryptoRng, Rng};
 use scuttlebutt::field::FiniteField as FF;
-use scuttlebutt::AbstractChannel;
+use scuttlebutt::{AbstractChannel, Block};
+use sha2::{Digest, Sha256};
 use std::clone::Clone;
+use std::collections::HashSet;
+use std::io::{Read, Write};
+use std::time::{Duration, Instant};
+use typenum::marker_traits::Unsigned;
+
+/// Number of auxillary informations tried when the sender programs the table, unless configured by [OpprfConfig::with_encode_attempts].
+pub const DEFAULT_ENCODE_ATTEMPTS: usize = 2;
+
+/// Length of each nonce exchanged with [OpprfConfig::with_run_nonce].
+pub const RUN_NONCE_LEN: usize = 16;
+
+// domain of the pad of the tag table of send_tagged, derived from the OPRF output.
+const TAG_PAD_DOMAIN: &[u8] = b"preprocessing_mpsi_with_vole/opprf/tag_pad/v1";
+
+// domain of tag_of.
+const TAG_MAC_DOMAIN: &[u8] = b"preprocessing_mpsi_with_vole/opprf/tag_mac/v1";
+
+// domain of the seed of the auxillary information of OpprfConfig::with_seeded_aux, derived from the domain tag of the run.
+const AUX_SEED_DOMAIN: &[u8] = b"preprocessing_mpsi_with_vole/opprf/aux_seed/v1";
+
+// domain of the hash of OpprfConfig::with_hash_seed exchanged in the handshake.
+const HASH_SEED_CHECK_DOMAIN: &[u8] = b"preprocessing_mpsi_with_vole/opprf/hash_seed_check/v1";
+
+/// Tuning knobs of [SepOpprfSenderWithVole] and [SepOpprfReceiverWithVole], passed once to `precomp_with_config`.
+///
+/// Start from [OpprfConfig::default] and override with the `with_*` setters. Both sides must use the same config.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OpprfConfig {
+    domain_tag: Vec<u8>,
+    min_capacity: usize,
+    encode_attempts: usize,
+    run_nonce: bool,
+    seeded_aux: bool,
+    hash_seed: Option<[u8; 16]>,
+}
+
+impl Default for OpprfConfig {
+    fn default() -> Self {
+        Self {
+            domain_tag: Vec::new(),
+            min_capacity: 0,
+            encode_attempts: DEFAULT_ENCODE_ATTEMPTS,
+            run_nonce: false,
+            seeded_aux: false,
+            hash_seed: None,
+        }
+    }
+}
+
+impl OpprfConfig {
+    /// Mix `domain_tag` into the underlying OPRF. Default: empty.
+    pub fn with_domain_tag(mut self, domain_tag: &[u8]) -> Self {
+        self.domain_tag = domain_tag.to_vec();
+        self
+    }
+
+    /// Size the solver for at least `min_capacity` points even if fewer queries are precomputed. Default: 0.
+    ///
+    /// A larger capacity makes the code vector (and so VOLE) longer.
+    pub fn with_min_capacity(mut self, min_capacity: usize) -> Self {
+        self.min_capacity = min_capacity;
+        self
+    }
+
+    /// Number of auxillary informations tried when the sender programs the table. Default: [DEFAULT_ENCODE_ATTEMPTS].
+    pub fn with_encode_attempts(mut self, encode_attempts: usize) -> Self {
+        self.encode_attempts = encode_attempts;
+        self
+    }
+
+    /// Re-randomize the outputs per run. Default: false.
+    ///
+    /// Both sides draw a [RUN_NONCE_LEN]-byte nonce from their RNG in `precomp` and exchange them, and the two nonces are appended to the domain tag.
+    /// So the OPRF of an element is unlinkable across runs even if an observer sees the outputs of several runs, while both sides agree on it within a run.
+    /// The outputs on the programmed points are still the programmed values.
+    pub fn with_run_nonce(mut self, run_nonce: bool) -> Self {
+        self.run_nonce = run_nonce;
+        self
+    }
+
+    /// Whether the outputs are re-randomized per run.
+    pub fn run_nonce(&self) -> bool {
+        self.run_nonce
+    }
+
+    /// Derive the auxillary information of the solver from the run nonces on both sides (See [Solver::gen_aux_from_seed]),
+    /// instead of sending it with the programmed table. Default: false. It requires [with_run_nonce](OpprfConfig::with_run_nonce).
+    ///
+    /// It saves the bytes of the auxillary information from the online phase. The sender can't pick another one when the encode fails,
+    /// so the encode is tried only once, whatever [with_encode_attempts](OpprfConfig::with_encode_attempts) says.
+    /// The seed is not saved in the state, so a state loaded by `--phase online` sends the auxillary information again.
+    pub fn with_seeded_aux(mut self, seeded_aux: bool) -> Self {
+        self.seeded_aux = seeded_aux;
+        self
+    }
+
+    /// Whether the auxillary information is derived from the run nonces.
+    pub fn seeded_aux(&self) -> bool {
+        self.seeded_aux
+    }
+
+    /// Derive the auxillary information of the solver (its hash seeds) from `seed` supplied by the caller on both sides
+    /// (See [Solver::gen_aux_from_seed]), e.g. to interoperate with another implementation. Default: none.
+    ///
+    /// Both sides exchange a hash of the seed in `precomp`, and fail with [PsiError::HashSeedMismatch] if they differ.
+    /// As [with_seeded_aux](OpprfConfig::with_seeded_aux), the auxillary information is not sent and the encode is tried only once.
+    /// It takes precedence over [with_seeded_aux](OpprfConfig::with_seeded_aux).
+    pub fn with_hash_seed(mut self, seed: [u8; 16]) -> Self {
+        self.hash_seed = Some(seed);
+        self
+    }
+
+    /// Seed of the auxillary information supplied by the caller.
+    pub fn hash_seed(&self) -> Option<[u8; 16]> {
+        self.hash_seed
+    }
+
+    /// Domain tag mixed into the underlying OPRF.
+    pub fn domain_tag(&self) -> &[u8] {
+        &self.domain_tag
+    }
+
+    /// Number of points the solver is sized for when `query_num` queries are precomputed.
+    pub fn capacity(&self, query_num: usize) -> usize {
+        query_num.max(self.min_capacity)
+    }
+
+    /// Number of auxillary informations tried when the sender programs the table.
+    pub fn encode_attempts(&self) -> usize {
+        self.encode_attempts
+    }
+}
+
+// domain tag of this run: `config.domain_tag`, followed by the nonces of the sender and of the receiver if `config.run_nonce`.
+fn run_domain_tag<C: AbstractChannel, RNG: CryptoRng + Rng>(
+    channel: &mut C,
+    rng: &mut RNG,
+    config: &OpprfConfig,
+    is_sender: bool,
+) -> Result<Vec<u8>, Error> {
+    let mut tag = config.domain_tag.clone();
+    if !config.run_nonce {
+        return Ok(tag);
+    }
+
+    let mut ours = [0u8; RUN_NONCE_LEN];
+    rng.fill(&mut ours[..]);
+    channel.write_bytes(&ours)?;
+    channel.flush()?;
+    let mut theirs = [0u8; RUN_NONCE_LEN];
+    channel.read_bytes(&mut theirs)?;
+
+    let (sender_nonce, receiver_nonce) = if is_sender {
+        (ours, theirs)
+    } else {
+        (theirs, ours)
+    };
+    tag.extend_from_slice(&sender_nonce);
+    tag.extend_from_slice(&receiver_nonce);
+    Ok(tag)
+}
+
+// seed of the auxillary information of the run, if `config.seeded_aux`. Both sides derive the same one from the domain tag made by run_domain_tag.
+fn run_aux_seed(config: &OpprfConfig, domain_tag: &[u8]) -> Result<Option<Block>, Error> {
+    if !config.seeded_aux {
+        return Ok(None);
+    }
+    if !config.run_nonce {
+        bail!(
+            "seeded_aux requires run_nonce: the seed is derived from the run nonces. @{}:{}",
+            file!(),
+            line!()
+        );
+    }
+
+    let mut hasher = Sha256::new();
+    hasher.update(AUX_SEED_DOMAIN);
+    hasher.update(domain_tag);
+    let digest = hasher.finalize();
+    let mut seed = [0u8; 16];
+    seed.copy_from_slice(&digest[..16]);
+    Ok(Some(Block::from(seed)))
+}
+
+// seed of the auxillary information agreed with the peer: `config.hash_seed` checked by a handshake, or the one of run_aux_seed.
+// Both sides send the hash of their seed before reading the peer's, as check_params_handshake.
+fn agree_aux_seed<C: AbstractChannel>(
+    channel: &mut C,
+    config: &OpprfConfig,
+    domain_tag: &[u8],
+) -> Result<Option<Block>, Error> {
+    let Some(seed) = config.hash_seed else {
+        return run_aux_seed(config, domain_tag);
+    };
+
+    let mut hasher = Sha256::new();
+    hasher.update(HASH_SEED_CHECK_DOMAIN);
+    hasher.update(seed);
+    let ours = hasher.finalize();
+    channel.write_bytes(&ours)?;
+    channel.flush()?;
+
+    let mut theirs = [0u8; 32];
+    channel.read_bytes(&mut theirs)?;
+    if theirs[..] != ours[..] {
+        return Err(PsiError::HashSeedMismatch.into());
+    }
+
+    Ok(Some(Block::from(seed)))
+}
+
+/// Time spent in each layer of one OPPRF run, returned by `send_with_timing` / `receive_with_timing`.
+///
+/// Each layer includes its own messages, so waiting for the peer is counted in the layer the awaited message belongs to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OpprfTiming {
+    /// OPRF layer: the OPRF protocol, and the OPRF evaluations on the programmed points (sender).
+    pub oprf: Duration,
+    /// OKVS programming layer: solver encoding (sender) or decoding (receiver), and sending or receiving the table.
+    pub solver: Duration,
+    /// The whole run.
+    pub total: Duration,
+}
+
+/// How the programmed value is blinded by the OPRF output (the pad) in the table, and unblinded by the receiver.
+///
+/// The sender programs `blind(z, fk(x))` at `x` and the receiver outputs `unblind(decode(x), fk(x))`, so `unblind(blind(z, pad), pad)` must be `z`.
+pub trait Blinding {
+    /// Value programmed for `value` under `pad`.
+    fn blind<F: FF>(&self, value: F, pad: F) -> Result<F, Error>;
+    /// Value recovered from the decoded `blinded` under `pad`.
+    fn unblind<F: FF>(&self, blinded: F, pad: F) -> F;
+}
+
+/// `z - pad` / `d + pad`. The blinding of the paper.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Additive;
+
+impl Blinding for Additive {
+    fn blind<F: FF>(&self, value: F, pad: F) -> Result<F, Error> {
+        Ok(value - pad)
+    }
+
+    fn unblind<F: FF>(&self, blinded: F, pad: F) -> F {
+        blinded + pad
+    }
+}
+
+/// `z / pad` / `d * pad`, for interoperability with libraries blinding multiplicatively.
+///
+/// Blinding fails if the pad is zero, which occurs with probability $`1/|\mathbb{F}|`$ per point.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Multiplicative;
+
+impl Blinding for Multiplicative {
+    fn blind<F: FF>(&self, value: F, pad: F) -> Result<F, Error> {
+        if pad == F::zero() {
+            bail!(
+                "OPRF output is zero, so it can't blind multiplicatively. @{}:{}",
+                file!(),
+                line!()
+            );
+        }
+        Ok(value / pad)
+    }
+
+    fn unblind<F: FF>(&self, blinded: F, pad: F) -> F {
+        blinded * pad
+    }
+}
+
+/// [Blinding] chosen at runtime by `with_blinding` of [SepOpprfSenderWithVole] and [SepOpprfReceiverWithVole]. Both sides must use the same mode.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BlindingMode {
+    /// [Additive].
+    #[default]
+    Additive,
+    /// [Multiplicative].
+    Multiplicative,
+}
+
+impl Blinding for BlindingMode {
+    fn blind<F: FF>(&self, value: F, pad: F) -> Result<F, Error> {
+        match self {
+            BlindingMode::Additive => Additive.blind(value, pad),
+            BlindingMode::Multiplicative => Multiplicative.blind(value, pad),
+        }
+    }
+
+    fn unblind<F: FF>(&self, blinded: F, pad: F) -> F {
+        match self {
+            BlindingMode::Additive => Additive.unblind(blinded, pad),
+            BlindingMode::Multiplicative => Multiplicative.unblind(blinded, pad),
+        }
+    }
+}
 
 /// Trait indicating that OPPRF constraints are satisfied.
 ///
@@ -204,6 +510,19 @@ where
     V: VoleShareForSender<F>,
 {
     params: S::Params,
+    encode_attempts: usize,
+    // runtime options, not saved in the state.
+    verify_encode: bool,
+    blinding: BlindingMode,
+    // pad the program with dummy points up to a multiple of this. See with_pad_to_bucket.
+    pad_bucket: Option<usize>,
+    // bits of each entry of the table on the wire. See with_wire_bits.
+    wire_bits: Option<usize>,
+    // seed of the auxillary information shared with the receiver. See OpprfConfig::with_seeded_aux.
+    aux_seed: Option<Block>,
+    // pool to encode the program in. See with_thread_pool.
+    #[cfg(feature = "rayon")]
+    pool: Option<std::sync::Arc<rayon::ThreadPool>>,
     oprf_sender: SepOprfSenderWithVole<F, S, V>,
     // fk: Option<Box<dyn Fn(&Self, F) -> Result<F, Error> + Send>>,
 }
@@ -234,17 +553,12 @@ where
         query_num: usize,
         system: Self::PrecompSystem,
     ) -> Result<Self, Error> {
-        let params = S::calc_params(query_num);
-        let oprf_sender = SepOprfSenderWithVole::precomp(channel, rng, query_num, system)
-            .with_context(|| format!("@{}:{}", file!(), line!()))?;
-        Ok(Self {
-            params,
-            oprf_sender,
-            // fk: None,
-        })
+        Self::precomp_with_tag(channel, rng, query_num, &[], system)
     }
 
     /// Actual implementation of send protocol, using Separated OPRF send protocol.
+    ///
+    /// `points` must not be empty. An empty program fails with [PsiError::EmptyProgram] before the OPRF runs, and so do the variants built on `send`.
     fn send<C, RNG>(
         self,
         channel: &mut C,
@@ -256,44 +570,11 @@ where
         C: AbstractChannel,
         RNG: CryptoRng + Rng,
     {
-        let fk = self
-            .oprf_sender
-            .send(channel, query_num, rng)
+        let (fk, _table) = self
+            .send_with_table(channel, points, query_num, rng)
             .with_context(|| format!("@{}:{}", file!(), line!()))?;
 
-        let points = points
-            .iter()
-            .map(|&(x, z)| {
-                let y = z - (fk(x).with_context(|| format!("@{}:{}", file!(), line!()))?);
-                Ok((x, y))
-            })
-            .collect::<Result<Vec<_>, Error>>()?;
-
-        let mut aux = S::gen_aux(rng).with_context(|| format!("@{}:{}", file!(), line!()))?;
-        let mut p = Err(anyhow!("dummy!"));
-        for _ in 0..2 {
-            p = S::encode(rng, &points, aux, self.params)
-                .with_context(|| format!("@{}:{}", file!(), line!()));
-            if p.is_ok() {
-                break;
-            }
-            aux = S::gen_aux(rng).with_context(|| format!("@{}:{}", file!(), line!()))?;
-        }
-        let p = p?;
-
-        S::aux_send(channel, rng, aux).with_context(|| format!("@{}:{}", file!(), line!()))?;
-
-        write_vec_f(channel, &p).with_context(|| format!("@{}:{}", file!(), line!()))?;
-
-        let params = self.params.clone();
-        let fk = move |x: F| -> Result<F, Error> {
-            let d = S::decode(&p, x, aux, params)
-                .with_context(|| format!("@{}:{}", file!(), line!()))?;
-            let res = d + fk(x).with_context(|| format!("@{}:{}", file!(), line!()))?;
-            Ok(res)
-        };
-
-        Ok(Box::new(fk))
+        Ok(fk)
     }
 
     /*
@@ -305,251 +586,4643 @@ where
     */
 }
 
-/// Actual implementation of Separated OPPRF receiver using VOLE.
-///
-/// Please look the parent document ( [crate::preprocessed::opprf] ) for usage example.
-pub struct SepOpprfReceiverWithVole<F, S, V>
+impl<F, S, V> SepOpprfSenderWithVole<F, S, V>
 where
     F: FF,
     S: Solver<F>,
-    V: VoleShareForReceiver<F>,
+    V: VoleShareForSender<F>,
 {
-    params: S::Params,
-    oprf_receiver: SepOprfReceiverWithVole<F, S, V>,
-}
+    /// Describe the field, the solver and the VOLE in use. e.g. `OPPRF[field=F128b, solver=Paxos, vole=LPN(medium)]`
+    pub fn describe(&self) -> String {
+        format!("OPPRF[{}]", self.describe_components())
+    }
 
-impl<F, S, V> ObliviousProgrammablePrf for SepOpprfReceiverWithVole<F, S, V>
-where
-    F: FF,
-    S: Solver<F>,
-    V: VoleShareForReceiver<F>,
-{
-    type Seed = ();
-    type Input = F;
-    type Output = F;
-}
+    pub(crate) fn describe_components(&self) -> String {
+        self.oprf_sender.describe_components()
+    }
 
-impl<F, S, V> SepOpprfReceiver for SepOpprfReceiverWithVole<F, S, V>
-where
-    F: FF,
-    S: Solver<F>,
-    V: VoleShareForReceiver<F>,
-{
-    type PrecompSystem = V;
+    /// Length of the VOLE correlation allocated by the precomputation. It is at least the code length of the solver for the set size,
+    /// and larger if the precomputation was made for a larger capacity (e.g. by [OpprfConfig::with_min_capacity]).
+    pub fn vole_length(&self) -> usize {
+        self.oprf_sender.vole_length()
+    }
 
-    /// Actual implementation of precomputation for the receiver, using Separated OPRF precomp protocol.
-    fn precomp<C: AbstractChannel, RNG: CryptoRng + Rng>(
+    // See [crate::preprocessed::state] for the layout.
+    pub(crate) fn write_state<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        write_params::<F, S, _>(writer, self.params)?;
+        write_u64(writer, self.encode_attempts as u64)?;
+        self.oprf_sender.write_state(writer)
+    }
+
+    /// Shrink the precomputation to `query_num` queries, e.g. made for the maximum number of queries by [precomp](SepOpprfSender::precomp).
+    ///
+    /// Only the prefix of the VOLE correlation needed for `query_num` is kept, so the online phase costs as if precomputed for `query_num`.
+    /// The receiver must shrink to the same `query_num`. It fails if `query_num` needs more than the precomputed length.
+    pub fn shrink(self, query_num: usize) -> Result<Self, Error> {
+        let oprf_sender = self
+            .oprf_sender
+            .shrink(query_num)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+        Ok(Self {
+            params: S::calc_params(query_num),
+            encode_attempts: self.encode_attempts,
+            verify_encode: self.verify_encode,
+            blinding: self.blinding,
+            pad_bucket: self.pad_bucket,
+            wire_bits: self.wire_bits,
+            aux_seed: self.aux_seed,
+            #[cfg(feature = "rayon")]
+            pool: self.pool,
+            oprf_sender,
+        })
+    }
+
+    /// Decode all the programmed points locally after encoding, and fail with [PsiError::EncodeVerificationFailed] instead of sending the table
+    /// if any of them doesn't decode to its value. Default: false.
+    ///
+    /// It catches solver bugs or silent corruption before the table is committed to the channel, at the cost of one decode per point.
+    /// It is a runtime option, so it is not saved by `write_state`.
+    pub fn with_verify_encode(mut self, verify_encode: bool) -> Self {
+        self.verify_encode = verify_encode;
+        self
+    }
+
+    /// Blind the programmed values by `blinding` in [send](SepOpprfSender::send) and the variants built on it. Default: [BlindingMode::Additive].
+    ///
+    /// The receiver must use the same mode by [with_blinding](SepOpprfReceiverWithVole::with_blinding). The mode isn't sent, so mismatched modes give wrong outputs.
+    /// It is a runtime option, so it is not saved by `write_state`. [send_with_oprf_outputs] always blinds additively.
+    pub fn with_blinding(mut self, blinding: BlindingMode) -> Self {
+        self.blinding = blinding;
+        self
+    }
+
+    /// Pad the points with random dummy points up to the next multiple of `bucket` in [send](SepOpprfSender::send) and the variants built on it,
+    /// so that the number of programmed points is hidden from the structure of the table and the time of the encode. Default: no padding.
+    ///
+    /// The dummy keys are random and distinct from the real keys, so they match a query only with negligible probability and never show up in the outputs.
+    /// The precomputation must have the capacity for the padded number of points (e.g. by [OpprfConfig::with_min_capacity]). A `bucket` of 0 or 1 doesn't pad.
+    /// It is a runtime option, so it is not saved by `write_state`.
+    pub fn with_pad_to_bucket(mut self, bucket: usize) -> Self {
+        self.pad_bucket = Some(bucket);
+        self
+    }
+
+    /// Send each entry of the table truncated to its lowest `bits` bits in [send](SepOpprfSender::send) and the variants built on it,
+    /// except [send_tagged](SepOpprfSenderWithVole::send_tagged). Default: whole elements.
+    ///
+    /// The outputs of the receiver then agree with the programmed values only on the lowest `bits` bits, so use it only where no more bits
+    /// are compared (e.g. conditional reconstruction of [Receiver::receive_with_compare_bits](crate::preprocessed::psi::Receiver::receive_with_compare_bits)).
+    /// It needs a solver of [Solver::SUM_DECODE] in a field of characteristic 2 and [BlindingMode::Additive], under which the truncated table
+    /// decodes to the truncated values. Otherwise, or if `bits` is not less than the field, the whole elements are sent.
+    /// The receiver must use the same `bits` by [with_wire_bits](SepOpprfReceiverWithVole::with_wire_bits). It is a runtime option, so it is not saved by `write_state`.
+    pub fn with_wire_bits(mut self, bits: usize) -> Self {
+        self.wire_bits = Some(bits);
+        self
+    }
+
+    /// Encode the program in `pool` by [Solver::encode_in] in [send](SepOpprfSender::send) and the variants built on it. Default: sequential encode.
+    ///
+    /// The table is the same as the one of the sequential encode. It is a runtime option, so it is not saved by `write_state`.
+    #[cfg(feature = "rayon")]
+    pub fn with_thread_pool(mut self, pool: std::sync::Arc<rayon::ThreadPool>) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    pub(crate) fn read_state<R: Read>(reader: &mut R, system: V) -> Result<Self, Error> {
+        let params = read_params::<F, S, _>(reader)?;
+        let encode_attempts = read_u64(reader)? as usize;
+        let oprf_sender = SepOprfSenderWithVole::read_state(reader, system)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+        Ok(Self {
+            params,
+            encode_attempts,
+            verify_encode: false,
+            blinding: BlindingMode::default(),
+            pad_bucket: None,
+            wire_bits: None,
+            aux_seed: None,
+            #[cfg(feature = "rayon")]
+            pool: None,
+            oprf_sender,
+        })
+    }
+
+    /// Same as [precomp](SepOpprfSender::precomp), but `domain_tag` is mixed into the underlying OPRF. Both sides must supply the same tag.
+    pub fn precomp_with_tag<C: AbstractChannel, RNG: CryptoRng + Rng>(
         channel: &mut C,
         rng: &mut RNG,
         query_num: usize,
-        system: Self::PrecompSystem,
+        domain_tag: &[u8],
+        system: V,
+    ) -> Result<Self, Error> {
+        let config = OpprfConfig::default().with_domain_tag(domain_tag);
+        Self::precomp_with_config(channel, rng, query_num, &config, system)
+    }
+
+    /// Same as [precomp](SepOpprfSender::precomp), but tuned by `config`. The receiver must use the same config.
+    pub fn precomp_with_config<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        rng: &mut RNG,
+        query_num: usize,
+        config: &OpprfConfig,
+        system: V,
     ) -> Result<Self, Error> {
-        let params = S::calc_params(query_num);
-        let oprf_receiver = SepOprfReceiverWithVole::precomp(channel, rng, query_num, system)
+        if config.encode_attempts == 0 {
+            bail!("encode_attempts must be positive. @{}:{}", file!(), line!());
+        }
+
+        let capacity = config.capacity(query_num);
+        let params = S::calc_params(capacity);
+        let domain_tag = run_domain_tag(channel, rng, config, true)
             .with_context(|| format!("@{}:{}", file!(), line!()))?;
+        let aux_seed = agree_aux_seed(channel, config, &domain_tag)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+        let oprf_sender =
+            SepOprfSenderWithVole::precomp(channel, rng, capacity, &domain_tag, system)
+                .with_context(|| format!("@{}:{}", file!(), line!()))?;
         Ok(Self {
             params,
-            oprf_receiver,
+            encode_attempts: config.encode_attempts,
+            verify_encode: false,
+            blinding: BlindingMode::default(),
+            pad_bucket: None,
+            wire_bits: None,
+            aux_seed,
+            #[cfg(feature = "rayon")]
+            pool: None,
+            oprf_sender,
+            // fk: None,
         })
     }
 
-    /// Actual implementation of receive protocol, using Separated OPRF receive protocol.
-    fn receive<C, RNG>(
+    /// Same as [precomp](SepOpprfSender::precomp), but the solver is sized by `params` (e.g. from [Solver::calc_params] of a larger size, or a config)
+    /// instead of computing it from the number of queries.
+    ///
+    /// The receiver must pass identical params to [SepOpprfReceiverWithVole::precomp_with_params]. Both sides exchange them first,
+    /// and it fails on both sides if they differ.
+    pub fn precomp_with_params<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        rng: &mut RNG,
+        params: S::Params,
+        system: V,
+    ) -> Result<Self, Error> {
+        check_params_handshake::<F, S, _>(channel, params)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        let oprf_sender =
+            SepOprfSenderWithVole::precomp_with_params(channel, rng, params, &[], system)
+                .with_context(|| format!("@{}:{}", file!(), line!()))?;
+        Ok(Self {
+            params,
+            encode_attempts: DEFAULT_ENCODE_ATTEMPTS,
+            verify_encode: false,
+            blinding: BlindingMode::default(),
+            pad_bucket: None,
+            wire_bits: None,
+            aux_seed: None,
+            #[cfg(feature = "rayon")]
+            pool: None,
+            oprf_sender,
+        })
+    }
+
+    /// Same as [send](SepOpprfSender::send), but also returns the programmed table sent to the receiver.
+    ///
+    /// The table can be written by [ProgrammedTable::export_table].
+    pub fn send_with_table<C, RNG>(
         self,
         channel: &mut C,
-        queries: &[Self::Input],
+        points: &[(F, F)],
+        query_num: usize,
         rng: &mut RNG,
-    ) -> Result<Vec<(Self::Input, Self::Output)>, Error>
+    ) -> Result<
+        (
+            Box<dyn Fn(F) -> Result<F, Error> + Send>,
+            ProgrammedTable<F, S>,
+        ),
+        Error,
+    >
     where
         C: AbstractChannel,
         RNG: CryptoRng + Rng,
     {
-        let Self {
-            params,
-            oprf_receiver,
-        } = self;
-
-        let oprf_res = oprf_receiver
-            .receive(channel, queries, rng)
+        let (fk, table, _timing) = self
+            .send_with_timing(channel, points, query_num, rng)
             .with_context(|| format!("@{}:{}", file!(), line!()))?;
 
-        let aux =
-            S::aux_receive(channel, rng).with_context(|| format!("@{}:{}", file!(), line!()))?;
+        Ok((fk, table))
+    }
 
-        let p = read_vec_f(channel).with_context(|| format!("@{}:{}", file!(), line!()))?;
+    /// Same as [send](SepOpprfSender::send), but `points` must be sorted by the canonical bytes of their keys (e.g. by [sort_canonical](crate::set_utils::sort_canonical)).
+    ///
+    /// The order is checked only by `debug_assert`. Sorted keys make duplicated keys adjacent, so conflicting ones are rejected
+    /// with [PsiError::KeyHashCollision] in one linear pass before the OPRF and the encode,
+    /// instead of after all the encode attempts have failed. With [with_pad_to_bucket](SepOpprfSenderWithVole::with_pad_to_bucket),
+    /// the dummy keys are checked against the real keys by binary search, instead of hashing all the real keys into a set.
+    /// The table is the same as the one of `send`, which stays the safe default for points in any order.
+    pub fn send_presorted<C, RNG>(
+        self,
+        channel: &mut C,
+        points: &[(F, F)],
+        query_num: usize,
+        rng: &mut RNG,
+    ) -> Result<Box<dyn Fn(F) -> Result<F, Error> + Send>, Error>
+    where
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+    {
+        debug_assert!(
+            points
+                .windows(2)
+                .all(|w| w[0].0.to_bytes() <= w[1].0.to_bytes()),
+            "points of send_presorted must be sorted by the canonical bytes of their keys."
+        );
 
-        let points = oprf_res
-            .iter()
-            .map(|&(x, fkx)| {
-                let y = S::decode(&p, x, aux, params)
-                    .with_context(|| format!("@{}:{}", file!(), line!()))?
-                    + fkx;
-                Ok((x, y))
-            })
-            .collect::<Result<Vec<_>, Error>>()?;
+        let keys = points
+            .windows(2)
+            .filter(|w| w[0].0 == w[1].0 && w[0].1 != w[1].1)
+            .map(|w| (format!("{:?}", w[0].0), format!("{:?}", w[1].0)))
+            .collect::<Vec<_>>();
+        if !keys.is_empty() {
+            return Err(PsiError::KeyHashCollision { keys }.into());
+        }
 
-        Ok(points)
+        let (fk, _table, _timing) = self
+            .send_with_hooks(channel, points, query_num, rng, None, true)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        Ok(fk)
     }
-}
 
-/// You are allowed to clone them **FOR BENCHMARKING PURPOSES ONLY**.
-///
-/// **DO NOT USE THEM IN PRODUCTION** because of the security reasons.
-impl<F, S, V> Clone for SepOpprfSenderWithVole<F, S, V>
-where
-    F: FF,
-    S: Solver<F>,
-    V: VoleShareForSender<F>,
-{
-    fn clone(&self) -> Self {
-        Self {
-            params: self.params,
-            oprf_sender: self.oprf_sender.clone(),
-            // fk: None,
-        }
+    /// Same as [send_with_table](SepOpprfSenderWithVole::send_with_table), but also returns the time spent in the OPRF layer and in the solver.
+    pub fn send_with_timing<C, RNG>(
+        self,
+        channel: &mut C,
+        points: &[(F, F)],
+        query_num: usize,
+        rng: &mut RNG,
+    ) -> Result<
+        (
+            Box<dyn Fn(F) -> Result<F, Error> + Send>,
+            ProgrammedTable<F, S>,
+            OpprfTiming,
+        ),
+        Error,
+    >
+    where
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+    {
+        self.send_with_hooks(channel, points, query_num, rng, None, false)
     }
-}
 
-/// You are allowed to clone them **FOR BENCHMARKING PURPOSES ONLY**.
-///
-/// **DO NOT USE THEM IN PRODUCTION** because of the security reasons.
-impl<F, S, V> Clone for SepOpprfReceiverWithVole<F, S, V>
-where
-    F: FF,
+    /// Same as [send](SepOpprfSender::send), but `on_encode_retry` is called with the attempt number (`1` for the first retry)
+    /// each time the encode fails and is retried with a fresh auxillary information.
+    ///
+    /// Retries are expected to be rare, so counting them (e.g. in the metrics) can detect solver parameters drifting from the set size.
+    pub fn send_with_on_encode_retry<C, RNG>(
+        self,
+        channel: &mut C,
+        points: &[(F, F)],
+        query_num: usize,
+        rng: &mut RNG,
+        on_encode_retry: Option<&dyn Fn(u32)>,
+    ) -> Result<Box<dyn Fn(F) -> Result<F, Error> + Send>, Error>
+    where
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+    {
+        let (fk, _table, _timing) = self
+            .send_with_hooks(channel, points, query_num, rng, on_encode_retry, false)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        Ok(fk)
+    }
+
+    // `presorted`: `points` are sorted by the canonical bytes of their keys. See send_presorted.
+    fn send_with_hooks<C, RNG>(
+        self,
+        channel: &mut C,
+        points: &[(F, F)],
+        query_num: usize,
+        rng: &mut RNG,
+        on_encode_retry: Option<&dyn Fn(u32)>,
+        presorted: bool,
+    ) -> Result<
+        (
+            Box<dyn Fn(F) -> Result<F, Error> + Send>,
+            ProgrammedTable<F, S>,
+            OpprfTiming,
+        ),
+        Error,
+    >
+    where
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+    {
+        // fail before the OPRF consumes the precomputation.
+        if points.is_empty() {
+            return Err(PsiError::EmptyProgram.into());
+        }
+
+        let mut timing = OpprfTiming::default();
+        let start = Instant::now();
+
+        let fk = self
+            .oprf_sender
+            .send(channel, query_num, rng)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+        timing.oprf += start.elapsed();
+
+        let padded;
+        let points = match self.pad_bucket {
+            Some(bucket) if presorted => {
+                padded = pad_sorted_points(points, bucket, rng);
+                &padded[..]
+            }
+            Some(bucket) => {
+                padded = pad_points(points, bucket, rng);
+                &padded[..]
+            }
+            None => points,
+        };
+
+        let table = program_with_oprf_outputs::<F, S, _, _>(
+            channel,
+            points,
+            &*fk,
+            self.params,
+            EncodeOptions {
+                on_retry: on_encode_retry,
+                blinding: self.blinding,
+                aux_seed: self.aux_seed,
+                wire_bits: truncated_bits::<F, S>(self.wire_bits, self.blinding),
+                #[cfg(feature = "rayon")]
+                pool: self.pool.as_deref(),
+                ..EncodeOptions::new(self.encode_attempts, self.verify_encode)
+            },
+            rng,
+            &mut timing,
+        )
+        .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        let table_for_fk = table.clone();
+        let blinding = self.blinding;
+        let fk = move |x: F| -> Result<F, Error> {
+            let d = table_for_fk
+                .decode(x)
+                .with_context(|| format!("@{}:{}", file!(), line!()))?;
+            let res = blinding.unblind(
+                d,
+                fk(x).with_context(|| format!("@{}:{}", file!(), line!()))?,
+            );
+            Ok(res)
+        };
+
+        timing.total = start.elapsed();
+
+        Ok((Box::new(fk), table, timing))
+    }
+
+    /// Sender side of [receive_membership](SepOpprfReceiverWithVole::receive_membership).
+    ///
+    /// All elements of `set` are programmed to zero, so the receiver can only learn whether each query is in `set`.
+    pub fn send_membership<C, RNG>(
+        self,
+        channel: &mut C,
+        set: &[F],
+        query_num: usize,
+        rng: &mut RNG,
+    ) -> Result<(), Error>
+    where
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+    {
+        let points = set.iter().map(|&x| (x, F::zero())).collect::<Vec<_>>();
+
+        self.send_with_table(channel, &points, query_num, rng)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        Ok(())
+    }
+
+    /// Sender side of [receive_with_u64_payload](SepOpprfReceiverWithVole::receive_with_u64_payload).
+    ///
+    /// Each key of `points` is programmed to its `u64` payload packed into one field element, without boxing payloads into `Vec<F>`.
+    /// It fails together with the receiver if the field has less than 128 bits.
+    pub fn send_with_u64_payload<C, RNG>(
+        self,
+        channel: &mut C,
+        points: &[(F, u64)],
+        query_num: usize,
+        rng: &mut RNG,
+    ) -> Result<(), Error>
+    where
+        F: FromU128,
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+    {
+        check_u64_payload_handshake::<F, _>(channel)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        let points = points
+            .iter()
+            .map(|&(x, payload)| (x, pack_u64::<F>(payload)))
+            .collect::<Vec<_>>();
+
+        self.send_with_table(channel, &points, query_num, rng)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        Ok(())
+    }
+
+    /// Sender side of [receive_tagged](SepOpprfReceiverWithVole::receive_tagged): each key of `points` is programmed to its value and
+    /// to the [tag_of] the value under `mac_key`.
+    ///
+    /// Both tables share one OPRF run. The value table is padded by the OPRF output, and the tag table by a hash of it,
+    /// so the two tables are independent for keys which are not programmed. It sends two tables, so the online communication of the solver doubles.
+    /// Both tables are encoded as in [send](SepOpprfSender::send), with the blinding, the padding and the seeded auxillary information of this sender.
+    pub fn send_tagged<C, RNG>(
+        self,
+        channel: &mut C,
+        points: &[(F, F)],
+        mac_key: F,
+        query_num: usize,
+        rng: &mut RNG,
+    ) -> Result<(), Error>
+    where
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+    {
+        // fail before the OPRF consumes the precomputation.
+        if points.is_empty() {
+            return Err(PsiError::EmptyProgram.into());
+        }
+
+        let fk = self
+            .oprf_sender
+            .send(channel, query_num, rng)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        let padded;
+        let points = match self.pad_bucket {
+            Some(bucket) => {
+                padded = pad_points(points, bucket, rng);
+                &padded[..]
+            }
+            None => points,
+        };
+
+        let encode = EncodeOptions {
+            blinding: self.blinding,
+            aux_seed: self.aux_seed,
+            #[cfg(feature = "rayon")]
+            pool: self.pool.as_deref(),
+            ..EncodeOptions::new(self.encode_attempts, self.verify_encode)
+        };
+
+        let mut timing = OpprfTiming::default();
+        program_with_oprf_outputs::<F, S, _, _>(
+            channel,
+            points,
+            &*fk,
+            self.params,
+            encode,
+            rng,
+            &mut timing,
+        )
+        .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        let tag_points = points
+            .iter()
+            .map(|&(x, value)| Ok((x, tag_of(mac_key, x, value)?)))
+            .collect::<Result<Vec<_>, Error>>()?;
+        let tag_pad = |x: F| -> Result<F, Error> { hash_with_tag(TAG_PAD_DOMAIN, x, fk(x)?) };
+        program_with_oprf_outputs::<F, S, _, _>(
+            channel,
+            &tag_points,
+            &tag_pad,
+            self.params,
+            encode,
+            rng,
+            &mut timing,
+        )
+        .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        Ok(())
+    }
+
+    /// Sender side of [receive_range](SepOpprfReceiverWithVole::receive_range): `ranges` of `width`-bit keys are programmed instead of a set.
+    ///
+    /// Each range `[lo, hi]` is decomposed into prefix blocks by [decompose_range], and the tagged keys of the blocks are programmed to zero.
+    /// Both sides must be precomputed for [range_query_num]. The receiver learns the number of ranges only through this bound.
+    pub fn send_range<C, RNG>(
+        self,
+        channel: &mut C,
+        ranges: &[(u128, u128)],
+        width: u32,
+        query_num: usize,
+        rng: &mut RNG,
+    ) -> Result<(), Error>
+    where
+        F: FromU128,
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+    {
+        let mut seen = HashSet::new();
+        let mut keys = Vec::new();
+        for &(lo, hi) in ranges.iter() {
+            let blocks = decompose_range(lo, hi, width)
+                .with_context(|| format!("@{}:{}", file!(), line!()))?;
+            for (network, prefix) in blocks {
+                // overlapping ranges may share blocks.
+                let key = prefix.tagged_key::<F>(network);
+                if seen.insert(key) {
+                    keys.push(key);
+                }
+            }
+        }
+
+        self.send_membership(channel, &keys, query_num, rng)
+            .with_context(|| format!("@{}:{}", file!(), line!()))
+    }
+
+    /// Same as [send](SepOpprfSender::send), but each point `(x, value, expiry)` has an optional expiry timestamp,
+    /// and the points expired at `now` (`expiry <= now`) are excluded before encoding. A point without expiry never expires.
+    ///
+    /// The receiver runs the usual [receive](SepOpprfReceiver::receive), and gets random outputs on the expired keys as on keys not programmed.
+    /// The timestamps are compared as they are, so their unit (e.g. seconds since the UNIX epoch) is up to the caller.
+    /// If all points are expired, it fails with [PsiError::EmptyProgram].
+    pub fn send_with_expiry<C, RNG>(
+        self,
+        channel: &mut C,
+        points: &[(F, F, Option<u64>)],
+        now: u64,
+        query_num: usize,
+        rng: &mut RNG,
+    ) -> Result<Box<dyn Fn(F) -> Result<F, Error> + Send>, Error>
+    where
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+    {
+        let points = points
+            .iter()
+            .filter(|(_, _, expiry)| expiry.map_or(true, |expiry| expiry > now))
+            .map(|&(x, value, _)| (x, value))
+            .collect::<Vec<_>>();
+
+        self.send(channel, &points, query_num, rng)
+            .with_context(|| format!("@{}:{}", file!(), line!()))
+    }
+
+    /// [send_membership](SepOpprfSenderWithVole::send_membership) preceded by a [BloomFilter] of `set` with false positive rate `fp_rate`.
+    ///
+    /// The receiver must run [receive_membership_with_prefilter](SepOpprfReceiverWithVole::receive_membership_with_prefilter).
+    ///
+    /// **Privacy tradeoff:** the filter reveals approximate membership of `set` to the receiver, who can test any element against it offline.
+    /// Use it only when this leakage is acceptable.
+    pub fn send_membership_with_prefilter<C, RNG>(
+        self,
+        channel: &mut C,
+        set: &[F],
+        fp_rate: f64,
+        query_num: usize,
+        rng: &mut RNG,
+    ) -> Result<(), Error>
+    where
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+    {
+        let filter = BloomFilter::from_set(set, fp_rate, rng)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+        filter
+            .send(channel)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        self.send_membership(channel, set, query_num, rng)
+            .with_context(|| format!("@{}:{}", file!(), line!()))
+    }
+}
+
+/// Actual implementation of Separated OPPRF receiver using VOLE.
+///
+/// Please look the parent document ( [crate::preprocessed::opprf] ) for usage example.
+pub struct SepOpprfReceiverWithVole<F, S, V>
+where
+    F: FF,
     S: Solver<F>,
     V: VoleShareForReceiver<F>,
 {
-    fn clone(&self) -> Self {
-        Self {
-            params: self.params,
-            oprf_receiver: self.oprf_receiver.clone(),
-        }
+    params: S::Params,
+    // runtime options, not saved in the state.
+    blinding: BlindingMode,
+    // See SepOpprfSenderWithVole::wire_bits.
+    wire_bits: Option<usize>,
+    // See SepOpprfSenderWithVole::aux_seed.
+    aux_seed: Option<Block>,
+    oprf_receiver: SepOprfReceiverWithVole<F, S, V>,
+}
+
+impl<F, S, V> ObliviousProgrammablePrf for SepOpprfReceiverWithVole<F, S, V>
+where
+    F: FF,
+    S: Solver<F>,
+    V: VoleShareForReceiver<F>,
+{
+    type Seed = ();
+    type Input = F;
+    type Output = F;
+}
+
+impl<F, S, V> SepOpprfReceiver for SepOpprfReceiverWithVole<F, S, V>
+where
+    F: FF,
+    S: Solver<F>,
+    V: VoleShareForReceiver<F>,
+{
+    type PrecompSystem = V;
+
+    /// Actual implementation of precomputation for the receiver, using Separated OPRF precomp protocol.
+    fn precomp<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        rng: &mut RNG,
+        query_num: usize,
+        system: Self::PrecompSystem,
+    ) -> Result<Self, Error> {
+        Self::precomp_with_tag(channel, rng, query_num, &[], system)
+    }
+
+    /// Actual implementation of receive protocol, using Separated OPRF receive protocol.
+    fn receive<C, RNG>(
+        self,
+        channel: &mut C,
+        queries: &[Self::Input],
+        rng: &mut RNG,
+    ) -> Result<Vec<(Self::Input, Self::Output)>, Error>
+    where
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+    {
+        let (points, _table) = self
+            .receive_with_table(channel, queries, rng)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        Ok(points)
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::solver::{PaxosSolver, Solver, SolverParams, VandelmondeSolver};
-    use crate::vole::{
-        LPNVoleReceiver, LPNVoleSender, LPN_EXTEND_MEDIUM, LPN_EXTEND_SMALL, LPN_SETUP_MEDIUM,
-        LPN_SETUP_SMALL,
-    };
-    use rand::distributions::{Distribution, Standard};
-    use rand::seq::SliceRandom;
-    use scuttlebutt::serialization::CanonicalSerialize;
-    use scuttlebutt::{field::F128b, AesRng, Channel};
-    use std::collections::{HashMap, HashSet};
-    use std::io::{BufReader, BufWriter};
-    use std::os::unix::net::UnixStream;
+impl<F, S, V> SepOpprfReceiverWithVole<F, S, V>
+where
+    F: FF,
+    S: Solver<F>,
+    V: VoleShareForReceiver<F>,
+{
+    /// Describe the field, the solver and the VOLE in use. e.g. `OPPRF[field=F128b, solver=Paxos, vole=LPN(medium)]`
+    pub fn describe(&self) -> String {
+        format!("OPPRF[{}]", self.describe_components())
+    }
+
+    pub(crate) fn describe_components(&self) -> String {
+        self.oprf_receiver.describe_components()
+    }
+
+    /// Length of the VOLE correlation allocated by the precomputation. See [SepOpprfSenderWithVole::vole_length].
+    pub fn vole_length(&self) -> usize {
+        self.oprf_receiver.vole_length()
+    }
+
+    // See [crate::preprocessed::state] for the layout.
+    pub(crate) fn write_state<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        write_params::<F, S, _>(writer, self.params)?;
+        self.oprf_receiver.write_state(writer)
+    }
+
+    /// Shrink the precomputation to `query_num` queries. See [SepOpprfSenderWithVole::shrink].
+    pub fn shrink(self, query_num: usize) -> Result<Self, Error> {
+        let oprf_receiver = self
+            .oprf_receiver
+            .shrink(query_num)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+        Ok(Self {
+            params: S::calc_params(query_num),
+            blinding: self.blinding,
+            wire_bits: self.wire_bits,
+            aux_seed: self.aux_seed,
+            oprf_receiver,
+        })
+    }
+
+    /// Unblind the decoded values by `blinding` in [receive](SepOpprfReceiver::receive) and the variants built on it. Default: [BlindingMode::Additive].
+    ///
+    /// The sender must use the same mode by [with_blinding](SepOpprfSenderWithVole::with_blinding). It is a runtime option, so it is not saved by `write_state`.
+    pub fn with_blinding(mut self, blinding: BlindingMode) -> Self {
+        self.blinding = blinding;
+        self
+    }
+
+    /// Receive the table truncated to the lowest `bits` bits of each entry in [receive](SepOpprfReceiver::receive) and the variants built on it,
+    /// except [receive_tagged](SepOpprfReceiverWithVole::receive_tagged). Default: whole elements.
+    ///
+    /// The sender must use the same `bits` by [with_wire_bits](SepOpprfSenderWithVole::with_wire_bits), which describes when the table is truncated.
+    /// It is a runtime option, so it is not saved by `write_state`.
+    pub fn with_wire_bits(mut self, bits: usize) -> Self {
+        self.wire_bits = Some(bits);
+        self
+    }
+
+    pub(crate) fn read_state<R: Read>(reader: &mut R, system: V) -> Result<Self, Error> {
+        let params = read_params::<F, S, _>(reader)?;
+        let oprf_receiver = SepOprfReceiverWithVole::read_state(reader, system)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+        Ok(Self {
+            params,
+            blinding: BlindingMode::default(),
+            wire_bits: None,
+            aux_seed: None,
+            oprf_receiver,
+        })
+    }
+
+    /// Same as [precomp](SepOpprfReceiver::precomp), but `domain_tag` is mixed into the underlying OPRF. Both sides must supply the same tag.
+    pub fn precomp_with_tag<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        rng: &mut RNG,
+        query_num: usize,
+        domain_tag: &[u8],
+        system: V,
+    ) -> Result<Self, Error> {
+        let config = OpprfConfig::default().with_domain_tag(domain_tag);
+        Self::precomp_with_config(channel, rng, query_num, &config, system)
+    }
+
+    /// Same as [precomp](SepOpprfReceiver::precomp), but tuned by `config`. The sender must use the same config.
+    pub fn precomp_with_config<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        rng: &mut RNG,
+        query_num: usize,
+        config: &OpprfConfig,
+        system: V,
+    ) -> Result<Self, Error> {
+        let capacity = config.capacity(query_num);
+        let params = S::calc_params(capacity);
+        let domain_tag = run_domain_tag(channel, rng, config, false)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+        let aux_seed = agree_aux_seed(channel, config, &domain_tag)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+        let oprf_receiver =
+            SepOprfReceiverWithVole::precomp(channel, rng, capacity, &domain_tag, system)
+                .with_context(|| format!("@{}:{}", file!(), line!()))?;
+        Ok(Self {
+            params,
+            blinding: BlindingMode::default(),
+            wire_bits: None,
+            aux_seed,
+            oprf_receiver,
+        })
+    }
+
+    /// Same as [precomp](SepOpprfReceiver::precomp), but the solver is sized by `params`. See [SepOpprfSenderWithVole::precomp_with_params].
+    pub fn precomp_with_params<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        rng: &mut RNG,
+        params: S::Params,
+        system: V,
+    ) -> Result<Self, Error> {
+        check_params_handshake::<F, S, _>(channel, params)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        let oprf_receiver =
+            SepOprfReceiverWithVole::precomp_with_params(channel, rng, params, &[], system)
+                .with_context(|| format!("@{}:{}", file!(), line!()))?;
+        Ok(Self {
+            params,
+            blinding: BlindingMode::default(),
+            wire_bits: None,
+            aux_seed: None,
+            oprf_receiver,
+        })
+    }
+
+    /// Same as [receive](SepOpprfReceiver::receive), but also returns the programmed table received from the sender.
+    pub fn receive_with_table<C, RNG>(
+        self,
+        channel: &mut C,
+        queries: &[F],
+        rng: &mut RNG,
+    ) -> Result<(Vec<(F, F)>, ProgrammedTable<F, S>), Error>
+    where
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+    {
+        let (points, table, _timing) = self
+            .receive_with_timing(channel, queries, rng)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        Ok((points, table))
+    }
+
+    /// Same as [receive_with_table](SepOpprfReceiverWithVole::receive_with_table), but also returns the time spent in the OPRF layer and in the solver.
+    pub fn receive_with_timing<C, RNG>(
+        self,
+        channel: &mut C,
+        queries: &[F],
+        rng: &mut RNG,
+    ) -> Result<(Vec<(F, F)>, ProgrammedTable<F, S>, OpprfTiming), Error>
+    where
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+    {
+        let Self {
+            params,
+            blinding,
+            wire_bits,
+            aux_seed,
+            oprf_receiver,
+        } = self;
+
+        let mut timing = OpprfTiming::default();
+        let start = Instant::now();
+
+        let oprf_res = oprf_receiver
+            .receive(channel, queries, rng)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+        timing.oprf = start.elapsed();
+
+        let solver_start = Instant::now();
+        let (points, table) = unblind_with_oprf_outputs::<F, S, _, _>(
+            channel,
+            &oprf_res,
+            params,
+            blinding,
+            truncated_bits::<F, S>(wire_bits, blinding),
+            aux_seed,
+            rng,
+        )
+        .with_context(|| format!("@{}:{}", file!(), line!()))?;
+        timing.solver = solver_start.elapsed();
+        timing.total = start.elapsed();
+
+        Ok((points, table, timing))
+    }
+
+    /// Same as [receive](SepOpprfReceiver::receive), but outputs are computed `batch_size` queries at a time and
+    /// passed to `on_batch` as soon as each batch completes, in query order.
+    ///
+    /// The messages are the same as [receive](SepOpprfReceiver::receive), so the sender runs the usual [send](SepOpprfSender::send).
+    /// Only the evaluation after the programmed table arrives is batched.
+    pub fn receive_with_flush<C, RNG, G>(
+        self,
+        channel: &mut C,
+        queries: &[F],
+        rng: &mut RNG,
+        batch_size: usize,
+        mut on_batch: G,
+    ) -> Result<(), Error>
+    where
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+        G: FnMut(&[(F, F)]),
+    {
+        let mut outputs = self
+            .receive_iter(channel, queries, rng, batch_size)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+        while let Some(points) = outputs.next_batch() {
+            on_batch(points?.as_slice());
+        }
+
+        Ok(())
+    }
+
+    /// Same as [receive](SepOpprfReceiver::receive), but returns an iterator over the outputs in query order,
+    /// which evaluates them `batch_size` queries at a time when the previous batch is consumed.
+    ///
+    /// The messages are the same as [receive](SepOpprfReceiver::receive), and all of them are exchanged before this returns.
+    /// Only the evaluation is lazy, so the iterator doesn't borrow the channel. An error of a batch ends the iteration.
+    pub fn receive_iter<'a, C, RNG>(
+        self,
+        channel: &mut C,
+        queries: &'a [F],
+        rng: &mut RNG,
+        batch_size: usize,
+    ) -> Result<OpprfOutputs<'a, F, S>, Error>
+    where
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+    {
+        if batch_size == 0 {
+            bail!("batch_size must be positive. @{}:{}", file!(), line!());
+        }
+
+        let Self {
+            params,
+            blinding,
+            wire_bits,
+            aux_seed,
+            oprf_receiver,
+        } = self;
+
+        let evaluator = oprf_receiver
+            .receive_deferred(channel, queries, rng)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        let aux = receive_aux::<F, S, _, _>(channel, aux_seed, rng)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        let p = read_table(channel, truncated_bits::<F, S>(wire_bits, blinding))
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        Ok(OpprfOutputs {
+            batches: queries.chunks(batch_size),
+            decoder: ProgrammedTable::<F, S>::new(params, aux, p).decoder(),
+            evaluator,
+            blinding,
+            batch: Vec::new().into_iter(),
+        })
+    }
+
+    /// Convenience wrapper of [receive_with_flush](SepOpprfReceiverWithVole::receive_with_flush) which takes the queries from
+    /// `query_source` (e.g. a second socket of a data feed) instead of a slice.
+    ///
+    /// The source is a sequence of queries in the canonical bytes of `F`, read up to its end. It fails if it ends in the middle of a query
+    /// or a query is not an element. It doesn't stream the queries: the OPRF takes all the queries in one message, so the whole source
+    /// is read into memory before the first message. Only the outputs are streamed to `on_batch`. A source which doesn't fit in memory
+    /// has to be split and queried by one session (precomputation) per part.
+    pub fn receive_all_from_reader<C, R, RNG, G>(
+        self,
+        channel: &mut C,
+        query_source: R,
+        rng: &mut RNG,
+        batch_size: usize,
+        on_batch: G,
+    ) -> Result<(), Error>
+    where
+        C: AbstractChannel,
+        R: Read,
+        RNG: CryptoRng + Rng,
+        G: FnMut(&[(F, F)]),
+    {
+        let queries = read_queries::<F, _>(query_source)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        self.receive_with_flush(channel, &queries, rng, batch_size, on_batch)
+            .with_context(|| format!("@{}:{}", file!(), line!()))
+    }
+
+    /// Receive only the membership bits of `queries`, in query order.
+    ///
+    /// The sender must run [send_membership](SepOpprfSenderWithVole::send_membership).
+    /// A query is regarded as a member if its OPPRF output is zero. False positive occurs with probability $`1/|\mathbb{F}|`$ per query.
+    pub fn receive_membership<C, RNG>(
+        self,
+        channel: &mut C,
+        queries: &[F],
+        rng: &mut RNG,
+    ) -> Result<Vec<bool>, Error>
+    where
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+    {
+        let (points, _table) = self
+            .receive_with_table(channel, queries, rng)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        Ok(points.into_iter().map(|(_, y)| y == F::zero()).collect())
+    }
+
+    /// Receive the `u64` payloads of the queries held by the sender, as `(query, payload)` in query order.
+    ///
+    /// The sender must run [send_with_u64_payload](SepOpprfSenderWithVole::send_with_u64_payload).
+    /// A payload is packed into the low 64 bits of a field element, and a query is regarded as a member if the upper bits of its OPPRF output are zero.
+    /// So the field must have at least 128 bits (e.g. `F128b`), and false positive occurs with probability $`2^{-64}`$ per query.
+    /// Both sides declare their field length before the OPRF, so that a smaller field makes both of them fail instead of the sender blocking.
+    pub fn receive_with_u64_payload<C, RNG>(
+        self,
+        channel: &mut C,
+        queries: &[F],
+        rng: &mut RNG,
+    ) -> Result<Vec<(F, u64)>, Error>
+    where
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+    {
+        check_u64_payload_handshake::<F, _>(channel)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        let (points, _table) = self
+            .receive_with_table(channel, queries, rng)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        Ok(points
+            .into_iter()
+            .filter_map(|(x, y)| unpack_u64(y).map(|payload| (x, payload)))
+            .collect())
+    }
+
+    /// Receive `(query, (value, tag))` of `queries`, in query order.
+    ///
+    /// The sender must run [send_tagged](SepOpprfSenderWithVole::send_tagged). For a key programmed by the sender, the value is the programmed one
+    /// and `tag == tag_of(mac_key, query, value)` holds, where `mac_key` is shared with the sender. For other keys both are pseudo-random,
+    /// so the tag verifies only with probability $`1/|\mathbb{F}|`$.
+    pub fn receive_tagged<C, RNG>(
+        self,
+        channel: &mut C,
+        queries: &[F],
+        rng: &mut RNG,
+    ) -> Result<Vec<(F, (F, F))>, Error>
+    where
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+    {
+        let Self {
+            params,
+            blinding,
+            wire_bits: _,
+            aux_seed,
+            oprf_receiver,
+        } = self;
+
+        let oprf_res = oprf_receiver
+            .receive(channel, queries, rng)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        let (values, _table) = unblind_with_oprf_outputs::<F, S, _, _>(
+            channel, &oprf_res, params, blinding, None, aux_seed, rng,
+        )
+        .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        let tag_pads = oprf_res
+            .iter()
+            .map(|&(x, fkx)| Ok((x, hash_with_tag(TAG_PAD_DOMAIN, x, fkx)?)))
+            .collect::<Result<Vec<_>, Error>>()?;
+        let (tags, _table) = unblind_with_oprf_outputs::<F, S, _, _>(
+            channel, &tag_pads, params, blinding, None, aux_seed, rng,
+        )
+        .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        Ok(values
+            .into_iter()
+            .zip(tags)
+            .map(|((x, value), (_, tag))| (x, (value, tag)))
+            .collect())
+    }
+
+    /// Range mode of [receive_membership](SepOpprfReceiverWithVole::receive_membership): whether each of `points` falls in some range of the sender, in point order.
+    ///
+    /// The sender must run [send_range](SepOpprfSenderWithVole::send_range) with the same `width`.
+    /// Every point is queried with its [tagged keys](PrefixKey::tagged_key) of all `width + 1` prefix lengths,
+    /// so the sender can't tell which block matched.
+    pub fn receive_range<C, RNG>(
+        self,
+        channel: &mut C,
+        points: &[u128],
+        width: u32,
+        rng: &mut RNG,
+    ) -> Result<Vec<bool>, Error>
+    where
+        F: FromU128,
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+    {
+        if width == 0 || width > 127 {
+            bail!(
+                "range key width (={}) must be in 1..=127. @{}:{}",
+                width,
+                file!(),
+                line!()
+            );
+        }
+
+        let queries = points
+            .iter()
+            .flat_map(|&x| {
+                (0..=width).map(move |bits| PrefixKey::new(width, bits).unwrap().tagged_key::<F>(x))
+            })
+            .collect::<Vec<_>>();
+
+        let membership = self
+            .receive_membership(channel, &queries, rng)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        Ok(membership
+            .chunks(width as usize + 1)
+            .map(|bits| bits.iter().any(|&b| b))
+            .collect())
+    }
+
+    /// [receive_membership](SepOpprfReceiverWithVole::receive_membership) only on the queries passing the [BloomFilter] sent by
+    /// [send_membership_with_prefilter](SepOpprfSenderWithVole::send_membership_with_prefilter).
+    ///
+    /// Returns the membership bits in query order and the number of queries evaluated by the OPPRF.
+    /// Queries rejected by the filter are surely not members. The sender does not learn how many queries passed,
+    /// since the size of the programmed table is fixed by the precomputation.
+    /// See [send_membership_with_prefilter](SepOpprfSenderWithVole::send_membership_with_prefilter) for the privacy tradeoff.
+    pub fn receive_membership_with_prefilter<C, RNG>(
+        self,
+        channel: &mut C,
+        queries: &[F],
+        rng: &mut RNG,
+    ) -> Result<(Vec<bool>, usize), Error>
+    where
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+    {
+        let filter =
+            BloomFilter::receive(channel).with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        let passed = queries
+            .iter()
+            .filter(|x| filter.contains(*x))
+            .copied()
+            .collect::<Vec<_>>();
+
+        let passed_membership = self
+            .receive_membership(channel, &passed, rng)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+        let members = passed
+            .iter()
+            .zip(passed_membership)
+            .filter_map(|(&x, b)| if b { Some(x) } else { None })
+            .collect::<HashSet<F>>();
+
+        let membership = queries.iter().map(|x| members.contains(x)).collect();
+
+        Ok((membership, passed.len()))
+    }
+}
+
+// pack a u64 payload into the low 64 bits of a field element. The upper bits are left zero as the membership tag.
+fn pack_u64<F: FF + FromU128>(payload: u64) -> F {
+    F::from_u128(payload as u128)
+}
+
+// Both sides of the u64 payload send their field length before reading the peer's, as check_params_handshake.
+// The check depends only on the two lengths, so the sender and the receiver fail together.
+fn check_u64_payload_handshake<F: FF, C: AbstractChannel>(channel: &mut C) -> Result<(), Error> {
+    let ours = F::zero().to_bytes().len();
+    channel.write_u64(ours as u64)?;
+    channel.flush()?;
+
+    let theirs = channel.read_u64()? as usize;
+    if theirs != ours {
+        bail!(
+            "the peer's field has {} bits, but ours has {} bits. @{}:{}",
+            theirs * 8,
+            ours * 8,
+            file!(),
+            line!()
+        );
+    }
+    if ours < 16 {
+        bail!(
+            "u64 payloads need a field with at least 128 bits, but it has {} bits. @{}:{}",
+            ours * 8,
+            file!(),
+            line!()
+        );
+    }
+
+    Ok(())
+}
+
+// inverse of pack_u64. None if the upper bits are not zero, i.e. the output is not a programmed payload.
+fn unpack_u64<F: FF>(y: F) -> Option<u64> {
+    let bytes = y.to_bytes();
+    if bytes[8..].iter().any(|&b| b != 0) {
+        return None;
+    }
+    Some(u64::from_le_bytes(bytes[..8].try_into().unwrap()))
+}
+
+/// Program the OPPRF with OPRF outputs supplied by `oprf_eval`, and send the programmed table.
+///
+/// This is the OPPRF step of [send_with_table](SepOpprfSenderWithVole::send_with_table) without the OPRF step,
+/// so the OPRF can be run separately (e.g. by another library or another VOLE backend).
+/// `oprf_eval` must be the OPRF key of the sender, and the receiver must run [receive_with_oprf_outputs] with its outputs on the queries.
+/// The OPPRF output of $`x`$ is `table.decode(x) + oprf_eval(x)`.
+pub fn send_with_oprf_outputs<F, S, C, RNG>(
+    channel: &mut C,
+    points: &[(F, F)],
+    oprf_eval: &dyn Fn(F) -> Result<F, Error>,
+    params: S::Params,
+    rng: &mut RNG,
+) -> Result<ProgrammedTable<F, S>, Error>
+where
+    F: FF,
+    S: Solver<F>,
+    C: AbstractChannel,
+    RNG: CryptoRng + Rng,
+{
+    program_with_oprf_outputs::<F, S, _, _>(
+        channel,
+        points,
+        oprf_eval,
+        params,
+        EncodeOptions::new(DEFAULT_ENCODE_ATTEMPTS, false),
+        rng,
+        &mut OpprfTiming::default(),
+    )
+}
+
+// how program_with_oprf_outputs encodes the table.
+#[derive(Clone, Copy)]
+struct EncodeOptions<'a> {
+    attempts: usize,
+    verify: bool,
+    on_retry: Option<&'a dyn Fn(u32)>,
+    blinding: BlindingMode,
+    aux_seed: Option<Block>,
+    // bits of each entry on the wire, checked by truncated_bits. None sends whole elements.
+    wire_bits: Option<usize>,
+    #[cfg(feature = "rayon")]
+    pool: Option<&'a rayon::ThreadPool>,
+}
+
+impl EncodeOptions<'_> {
+    fn new(attempts: usize, verify: bool) -> Self {
+        Self {
+            attempts,
+            verify,
+            on_retry: None,
+            blinding: BlindingMode::default(),
+            aux_seed: None,
+            wire_bits: None,
+            #[cfg(feature = "rayon")]
+            pool: None,
+        }
+    }
+
+    // Solver::encode, or Solver::encode_in if a pool is given.
+    fn encode<F: FF, S: Solver<F>, RNG: CryptoRng + Rng>(
+        &self,
+        rng: &mut RNG,
+        points: &[(F, F)],
+        aux: S::AuxInfo,
+        params: S::Params,
+    ) -> Result<Vec<F>, Error> {
+        #[cfg(feature = "rayon")]
+        if let Some(pool) = self.pool {
+            return S::encode_in(Some(pool), rng, points, aux, params);
+        }
+        S::encode(rng, points, aux, params)
+    }
+
+    // encode_with_retry_hook, encoding each attempt by Self::encode.
+    fn encode_with_retry<F: FF, S: Solver<F>, RNG: CryptoRng + Rng>(
+        &self,
+        rng: &mut RNG,
+        points: &[(F, F)],
+        params: S::Params,
+    ) -> Result<(Vec<F>, S::AuxInfo), Error> {
+        #[cfg(feature = "rayon")]
+        if let Some(pool) = self.pool {
+            return encode_with_retry_in::<F, S, _>(
+                Some(pool),
+                rng,
+                points,
+                params,
+                self.attempts,
+                self.on_retry,
+            );
+        }
+        encode_with_retry_hook::<F, S, _>(rng, points, params, self.attempts, self.on_retry)
+    }
+}
+
+fn program_with_oprf_outputs<F, S, C, RNG>(
+    channel: &mut C,
+    points: &[(F, F)],
+    oprf_eval: &dyn Fn(F) -> Result<F, Error>,
+    params: S::Params,
+    encode: EncodeOptions<'_>,
+    rng: &mut RNG,
+    timing: &mut OpprfTiming,
+) -> Result<ProgrammedTable<F, S>, Error>
+where
+    F: FF,
+    S: Solver<F>,
+    C: AbstractChannel,
+    RNG: CryptoRng + Rng,
+{
+    if points.is_empty() {
+        return Err(PsiError::EmptyProgram.into());
+    }
+
+    let start = Instant::now();
+    let points = points
+        .iter()
+        .map(|&(x, z)| {
+            let pad = oprf_eval(x).with_context(|| format!("@{}:{}", file!(), line!()))?;
+            let y = encode
+                .blinding
+                .blind(z, pad)
+                .with_context(|| format!("@{}:{}", file!(), line!()))?;
+            Ok((x, y))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    timing.oprf += start.elapsed();
+
+    let start = Instant::now();
+    let (p, aux) = match encode.aux_seed {
+        Some(seed) => {
+            let aux =
+                S::gen_aux_from_seed(seed).with_context(|| format!("@{}:{}", file!(), line!()))?;
+            let p = encode
+                .encode::<F, S, _>(rng, &points, aux, params)
+                .with_context(|| format!("@{}:{}", file!(), line!()))?;
+            (p, aux)
+        }
+        None => encode
+            .encode_with_retry::<F, S, _>(rng, &points, params)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?,
+    };
+
+    if encode.verify {
+        verify_encoded::<F, S>(&points, &p, aux, params)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+    }
+
+    if encode.aux_seed.is_none() {
+        S::aux_send(channel, rng, aux).with_context(|| format!("@{}:{}", file!(), line!()))?;
+    }
+
+    match encode.wire_bits {
+        Some(bits) => write_vec_f_truncated(channel, &p, bits),
+        None => write_vec_f(channel, &p),
+    }
+    .with_context(|| format!("@{}:{}", file!(), line!()))?;
+    timing.solver += start.elapsed();
+
+    Ok(ProgrammedTable::new(params, aux, p))
+}
+
+// bits of each entry of the table on the wire: `wire_bits` if they are fewer than the field and the truncated table decodes to the truncated values,
+// i.e. the solver decodes by sums in a field of characteristic 2 and the blinding is additive. None sends whole elements.
+fn truncated_bits<F: FF, S: Solver<F>>(
+    wire_bits: Option<usize>,
+    blinding: BlindingMode,
+) -> Option<usize> {
+    wire_bits.filter(|&bits| {
+        (1..F::ByteReprLen::to_usize() * 8).contains(&bits)
+            && S::SUM_DECODE
+            && F::one() + F::one() == F::zero()
+            && blinding == BlindingMode::Additive
+    })
+}
+
+// read the table written by program_with_oprf_outputs with `wire_bits` of truncated_bits.
+fn read_table<F: FF, C: AbstractChannel>(
+    channel: &mut C,
+    wire_bits: Option<usize>,
+) -> Result<Vec<F>, Error> {
+    match wire_bits {
+        Some(bits) => read_vec_f_truncated(channel, bits),
+        None => read_vec_f(channel),
+    }
+}
+
+// read queries in the canonical bytes of `F` up to the end of `reader`, all into memory.
+fn read_queries<F: FF, R: Read>(mut reader: R) -> Result<Vec<F>, Error> {
+    let mut bytes = Vec::new();
+    reader
+        .read_to_end(&mut bytes)
+        .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+    let len = F::ByteReprLen::to_usize();
+    if bytes.len() % len != 0 {
+        bail!(
+            "the query source ends in the middle of a query: {} bytes are not a multiple of {}. @{}:{}",
+            bytes.len(),
+            len,
+            file!(),
+            line!()
+        );
+    }
+
+    bytes
+        .chunks_exact(len)
+        .enumerate()
+        .map(|(i, chunk)| {
+            F::from_bytes(chunk.into()).with_context(|| {
+                format!(
+                    "query {} is not an element. @{}:{}",
+                    i + 1,
+                    file!(),
+                    line!()
+                )
+            })
+        })
+        .collect()
+}
+
+// pad `points` with random dummy points up to a multiple of `bucket`. The dummy keys collide neither with the real keys nor with each other.
+fn pad_points<F: FF, RNG: Rng>(points: &[(F, F)], bucket: usize, rng: &mut RNG) -> Vec<(F, F)> {
+    let padded_len = points.len().next_multiple_of(bucket.max(1));
+    let mut keys = points.iter().map(|&(x, _)| x).collect::<HashSet<F>>();
+    let mut padded = Vec::with_capacity(padded_len);
+    padded.extend_from_slice(points);
+    while padded.len() < padded_len {
+        let x = random_f(rng);
+        if keys.insert(x) {
+            padded.push((x, random_f(rng)));
+        }
+    }
+    padded
+}
+
+// same as pad_points for `points` sorted by the canonical bytes of their keys, which are looked up by binary search.
+// Only the dummy keys are kept in a set, so the cost doesn't grow with the number of real keys beyond the search.
+fn pad_sorted_points<F: FF, RNG: Rng>(
+    points: &[(F, F)],
+    bucket: usize,
+    rng: &mut RNG,
+) -> Vec<(F, F)> {
+    let padded_len = points.len().next_multiple_of(bucket.max(1));
+    let mut dummies = HashSet::new();
+    let mut padded = Vec::with_capacity(padded_len);
+    padded.extend_from_slice(points);
+    while padded.len() < padded_len {
+        let x = random_f::<F, _>(rng);
+        let is_real = points
+            .binary_search_by_key(&x.to_bytes(), |(k, _)| k.to_bytes())
+            .is_ok();
+        if !is_real && dummies.insert(x) {
+            padded.push((x, random_f(rng)));
+        }
+    }
+    padded
+}
+
+// uniformly random field element, drawn from random bytes and rejected while they aren't a canonical representation.
+fn random_f<F: FF, RNG: Rng>(rng: &mut RNG) -> F {
+    let mut buf = vec![0u8; F::ByteReprLen::to_usize()];
+    loop {
+        rng.fill(buf.as_mut_slice());
+        if let Ok(x) = F::from_bytes(buf.as_slice().into()) {
+            return x;
+        }
+    }
+}
+
+// check that every point decodes to its value under the code vector `p`.
+fn verify_encoded<F: FF, S: Solver<F>>(
+    points: &[(F, F)],
+    p: &[F],
+    aux: S::AuxInfo,
+    params: S::Params,
+) -> Result<(), Error> {
+    let decoder = S::prepare_decoder(p, aux, params);
+    let mut mismatches = 0;
+    for &(x, y) in points.iter() {
+        if decoder
+            .decode(x)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?
+            != y
+        {
+            mismatches += 1;
+        }
+    }
+
+    if mismatches > 0 {
+        return Err(PsiError::EncodeVerificationFailed {
+            mismatches,
+            points: points.len(),
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Outputs of [SepOpprfReceiverWithVole::receive_iter], evaluated lazily a batch at a time in query order.
+pub struct OpprfOutputs<'a, F, S>
+where
+    F: FF,
+    S: Solver<F>,
+{
+    batches: std::slice::Chunks<'a, F>,
+    decoder: S::Decoder,
+    evaluator: OprfEvaluator<F, S>,
+    blinding: BlindingMode,
+    // evaluated outputs of the current batch not consumed yet.
+    batch: std::vec::IntoIter<(F, F)>,
+}
+
+impl<F, S> OpprfOutputs<'_, F, S>
+where
+    F: FF,
+    S: Solver<F>,
+{
+    // evaluate the next batch. After an error, no batch is left.
+    fn next_batch(&mut self) -> Option<Result<Vec<(F, F)>, Error>> {
+        let batch = self.batches.next()?;
+        let points = batch
+            .iter()
+            .map(|&x| {
+                let d = self
+                    .decoder
+                    .decode(x)
+                    .with_context(|| format!("@{}:{}", file!(), line!()))?;
+                let pad = self
+                    .evaluator
+                    .eval(x)
+                    .with_context(|| format!("@{}:{}", file!(), line!()))?;
+                Ok((x, self.blinding.unblind(d, pad)))
+            })
+            .collect::<Result<Vec<_>, Error>>();
+
+        if points.is_err() {
+            self.batches = (&[] as &[F]).chunks(1);
+        }
+        Some(points)
+    }
+}
+
+impl<F, S> Iterator for OpprfOutputs<'_, F, S>
+where
+    F: FF,
+    S: Solver<F>,
+{
+    type Item = Result<(F, F), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(point) = self.batch.next() {
+                return Some(Ok(point));
+            }
+            match self.next_batch()? {
+                Ok(points) => self.batch = points.into_iter(),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+/// Receiver side of [send_with_oprf_outputs].
+///
+/// `oprf_outputs` are pairs of a query and its OPRF output, obtained separately. Returns the OPPRF outputs in the same order and the programmed table.
+pub fn receive_with_oprf_outputs<F, S, C, RNG>(
+    channel: &mut C,
+    oprf_outputs: &[(F, F)],
+    params: S::Params,
+    rng: &mut RNG,
+) -> Result<(Vec<(F, F)>, ProgrammedTable<F, S>), Error>
+where
+    F: FF,
+    S: Solver<F>,
+    C: AbstractChannel,
+    RNG: CryptoRng + Rng,
+{
+    unblind_with_oprf_outputs::<F, S, _, _>(
+        channel,
+        oprf_outputs,
+        params,
+        BlindingMode::default(),
+        None,
+        None,
+        rng,
+    )
+}
+
+fn unblind_with_oprf_outputs<F, S, C, RNG>(
+    channel: &mut C,
+    oprf_outputs: &[(F, F)],
+    params: S::Params,
+    blinding: BlindingMode,
+    wire_bits: Option<usize>,
+    aux_seed: Option<Block>,
+    rng: &mut RNG,
+) -> Result<(Vec<(F, F)>, ProgrammedTable<F, S>), Error>
+where
+    F: FF,
+    S: Solver<F>,
+    C: AbstractChannel,
+    RNG: CryptoRng + Rng,
+{
+    let aux = receive_aux::<F, S, _, _>(channel, aux_seed, rng)
+        .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+    let p = read_table(channel, wire_bits).with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+    let table = ProgrammedTable::<F, S>::new(params, aux, p);
+    let decoder = table.decoder();
+
+    let points = oprf_outputs
+        .iter()
+        .map(|&(x, fkx)| {
+            let d = decoder
+                .decode(x)
+                .with_context(|| format!("@{}:{}", file!(), line!()))?;
+            Ok((x, blinding.unblind(d, fkx)))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    Ok((points, table))
+}
+
+// auxillary information of the programmed table: derived from `aux_seed` if shared, or received from the sender.
+fn receive_aux<F, S, C, RNG>(
+    channel: &mut C,
+    aux_seed: Option<Block>,
+    rng: &mut RNG,
+) -> Result<S::AuxInfo, Error>
+where
+    F: FF,
+    S: Solver<F>,
+    C: AbstractChannel,
+    RNG: CryptoRng + Rng,
+{
+    match aux_seed {
+        Some(seed) => S::gen_aux_from_seed(seed),
+        None => S::aux_receive(channel, rng),
+    }
+}
+
+/// Tag of `value` programmed to `x` by [send_tagged](SepOpprfSenderWithVole::send_tagged): a MAC of `(x, value)` under `mac_key`, shared by both sides.
+pub fn tag_of<F: FF>(mac_key: F, x: F, value: F) -> Result<F, Error> {
+    let mut tag = TAG_MAC_DOMAIN.to_vec();
+    tag.extend_from_slice(&mac_key.to_bytes());
+    hash_with_tag(&tag, x, value)
+}
+
+/// Number of queries both sides of the range mode ([send_range](SepOpprfSenderWithVole::send_range) /
+/// [receive_range](SepOpprfReceiverWithVole::receive_range)) must be precomputed for,
+/// when the receiver has `num_points` points and the sender has `num_ranges` ranges of `width`-bit keys.
+pub fn range_query_num(num_points: usize, num_ranges: usize, width: u32) -> usize {
+    let width = width as usize;
+    (num_points * (width + 1)).max(num_ranges * 2 * width)
+}
+
+/// Complement mode of [receive_membership](SepOpprfReceiverWithVole::receive_membership): the elements of `queries` NOT in the sender's set,
+/// i.e. `queries \ set`, in query order.
+///
+/// The sender must run [send_membership](SepOpprfSenderWithVole::send_membership). The receiver learns nothing more than by the membership bits,
+/// so it's only a convenience for workflows that want the receiver's private-only records.
+pub fn run_complement<F, S, V, C, RNG>(
+    receiver: SepOpprfReceiverWithVole<F, S, V>,
+    channel: &mut C,
+    queries: &[F],
+    rng: &mut RNG,
+) -> Result<Vec<F>, Error>
+where
+    F: FF,
+    S: Solver<F>,
+    V: VoleShareForReceiver<F>,
+    C: AbstractChannel,
+    RNG: CryptoRng + Rng,
+{
+    let membership = receiver
+        .receive_membership(channel, queries, rng)
+        .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+    Ok(queries
+        .iter()
+        .zip(membership)
+        .filter_map(|(&x, b)| if b { None } else { Some(x) })
+        .collect())
+}
+
+/// Receiver side of the Jaccard index $`|A \cap B| / |A \cup B|`$, where $`A`$ is `queries` (distinct elements) and $`B`$ is the set of the sender.
+///
+/// The sender must run [run_jaccard_sender]. The sender first reveals $`|B|`$, and then the intersection cardinality is counted
+/// from the membership bits of [receive_membership](SepOpprfReceiverWithVole::receive_membership).
+/// The index is an estimate only because of the false positives of the membership bits ($`1/|\mathbb{F}|`$ per query).
+///
+/// **What each party learns:** this crate has no cardinality-only PSI, so the receiver learns which of its elements are in $`B`$
+/// (i.e. the intersection itself), in addition to $`|B|`$. The sender learns only the number of queries bounded by `query_num`.
+/// Use it only where revealing the intersection to the receiver is acceptable.
+pub fn run_jaccard<F, S, V, C, RNG>(
+    receiver: SepOpprfReceiverWithVole<F, S, V>,
+    channel: &mut C,
+    queries: &[F],
+    rng: &mut RNG,
+) -> Result<f64, Error>
+where
+    F: FF,
+    S: Solver<F>,
+    V: VoleShareForReceiver<F>,
+    C: AbstractChannel,
+    RNG: CryptoRng + Rng,
+{
+    let sender_size = channel.read_u64()? as usize;
+
+    let membership = receiver
+        .receive_membership(channel, queries, rng)
+        .with_context(|| format!("@{}:{}", file!(), line!()))?;
+    let cardinality = membership.into_iter().filter(|&b| b).count();
+
+    let union = queries.len() + sender_size - cardinality;
+    if union == 0 {
+        bail!(
+            "the Jaccard index of two empty sets is undefined. @{}:{}",
+            file!(),
+            line!()
+        );
+    }
+
+    Ok(cardinality as f64 / union as f64)
+}
+
+/// Sender side of [run_jaccard]: reveal the size of `set` and run [send_membership](SepOpprfSenderWithVole::send_membership).
+pub fn run_jaccard_sender<F, S, V, C, RNG>(
+    sender: SepOpprfSenderWithVole<F, S, V>,
+    channel: &mut C,
+    set: &[F],
+    query_num: usize,
+    rng: &mut RNG,
+) -> Result<(), Error>
+where
+    F: FF,
+    S: Solver<F>,
+    V: VoleShareForSender<F>,
+    C: AbstractChannel,
+    RNG: CryptoRng + Rng,
+{
+    channel.write_u64(set.len() as u64)?;
+    channel.flush()?;
+
+    sender
+        .send_membership(channel, set, query_num, rng)
+        .with_context(|| format!("@{}:{}", file!(), line!()))
+}
+
+/// Run [receive_membership](SepOpprfReceiverWithVole::receive_membership) against several senders in turn,
+/// and return the elements of `queries` contained in all of their sets, in query order.
+///
+/// `receivers[i]` must be precomputed with the sender connected by `channels[i]`, which runs
+/// [send_membership](SepOpprfSenderWithVole::send_membership). All of `queries` are sent to every sender,
+/// so that a sender can't learn the results of the previous senders from the number of queries.
+pub fn run_receiver_multi<F, S, V, C, RNG>(
+    receivers: Vec<SepOpprfReceiverWithVole<F, S, V>>,
+    channels: &mut [C],
+    queries: &[F],
+    rng: &mut RNG,
+) -> Result<Vec<F>, Error>
+where
+    F: FF,
+    S: Solver<F>,
+    V: VoleShareForReceiver<F>,
+    C: AbstractChannel,
+    RNG: CryptoRng + Rng,
+{
+    if receivers.len() != channels.len() {
+        bail!(
+            "{} receivers are given for {} channels. @{}:{}",
+            receivers.len(),
+            channels.len(),
+            file!(),
+            line!()
+        );
+    }
+
+    let mut in_all = vec![true; queries.len()];
+
+    for (i, (receiver, channel)) in receivers.into_iter().zip(channels.iter_mut()).enumerate() {
+        let membership = receiver
+            .receive_membership(channel, queries, rng)
+            .with_context(|| format!("sender {} @{}:{}", i, file!(), line!()))?;
+
+        for (b, m) in in_all.iter_mut().zip(membership) {
+            *b &= m;
+        }
+    }
+
+    Ok(queries
+        .iter()
+        .zip(in_all)
+        .filter_map(|(&x, b)| if b { Some(x) } else { None })
+        .collect())
+}
+
+// domain tags of the hashes of the three-server shared output.
+const SHARED_3PARTY_BIN_TAG: &[u8] = b"shared-3party-bin";
+const SHARED_3PARTY_KEY_TAG: &[u8] = b"shared-3party-key";
+const SHARED_3PARTY_DIGEST_TAG: &[u8] = b"shared-3party-digest";
+// number of hash functions of the cuckoo hashing by the receiver.
+const SHARED_3PARTY_HASHES: usize = 3;
+// evictions before an insertion of the cuckoo hashing fails, and keys tried before the receiver gives up.
+const SHARED_3PARTY_MAX_EVICTIONS: usize = 500;
+const SHARED_3PARTY_CUCKOO_ATTEMPTS: usize = 8;
+// bits of the digests compared by the equality test. The AND tree has log2 of this levels.
+const SHARED_3PARTY_DIGEST_BITS: u32 = 64;
+
+/// Number of bins of the cuckoo hashing of [run_shared_3party_receiver] for `receiver_size` inputs.
+pub fn shared_3party_bins(receiver_size: usize) -> usize {
+    2 * receiver_size.max(1)
+}
+
+/// `query_num` to precompute the OPPRF of the three-server shared output for: the receiver queries each input once,
+/// and the PSI sender programs each element to all its bins.
+pub fn shared_3party_query_num(receiver_size: usize, sender_size: usize) -> usize {
+    receiver_size.max(SHARED_3PARTY_HASHES * sender_size).max(1)
+}
+
+// `i`-th bin of `x` under the key of the receiver.
+fn shared_3party_bin<F: FF>(key: u64, i: usize, x: F, bins: usize) -> usize {
+    let mut hasher = Sha256::new();
+    hasher.update(SHARED_3PARTY_BIN_TAG);
+    hasher.update(key.to_le_bytes());
+    hasher.update((i as u64).to_le_bytes());
+    hasher.update(x.to_bytes());
+    let digest = hasher.finalize();
+    (u64::from_le_bytes(digest[..8].try_into().unwrap()) % bins as u64) as usize
+}
+
+// key of `x` in `bin` programmed to (and queried in) the OPPRF, so that an element matches only in its own bin.
+fn shared_3party_key<F: FF>(x: F, bin: usize) -> Result<F, Error> {
+    let tag = [SHARED_3PARTY_KEY_TAG, &(bin as u64).to_le_bytes()].concat();
+    hash_with_tag(&tag, x, F::zero()).with_context(|| format!("@{}:{}", file!(), line!()))
+}
+
+// digest of an OPPRF output compared by the equality test.
+fn shared_3party_digest<F: FF>(x: F) -> u64 {
+    let mut hasher = Sha256::new();
+    hasher.update(SHARED_3PARTY_DIGEST_TAG);
+    hasher.update(x.to_bytes());
+    u64::from_le_bytes(hasher.finalize()[..8].try_into().unwrap())
+}
+
+// bin of each of `inputs` by the cuckoo hashing with `SHARED_3PARTY_HASHES` hash functions, or None if an insertion fails.
+fn shared_3party_cuckoo<F: FF>(inputs: &[F], key: u64, bins: usize) -> Option<Vec<usize>> {
+    // (input, hash function) in each bin.
+    let mut table: Vec<Option<(usize, usize)>> = vec![None; bins];
+    for start in 0..inputs.len() {
+        let mut entry = (start, 0);
+        let mut placed = false;
+        for _ in 0..SHARED_3PARTY_MAX_EVICTIONS {
+            let bin = shared_3party_bin(key, entry.1, inputs[entry.0], bins);
+            match table[bin].replace(entry) {
+                None => {
+                    placed = true;
+                    break;
+                }
+                Some((evicted, i)) => entry = (evicted, (i + 1) % SHARED_3PARTY_HASHES),
+            }
+        }
+        if !placed {
+            return None;
+        }
+    }
+
+    let mut assignment = vec![0; inputs.len()];
+    for (bin, entry) in table.into_iter().enumerate() {
+        if let Some((input, _)) = entry {
+            assignment[input] = bin;
+        }
+    }
+    Some(assignment)
+}
+
+fn write_u64s<C: AbstractChannel>(channel: &mut C, xs: &[u64]) -> Result<(), Error> {
+    for &x in xs {
+        channel.write_u64(x)?;
+    }
+    channel.flush()?;
+    Ok(())
+}
+
+// read exactly `len` u64s: the length is known to both sides, so it is not sent.
+fn read_u64s<C: AbstractChannel>(channel: &mut C, len: usize) -> Result<Vec<u64>, Error> {
+    (0..len).map(|_| Ok(channel.read_u64()?)).collect()
+}
+
+// The receiver opens first and the PSI sender replies, so that neither blocks on writing.
+fn exchange_u64s<C: AbstractChannel>(
+    channel: &mut C,
+    ours: &[u64],
+    is_receiver: bool,
+) -> Result<Vec<u64>, Error> {
+    if is_receiver {
+        write_u64s(channel, ours)?;
+    }
+    let theirs = read_u64s(channel, ours.len())?;
+    if !is_receiver {
+        write_u64s(channel, ours)?;
+    }
+    Ok(theirs)
+}
+
+// Correlated randomness of one side, dealt by the output server in [run_shared_3party_output]:
+// XOR shares of the Beaver triples of each level of the AND tree (`[a, b, c]` for each bin),
+// and a random bit `r` of each bin shared both by XOR (`bits`) and additively in F (`rho`).
+struct Shared3PartyCorrelation<F> {
+    triples: Vec<u64>,
+    bits: Vec<u64>,
+    rho: Vec<F>,
+}
+
+fn shared_3party_levels() -> usize {
+    SHARED_3PARTY_DIGEST_BITS.trailing_zeros() as usize
+}
+
+impl<F: FF> Shared3PartyCorrelation<F> {
+    // pair of the correlations of the receiver and of the PSI sender for `bins` bins.
+    fn deal<RNG: CryptoRng + Rng>(bins: usize, rng: &mut RNG) -> (Self, Self)
+    where
+        Standard: Distribution<F>,
+    {
+        let mut triples = (Vec::new(), Vec::new());
+        let mut width = SHARED_3PARTY_DIGEST_BITS;
+        while width > 1 {
+            let half = width / 2;
+            let mask = (1u64 << half) - 1;
+            for _ in 0..bins {
+                let [a_r, a_s, b_r, b_s, c_r] = [(); 5].map(|_| rng.gen::<u64>() & mask);
+                let c_s = ((a_r ^ a_s) & (b_r ^ b_s)) ^ c_r;
+                triples.0.extend([a_r, b_r, c_r]);
+                triples.1.extend([a_s, b_s, c_s]);
+            }
+            width = half;
+        }
+
+        let bits = (0..bins).map(|_| rng.gen::<u64>() & 1).collect::<Vec<_>>();
+        let bits_r = (0..bins).map(|_| rng.gen::<u64>() & 1).collect::<Vec<_>>();
+        let rho_r = (0..bins).map(|_| rng.gen::<F>()).collect::<Vec<_>>();
+        let bits_s = bits
+            .iter()
+            .zip(bits_r.iter())
+            .map(|(&r, &r_r)| r ^ r_r)
+            .collect();
+        let rho_s = bits
+            .iter()
+            .zip(rho_r.iter())
+            .map(|(&r, &rho)| (if r == 1 { F::one() } else { F::zero() }) - rho)
+            .collect();
+
+        (
+            Self {
+                triples: triples.0,
+                bits: bits_r,
+                rho: rho_r,
+            },
+            Self {
+                triples: triples.1,
+                bits: bits_s,
+                rho: rho_s,
+            },
+        )
+    }
+
+    fn write<C: AbstractChannel>(&self, channel: &mut C) -> Result<(), Error> {
+        write_u64s(channel, &self.triples)?;
+        write_u64s(channel, &self.bits)?;
+        write_vec_f(channel, &self.rho).with_context(|| format!("@{}:{}", file!(), line!()))?;
+        channel.flush()?;
+        Ok(())
+    }
+
+    fn read<C: AbstractChannel>(channel: &mut C, bins: usize) -> Result<Self, Error> {
+        let triples = read_u64s(channel, 3 * bins * shared_3party_levels())?;
+        let bits = read_u64s(channel, bins)?;
+        let rho: Vec<F> =
+            read_vec_f(channel).with_context(|| format!("@{}:{}", file!(), line!()))?;
+        if rho.len() != bins {
+            bail!(
+                "{} shares of the random bits are given for {} bins. @{}:{}",
+                rho.len(),
+                bins,
+                file!(),
+                line!()
+            );
+        }
+        Ok(Self { triples, bits, rho })
+    }
+
+    // Additive shares in F of whether the digests of both sides in each bin are equal. `digests` of the receiver are
+    // complemented, so that the XOR of both sides is all ones iff they are equal, which the AND tree tests.
+    fn equality_shares<C: AbstractChannel>(
+        &self,
+        channel: &mut C,
+        mut digests: Vec<u64>,
+        is_receiver: bool,
+    ) -> Result<Vec<F>, Error> {
+        let bins = digests.len();
+        let mut width = SHARED_3PARTY_DIGEST_BITS;
+        for level in self.triples.chunks_exact(3 * bins) {
+            // AND of the lower and the upper halves by a Beaver triple on XOR shares.
+            let half = width / 2;
+            let mask = (1u64 << half) - 1;
+            let opened = digests
+                .iter()
+                .zip(level.chunks_exact(3))
+                .flat_map(|(&v, t)| [(v & mask) ^ t[0], (v >> half) ^ t[1]])
+                .collect::<Vec<_>>();
+            let peer = exchange_u64s(channel, &opened, is_receiver)?;
+            for ((v, t), (ours, theirs)) in digests
+                .iter_mut()
+                .zip(level.chunks_exact(3))
+                .zip(opened.chunks_exact(2).zip(peer.chunks_exact(2)))
+            {
+                let e = ours[0] ^ theirs[0];
+                let d = ours[1] ^ theirs[1];
+                *v = t[2] ^ (e & t[1]) ^ (d & t[0]) ^ if is_receiver { e & d } else { 0 };
+            }
+            width = half;
+        }
+
+        // convert the XOR shares of the bit `b` to additive shares in F: for the opened `c = b ^ r`,
+        // `b` is `r` if `c` is 0, and `1 - r` otherwise.
+        let opened = digests
+            .iter()
+            .zip(self.bits.iter())
+            .map(|(&v, &r)| (v ^ r) & 1)
+            .collect::<Vec<_>>();
+        let peer = exchange_u64s(channel, &opened, is_receiver)?;
+        Ok(opened
+            .iter()
+            .zip(peer)
+            .zip(self.rho.iter())
+            .map(
+                |((&ours, theirs), &rho)| match (ours ^ theirs, is_receiver) {
+                    (0, _) => rho,
+                    (_, true) => F::one() - rho,
+                    (_, false) => F::zero() - rho,
+                },
+            )
+            .collect())
+    }
+}
+
+/// Receiver side of the three-server shared output: additive shares in F of the membership vector of `inputs` are
+/// delivered to an output server, and neither the receiver nor the PSI sender learns the membership.
+///
+/// The receiver assigns each input to a distinct bin by the cuckoo hashing, and queries it in its bin to the OPPRF
+/// (precomputed with the PSI sender for [shared_3party_query_num]). The PSI sender programs its elements in all their bins
+/// to a random target of each bin, and the output of a query equals the target of its bin iff the input is in the set of the PSI sender.
+/// Both sides compute additive shares of the equality by an AND tree on the digests, with the correlated randomness dealt by the output server,
+/// then re-randomize them by masks of the receiver.
+///
+/// The share of the receiver is sent in input order with the bin of each input, and the output server reconstructs by
+/// [run_shared_3party_output] and [reconstruct_shared_3party]. Each share alone is uniformly random, and the output server learns only
+/// the membership vector and the bins of the inputs (random to it, since the key of the bins is not sent to it), as long as no two of them collude.
+pub fn run_shared_3party_receiver<F, S, V, C, O, RNG>(
+    receiver: SepOpprfReceiverWithVole<F, S, V>,
+    channel: &mut C,
+    output: &mut O,
+    inputs: &[F],
+    rng: &mut RNG,
+) -> Result<(), Error>
+where
+    F: FF,
+    S: Solver<F>,
+    V: VoleShareForReceiver<F>,
+    C: AbstractChannel,
+    O: AbstractChannel,
+    RNG: CryptoRng + Rng,
+    Standard: Distribution<F>,
+{
+    let bins = shared_3party_bins(inputs.len());
+    let mut cuckoo = None;
+    for _ in 0..SHARED_3PARTY_CUCKOO_ATTEMPTS {
+        let key = rng.gen::<u64>();
+        if let Some(assignment) = shared_3party_cuckoo(inputs, key, bins) {
+            cuckoo = Some((key, assignment));
+            break;
+        }
+    }
+    let Some((key, assignment)) = cuckoo else {
+        bail!(
+            "Failed to assign {} inputs to {} bins by the cuckoo hashing. Are there many duplicates? @{}:{}",
+            inputs.len(),
+            bins,
+            file!(),
+            line!()
+        );
+    };
+    channel.write_u64(key)?;
+    channel.write_u64(bins as u64)?;
+    channel.flush()?;
+
+    let queries = inputs
+        .iter()
+        .zip(assignment.iter())
+        .map(|(&x, &bin)| shared_3party_key(x, bin))
+        .collect::<Result<Vec<_>, _>>()?;
+    let outputs = receiver
+        .receive(channel, &queries, rng)
+        .with_context(|| format!("@{}:{}", file!(), line!()))?;
+    // the empty bins compare the digest of a random value.
+    let mut digests = (0..bins)
+        .map(|_| !shared_3party_digest(rng.gen::<F>()))
+        .collect::<Vec<_>>();
+    for (&bin, (_, y)) in assignment.iter().zip(outputs) {
+        digests[bin] = !shared_3party_digest(y);
+    }
+
+    output.write_u64(bins as u64)?;
+    output.flush()?;
+    let correlation = Shared3PartyCorrelation::<F>::read(output, bins)
+        .with_context(|| format!("@{}:{}", file!(), line!()))?;
+    let shares = correlation
+        .equality_shares(channel, digests, true)
+        .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+    let masks = (0..bins).map(|_| rng.gen::<F>()).collect::<Vec<_>>();
+    write_vec_f(channel, &masks).with_context(|| format!("@{}:{}", file!(), line!()))?;
+    channel.flush()?;
+
+    let share = assignment
+        .iter()
+        .map(|&bin| shares[bin] + masks[bin])
+        .collect::<Vec<_>>();
+    write_vec_f(output, &share).with_context(|| format!("@{}:{}", file!(), line!()))?;
+    write_u64s(
+        output,
+        &assignment.iter().map(|&bin| bin as u64).collect::<Vec<_>>(),
+    )?;
+    output.flush()?;
+    Ok(())
+}
+
+/// PSI sender side of [run_shared_3party_receiver]: `set` is programmed with `sender` for `query_num` (See [shared_3party_query_num]),
+/// and the share of each bin is sent to the output server through `output`.
+pub fn run_shared_3party_sender<F, S, V, C, O, RNG>(
+    sender: SepOpprfSenderWithVole<F, S, V>,
+    channel: &mut C,
+    output: &mut O,
+    set: &[F],
+    query_num: usize,
+    rng: &mut RNG,
+) -> Result<(), Error>
+where
+    F: FF,
+    S: Solver<F>,
+    V: VoleShareForSender<F>,
+    C: AbstractChannel,
+    O: AbstractChannel,
+    RNG: CryptoRng + Rng,
+    Standard: Distribution<F>,
+{
+    let key = channel.read_u64()?;
+    let bins = channel.read_u64()? as usize;
+    if bins == 0 || bins > shared_3party_bins(query_num) {
+        bail!(
+            "The receiver uses {} bins, but the OPPRF is for {} queries. @{}:{}",
+            bins,
+            query_num,
+            file!(),
+            line!()
+        );
+    }
+
+    let targets = (0..bins).map(|_| rng.gen::<F>()).collect::<Vec<_>>();
+    let mut keys = HashSet::new();
+    let mut points = Vec::new();
+    for &y in set {
+        for i in 0..SHARED_3PARTY_HASHES {
+            let bin = shared_3party_bin(key, i, y, bins);
+            let k = shared_3party_key(y, bin)?;
+            if keys.insert(k) {
+                points.push((k, targets[bin]));
+            }
+        }
+    }
+    // the OPPRF rejects an empty program, and a random point matches no query.
+    if points.is_empty() {
+        points.push((rng.gen::<F>(), rng.gen::<F>()));
+    }
+    sender
+        .send(channel, &points, query_num, rng)
+        .with_context(|| format!("@{}:{}", file!(), line!()))?;
+    let digests = targets
+        .iter()
+        .map(|&t| shared_3party_digest(t))
+        .collect::<Vec<_>>();
+
+    let correlation = Shared3PartyCorrelation::<F>::read(output, bins)
+        .with_context(|| format!("@{}:{}", file!(), line!()))?;
+    let shares = correlation
+        .equality_shares(channel, digests, false)
+        .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+    let masks: Vec<F> = read_vec_f(channel).with_context(|| format!("@{}:{}", file!(), line!()))?;
+    if masks.len() != bins {
+        bail!(
+            "{} masks are given for {} bins. @{}:{}",
+            masks.len(),
+            bins,
+            file!(),
+            line!()
+        );
+    }
+    let share = shares
+        .iter()
+        .zip(masks)
+        .map(|(&s, mask)| s - mask)
+        .collect::<Vec<_>>();
+    write_vec_f(output, &share).with_context(|| format!("@{}:{}", file!(), line!()))?;
+    output.flush()?;
+    Ok(())
+}
+
+/// Output server side of [run_shared_3party_receiver]: deal the correlated randomness to the receiver (`from_receiver`) and to the PSI sender (`from_sender`),
+/// then return their additive shares of the membership vector of the inputs of the receiver, in input order.
+///
+/// `receiver_share[i] + sender_share[i]` is one if the `i`-th input is in the set of the PSI sender, and zero otherwise (See [reconstruct_shared_3party]).
+/// A non-member is reported as a member with probability about 2^-64 since the equality test compares 64 bits digests.
+pub fn run_shared_3party_output<F, C1, C2, RNG>(
+    from_receiver: &mut C1,
+    from_sender: &mut C2,
+    rng: &mut RNG,
+) -> Result<(Vec<F>, Vec<F>), Error>
+where
+    F: FF,
+    C1: AbstractChannel,
+    C2: AbstractChannel,
+    RNG: CryptoRng + Rng,
+    Standard: Distribution<F>,
+{
+    let bins = from_receiver.read_u64()? as usize;
+    let (for_receiver, for_sender) = Shared3PartyCorrelation::<F>::deal(bins, rng);
+    for_receiver
+        .write(from_receiver)
+        .with_context(|| format!("@{}:{}", file!(), line!()))?;
+    for_sender
+        .write(from_sender)
+        .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+    let receiver_share: Vec<F> =
+        read_vec_f(from_receiver).with_context(|| format!("@{}:{}", file!(), line!()))?;
+    let assignment = read_u64s(from_receiver, receiver_share.len())?;
+    let sender_bins: Vec<F> =
+        read_vec_f(from_sender).with_context(|| format!("@{}:{}", file!(), line!()))?;
+    if sender_bins.len() != bins {
+        bail!(
+            "The PSI sender shares {} bins, but the receiver uses {} bins. @{}:{}",
+            sender_bins.len(),
+            bins,
+            file!(),
+            line!()
+        );
+    }
+
+    let sender_share = assignment
+        .iter()
+        .map(|&bin| match sender_bins.get(bin as usize) {
+            Some(&s) => Ok(s),
+            None => bail!(
+                "The bin {} of the receiver is out of {} bins. @{}:{}",
+                bin,
+                bins,
+                file!(),
+                line!()
+            ),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((receiver_share, sender_share))
+}
+
+/// Reconstruct the membership vector from the additive shares returned by [run_shared_3party_output].
+///
+/// It fails if a sum is neither zero nor one, which no honest run gives.
+pub fn reconstruct_shared_3party<F: FF>(
+    receiver_share: &[F],
+    sender_share: &[F],
+) -> Result<Vec<bool>, Error> {
+    if receiver_share.len() != sender_share.len() {
+        bail!(
+            "{} shares of the receiver are given for {} shares of the PSI sender. @{}:{}",
+            receiver_share.len(),
+            sender_share.len(),
+            file!(),
+            line!()
+        );
+    }
+
+    receiver_share
+        .iter()
+        .zip(sender_share.iter())
+        .enumerate()
+        .map(|(i, (&a, &b))| {
+            let m = a + b;
+            if m == F::one() {
+                Ok(true)
+            } else if m == F::zero() {
+                Ok(false)
+            } else {
+                bail!(
+                    "The shares of the input {} sum to neither zero nor one. @{}:{}",
+                    i,
+                    file!(),
+                    line!()
+                )
+            }
+        })
+        .collect()
+}
+
+// Both sides send their params before reading the peer's, so the order of the sender and the receiver doesn't matter.
+// A mismatch would make the code vectors incompatible and decoding would silently fail.
+fn check_params_handshake<F: FF, S: Solver<F>, C: AbstractChannel>(
+    channel: &mut C,
+    params: S::Params,
+) -> Result<(), Error> {
+    let ours = params.to_bytes();
+    channel.write_u64(ours.len() as u64)?;
+    channel.write_bytes(&ours)?;
+    channel.flush()?;
+
+    let len = channel.read_u64()? as usize;
+    if len != ours.len() {
+        bail!(
+            "the peer's solver params ({} bytes) differ from ours ({} bytes). @{}:{}",
+            len,
+            ours.len(),
+            file!(),
+            line!()
+        );
+    }
+    let mut theirs = vec![0u8; len];
+    channel.read_bytes(&mut theirs)?;
+    if theirs != ours {
+        bail!(
+            "the peer's solver params differ from ours. Both sides must pass the same params. @{}:{}",
+            file!(),
+            line!()
+        );
+    }
+
+    Ok(())
+}
+
+/// You are allowed to clone them **FOR BENCHMARKING PURPOSES ONLY**.
+///
+/// **DO NOT USE THEM IN PRODUCTION** because of the security reasons.
+impl<F, S, V> Clone for SepOpprfSenderWithVole<F, S, V>
+where
+    F: FF,
+    S: Solver<F>,
+    V: VoleShareForSender<F>,
+{
+    fn clone(&self) -> Self {
+        Self {
+            params: self.params,
+            encode_attempts: self.encode_attempts,
+            verify_encode: self.verify_encode,
+            blinding: self.blinding,
+            pad_bucket: self.pad_bucket,
+            wire_bits: self.wire_bits,
+            aux_seed: self.aux_seed,
+            #[cfg(feature = "rayon")]
+            pool: self.pool.clone(),
+            oprf_sender: self.oprf_sender.clone(),
+            // fk: None,
+        }
+    }
+}
+
+/// You are allowed to clone them **FOR BENCHMARKING PURPOSES ONLY**.
+///
+/// **DO NOT USE THEM IN PRODUCTION** because of the security reasons.
+impl<F, S, V> Clone for SepOpprfReceiverWithVole<F, S, V>
+where
+    F: FF,
+    S: Solver<F>,
+    V: VoleShareForReceiver<F>,
+{
+    fn clone(&self) -> Self {
+        Self {
+            params: self.params,
+            blinding: self.blinding,
+            wire_bits: self.wire_bits,
+            aux_seed: self.aux_seed,
+            oprf_receiver: self.oprf_receiver.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channel_utils::channel_from;
+    use crate::channel_utils::counting_channel::{ByteCounters, CountingChannel};
+    use crate::set_utils::gen_overlapping_sets;
+    use crate::solver::{PaxosSolver, Solver, SolverParams, VandelmondeSolver};
+    use crate::vole::{
+        LPNVoleReceiver, LPNVoleSender, LPN_EXTEND_MEDIUM, LPN_EXTEND_SMALL, LPN_SETUP_MEDIUM,
+        LPN_SETUP_SMALL,
+    };
+    use rand::SeedableRng;
+    use scuttlebutt::serialization::CanonicalSerialize;
+    use scuttlebutt::{field::F128b, AesRng, Block, Channel};
+    use std::collections::{HashMap, HashSet};
+    use std::io::{BufReader, BufWriter};
+    use std::os::unix::net::UnixStream;
+    use std::sync::Arc;
+
+    // random sets, not reproducible on purpose.
+    fn create_sets<F: FF>(set_size: usize, common_size: usize) -> (Vec<F>, Vec<F>, Vec<F>)
+    where
+        Standard: Distribution<F>,
+    {
+        gen_overlapping_sets(set_size, common_size, AesRng::new().gen()).unwrap()
+    }
+
+    #[allow(non_snake_case)]
+    fn usize2F128b(x: usize) -> F128b {
+        let x = x as u128;
+        let x = x.to_le_bytes();
+        let res = F128b::from_bytes(&x.into()).unwrap();
+
+        // dbg!(res);
+
+        res
+    }
+
+    type UnixChannel = Channel<BufReader<UnixStream>, BufWriter<UnixStream>>;
+
+    // runs `send` in a thread and `receive` here on the OPPRF of `S` precomputed over a UnixStream pair
+    // for `sender_num` and `receiver_num` queries by LPN VOLE, and returns the output of `receive`.
+    fn run_lpn_opprf<S, T>(
+        sender_num: usize,
+        receiver_num: usize,
+        send: impl FnOnce(
+                SepOpprfSenderWithVole<F128b, S, LPNVoleSender<F128b>>,
+                &mut UnixChannel,
+                &mut AesRng,
+            ) + Send
+            + 'static,
+        receive: impl FnOnce(
+            SepOpprfReceiverWithVole<F128b, S, LPNVoleReceiver<F128b>>,
+            &mut UnixChannel,
+            &mut AesRng,
+        ) -> T,
+    ) -> T
+    where
+        S: Solver<F128b> + 'static,
+    {
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let mut channel = channel_from(sender).unwrap();
+            let opprf_sender = SepOpprfSenderWithVole::<F128b, S, _>::precomp(
+                &mut channel,
+                &mut rng,
+                sender_num,
+                LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+            )
+            .unwrap();
+            send(opprf_sender, &mut channel, &mut rng);
+        });
+
+        let mut rng = AesRng::new();
+        let mut channel = channel_from(receiver).unwrap();
+        let opprf_receiver = SepOpprfReceiverWithVole::<F128b, S, _>::precomp(
+            &mut channel,
+            &mut rng,
+            receiver_num,
+            LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+        )
+        .unwrap();
+        let res = receive(opprf_receiver, &mut channel, &mut rng);
+
+        handle.join().unwrap();
+        res
+    }
+
+    fn test_sep_opprf_base<S: Solver<F128b>>(set_size: usize, common_size: usize, verbose: bool) {
+        let (sender_set, receiver_set, intersection) = create_sets::<F128b>(set_size, common_size);
+        let points = sender_set
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| (x, usize2F128b(i)))
+            .collect::<Vec<_>>();
+
+        let sender_set_2 = sender_set.clone();
+        let points_2 = points.clone();
+
+        let points: HashMap<F128b, F128b> = HashMap::from_iter(points.into_iter());
+
+        let m_size = S::calc_params(set_size).code_length();
+        let (setup_params, extend_params) = if m_size < (1 << 17) {
+            (LPN_SETUP_SMALL, LPN_EXTEND_SMALL)
+        } else {
+            (LPN_SETUP_MEDIUM, LPN_EXTEND_MEDIUM)
+        };
+
+        if verbose {
+            println!("sender_set = {:?}\nlen: {}", sender_set, sender_set.len());
+            println!(
+                "receiver_set = {:?}\nlen: {}",
+                receiver_set,
+                receiver_set.len()
+            );
+            println!(
+                "intersection = {:?}\nlen: {}",
+                intersection,
+                intersection.len()
+            );
+        }
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let sender_set = sender_set_2;
+            let points = points_2;
+
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+
+            let vole_share_for_s = LPNVoleSender::new(setup_params, extend_params);
+
+            let opprf_sender = SepOpprfSenderWithVole::<F128b, S, _>::precomp(
+                &mut channel,
+                &mut rng,
+                sender_set.len(),
+                vole_share_for_s,
+            )
+            .unwrap();
+
+            let fk = opprf_sender
+                .send(&mut channel, &points, points.len(), &mut rng)
+                .unwrap();
+
+            for &(x, y) in points.iter() {
+                let y_computed = fk(x).unwrap();
+                assert_eq!(y, y_computed);
+            }
+        });
+
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+
+        let vole_share_for_r = LPNVoleReceiver::new(setup_params, extend_params);
+
+        let opprf_receiver = SepOpprfReceiverWithVole::<F128b, S, _>::precomp(
+            &mut channel,
+            &mut rng,
+            receiver_set.len(),
+            vole_share_for_r,
+        )
+        .unwrap();
+
+        let received = opprf_receiver
+            .receive(&mut channel, &receiver_set, &mut rng)
+            .unwrap();
+
+        handle.join().unwrap();
+
+        if verbose {
+            dbg!(received.clone());
+        }
+
+        for (x, y) in received {
+            if let Some(&original_y) = points.get(&x) {
+                assert_eq!(y, original_y);
+                if verbose {
+                    println!("{:?} is in the sender set. and f({:?}) = {:?}", x, x, y);
+                }
+            } else if verbose {
+                println!("{:?} is not in the sender set", x);
+            }
+        }
+    }
+
+    #[test]
+    fn test_sep_opprf_vandelmonde_small() {
+        test_sep_opprf_base::<VandelmondeSolver<F128b>>(10, 5, true);
+    }
+
+    #[test]
+    fn test_sep_opprf_paxos_small() {
+        test_sep_opprf_base::<PaxosSolver<F128b>>(10, 5, true);
+    }
+
+    #[test]
+    fn test_sep_opprf_paxos_middle() {
+        test_sep_opprf_base::<PaxosSolver<F128b>>(100, 50, false);
+    }
+
+    #[test]
+    fn test_sep_opprf_paxos_large() {
+        test_sep_opprf_base::<PaxosSolver<F128b>>(1 << 12, 1 << 6, false);
+    }
+
+    #[test]
+    fn test_sep_opprf_export_table() {
+        let (sender_set, receiver_set, _) = create_sets::<F128b>(10, 5);
+        let points = sender_set
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| (x, usize2F128b(i)))
+            .collect::<Vec<_>>();
+        let points_2 = points.clone();
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let points = points_2;
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+
+            let vole_share_for_s = LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL);
+            let opprf_sender = SepOpprfSenderWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
+                &mut channel,
+                &mut rng,
+                points.len(),
+                vole_share_for_s,
+            )
+            .unwrap();
+
+            let (_fk, table) = opprf_sender
+                .send_with_table(&mut channel, &points, points.len(), &mut rng)
+                .unwrap();
+
+            let mut bytes = Vec::new();
+            table.export_table(&mut bytes).unwrap();
+            bytes
+        });
+
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+
+        let vole_share_for_r = LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL);
+        let opprf_receiver = SepOpprfReceiverWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
+            &mut channel,
+            &mut rng,
+            receiver_set.len(),
+            vole_share_for_r,
+        )
+        .unwrap();
+
+        let (_received, table) = opprf_receiver
+            .receive_with_table(&mut channel, &receiver_set, &mut rng)
+            .unwrap();
+
+        let sender_bytes = handle.join().unwrap();
+
+        let mut receiver_bytes = Vec::new();
+        table.export_table(&mut receiver_bytes).unwrap();
+        assert_eq!(sender_bytes, receiver_bytes);
+
+        let imported = ProgrammedTable::<F128b, PaxosSolver<F128b>>::import_table(
+            &mut sender_bytes.as_slice(),
+        )
+        .unwrap();
+        assert_eq!(imported.p(), table.p());
+    }
+
+    #[test]
+    fn test_sep_opprf_membership() {
+        let (sender_set, receiver_set, _) = create_sets::<F128b>(100, 30);
+        let sender_set_2 = sender_set.clone();
+
+        let membership = run_lpn_opprf::<PaxosSolver<F128b>, _>(
+            sender_set.len(),
+            receiver_set.len(),
+            move |opprf_sender, channel, rng| {
+                opprf_sender
+                    .send_membership(channel, &sender_set_2, sender_set_2.len(), rng)
+                    .unwrap();
+            },
+            |opprf_receiver, channel, rng| {
+                opprf_receiver
+                    .receive_membership(channel, &receiver_set, rng)
+                    .unwrap()
+            },
+        );
+
+        let sender_set: HashSet<F128b> = HashSet::from_iter(sender_set);
+        let expected = receiver_set
+            .iter()
+            .map(|x| sender_set.contains(x))
+            .collect::<Vec<_>>();
+
+        assert_eq!(membership, expected);
+    }
+
+    #[test]
+    fn test_sep_opprf_membership_with_prefilter() {
+        let (sender_set, receiver_set, _) = create_sets::<F128b>(100, 30);
+        let sender_set_2 = sender_set.clone();
+
+        let (membership, evaluated) = run_lpn_opprf::<PaxosSolver<F128b>, _>(
+            sender_set.len(),
+            receiver_set.len(),
+            move |opprf_sender, channel, rng| {
+                opprf_sender
+                    .send_membership_with_prefilter(
+                        channel,
+                        &sender_set_2,
+                        0.01,
+                        sender_set_2.len(),
+                        rng,
+                    )
+                    .unwrap();
+            },
+            |opprf_receiver, channel, rng| {
+                opprf_receiver
+                    .receive_membership_with_prefilter(channel, &receiver_set, rng)
+                    .unwrap()
+            },
+        );
+
+        let sender_set: HashSet<F128b> = HashSet::from_iter(sender_set);
+        let expected = receiver_set
+            .iter()
+            .map(|x| sender_set.contains(x))
+            .collect::<Vec<_>>();
+
+        assert_eq!(membership, expected);
+        // the 30 members always pass, and few of the 70 non-members pass with fp_rate 0.01.
+        assert!(evaluated >= 30);
+        assert!(evaluated < receiver_set.len());
+    }
+
+    #[test]
+    fn test_sep_opprf_range() {
+        let width = 32;
+        let ranges = vec![(100, 200), (1000, 1000)];
+        let points = vec![150, 99, 201, 100, 200, 1000, 5000];
+        let query_num = range_query_num(points.len(), ranges.len(), width);
+
+        let res = run_lpn_opprf::<PaxosSolver<F128b>, _>(
+            query_num,
+            query_num,
+            move |opprf_sender, channel, rng| {
+                opprf_sender
+                    .send_range(channel, &ranges, width, query_num, rng)
+                    .unwrap();
+            },
+            |opprf_receiver, channel, rng| {
+                opprf_receiver
+                    .receive_range(channel, &points, width, rng)
+                    .unwrap()
+            },
+        );
+
+        assert_eq!(res, vec![true, false, false, true, true, true, false]);
+    }
+
+    #[test]
+    fn test_sep_opprf_u64_payload() {
+        let (sender_set, receiver_set, _) = create_sets::<F128b>(100, 30);
+        let mut rng = AesRng::new();
+        let sender_points = sender_set
+            .iter()
+            .map(|&x| (x, rng.gen::<u64>()))
+            .collect::<Vec<_>>();
+        let sender_points_2 = sender_points.clone();
+
+        let res = run_lpn_opprf::<PaxosSolver<F128b>, _>(
+            sender_points.len(),
+            receiver_set.len(),
+            move |opprf_sender, channel, rng| {
+                opprf_sender
+                    .send_with_u64_payload(channel, &sender_points_2, sender_points_2.len(), rng)
+                    .unwrap();
+            },
+            |opprf_receiver, channel, rng| {
+                opprf_receiver
+                    .receive_with_u64_payload(channel, &receiver_set, rng)
+                    .unwrap()
+            },
+        );
+
+        let payloads: HashMap<F128b, u64> = HashMap::from_iter(sender_points);
+        let expected = receiver_set
+            .iter()
+            .filter_map(|x| payloads.get(x).map(|&payload| (*x, payload)))
+            .collect::<Vec<_>>();
+
+        assert_eq!(res.len(), 30);
+        assert_eq!(res, expected);
+    }
+
+    #[test]
+    fn test_u64_payload_handshake_small_field() {
+        use scuttlebutt::field::F64b;
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            check_u64_payload_handshake::<F64b, _>(&mut channel)
+        });
+
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        let res = check_u64_payload_handshake::<F64b, _>(&mut channel);
+
+        // both sides fail, and neither blocks.
+        assert!(res.is_err());
+        assert!(handle.join().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_u64_payload_handshake_field_mismatch() {
+        use scuttlebutt::field::F64b;
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            check_u64_payload_handshake::<F64b, _>(&mut channel)
+        });
+
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        let res = check_u64_payload_handshake::<F128b, _>(&mut channel);
+
+        assert!(res.is_err());
+        assert!(handle.join().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_sep_opprf_verify_encode() {
+        let (sender_set, receiver_set, intersection) = create_sets::<F128b>(50, 20);
+        let points = sender_set
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| (x, usize2F128b(i + 1)))
+            .collect::<Vec<_>>();
+        let programmed: HashMap<F128b, F128b> = HashMap::from_iter(points.iter().cloned());
+
+        let received = run_lpn_opprf::<PaxosSolver<F128b>, _>(
+            points.len(),
+            receiver_set.len(),
+            move |opprf_sender, channel, rng| {
+                // a correct table passes the check.
+                opprf_sender
+                    .with_verify_encode(true)
+                    .send(channel, &points, points.len(), rng)
+                    .unwrap();
+            },
+            |opprf_receiver, channel, rng| {
+                opprf_receiver.receive(channel, &receiver_set, rng).unwrap()
+            },
+        );
+
+        for x in intersection.iter() {
+            let &(_, y) = received.iter().find(|(q, _)| q == x).unwrap();
+            assert_eq!(y, programmed[x]);
+        }
+    }
+
+    // VandelmondeSolver whose code vector is corrupted right after encoding, as a broken solver.
+    struct CorruptSolver;
+
+    impl Solver<F128b> for CorruptSolver {
+        const ID: u8 = VandelmondeSolver::<F128b>::ID;
+        const NAME: &'static str = "Corrupt";
+
+        type AuxInfo = <VandelmondeSolver<F128b> as Solver<F128b>>::AuxInfo;
+        type Params = <VandelmondeSolver<F128b> as Solver<F128b>>::Params;
+        type Decoder = crate::solver::StatelessDecoder<F128b, Self>;
+
+        fn gen_aux<RNG: CryptoRng + Rng>(rng: &mut RNG) -> Result<Self::AuxInfo, Error> {
+            VandelmondeSolver::<F128b>::gen_aux(rng)
+        }
+
+        fn aux_send<C: AbstractChannel, RNG: CryptoRng + Rng>(
+            channel: &mut C,
+            rng: &mut RNG,
+            aux: Self::AuxInfo,
+        ) -> Result<(), Error> {
+            VandelmondeSolver::<F128b>::aux_send(channel, rng, aux)
+        }
+
+        fn aux_receive<C: AbstractChannel, RNG: CryptoRng + Rng>(
+            channel: &mut C,
+            rng: &mut RNG,
+        ) -> Result<Self::AuxInfo, Error> {
+            VandelmondeSolver::<F128b>::aux_receive(channel, rng)
+        }
+
+        fn aux_to_bytes(aux: Self::AuxInfo) -> Vec<u8> {
+            VandelmondeSolver::<F128b>::aux_to_bytes(aux)
+        }
+
+        fn aux_from_bytes(bytes: &[u8]) -> Result<Self::AuxInfo, Error> {
+            VandelmondeSolver::<F128b>::aux_from_bytes(bytes)
+        }
+
+        fn calc_params(n: usize) -> Self::Params {
+            VandelmondeSolver::<F128b>::calc_params(n)
+        }
+
+        fn encode<RNG: CryptoRng + Rng>(
+            rng: &mut RNG,
+            points: &[(F128b, F128b)],
+            aux: Self::AuxInfo,
+            params: Self::Params,
+        ) -> Result<Vec<F128b>, Error> {
+            let mut p = VandelmondeSolver::<F128b>::encode(rng, points, aux, params)?;
+            p[0] += F128b::one();
+            Ok(p)
+        }
+
+        fn decode(
+            p: &[F128b],
+            x: F128b,
+            aux: Self::AuxInfo,
+            params: Self::Params,
+        ) -> Result<F128b, Error> {
+            VandelmondeSolver::<F128b>::decode(p, x, aux, params)
+        }
+
+        fn prepare_decoder(p: &[F128b], aux: Self::AuxInfo, params: Self::Params) -> Self::Decoder {
+            crate::solver::StatelessDecoder::new(p, aux, params)
+        }
+    }
+
+    #[test]
+    fn test_sep_opprf_verify_encode_corrupt() {
+        let (sender_set, receiver_set, _) = create_sets::<F128b>(50, 20);
+        let points = sender_set
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| (x, usize2F128b(i + 1)))
+            .collect::<Vec<_>>();
+
+        let received = run_lpn_opprf::<CorruptSolver, _>(
+            points.len(),
+            receiver_set.len(),
+            move |opprf_sender, channel, rng| {
+                // the corrupted table is not sent.
+                let err = opprf_sender
+                    .with_verify_encode(true)
+                    .send(channel, &points, points.len(), rng)
+                    .err()
+                    .unwrap();
+                assert!(matches!(
+                    err.downcast_ref::<PsiError>(),
+                    Some(PsiError::EncodeVerificationFailed { points: 50, .. })
+                ));
+            },
+            |opprf_receiver, channel, rng| opprf_receiver.receive(channel, &receiver_set, rng),
+        );
+
+        // the sender aborts instead of sending the table, so the receiver sees the channel closed.
+        assert!(received.is_err());
+    }
+
+    #[test]
+    fn test_sep_opprf_send_with_expiry() {
+        let now = 1_000;
+        let (sender_set, receiver_set, intersection) = create_sets::<F128b>(30, 30);
+        // past, just now, future and no expiry in turn. A point expiring just at `now` is expired.
+        let points = sender_set
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| {
+                let expiry = match i % 4 {
+                    0 => Some(now - 1),
+                    1 => Some(now),
+                    2 => Some(now + 1),
+                    _ => None,
+                };
+                (x, usize2F128b(i + 1), expiry)
+            })
+            .collect::<Vec<_>>();
+        let points_for_s = points.clone();
+
+        let received: HashMap<F128b, F128b> = run_lpn_opprf::<PaxosSolver<F128b>, _>(
+            points.len(),
+            receiver_set.len(),
+            move |opprf_sender, channel, rng| {
+                opprf_sender
+                    .send_with_expiry(channel, &points_for_s, now, points_for_s.len(), rng)
+                    .unwrap();
+            },
+            |opprf_receiver, channel, rng| {
+                HashMap::from_iter(opprf_receiver.receive(channel, &receiver_set, rng).unwrap())
+            },
+        );
+
+        assert_eq!(intersection.len(), points.len());
+        let mut matched = 0;
+        for &(x, value, expiry) in points.iter() {
+            let alive = expiry.map_or(true, |expiry| expiry > now);
+            assert_eq!(received[&x] == value, alive);
+            matched += alive as usize;
+        }
+        assert_eq!(matched, 14);
+    }
+
+    #[test]
+    fn test_sep_opprf_empty_program() {
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let mut channel = channel_from(sender).unwrap();
+            let opprf_sender = SepOpprfSenderWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
+                &mut channel,
+                &mut rng,
+                10,
+                LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+            )
+            .unwrap();
+
+            let err = opprf_sender
+                .send(&mut channel, &[], 10, &mut rng)
+                .err()
+                .unwrap();
+            assert_eq!(
+                err.downcast_ref::<PsiError>(),
+                Some(&PsiError::EmptyProgram)
+            );
+        });
+
+        let mut rng = AesRng::new();
+        let mut channel = channel_from(receiver).unwrap();
+        let receiver_set = create_sets::<F128b>(10, 5).1;
+        let opprf_receiver = SepOpprfReceiverWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
+            &mut channel,
+            &mut rng,
+            receiver_set.len(),
+            LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+        )
+        .unwrap();
+        handle.join().unwrap();
+
+        // the sender refuses before the OPRF, so the receiver sees the channel closed.
+        assert!(opprf_receiver
+            .receive(&mut channel, &receiver_set, &mut rng)
+            .is_err());
+    }
+
+    #[test]
+    fn test_sep_opprf_multiplicative_blinding() {
+        let mut rng = AesRng::new();
+        let value: F128b = rng.gen();
+        let pad: F128b = rng.gen();
+        for mode in [BlindingMode::Additive, BlindingMode::Multiplicative] {
+            assert_eq!(mode.unblind(mode.blind(value, pad).unwrap(), pad), value);
+        }
+        assert!(Multiplicative.blind(value, F128b::zero()).is_err());
+
+        let (sender_set, receiver_set, intersection) = create_sets::<F128b>(50, 20);
+        let points = sender_set
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| (x, usize2F128b(i + 1)))
+            .collect::<Vec<_>>();
+        let programmed: HashMap<F128b, F128b> = HashMap::from_iter(points.iter().cloned());
+        let programmed_for_sender = programmed.clone();
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let mut channel = channel_from(sender).unwrap();
+            let opprf_sender = SepOpprfSenderWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
+                &mut channel,
+                &mut rng,
+                points.len(),
+                LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+            )
+            .unwrap()
+            .with_blinding(BlindingMode::Multiplicative);
+
+            let fk = opprf_sender
+                .send(&mut channel, &points, points.len(), &mut rng)
+                .unwrap();
+            for (x, z) in programmed_for_sender.iter() {
+                assert_eq!(fk(*x).unwrap(), *z);
+            }
+        });
+
+        let mut rng = AesRng::new();
+        let mut channel = channel_from(receiver).unwrap();
+        let opprf_receiver = SepOpprfReceiverWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
+            &mut channel,
+            &mut rng,
+            receiver_set.len(),
+            LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+        )
+        .unwrap()
+        .with_blinding(BlindingMode::Multiplicative);
+
+        let received = opprf_receiver
+            .receive(&mut channel, &receiver_set, &mut rng)
+            .unwrap();
+        handle.join().unwrap();
+
+        for x in intersection.iter() {
+            let &(_, y) = received.iter().find(|(q, _)| q == x).unwrap();
+            assert_eq!(y, programmed[x]);
+        }
+    }
+
+    thread_local! {
+        // encodes of FlakySolver left to fail on this thread.
+        static FLAKY_FAILURES: std::cell::Cell<u32> = std::cell::Cell::new(0);
+    }
+
+    // PaxosSolver whose encode fails while FLAKY_FAILURES is positive, to force retries.
+    struct FlakySolver;
+
+    type Paxos = PaxosSolver<F128b>;
+
+    impl Solver<F128b> for FlakySolver {
+        const ID: u8 = Paxos::ID;
+        const NAME: &'static str = "Flaky";
+
+        type AuxInfo = <Paxos as Solver<F128b>>::AuxInfo;
+        type Params = <Paxos as Solver<F128b>>::Params;
+        type Decoder = <Paxos as Solver<F128b>>::Decoder;
+
+        fn gen_aux<RNG: CryptoRng + Rng>(rng: &mut RNG) -> Result<Self::AuxInfo, Error> {
+            Paxos::gen_aux(rng)
+        }
+
+        fn aux_send<C: AbstractChannel, RNG: CryptoRng + Rng>(
+            channel: &mut C,
+            rng: &mut RNG,
+            aux: Self::AuxInfo,
+        ) -> Result<(), Error> {
+            Paxos::aux_send(channel, rng, aux)
+        }
+
+        fn aux_receive<C: AbstractChannel, RNG: CryptoRng + Rng>(
+            channel: &mut C,
+            rng: &mut RNG,
+        ) -> Result<Self::AuxInfo, Error> {
+            Paxos::aux_receive(channel, rng)
+        }
+
+        fn aux_to_bytes(aux: Self::AuxInfo) -> Vec<u8> {
+            Paxos::aux_to_bytes(aux)
+        }
+
+        fn aux_from_bytes(bytes: &[u8]) -> Result<Self::AuxInfo, Error> {
+            Paxos::aux_from_bytes(bytes)
+        }
+
+        fn calc_params(n: usize) -> Self::Params {
+            Paxos::calc_params(n)
+        }
+
+        fn encode<RNG: CryptoRng + Rng>(
+            rng: &mut RNG,
+            points: &[(F128b, F128b)],
+            aux: Self::AuxInfo,
+            params: Self::Params,
+        ) -> Result<Vec<F128b>, Error> {
+            if FLAKY_FAILURES.with(|c| c.replace(c.get().saturating_sub(1))) > 0 {
+                bail!("flaky encode failed.");
+            }
+            Paxos::encode(rng, points, aux, params)
+        }
+
+        fn decode(
+            p: &[F128b],
+            x: F128b,
+            aux: Self::AuxInfo,
+            params: Self::Params,
+        ) -> Result<F128b, Error> {
+            Paxos::decode(p, x, aux, params)
+        }
+
+        fn prepare_decoder(p: &[F128b], aux: Self::AuxInfo, params: Self::Params) -> Self::Decoder {
+            Paxos::prepare_decoder(p, aux, params)
+        }
+    }
+
+    #[test]
+    fn test_sep_opprf_on_encode_retry() {
+        let (sender_set, receiver_set, intersection) = create_sets::<F128b>(50, 20);
+        let points = sender_set
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| (x, usize2F128b(i + 1)))
+            .collect::<Vec<_>>();
+        let programmed: HashMap<F128b, F128b> = HashMap::from_iter(points.iter().cloned());
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let mut channel = channel_from(sender).unwrap();
+            let opprf_sender = SepOpprfSenderWithVole::<F128b, FlakySolver, _>::precomp(
+                &mut channel,
+                &mut rng,
+                points.len(),
+                LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+            )
+            .unwrap();
+
+            let retries = std::cell::RefCell::new(Vec::new());
+            let on_retry = |attempt: u32| retries.borrow_mut().push(attempt);
+            FLAKY_FAILURES.with(|c| c.set(1));
+            opprf_sender
+                .send_with_on_encode_retry(
+                    &mut channel,
+                    &points,
+                    points.len(),
+                    &mut rng,
+                    Some(&on_retry),
+                )
+                .unwrap();
+            retries.into_inner()
+        });
+
+        let mut rng = AesRng::new();
+        let mut channel = channel_from(receiver).unwrap();
+        let opprf_receiver = SepOpprfReceiverWithVole::<F128b, FlakySolver, _>::precomp(
+            &mut channel,
+            &mut rng,
+            receiver_set.len(),
+            LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+        )
+        .unwrap();
+        let received = opprf_receiver
+            .receive(&mut channel, &receiver_set, &mut rng)
+            .unwrap();
+
+        assert_eq!(handle.join().unwrap(), vec![1]);
+        for x in intersection.iter() {
+            let &(_, y) = received.iter().find(|(q, _)| q == x).unwrap();
+            assert_eq!(y, programmed[x]);
+        }
+    }
+
+    #[test]
+    fn test_sep_opprf_tagged() {
+        let (sender_set, receiver_set, intersection) = create_sets::<F128b>(50, 20);
+        let points = sender_set
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| (x, usize2F128b(i + 1)))
+            .collect::<Vec<_>>();
+        let programmed: HashMap<F128b, F128b> = HashMap::from_iter(points.iter().cloned());
+        let mut rng = AesRng::new();
+        let mac_key: F128b = rng.gen();
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let mut channel = channel_from(sender).unwrap();
+            let opprf_sender = SepOpprfSenderWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
+                &mut channel,
+                &mut rng,
+                points.len(),
+                LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+            )
+            .unwrap();
+            opprf_sender
+                .send_tagged(&mut channel, &points, mac_key, points.len(), &mut rng)
+                .unwrap();
+        });
+
+        let mut channel = channel_from(receiver).unwrap();
+        let opprf_receiver = SepOpprfReceiverWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
+            &mut channel,
+            &mut rng,
+            receiver_set.len(),
+            LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+        )
+        .unwrap();
+        let received = opprf_receiver
+            .receive_tagged(&mut channel, &receiver_set, &mut rng)
+            .unwrap();
+        handle.join().unwrap();
+
+        assert_eq!(received.len(), receiver_set.len());
+        for &(x, (value, tag)) in received.iter() {
+            let verified = tag == tag_of(mac_key, x, value).unwrap();
+            if intersection.contains(&x) {
+                assert_eq!(value, programmed[&x]);
+                assert!(verified);
+            } else {
+                assert!(!verified);
+            }
+        }
+    }
+
+    #[test]
+    fn test_sep_opprf_tagged_with_options() {
+        let (sender_set, receiver_set, intersection) = create_sets::<F128b>(50, 20);
+        let points = sender_set
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| (x, usize2F128b(i + 1)))
+            .collect::<Vec<_>>();
+        let programmed: HashMap<F128b, F128b> = HashMap::from_iter(points.iter().cloned());
+        let mut rng = AesRng::new();
+        let mac_key: F128b = rng.gen();
+
+        let config = OpprfConfig::default()
+            .with_run_nonce(true)
+            .with_seeded_aux(true)
+            .with_min_capacity(64);
+        let config_for_s = config.clone();
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let mut channel = channel_from(sender).unwrap();
+            let opprf_sender =
+                SepOpprfSenderWithVole::<F128b, PaxosSolver<F128b>, _>::precomp_with_config(
+                    &mut channel,
+                    &mut rng,
+                    points.len(),
+                    &config_for_s,
+                    LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+                )
+                .unwrap()
+                .with_blinding(BlindingMode::Multiplicative)
+                .with_pad_to_bucket(64);
+            opprf_sender
+                .send_tagged(&mut channel, &points, mac_key, points.len(), &mut rng)
+                .unwrap();
+        });
+
+        let mut channel = channel_from(receiver).unwrap();
+        let opprf_receiver =
+            SepOpprfReceiverWithVole::<F128b, PaxosSolver<F128b>, _>::precomp_with_config(
+                &mut channel,
+                &mut rng,
+                receiver_set.len(),
+                &config,
+                LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+            )
+            .unwrap()
+            .with_blinding(BlindingMode::Multiplicative);
+        let received = opprf_receiver
+            .receive_tagged(&mut channel, &receiver_set, &mut rng)
+            .unwrap();
+        handle.join().unwrap();
+
+        for &(x, (value, tag)) in received.iter() {
+            let verified = tag == tag_of(mac_key, x, value).unwrap();
+            if intersection.contains(&x) {
+                assert_eq!(value, programmed[&x]);
+                assert!(verified);
+            } else {
+                assert!(!verified);
+            }
+        }
+    }
+
+    #[test]
+    fn test_sep_opprf_tagged_empty_program() {
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let mut channel = channel_from(sender).unwrap();
+            let opprf_sender = SepOpprfSenderWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
+                &mut channel,
+                &mut rng,
+                10,
+                LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+            )
+            .unwrap();
+
+            let err = opprf_sender
+                .send_tagged(&mut channel, &[], F128b::one(), 10, &mut rng)
+                .err()
+                .unwrap();
+            assert_eq!(
+                err.downcast_ref::<PsiError>(),
+                Some(&PsiError::EmptyProgram)
+            );
+        });
+
+        let mut rng = AesRng::new();
+        let mut channel = channel_from(receiver).unwrap();
+        let receiver_set = create_sets::<F128b>(10, 5).1;
+        let opprf_receiver = SepOpprfReceiverWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
+            &mut channel,
+            &mut rng,
+            receiver_set.len(),
+            LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+        )
+        .unwrap();
+        // joined before the receiver runs the OPRF: the sender must refuse without waiting for it.
+        handle.join().unwrap();
+
+        assert!(opprf_receiver
+            .receive_tagged(&mut channel, &receiver_set, &mut rng)
+            .is_err());
+    }
+
+    #[test]
+    fn test_run_jaccard() {
+        // |A ∩ B| = 20 and |A ∪ B| = 40 + 50 - 20 = 70.
+        let (sender_set, receiver_set, _) = gen_overlapping_sets::<F128b>(50, 20, 3).unwrap();
+        let (common, private): (Vec<_>, Vec<_>) = receiver_set
+            .into_iter()
+            .partition(|x| sender_set.contains(x));
+        let receiver_set = common
+            .into_iter()
+            .chain(private.into_iter().take(20))
+            .collect::<Vec<_>>();
+        assert_eq!(receiver_set.len(), 40);
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let mut channel = channel_from(sender).unwrap();
+            let opprf_sender = SepOpprfSenderWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
+                &mut channel,
+                &mut rng,
+                sender_set.len(),
+                LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+            )
+            .unwrap();
+            run_jaccard_sender(
+                opprf_sender,
+                &mut channel,
+                &sender_set,
+                sender_set.len(),
+                &mut rng,
+            )
+            .unwrap();
+        });
+
+        let mut rng = AesRng::new();
+        let mut channel = channel_from(receiver).unwrap();
+        let opprf_receiver = SepOpprfReceiverWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
+            &mut channel,
+            &mut rng,
+            // both sides are precomputed for the larger set.
+            50,
+            LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+        )
+        .unwrap();
+        let jaccard = run_jaccard(opprf_receiver, &mut channel, &receiver_set, &mut rng).unwrap();
+        handle.join().unwrap();
+
+        assert_eq!(jaccard, 20.0 / 70.0);
+    }
+
+    #[test]
+    fn test_sep_opprf_receive_with_flush() {
+        let (sender_set, receiver_set, _) = create_sets::<F128b>(100, 30);
+        let points = sender_set
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| (x, usize2F128b(i)))
+            .collect::<Vec<_>>();
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+
+            let vole_share_for_s = LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL);
+            let opprf_sender = SepOpprfSenderWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
+                &mut channel,
+                &mut rng,
+                points.len(),
+                vole_share_for_s,
+            )
+            .unwrap();
+
+            // run the same online phase twice with the same randomness.
+            for _ in 0..2 {
+                let mut rng = AesRng::from_seed(Block::from(1u128));
+                opprf_sender
+                    .clone()
+                    .send(&mut channel, &points, points.len(), &mut rng)
+                    .unwrap();
+            }
+        });
+
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+
+        let vole_share_for_r = LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL);
+        let opprf_receiver = SepOpprfReceiverWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
+            &mut channel,
+            &mut rng,
+            receiver_set.len(),
+            vole_share_for_r,
+        )
+        .unwrap();
+
+        let mut rng = AesRng::from_seed(Block::from(2u128));
+        let received = opprf_receiver
+            .clone()
+            .receive(&mut channel, &receiver_set, &mut rng)
+            .unwrap();
+
+        let mut rng = AesRng::from_seed(Block::from(2u128));
+        let mut batches = Vec::new();
+        opprf_receiver
+            .receive_with_flush(&mut channel, &receiver_set, &mut rng, 7, |batch| {
+                batches.push(batch.to_vec())
+            })
+            .unwrap();
+
+        handle.join().unwrap();
+
+        assert_eq!(batches.len(), (receiver_set.len() + 6) / 7);
+        assert!(batches.iter().all(|batch| batch.len() <= 7));
+        assert_eq!(batches.concat(), received);
+    }
+
+    #[test]
+    fn test_sep_opprf_receive_iter() {
+        let (sender_set, receiver_set, _) = create_sets::<F128b>(100, 30);
+        let points = sender_set
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| (x, usize2F128b(i)))
+            .collect::<Vec<_>>();
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let mut channel = channel_from(sender).unwrap();
+            let opprf_sender = SepOpprfSenderWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
+                &mut channel,
+                &mut rng,
+                points.len(),
+                LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+            )
+            .unwrap();
+
+            // run the same online phase twice with the same randomness.
+            for _ in 0..2 {
+                let mut rng = AesRng::from_seed(Block::from(1u128));
+                opprf_sender
+                    .clone()
+                    .send(&mut channel, &points, points.len(), &mut rng)
+                    .unwrap();
+            }
+        });
+
+        let mut rng = AesRng::new();
+        let mut channel = channel_from(receiver).unwrap();
+        let opprf_receiver = SepOpprfReceiverWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
+            &mut channel,
+            &mut rng,
+            receiver_set.len(),
+            LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+        )
+        .unwrap();
+
+        let mut rng = AesRng::from_seed(Block::from(2u128));
+        let received = opprf_receiver
+            .clone()
+            .receive(&mut channel, &receiver_set, &mut rng)
+            .unwrap();
+
+        let mut rng = AesRng::from_seed(Block::from(2u128));
+        let outputs = opprf_receiver
+            .receive_iter(&mut channel, &receiver_set, &mut rng, 7)
+            .unwrap();
+        handle.join().unwrap();
+
+        // all messages are exchanged before the iteration.
+        drop(channel);
+        let streamed = outputs.collect::<Result<Vec<_>, Error>>().unwrap();
+        assert_eq!(streamed, received);
+    }
+
+    #[test]
+    fn test_sep_opprf_receive_all_from_reader() {
+        let (sender_set, receiver_set, _) = create_sets::<F128b>(100, 30);
+        let points = sender_set
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| (x, usize2F128b(i)))
+            .collect::<Vec<_>>();
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let mut channel = channel_from(sender).unwrap();
+            let opprf_sender = SepOpprfSenderWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
+                &mut channel,
+                &mut rng,
+                points.len(),
+                LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+            )
+            .unwrap();
+
+            // run the same online phase twice with the same randomness.
+            for _ in 0..2 {
+                let mut rng = AesRng::from_seed(Block::from(1u128));
+                opprf_sender
+                    .clone()
+                    .send(&mut channel, &points, points.len(), &mut rng)
+                    .unwrap();
+            }
+        });
+
+        let mut rng = AesRng::new();
+        let mut channel = channel_from(receiver).unwrap();
+        let opprf_receiver = SepOpprfReceiverWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
+            &mut channel,
+            &mut rng,
+            receiver_set.len(),
+            LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+        )
+        .unwrap();
+
+        let mut rng = AesRng::from_seed(Block::from(2u128));
+        let received = opprf_receiver
+            .clone()
+            .receive(&mut channel, &receiver_set, &mut rng)
+            .unwrap();
+
+        let source = receiver_set
+            .iter()
+            .flat_map(|x| x.to_bytes())
+            .collect::<Vec<u8>>();
+        let mut streamed = Vec::new();
+        let mut rng = AesRng::from_seed(Block::from(2u128));
+        opprf_receiver
+            .receive_all_from_reader(&mut channel, &source[..], &mut rng, 7, |batch| {
+                streamed.extend_from_slice(batch)
+            })
+            .unwrap();
+        handle.join().unwrap();
+        assert_eq!(streamed, received);
+
+        // a truncated source is rejected before any message.
+        assert!(read_queries::<F128b, _>(&source[..source.len() - 1]).is_err());
+        assert_eq!(read_queries::<F128b, _>(&source[..]).unwrap(), receiver_set);
+    }
+
+    #[test]
+    fn test_sep_opprf_presorted() {
+        let (sender_set, receiver_set, intersection) = create_sets::<F128b>(50, 20);
+        let points = sender_set
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| (x, usize2F128b(i + 1)))
+            .collect::<Vec<_>>();
+        let programmed: HashMap<F128b, F128b> = HashMap::from_iter(points.iter().cloned());
+        let mut sorted = points.clone();
+        sorted.sort_by_key(|(x, _)| x.to_bytes());
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let mut channel = channel_from(sender).unwrap();
+            let opprf_sender = SepOpprfSenderWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
+                &mut channel,
+                &mut rng,
+                points.len(),
+                LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+            )
+            .unwrap();
+
+            // the unsorted input, the sorted one, and the sorted one by send_presorted with the same randomness.
+            for (points, presorted) in [(&points, false), (&sorted, false), (&sorted, true)] {
+                let mut rng = AesRng::from_seed(Block::from(1u128));
+                let opprf_sender = opprf_sender.clone();
+                if presorted {
+                    opprf_sender
+                        .send_presorted(&mut channel, points, points.len(), &mut rng)
+                        .unwrap();
+                } else {
+                    opprf_sender
+                        .send(&mut channel, points, points.len(), &mut rng)
+                        .unwrap();
+                }
+            }
+
+            // conflicting duplicated keys are adjacent once sorted.
+            let mut conflicting = sorted.clone();
+            conflicting.insert(1, (sorted[0].0, sorted[1].1));
+            let err = opprf_sender
+                .send_presorted(&mut channel, &conflicting, conflicting.len(), &mut rng)
+                .err()
+                .unwrap();
+            assert!(matches!(
+                err.downcast_ref::<PsiError>(),
+                Some(PsiError::KeyHashCollision { .. })
+            ));
+        });
+
+        let mut rng = AesRng::new();
+        let mut channel = channel_from(receiver).unwrap();
+        let opprf_receiver = SepOpprfReceiverWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
+            &mut channel,
+            &mut rng,
+            receiver_set.len(),
+            LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+        )
+        .unwrap();
+
+        let runs = (0..3)
+            .map(|_| {
+                let mut rng = AesRng::from_seed(Block::from(2u128));
+                opprf_receiver
+                    .clone()
+                    .receive(&mut channel, &receiver_set, &mut rng)
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        handle.join().unwrap();
+
+        assert_eq!(runs[1], runs[2]);
+        for received in runs.iter() {
+            for x in intersection.iter() {
+                let &(_, y) = received.iter().find(|(q, _)| q == x).unwrap();
+                assert_eq!(y, programmed[x]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_opprf_with_external_oprf() {
+        let (sender_set, receiver_set, intersection) = create_sets::<F128b>(100, 30);
+        let points = sender_set
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| (x, usize2F128b(i + 1)))
+            .collect::<Vec<_>>();
+        let programmed: HashMap<F128b, F128b> = HashMap::from_iter(points.iter().cloned());
+
+        // mock OPRF shared by both sides. NOT oblivious at all.
+        let key = usize2F128b(12345);
+        let mock_oprf = move |x: F128b| -> Result<F128b, Error> { Ok(x * key + key) };
+
+        let params = PaxosSolver::<F128b>::calc_params(points.len());
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+
+            let table = send_with_oprf_outputs::<F128b, PaxosSolver<F128b>, _, _>(
+                &mut channel,
+                &points,
+                &mock_oprf,
+                params,
+                &mut rng,
+            )
+            .unwrap();
+
+            for &(x, z) in points.iter() {
+                assert_eq!(table.decode(x).unwrap() + mock_oprf(x).unwrap(), z);
+            }
+        });
+
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+
+        let oprf_outputs = receiver_set
+            .iter()
+            .map(|&x| (x, mock_oprf(x).unwrap()))
+            .collect::<Vec<_>>();
+
+        let (received, _table) = receive_with_oprf_outputs::<F128b, PaxosSolver<F128b>, _, _>(
+            &mut channel,
+            &oprf_outputs,
+            params,
+            &mut rng,
+        )
+        .unwrap();
+
+        handle.join().unwrap();
+
+        assert_eq!(received.len(), receiver_set.len());
+        for x in intersection.iter() {
+            let &(_, y) = received.iter().find(|(q, _)| q == x).unwrap();
+            assert_eq!(y, programmed[x]);
+        }
+    }
+
+    #[test]
+    fn test_sep_opprf_presorted_pad() {
+        let (sender_set, receiver_set, intersection) = create_sets::<F128b>(50, 20);
+        let mut points = sender_set
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| (x, usize2F128b(i + 1)))
+            .collect::<Vec<_>>();
+        points.sort_by_key(|(x, _)| x.to_bytes());
+        let programmed: HashMap<F128b, F128b> = HashMap::from_iter(points.iter().cloned());
+
+        // the same dummies as pad_points with the same randomness.
+        let seed = Block::from(3u128);
+        assert_eq!(
+            pad_sorted_points(&points, 64, &mut AesRng::from_seed(seed)),
+            pad_points(&points, 64, &mut AesRng::from_seed(seed))
+        );
+
+        let config = OpprfConfig::default().with_min_capacity(64);
+        let config_for_s = config.clone();
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let mut channel = channel_from(sender).unwrap();
+            let opprf_sender =
+                SepOpprfSenderWithVole::<F128b, PaxosSolver<F128b>, _>::precomp_with_config(
+                    &mut channel,
+                    &mut rng,
+                    points.len(),
+                    &config_for_s,
+                    LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+                )
+                .unwrap()
+                .with_pad_to_bucket(64);
+
+            // send and send_presorted with the same randomness pad to the same table.
+            for presorted in [false, true] {
+                let mut rng = AesRng::from_seed(Block::from(1u128));
+                let opprf_sender = opprf_sender.clone();
+                if presorted {
+                    opprf_sender
+                        .send_presorted(&mut channel, &points, points.len(), &mut rng)
+                        .unwrap();
+                } else {
+                    opprf_sender
+                        .send(&mut channel, &points, points.len(), &mut rng)
+                        .unwrap();
+                }
+            }
+        });
+
+        let mut rng = AesRng::new();
+        let mut channel = channel_from(receiver).unwrap();
+        let opprf_receiver =
+            SepOpprfReceiverWithVole::<F128b, PaxosSolver<F128b>, _>::precomp_with_config(
+                &mut channel,
+                &mut rng,
+                receiver_set.len(),
+                &config,
+                LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+            )
+            .unwrap();
+
+        let runs = (0..2)
+            .map(|_| {
+                let mut rng = AesRng::from_seed(Block::from(2u128));
+                opprf_receiver
+                    .clone()
+                    .receive(&mut channel, &receiver_set, &mut rng)
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        handle.join().unwrap();
+
+        assert_eq!(runs[0], runs[1]);
+        for received in runs.iter() {
+            for x in intersection.iter() {
+                let &(_, y) = received.iter().find(|(q, _)| q == x).unwrap();
+                assert_eq!(y, programmed[x]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_sep_opprf_pad_to_bucket() {
+        let (sender_set, receiver_set, intersection) = create_sets::<F128b>(7, 3);
+        let points = sender_set
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| (x, usize2F128b(i + 1)))
+            .collect::<Vec<_>>();
+        let programmed: HashMap<F128b, F128b> = HashMap::from_iter(points.iter().cloned());
+
+        let padded = pad_points(&points, 16, &mut AesRng::new());
+        assert_eq!(padded.len(), 16);
+        assert_eq!(&padded[..7], &points[..]);
+        let keys: HashSet<F128b> = HashSet::from_iter(padded.iter().map(|&(x, _)| x));
+        assert_eq!(keys.len(), 16);
+        assert_eq!(pad_points(&padded, 16, &mut AesRng::new()).len(), 16);
+        assert_eq!(pad_points(&points, 0, &mut AesRng::new()).len(), 7);
+
+        let config = OpprfConfig::default().with_min_capacity(16);
+        let config_for_s = config.clone();
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let mut channel = channel_from(sender).unwrap();
+            let opprf_sender =
+                SepOpprfSenderWithVole::<F128b, PaxosSolver<F128b>, _>::precomp_with_config(
+                    &mut channel,
+                    &mut rng,
+                    points.len(),
+                    &config_for_s,
+                    LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+                )
+                .unwrap()
+                .with_pad_to_bucket(16);
+
+            let (fk, _table) = opprf_sender
+                .send_with_table(&mut channel, &points, points.len(), &mut rng)
+                .unwrap();
+            for &(x, y) in points.iter() {
+                assert_eq!(fk(x).unwrap(), y);
+            }
+        });
+
+        let mut rng = AesRng::new();
+        let mut channel = channel_from(receiver).unwrap();
+        let opprf_receiver =
+            SepOpprfReceiverWithVole::<F128b, PaxosSolver<F128b>, _>::precomp_with_config(
+                &mut channel,
+                &mut rng,
+                receiver_set.len(),
+                &config,
+                LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+            )
+            .unwrap();
+
+        let received = opprf_receiver
+            .receive(&mut channel, &receiver_set, &mut rng)
+            .unwrap();
+        handle.join().unwrap();
+
+        // real matches are unaffected by the dummies.
+        for x in intersection.iter() {
+            let &(_, y) = received.iter().find(|(q, _)| q == x).unwrap();
+            assert_eq!(y, programmed[x]);
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_sep_opprf_with_thread_pool() {
+        let (sender_set, receiver_set, intersection) = create_sets::<F128b>(100, 30);
+        let points = sender_set
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| (x, usize2F128b(i + 1)))
+            .collect::<Vec<_>>();
+        let programmed: HashMap<F128b, F128b> = HashMap::from_iter(points.iter().cloned());
+        let pool = std::sync::Arc::new(
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(2)
+                .build()
+                .unwrap(),
+        );
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let mut channel = channel_from(sender).unwrap();
+            let opprf_sender =
+                SepOpprfSenderWithVole::<F128b, PaxosSolver<F128b>, _>::precomp_with_config(
+                    &mut channel,
+                    &mut rng,
+                    points.len(),
+                    &OpprfConfig::default(),
+                    LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+                )
+                .unwrap()
+                .with_thread_pool(pool);
+
+            let fk = opprf_sender
+                .send(&mut channel, &points, points.len(), &mut rng)
+                .unwrap();
+            for &(x, y) in points.iter() {
+                assert_eq!(fk(x).unwrap(), y);
+            }
+        });
+
+        let mut rng = AesRng::new();
+        let mut channel = channel_from(receiver).unwrap();
+        let opprf_receiver =
+            SepOpprfReceiverWithVole::<F128b, PaxosSolver<F128b>, _>::precomp_with_config(
+                &mut channel,
+                &mut rng,
+                receiver_set.len(),
+                &OpprfConfig::default(),
+                LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+            )
+            .unwrap();
+
+        let received = opprf_receiver
+            .receive(&mut channel, &receiver_set, &mut rng)
+            .unwrap();
+        handle.join().unwrap();
+
+        for x in intersection.iter() {
+            let &(_, y) = received.iter().find(|(q, _)| q == x).unwrap();
+            assert_eq!(y, programmed[x]);
+        }
+    }
+
+    #[test]
+    fn test_opprf_config() {
+        let (sender_set, receiver_set, intersection) = create_sets::<F128b>(10, 4);
+        let points = sender_set
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| (x, usize2F128b(i + 1)))
+            .collect::<Vec<_>>();
+        let programmed: HashMap<F128b, F128b> = HashMap::from_iter(points.iter().cloned());
+
+        let config = OpprfConfig::default()
+            .with_domain_tag(b"config-test")
+            .with_min_capacity(1000)
+            .with_encode_attempts(5);
+        assert_ne!(config, OpprfConfig::default());
+        assert_eq!(config.capacity(10), 1000);
+        assert_eq!(config.capacity(2000), 2000);
+        assert_eq!(config.encode_attempts(), 5);
+        let config_for_s = config.clone();
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+
+            let vole_share_for_s = LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL);
+            let opprf_sender =
+                SepOpprfSenderWithVole::<F128b, PaxosSolver<F128b>, _>::precomp_with_config(
+                    &mut channel,
+                    &mut rng,
+                    points.len(),
+                    &config_for_s,
+                    vole_share_for_s,
+                )
+                .unwrap();
+
+            let (_fk, table) = opprf_sender
+                .send_with_table(&mut channel, &points, points.len(), &mut rng)
+                .unwrap();
+            table
+        });
+
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+
+        let vole_share_for_r = LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL);
+        let opprf_receiver =
+            SepOpprfReceiverWithVole::<F128b, PaxosSolver<F128b>, _>::precomp_with_config(
+                &mut channel,
+                &mut rng,
+                receiver_set.len(),
+                &config,
+                vole_share_for_r,
+            )
+            .unwrap();
+
+        let (received, table) = opprf_receiver
+            .receive_with_table(&mut channel, &receiver_set, &mut rng)
+            .unwrap();
+
+        let sender_table = handle.join().unwrap();
+
+        // the table is sized for the configured capacity, not for the 10 points.
+        let code_length = PaxosSolver::<F128b>::calc_params(1000).code_length();
+        assert!(code_length > PaxosSolver::<F128b>::calc_params(10).code_length());
+        assert_eq!(table.params().code_length(), code_length);
+        assert_eq!(table.p().len(), code_length);
+        assert_eq!(sender_table.p(), table.p());
+
+        for x in intersection.iter() {
+            let &(_, y) = received.iter().find(|(q, _)| q == x).unwrap();
+            assert_eq!(y, programmed[x]);
+        }
+    }
+
+    #[test]
+    fn test_opprf_vole_length() {
+        let set_size = 100;
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let mut channel = channel_from(sender).unwrap();
+            let opprf_sender = SepOpprfSenderWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
+                &mut channel,
+                &mut rng,
+                set_size,
+                LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+            )
+            .unwrap();
+            opprf_sender.vole_length()
+        });
+
+        let mut rng = AesRng::new();
+        let mut channel = channel_from(receiver).unwrap();
+        let opprf_receiver = SepOpprfReceiverWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
+            &mut channel,
+            &mut rng,
+            set_size,
+            LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+        )
+        .unwrap();
+        let sender_length = handle.join().unwrap();
+
+        let code_length = PaxosSolver::<F128b>::calc_params(set_size).code_length();
+        assert!(opprf_receiver.vole_length() >= code_length);
+        assert_eq!(opprf_receiver.vole_length(), sender_length);
+
+        // shrinking keeps only the prefix needed for the smaller set.
+        let shrunk = opprf_receiver.shrink(10).unwrap();
+        assert_eq!(
+            shrunk.vole_length(),
+            PaxosSolver::<F128b>::calc_params(10).code_length()
+        );
+    }
+
+    #[test]
+    fn test_opprf_run_nonce() {
+        let (sender_set, receiver_set, intersection) = create_sets::<F128b>(10, 4);
+        let points = sender_set
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| (x, usize2F128b(i + 1)))
+            .collect::<Vec<_>>();
+        let programmed: HashMap<F128b, F128b> = HashMap::from_iter(points.iter().cloned());
+
+        let config = OpprfConfig::default().with_run_nonce(true);
+        assert!(config.run_nonce());
+
+        let run_once = || {
+            let (sender, receiver) = UnixStream::pair().unwrap();
+            let points = points.clone();
+            let config_for_s = config.clone();
+            let handle = std::thread::spawn(move || {
+                let mut rng = AesRng::new();
+                let mut channel = channel_from(sender).unwrap();
+                let opprf_sender =
+                    SepOpprfSenderWithVole::<F128b, PaxosSolver<F128b>, _>::precomp_with_config(
+                        &mut channel,
+                        &mut rng,
+                        points.len(),
+                        &config_for_s,
+                        LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+                    )
+                    .unwrap();
+                opprf_sender
+                    .send(&mut channel, &points, points.len(), &mut rng)
+                    .unwrap();
+            });
+
+            let mut rng = AesRng::new();
+            let mut channel = channel_from(receiver).unwrap();
+            let opprf_receiver =
+                SepOpprfReceiverWithVole::<F128b, PaxosSolver<F128b>, _>::precomp_with_config(
+                    &mut channel,
+                    &mut rng,
+                    receiver_set.len(),
+                    &config,
+                    LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+                )
+                .unwrap();
+            let received = opprf_receiver
+                .receive(&mut channel, &receiver_set, &mut rng)
+                .unwrap();
+            handle.join().unwrap();
+            HashMap::<F128b, F128b>::from_iter(received)
+        };
+
+        let first = run_once();
+        let second = run_once();
+
+        for received in [&first, &second] {
+            for x in intersection.iter() {
+                assert_eq!(received[x], programmed[x]);
+            }
+        }
+
+        // the same element queried in two runs is not linkable by its output.
+        let intersection: HashSet<F128b> = HashSet::from_iter(intersection.iter().cloned());
+        for x in receiver_set.iter().filter(|x| !intersection.contains(x)) {
+            assert_ne!(first[x], second[x]);
+        }
+    }
+
+    #[test]
+    fn test_opprf_with_params() {
+        let (sender_set, receiver_set, intersection) = create_sets::<F128b>(10, 4);
+        let points = sender_set
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| (x, usize2F128b(i + 1)))
+            .collect::<Vec<_>>();
+        let programmed: HashMap<F128b, F128b> = HashMap::from_iter(points.iter().cloned());
+
+        // shared explicit params, sized for more points than the queries.
+        let params = PaxosSolver::<F128b>::calc_params(500);
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+
+            let vole_share_for_s = LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL);
+            let opprf_sender =
+                SepOpprfSenderWithVole::<F128b, PaxosSolver<F128b>, _>::precomp_with_params(
+                    &mut channel,
+                    &mut rng,
+                    params,
+                    vole_share_for_s,
+                )
+                .unwrap();
+
+            opprf_sender
+                .send(&mut channel, &points, points.len(), &mut rng)
+                .unwrap();
+        });
+
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+
+        let vole_share_for_r = LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL);
+        let opprf_receiver =
+            SepOpprfReceiverWithVole::<F128b, PaxosSolver<F128b>, _>::precomp_with_params(
+                &mut channel,
+                &mut rng,
+                params,
+                vole_share_for_r,
+            )
+            .unwrap();
+
+        let (received, table) = opprf_receiver
+            .receive_with_table(&mut channel, &receiver_set, &mut rng)
+            .unwrap();
+        handle.join().unwrap();
 
-    fn create_sets<F: FF>(set_size: usize, common_size: usize) -> (Vec<F>, Vec<F>, Vec<F>)
-    where
-        Standard: Distribution<F>,
-    {
-        if set_size < common_size {
-            panic!("set_size (={}) < common_size (={})", set_size, common_size);
+        assert_eq!(table.params().to_bytes(), params.to_bytes());
+        for x in intersection.iter() {
+            let &(_, y) = received.iter().find(|(q, _)| q == x).unwrap();
+            assert_eq!(y, programmed[x]);
         }
 
+        // different params are rejected by both sides.
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+
+            let vole_share_for_s = LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL);
+            SepOpprfSenderWithVole::<F128b, PaxosSolver<F128b>, _>::precomp_with_params(
+                &mut channel,
+                &mut rng,
+                PaxosSolver::<F128b>::calc_params(100),
+                vole_share_for_s,
+            )
+            .is_err()
+        });
+
         let mut rng = AesRng::new();
-        let common = (0..common_size).map(|_| rng.gen::<F>()).collect::<Vec<_>>();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
 
-        let mut set1 = HashSet::<F>::from_iter(common.clone().into_iter());
-        while set1.len() < set_size {
-            set1.insert(rng.gen::<F>());
-        }
+        let vole_share_for_r = LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL);
+        let res = SepOpprfReceiverWithVole::<F128b, PaxosSolver<F128b>, _>::precomp_with_params(
+            &mut channel,
+            &mut rng,
+            PaxosSolver::<F128b>::calc_params(200),
+            vole_share_for_r,
+        );
+        assert!(res.is_err());
+        assert!(handle.join().unwrap());
+    }
 
-        let mut set2 = HashSet::<F>::from_iter(common.clone().into_iter());
-        while set2.len() < set_size {
-            set2.insert(rng.gen::<F>());
-        }
+    #[test]
+    fn test_opprf_message_count() {
+        // (messages sent by the sender, by the receiver) in the online phase.
+        let online_messages = |set_size: usize| {
+            let (sender_set, receiver_set, _) = create_sets::<F128b>(set_size, set_size / 2);
+            let points = sender_set
+                .iter()
+                .map(|&x| (x, F128b::zero()))
+                .collect::<Vec<_>>();
 
-        let common = set1
-            .iter()
-            .filter_map(|x| if set2.contains(x) { Some(*x) } else { None })
-            .collect::<Vec<_>>();
+            let (sender, receiver) = UnixStream::pair().unwrap();
+            let handle = std::thread::spawn(move || {
+                let mut rng = AesRng::new();
+                let mut channel = channel_from(sender).unwrap();
+                let vole_share_for_s = LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL);
+                let opprf_sender = SepOpprfSenderWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
+                    &mut channel,
+                    &mut rng,
+                    points.len(),
+                    vole_share_for_s,
+                )
+                .unwrap();
 
-        let mut set1 = set1.into_iter().collect::<Vec<F>>();
-        set1.shuffle(&mut rng);
-        let mut set2 = set2.into_iter().collect::<Vec<_>>();
-        set2.shuffle(&mut rng);
+                let counters = Arc::new(ByteCounters::default());
+                let mut channel = CountingChannel::new(channel, Arc::clone(&counters));
+                opprf_sender
+                    .send(&mut channel, &points, points.len(), &mut rng)
+                    .unwrap();
+                counters.message_count()
+            });
 
-        (set1, set2, common)
+            let mut rng = AesRng::new();
+            let mut channel = channel_from(receiver).unwrap();
+            let vole_share_for_r = LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL);
+            let opprf_receiver = SepOpprfReceiverWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
+                &mut channel,
+                &mut rng,
+                receiver_set.len(),
+                vole_share_for_r,
+            )
+            .unwrap();
+
+            let counters = Arc::new(ByteCounters::default());
+            let mut channel = CountingChannel::new(channel, Arc::clone(&counters));
+            opprf_receiver
+                .receive(&mut channel, &receiver_set, &mut rng)
+                .unwrap();
+
+            (handle.join().unwrap(), counters.message_count())
+        };
+
+        // one round trip: the masked code vector from the receiver, then the programmed table from the sender.
+        assert_eq!(online_messages(10), (1, 1));
+        assert_eq!(online_messages(1000), (1, 1));
     }
 
-    #[allow(non_snake_case)]
-    fn usize2F128b(x: usize) -> F128b {
-        let x = x as u128;
-        let x = x.to_le_bytes();
-        let res = F128b::from_bytes(&x.into()).unwrap();
+    #[test]
+    fn test_opprf_wire_bits() {
+        // bytes sent by the sender in the online phase, checking the lowest `bits` bits of the outputs on the intersection.
+        let online_sent = |bits: usize| {
+            let (sender_set, receiver_set, intersection) = create_sets::<F128b>(100, 40);
+            let points = sender_set
+                .iter()
+                .enumerate()
+                .map(|(i, &x)| (x, usize2F128b(i + 1)))
+                .collect::<Vec<_>>();
+            let programmed: HashMap<F128b, F128b> = HashMap::from_iter(points.iter().cloned());
 
-        // dbg!(res);
+            let (sender, receiver) = UnixStream::pair().unwrap();
+            let handle = std::thread::spawn(move || {
+                let mut rng = AesRng::new();
+                let channel = channel_from(sender).unwrap();
+                let counters = Arc::new(ByteCounters::default());
+                let mut channel = CountingChannel::new(channel, Arc::clone(&counters));
+                let opprf_sender = SepOpprfSenderWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
+                    &mut channel,
+                    &mut rng,
+                    points.len(),
+                    LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+                )
+                .unwrap()
+                .with_wire_bits(bits);
 
-        res
+                let before = counters.sent();
+                opprf_sender
+                    .send(&mut channel, &points, points.len(), &mut rng)
+                    .unwrap();
+                counters.sent() - before
+            });
+
+            let mut rng = AesRng::new();
+            let mut channel = channel_from(receiver).unwrap();
+            let opprf_receiver = SepOpprfReceiverWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
+                &mut channel,
+                &mut rng,
+                receiver_set.len(),
+                LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+            )
+            .unwrap()
+            .with_wire_bits(bits);
+            let received = opprf_receiver
+                .receive(&mut channel, &receiver_set, &mut rng)
+                .unwrap();
+
+            for x in intersection.iter() {
+                let &(_, y) = received.iter().find(|(q, _)| q == x).unwrap();
+                let (y, z) = (y.to_bytes(), programmed[x].to_bytes());
+                assert_eq!(y[..bits / 8], z[..bits / 8]);
+            }
+            handle.join().unwrap()
+        };
+
+        // each entry of the code vector is sent in 2 bytes instead of 16.
+        let code_length = PaxosSolver::<F128b>::calc_params(100).code_length();
+        assert_eq!(online_sent(128) - online_sent(16), 14 * code_length as u64);
+
+        // truncated only where the truncated table decodes to the truncated values.
+        let additive = BlindingMode::Additive;
+        assert_eq!(
+            truncated_bits::<F128b, PaxosSolver<F128b>>(Some(16), additive),
+            Some(16)
+        );
+        assert_eq!(
+            truncated_bits::<F128b, PaxosSolver<F128b>>(Some(128), additive),
+            None
+        );
+        assert_eq!(
+            truncated_bits::<F128b, VandelmondeSolver<F128b>>(Some(16), additive),
+            None
+        );
+        assert_eq!(
+            truncated_bits::<F128b, PaxosSolver<F128b>>(Some(16), BlindingMode::Multiplicative),
+            None
+        );
     }
 
-    fn test_sep_opprf_base<S: Solver<F128b>>(set_size: usize, common_size: usize, verbose: bool) {
-        let (sender_set, receiver_set, intersection) = create_sets::<F128b>(set_size, common_size);
+    #[test]
+    fn test_opprf_seeded_aux() {
+        // bytes sent by the sender in the online phase, checking the outputs on the intersection.
+        let online_sent = |seeded_aux: bool| {
+            let (sender_set, receiver_set, intersection) = create_sets::<F128b>(100, 40);
+            let points = sender_set
+                .iter()
+                .enumerate()
+                .map(|(i, &x)| (x, usize2F128b(i + 1)))
+                .collect::<Vec<_>>();
+            let programmed: HashMap<F128b, F128b> = HashMap::from_iter(points.iter().cloned());
+            let config = OpprfConfig::default()
+                .with_run_nonce(true)
+                .with_seeded_aux(seeded_aux);
+            let config_for_s = config.clone();
+
+            let (sender, receiver) = UnixStream::pair().unwrap();
+            let handle = std::thread::spawn(move || {
+                let mut rng = AesRng::new();
+                let mut channel = channel_from(sender).unwrap();
+                let opprf_sender =
+                    SepOpprfSenderWithVole::<F128b, PaxosSolver<F128b>, _>::precomp_with_config(
+                        &mut channel,
+                        &mut rng,
+                        points.len(),
+                        &config_for_s,
+                        LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+                    )
+                    .unwrap();
+
+                let counters = Arc::new(ByteCounters::default());
+                let mut channel = CountingChannel::new(channel, Arc::clone(&counters));
+                opprf_sender
+                    .send(&mut channel, &points, points.len(), &mut rng)
+                    .unwrap();
+                counters.sent()
+            });
+
+            let mut rng = AesRng::new();
+            let mut channel = channel_from(receiver).unwrap();
+            let opprf_receiver =
+                SepOpprfReceiverWithVole::<F128b, PaxosSolver<F128b>, _>::precomp_with_config(
+                    &mut channel,
+                    &mut rng,
+                    receiver_set.len(),
+                    &config,
+                    LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+                )
+                .unwrap();
+            let received = opprf_receiver
+                .receive(&mut channel, &receiver_set, &mut rng)
+                .unwrap();
+
+            for x in intersection.iter() {
+                let &(_, y) = received.iter().find(|(q, _)| q == x).unwrap();
+                assert_eq!(y, programmed[x]);
+            }
+            handle.join().unwrap()
+        };
+
+        // the auxillary information of PaxosSolver (three u64) is not sent.
+        assert_eq!(online_sent(false) - online_sent(true), 24);
+
+        // the same seed gives the same auxillary information.
+        let seed = Block::from(7u128);
+        assert_eq!(
+            PaxosSolver::<F128b>::gen_aux_from_seed(seed).unwrap(),
+            PaxosSolver::<F128b>::gen_aux_from_seed(seed).unwrap()
+        );
+
+        // the seed is derived from the run nonces.
+        let config = OpprfConfig::default().with_seeded_aux(true);
+        assert!(config.seeded_aux());
+        assert!(run_aux_seed(&config, b"").is_err());
+        let config = config.with_run_nonce(true);
+        assert_eq!(
+            run_aux_seed(&config, b"tag").unwrap(),
+            run_aux_seed(&config, b"tag").unwrap()
+        );
+        assert_ne!(
+            run_aux_seed(&config, b"tag").unwrap(),
+            run_aux_seed(&config, b"other").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_opprf_hash_seed() {
+        let (sender_set, receiver_set, intersection) = create_sets::<F128b>(100, 40);
         let points = sender_set
             .iter()
             .enumerate()
-            .map(|(i, &x)| (x, usize2F128b(i)))
+            .map(|(i, &x)| (x, usize2F128b(i + 1)))
             .collect::<Vec<_>>();
+        let programmed: HashMap<F128b, F128b> = HashMap::from_iter(points.iter().cloned());
 
-        let sender_set_2 = sender_set.clone();
-        let points_2 = points.clone();
+        let run = |sender_seed: [u8; 16], receiver_seed: [u8; 16]| {
+            let points = points.clone();
+            let config_for_s = OpprfConfig::default().with_hash_seed(sender_seed);
+            let config = OpprfConfig::default().with_hash_seed(receiver_seed);
 
-        let points: HashMap<F128b, F128b> = HashMap::from_iter(points.into_iter());
+            let (sender, receiver) = UnixStream::pair().unwrap();
+            let handle = std::thread::spawn(move || -> Result<(), Error> {
+                let mut rng = AesRng::new();
+                let mut channel = channel_from(sender)?;
+                let opprf_sender =
+                    SepOpprfSenderWithVole::<F128b, PaxosSolver<F128b>, _>::precomp_with_config(
+                        &mut channel,
+                        &mut rng,
+                        points.len(),
+                        &config_for_s,
+                        LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+                    )?;
+                opprf_sender.send(&mut channel, &points, points.len(), &mut rng)?;
+                Ok(())
+            });
 
-        let m_size = S::calc_params(set_size).code_length();
-        let (setup_params, extend_params) = if m_size < (1 << 17) {
-            (LPN_SETUP_SMALL, LPN_EXTEND_SMALL)
-        } else {
-            (LPN_SETUP_MEDIUM, LPN_EXTEND_MEDIUM)
+            let mut rng = AesRng::new();
+            let mut channel = channel_from(receiver).unwrap();
+            let received =
+                SepOpprfReceiverWithVole::<F128b, PaxosSolver<F128b>, _>::precomp_with_config(
+                    &mut channel,
+                    &mut rng,
+                    receiver_set.len(),
+                    &config,
+                    LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+                )
+                .and_then(|opprf_receiver| {
+                    opprf_receiver.receive(&mut channel, &receiver_set, &mut rng)
+                });
+            (received, handle.join().unwrap())
         };
 
-        if verbose {
-            println!("sender_set = {:?}\nlen: {}", sender_set, sender_set.len());
-            println!(
-                "receiver_set = {:?}\nlen: {}",
-                receiver_set,
-                receiver_set.len()
-            );
-            println!(
-                "intersection = {:?}\nlen: {}",
-                intersection,
-                intersection.len()
+        let (received, sent) = run([1; 16], [1; 16]);
+        sent.unwrap();
+        let received = received.unwrap();
+        for x in intersection.iter() {
+            let &(_, y) = received.iter().find(|(q, _)| q == x).unwrap();
+            assert_eq!(y, programmed[x]);
+        }
+
+        // both sides abort before the OPRF.
+        let (received, sent) = run([1; 16], [2; 16]);
+        for err in [received.err().unwrap(), sent.unwrap_err()] {
+            assert_eq!(
+                err.downcast_ref::<PsiError>(),
+                Some(&PsiError::HashSeedMismatch),
+                "{:#}",
+                err
             );
         }
 
+        let config = OpprfConfig::default().with_hash_seed([3; 16]);
+        assert_eq!(config.hash_seed(), Some([3; 16]));
+    }
+
+    #[test]
+    fn test_opprf_timing() {
+        let (sender_set, receiver_set, _) = create_sets::<F128b>(1000, 100);
+        let points = sender_set
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| (x, usize2F128b(i + 1)))
+            .collect::<Vec<_>>();
+
+        // the unmeasured gaps between the layers are only bookkeeping.
+        let check = |timing: OpprfTiming| {
+            assert!(timing.oprf > Duration::ZERO, "{:?}", timing);
+            assert!(timing.solver > Duration::ZERO, "{:?}", timing);
+            let sum = timing.oprf + timing.solver;
+            assert!(sum <= timing.total, "{:?}", timing);
+            assert!(sum * 10 >= timing.total * 9, "{:?}", timing);
+        };
+
         let (sender, receiver) = UnixStream::pair().unwrap();
         let handle = std::thread::spawn(move || {
-            let sender_set = sender_set_2;
-            let points = points_2;
-
             let mut rng = AesRng::new();
             let reader = BufReader::new(sender.try_clone().unwrap());
             let writer = BufWriter::new(sender);
             let mut channel = Channel::new(reader, writer);
 
-            let vole_share_for_s = LPNVoleSender::new(setup_params, extend_params);
-
-            let opprf_sender = SepOpprfSenderWithVole::<F128b, S, _>::precomp(
+            let vole_share_for_s = LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL);
+            let opprf_sender = SepOpprfSenderWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
                 &mut channel,
                 &mut rng,
-                sender_set.len(),
+                points.len(),
                 vole_share_for_s,
             )
             .unwrap();
 
-            let fk = opprf_sender
-                .send(&mut channel, &points, points.len(), &mut rng)
+            let (_fk, _table, timing) = opprf_sender
+                .send_with_timing(&mut channel, &points, points.len(), &mut rng)
                 .unwrap();
-
-            for &(x, y) in points.iter() {
-                let y_computed = fk(x).unwrap();
-                assert_eq!(y, y_computed);
-            }
+            timing
         });
 
         let mut rng = AesRng::new();
@@ -557,9 +5230,8 @@ mod tests {
         let writer = BufWriter::new(receiver);
         let mut channel = Channel::new(reader, writer);
 
-        let vole_share_for_r = LPNVoleReceiver::new(setup_params, extend_params);
-
-        let opprf_receiver = SepOpprfReceiverWithVole::<F128b, S, _>::precomp(
+        let vole_share_for_r = LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL);
+        let opprf_receiver = SepOpprfReceiverWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
             &mut channel,
             &mut rng,
             receiver_set.len(),
@@ -567,45 +5239,255 @@ mod tests {
         )
         .unwrap();
 
-        let received = opprf_receiver
-            .receive(&mut channel, &receiver_set, &mut rng)
+        let (_points, _table, receiver_timing) = opprf_receiver
+            .receive_with_timing(&mut channel, &receiver_set, &mut rng)
             .unwrap();
 
-        handle.join().unwrap();
+        check(handle.join().unwrap());
+        check(receiver_timing);
+    }
 
-        if verbose {
-            dbg!(received.clone());
+    #[test]
+    fn test_run_receiver_multi() {
+        const SENDER_NUM: usize = 3;
+        let set_size = 50;
+
+        let mut rng = AesRng::new();
+        let common = (0..10).map(|_| rng.gen::<F128b>()).collect::<Vec<_>>();
+        // shared by the receiver and the first two senders only.
+        let partial = (0..5).map(|_| rng.gen::<F128b>()).collect::<Vec<_>>();
+
+        let mut receiver_set = common.clone();
+        receiver_set.extend(partial.iter().copied());
+        while receiver_set.len() < set_size {
+            receiver_set.push(rng.gen::<F128b>());
         }
 
-        for (x, y) in received {
-            if let Some(&original_y) = points.get(&x) {
-                assert_eq!(y, original_y);
-                if verbose {
-                    println!("{:?} is in the sender set. and f({:?}) = {:?}", x, x, y);
-                }
-            } else if verbose {
-                println!("{:?} is not in the sender set", x);
+        let mut handles = Vec::new();
+        let mut channels = Vec::new();
+        let mut receivers = Vec::new();
+
+        for i in 0..SENDER_NUM {
+            let mut sender_set = common.clone();
+            if i < SENDER_NUM - 1 {
+                sender_set.extend(partial.iter().copied());
             }
+            while sender_set.len() < set_size {
+                sender_set.push(rng.gen::<F128b>());
+            }
+
+            let (sender, receiver) = UnixStream::pair().unwrap();
+            handles.push(std::thread::spawn(move || {
+                let mut rng = AesRng::new();
+                let reader = BufReader::new(sender.try_clone().unwrap());
+                let writer = BufWriter::new(sender);
+                let mut channel = Channel::new(reader, writer);
+
+                let vole_share_for_s = LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL);
+                let opprf_sender = SepOpprfSenderWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
+                    &mut channel,
+                    &mut rng,
+                    set_size,
+                    vole_share_for_s,
+                )
+                .unwrap();
+
+                opprf_sender
+                    .send_membership(&mut channel, &sender_set, set_size, &mut rng)
+                    .unwrap();
+            }));
+
+            let reader = BufReader::new(receiver.try_clone().unwrap());
+            let writer = BufWriter::new(receiver);
+            let mut channel = Channel::new(reader, writer);
+
+            let vole_share_for_r = LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL);
+            receivers.push(
+                SepOpprfReceiverWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
+                    &mut channel,
+                    &mut rng,
+                    set_size,
+                    vole_share_for_r,
+                )
+                .unwrap(),
+            );
+            channels.push(channel);
         }
-    }
 
-    #[test]
-    fn test_sep_opprf_vandelmonde_small() {
-        test_sep_opprf_base::<VandelmondeSolver<F128b>>(10, 5, true);
+        let res = run_receiver_multi(receivers, &mut channels, &receiver_set, &mut rng).unwrap();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(HashSet::<F128b>::from_iter(res), HashSet::from_iter(common));
     }
 
     #[test]
-    fn test_sep_opprf_paxos_small() {
-        test_sep_opprf_base::<PaxosSolver<F128b>>(10, 5, true);
+    fn test_run_shared_3party() {
+        let receiver_set = (0..20).map(usize2F128b).collect::<Vec<_>>();
+        let sender_set = (10..40).map(usize2F128b).collect::<Vec<_>>();
+        let query_num = shared_3party_query_num(receiver_set.len(), sender_set.len());
+        let expected = (0..20).map(|i| i >= 10).collect::<Vec<_>>();
+
+        let (rs, sr) = UnixStream::pair().unwrap();
+        let (ro, or) = UnixStream::pair().unwrap();
+        let (so, os) = UnixStream::pair().unwrap();
+
+        // PSI sender: the OPPRF sender.
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let mut channel = channel_from(sr).unwrap();
+            let mut output = channel_from(so).unwrap();
+            let vole_share_for_s = LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL);
+            let opprf_sender = SepOpprfSenderWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
+                &mut channel,
+                &mut rng,
+                query_num,
+                vole_share_for_s,
+            )
+            .unwrap();
+            run_shared_3party_sender(
+                opprf_sender,
+                &mut channel,
+                &mut output,
+                &sender_set,
+                query_num,
+                &mut rng,
+            )
+            .unwrap();
+        });
+
+        // output server.
+        let output_handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let mut from_receiver = channel_from(or).unwrap();
+            let mut from_sender = channel_from(os).unwrap();
+            run_shared_3party_output::<F128b, _, _, _>(
+                &mut from_receiver,
+                &mut from_sender,
+                &mut rng,
+            )
+            .unwrap()
+        });
+
+        // receiver: the OPPRF receiver.
+        let mut rng = AesRng::new();
+        let mut channel = channel_from(rs).unwrap();
+        let mut output = channel_from(ro).unwrap();
+        let vole_share_for_r = LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL);
+        let opprf_receiver = SepOpprfReceiverWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
+            &mut channel,
+            &mut rng,
+            query_num,
+            vole_share_for_r,
+        )
+        .unwrap();
+        run_shared_3party_receiver(
+            opprf_receiver,
+            &mut channel,
+            &mut output,
+            &receiver_set,
+            &mut rng,
+        )
+        .unwrap();
+
+        handle.join().unwrap();
+        let (receiver_share, sender_share) = output_handle.join().unwrap();
+        assert_eq!(
+            reconstruct_shared_3party(&receiver_share, &sender_share).unwrap(),
+            expected
+        );
+
+        // each share alone is uniformly random: no element is zero or one or repeated, and about half of the bits are set.
+        for share in [&receiver_share, &sender_share] {
+            assert_eq!(share.len(), expected.len());
+            assert!(share
+                .iter()
+                .all(|&x| x != F128b::zero() && x != F128b::one()));
+            assert_eq!(
+                HashSet::<F128b>::from_iter(share.iter().copied()).len(),
+                share.len()
+            );
+            let ones = share
+                .iter()
+                .flat_map(|x| x.to_bytes())
+                .map(u8::count_ones)
+                .sum::<u32>();
+            let bits = 128 * share.len() as u32;
+            assert!(
+                ones.abs_diff(bits / 2) < bits / 10,
+                "{} of {} bits are set.",
+                ones,
+                bits
+            );
+        }
     }
 
     #[test]
-    fn test_sep_opprf_paxos_middle() {
-        test_sep_opprf_base::<PaxosSolver<F128b>>(100, 50, false);
+    fn test_reconstruct_shared_3party() {
+        let mut rng = AesRng::new();
+        let a = (0..4).map(|_| rng.gen::<F128b>()).collect::<Vec<_>>();
+        let b = a
+            .iter()
+            .zip([true, false, false, true])
+            .map(|(&a, m)| (if m { F128b::one() } else { F128b::zero() }) - a)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            reconstruct_shared_3party(&a, &b).unwrap(),
+            vec![true, false, false, true]
+        );
+        assert!(reconstruct_shared_3party(&a, &a[..3]).is_err());
+        // random shares sum to neither zero nor one.
+        let c = (0..4).map(|_| rng.gen::<F128b>()).collect::<Vec<_>>();
+        assert!(reconstruct_shared_3party(&a, &c).is_err());
     }
 
     #[test]
-    fn test_sep_opprf_paxos_large() {
-        test_sep_opprf_base::<PaxosSolver<F128b>>(1 << 12, 1 << 6, false);
+    fn test_run_complement() {
+        let receiver_set = (0..20).map(F128b::from_u128).collect::<Vec<_>>();
+        let sender_set = (10..40).map(F128b::from_u128).collect::<Vec<_>>();
+        let query_num = sender_set.len();
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+
+            let vole_share_for_s = LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL);
+            let opprf_sender = SepOpprfSenderWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
+                &mut channel,
+                &mut rng,
+                query_num,
+                vole_share_for_s,
+            )
+            .unwrap();
+
+            opprf_sender
+                .send_membership(&mut channel, &sender_set, query_num, &mut rng)
+                .unwrap();
+        });
+
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+
+        let vole_share_for_r = LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL);
+        let opprf_receiver = SepOpprfReceiverWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
+            &mut channel,
+            &mut rng,
+            query_num,
+            vole_share_for_r,
+        )
+        .unwrap();
+
+        let res = run_complement(opprf_receiver, &mut channel, &receiver_set, &mut rng).unwrap();
+
+        handle.join().unwrap();
+
+        assert_eq!(res, (0..10).map(F128b::from_u128).collect::<Vec<_>>());
     }
 }
@@ -7,7 +7,7 @@
 //! - Receiver: Getting Random values from PRF corresponding to receiver's query values.
 //!
 //! ```
-//! use preprocessing_mpsi_with_vole::channel_utils::{read_vec_f, write_vec_f};
+//! use preprocessing_mpsi_with_vole::channel_utils::{read_vec_f, write_vec_f_mapped};
 //! use preprocessing_mpsi_with_vole::preprocessed::oprf::{
 //!     SepOprfReceiver, SepOprfReceiverWithVole, SepOprfSender, SepOprfSenderWithVole,
 //! };
@@ -46,6 +46,7 @@
 //!         &mut channel,
 //!         &mut rng,
 //!         queries_for_s.len(),
+//!         b"example",
 //!         vole_share_for_s,
 //!     )?;
 //!
@@ -53,12 +54,7 @@
 //!     let fk = oprf_sender
 //!         .send(&mut channel, queries_for_s.len(), &mut rng)?;
 //!
-//!     let fk_set = queries_for_s
-//!         .iter()
-//!         .map(|&x| fk(x))
-//!         .collect::<Result<Vec<_>>>()?;
-//!
-//!     write_vec_f(&mut channel, &fk_set)?;
+//!     write_vec_f_mapped(&mut channel, queries_for_s.into_iter(), fk)?;
 //!
 //!     Ok(())
 //! });
@@ -76,6 +72,7 @@
 //!     &mut channel,
 //!     &mut rng,
 //!     queries_for_r.len(),
+//!     b"example",
 //!     vole_share_for_r,
 //! )?;
 //!
@@ -99,15 +96,20 @@
 //! ```
 
 use crate::channel_utils::{read_vec_f, write_vec_f};
-use crate::hash_utils::{hash, hash_f};
-use crate::solver::{Solver, SolverParams};
+use crate::hash_utils::{hash_f, hash_with_tag};
+use crate::preprocessed::state::{
+    read_block, read_f, read_params, read_vec, write_block, write_f, write_params, write_vec,
+};
+use crate::preprocessed::table::field_name;
+use crate::solver::{encode_with_retry, Decoder, Solver, SolverParams};
 use crate::vole::{VoleShareForReceiver, VoleShareForSender};
-use anyhow::{anyhow, bail, Context, Error};
+use anyhow::{bail, Context, Error};
 use ocelot::oprf::ObliviousPrf;
 use rand::{CryptoRng, Rng};
 use scuttlebutt::field::FiniteField as FF;
 use scuttlebutt::AbstractChannel;
 use std::clone::Clone;
+use std::io::{Read, Write};
 use std::marker::PhantomData;
 
 /// Trait indicating that OPRF constraints are satisfied.
@@ -121,10 +123,13 @@ where
     type PrecompSystem;
 
     /// Precomputation for the sender. It runned in the offline phase.
+    ///
+    /// `domain_tag` is mixed into the PRF so that distinct deployments produce independent PRFs. Both sides must supply the same tag.
     fn precomp<C: AbstractChannel, RNG: CryptoRng + Rng>(
         channel: &mut C,
         rng: &mut RNG,
         query_num: usize,
+        domain_tag: &[u8],
         system: Self::PrecompSystem,
     ) -> Result<Self, Error>;
 
@@ -150,10 +155,13 @@ where
     type PrecompSystem;
 
     /// Precomputation for the receiver. It runned in the offline phase.
+    ///
+    /// `domain_tag` is mixed into the PRF so that distinct deployments produce independent PRFs. Both sides must supply the same tag.
     fn precomp<C: AbstractChannel, RNG: CryptoRng + Rng>(
         channel: &mut C,
         rng: &mut RNG,
         query_num: usize,
+        domain_tag: &[u8],
         system: Self::PrecompSystem,
     ) -> Result<Self, Error>;
 
@@ -181,6 +189,8 @@ where
     params: S::Params,
     delta: F,
     vec_b: Vec<F>,
+    domain_tag: Vec<u8>,
+    vole: V,
     // fk: Option<Box<dyn Fn(F) -> Result<F, Error> + Send>>,
     _p: PhantomData<(F, S, V)>,
 }
@@ -209,32 +219,16 @@ where
         channel: &mut C,
         rng: &mut RNG,
         query_num: usize,
-        mut vole_share_for_s: V,
+        domain_tag: &[u8],
+        vole_share_for_s: V,
     ) -> Result<Self, Error> {
-        let params = S::calc_params(query_num);
-        let m = params.code_length();
-
-        let (delta, vec_b) = vole_share_for_s
-            .receive(channel, rng, m)
-            .with_context(|| format!("@{}:{}", file!(), line!()))?;
-
-        if vec_b.len() != m {
-            bail!(
-                "vec_b.len() (={}) != m (={}) @ {}:{}",
-                vec_b.len(),
-                m,
-                file!(),
-                line!()
-            );
-        }
-
-        Ok(Self {
-            params,
-            delta,
-            vec_b,
-            // fk: None,
-            _p: PhantomData,
-        })
+        Self::precomp_with_params(
+            channel,
+            rng,
+            S::calc_params(query_num),
+            domain_tag,
+            vole_share_for_s,
+        )
     }
 
     /// Actual implementation of send protocol. It called in online phase and solver decoding is run.
@@ -268,13 +262,16 @@ where
             .map(|(ad, &b)| delta * ad + b)
             .collect::<Vec<_>>();
 
-        let params = self.params.clone();
+        let decoder = S::prepare_decoder(&k, aux, self.params);
+        let domain_tag = self.domain_tag;
         let fk = move |x| -> Result<F, Error> {
-            let d = S::decode(&k, x, aux, params)
+            let d = decoder
+                .decode(x)
                 .with_context(|| format!("@{}:{}", file!(), line!()))?;
             let f_dash =
                 d - (delta * hash_f(x).with_context(|| format!("@{}:{}", file!(), line!()))?);
-            let res = hash(f_dash, x).with_context(|| format!("@{}:{}", file!(), line!()))?;
+            let res = hash_with_tag(&domain_tag, f_dash, x)
+                .with_context(|| format!("@{}:{}", file!(), line!()))?;
             Ok(res)
         };
 
@@ -304,6 +301,8 @@ where
     params: S::Params,
     vec_a: Vec<F>,
     vec_c: Vec<F>,
+    domain_tag: Vec<u8>,
+    vole: V,
     _p: PhantomData<(F, S, V)>,
 }
 
@@ -331,10 +330,179 @@ where
         channel: &mut C,
         rng: &mut RNG,
         query_num: usize,
-        mut vole_share_for_r: V,
+        domain_tag: &[u8],
+        vole_share_for_r: V,
+    ) -> Result<Self, Error> {
+        Self::precomp_with_params(
+            channel,
+            rng,
+            S::calc_params(query_num),
+            domain_tag,
+            vole_share_for_r,
+        )
+    }
+
+    /// Actual implementation of receive protocol. It called in online phase and solver encoding (e.g. cukoo graph creating by PaXoS solver) is run.
+    fn receive<C, RNG>(
+        self,
+        channel: &mut C,
+        queries: &[Self::Input],
+        rng: &mut RNG,
+    ) -> Result<Vec<(Self::Input, Self::Output)>, Error>
+    where
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+    {
+        let evaluator = self
+            .receive_deferred(channel, queries, rng)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        let res = queries
+            .iter()
+            .map(|&x| Ok((x, evaluator.eval(x)?)))
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        Ok(res)
+    }
+}
+
+impl<F, S, V> SepOprfSenderWithVole<F, S, V>
+where
+    F: FF,
+    S: Solver<F>,
+    V: VoleShareForSender<F>,
+{
+    /// Same as [precomp](SepOprfSender::precomp), but the solver is sized by `params` instead of [Solver::calc_params].
+    /// The receiver must use the same params.
+    pub fn precomp_with_params<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        rng: &mut RNG,
+        params: S::Params,
+        domain_tag: &[u8],
+        mut vole_share_for_s: V,
     ) -> Result<Self, Error> {
+        let m = params.code_length();
+
+        let (delta, vec_b) = vole_share_for_s
+            .receive(channel, rng, m)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        if vec_b.len() != m {
+            bail!(
+                "vec_b.len() (={}) != m (={}) @ {}:{}",
+                vec_b.len(),
+                m,
+                file!(),
+                line!()
+            );
+        }
+
+        Ok(Self {
+            params,
+            delta,
+            vec_b,
+            domain_tag: domain_tag.to_vec(),
+            vole: vole_share_for_s,
+            // fk: None,
+            _p: PhantomData,
+        })
+    }
+
+    /// Describe the field, the solver and the VOLE in use. e.g. `OPRF[field=F128b, solver=Paxos, vole=LPN(medium)]`
+    pub fn describe(&self) -> String {
+        format!("OPRF[{}]", self.describe_components())
+    }
+
+    pub(crate) fn describe_components(&self) -> String {
+        format!(
+            "field={}, solver={}, vole={}",
+            field_name::<F>(),
+            S::NAME,
+            self.vole.describe()
+        )
+    }
+
+    /// Length of the precomputed VOLE correlation, i.e. the code length of the solver it was made for.
+    pub fn vole_length(&self) -> usize {
+        self.vec_b.len()
+    }
+
+    // See [crate::preprocessed::state] for the layout.
+    pub(crate) fn write_state<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        write_params::<F, S, _>(writer, self.params)?;
+        write_f(writer, self.delta)?;
+        write_vec(writer, &self.vec_b)?;
+        write_block(writer, &self.domain_tag)
+    }
+
+    // keep only the prefix of the VOLE correlation needed for `query_num` queries. A prefix of VOLE is still VOLE.
+    pub(crate) fn shrink(self, query_num: usize) -> Result<Self, Error> {
         let params = S::calc_params(query_num);
         let m = params.code_length();
+        if m > self.vec_b.len() {
+            bail!(
+                "m (={}) for {} queries exceeds the precomputed length (={}) @ {}:{}",
+                m,
+                query_num,
+                self.vec_b.len(),
+                file!(),
+                line!()
+            );
+        }
+
+        let mut vec_b = self.vec_b;
+        vec_b.truncate(m);
+
+        Ok(Self {
+            params,
+            vec_b,
+            ..self
+        })
+    }
+
+    pub(crate) fn read_state<R: Read>(reader: &mut R, vole: V) -> Result<Self, Error> {
+        let params = read_params::<F, S, _>(reader)?;
+        let delta = read_f(reader)?;
+        let vec_b = read_vec(reader)?;
+        let domain_tag = read_block(reader)?;
+
+        if vec_b.len() != params.code_length() {
+            bail!(
+                "vec_b.len() (={}) != m (={}) @ {}:{}",
+                vec_b.len(),
+                params.code_length(),
+                file!(),
+                line!()
+            );
+        }
+
+        Ok(Self {
+            params,
+            delta,
+            vec_b,
+            domain_tag,
+            vole,
+            _p: PhantomData,
+        })
+    }
+}
+
+impl<F, S, V> SepOprfReceiverWithVole<F, S, V>
+where
+    F: FF,
+    S: Solver<F>,
+    V: VoleShareForReceiver<F>,
+{
+    /// Same as [precomp](SepOprfReceiver::precomp), but the solver is sized by `params` instead of [Solver::calc_params].
+    /// The sender must use the same params.
+    pub fn precomp_with_params<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        rng: &mut RNG,
+        params: S::Params,
+        domain_tag: &[u8],
+        mut vole_share_for_r: V,
+    ) -> Result<Self, Error> {
+        let m = params.code_length();
 
         let (vec_a, vec_c) = vole_share_for_r
             .receive(channel, rng, m)
@@ -355,41 +523,35 @@ where
             params,
             vec_a,
             vec_c,
+            domain_tag: domain_tag.to_vec(),
+            vole: vole_share_for_r,
             _p: PhantomData,
         })
     }
 
-    /// Actual implementation of receive protocol. It called in online phase and solver encoding (e.g. cukoo graph creating by PaXoS solver) is run.
-    fn receive<C, RNG>(
+    /// First half of [receive](SepOprfReceiver::receive): encode `queries` and send them to the sender.
+    ///
+    /// The OPRF values of `queries` can be evaluated later by the returned [OprfEvaluator], e.g. in batches.
+    pub fn receive_deferred<C, RNG>(
         self,
         channel: &mut C,
-        queries: &[Self::Input],
+        queries: &[F],
         rng: &mut RNG,
-    ) -> Result<Vec<(Self::Input, Self::Output)>, Error>
+    ) -> Result<OprfEvaluator<F, S>, Error>
     where
         C: AbstractChannel,
         RNG: CryptoRng + Rng,
     {
         let points = queries
             .iter()
-            .map(|input| {
-                let x = input.clone();
+            .map(|&x| {
                 let y = hash_f(x).with_context(|| format!("@{}:{}", file!(), line!()))?;
                 Ok((x, y))
             })
             .collect::<Result<Vec<_>, Error>>()?;
 
-        let mut aux = S::gen_aux(rng).with_context(|| format!("@{}:{}", file!(), line!()))?;
-        let mut p = Err(anyhow!("dummy!"));
-        for _ in 0..2 {
-            p = S::encode(rng, &points, aux, self.params)
-                .with_context(|| format!("@{}:{}", file!(), line!()));
-            if p.is_ok() {
-                break;
-            }
-            aux = S::gen_aux(rng).with_context(|| format!("@{}:{}", file!(), line!()))?;
-        }
-        let p = p?;
+        let (p, aux) = encode_with_retry::<F, S, _>(rng, &points, self.params, 2)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
 
         S::aux_send(channel, rng, aux).with_context(|| format!("@{}:{}", file!(), line!()))?;
 
@@ -411,15 +573,396 @@ where
 
         write_vec_f(channel, &p_plus_a).with_context(|| format!("@{}:{}", file!(), line!()))?;
 
-        let res = queries
-            .into_iter()
-            .map(|&x| {
-                let d = S::decode(&self.vec_c, x, aux, self.params)
-                    .with_context(|| format!("@{}:{}", file!(), line!()))?;
-                let y = hash(d, x).with_context(|| format!("@{}:{}", file!(), line!()))?;
-                Ok((x, y))
+        Ok(OprfEvaluator {
+            decoder: S::prepare_decoder(&self.vec_c, aux, self.params),
+            domain_tag: self.domain_tag,
+        })
+    }
+
+    /// Describe the field, the solver and the VOLE in use. e.g. `OPRF[field=F128b, solver=Paxos, vole=LPN(medium)]`
+    pub fn describe(&self) -> String {
+        format!("OPRF[{}]", self.describe_components())
+    }
+
+    pub(crate) fn describe_components(&self) -> String {
+        format!(
+            "field={}, solver={}, vole={}",
+            field_name::<F>(),
+            S::NAME,
+            self.vole.describe()
+        )
+    }
+
+    /// Length of the precomputed VOLE correlation. See [SepOprfSenderWithVole::vole_length].
+    pub fn vole_length(&self) -> usize {
+        self.vec_a.len()
+    }
+
+    // See [crate::preprocessed::state] for the layout.
+    pub(crate) fn write_state<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        write_params::<F, S, _>(writer, self.params)?;
+        write_vec(writer, &self.vec_a)?;
+        write_vec(writer, &self.vec_c)?;
+        write_block(writer, &self.domain_tag)
+    }
+
+    // keep only the prefix of the VOLE correlation needed for `query_num` queries. See [SepOprfSenderWithVole::shrink].
+    pub(crate) fn shrink(self, query_num: usize) -> Result<Self, Error> {
+        let params = S::calc_params(query_num);
+        let m = params.code_length();
+        if m > self.vec_a.len() {
+            bail!(
+                "m (={}) for {} queries exceeds the precomputed length (={}) @ {}:{}",
+                m,
+                query_num,
+                self.vec_a.len(),
+                file!(),
+                line!()
+            );
+        }
+
+        let mut vec_a = self.vec_a;
+        let mut vec_c = self.vec_c;
+        vec_a.truncate(m);
+        vec_c.truncate(m);
+
+        Ok(Self {
+            params,
+            vec_a,
+            vec_c,
+            ..self
+        })
+    }
+
+    pub(crate) fn read_state<R: Read>(reader: &mut R, vole: V) -> Result<Self, Error> {
+        let params = read_params::<F, S, _>(reader)?;
+        let vec_a = read_vec(reader)?;
+        let vec_c = read_vec(reader)?;
+        let domain_tag = read_block(reader)?;
+
+        let m = params.code_length();
+        if vec_a.len() != m || vec_c.len() != m {
+            bail!(
+                "vec_a.len() (={}) != m (={}) or vec_c.len() (={}) != m @ {}:{}",
+                vec_a.len(),
+                m,
+                vec_c.len(),
+                file!(),
+                line!()
+            );
+        }
+
+        Ok(Self {
+            params,
+            vec_a,
+            vec_c,
+            domain_tag,
+            vole,
+            _p: PhantomData,
+        })
+    }
+}
+
+/// OPRF values of the receiver's queries made by [SepOprfReceiverWithVole::receive_deferred].
+pub struct OprfEvaluator<F, S>
+where
+    F: FF,
+    S: Solver<F>,
+{
+    decoder: S::Decoder,
+    domain_tag: Vec<u8>,
+}
+
+impl<F, S> OprfEvaluator<F, S>
+where
+    F: FF,
+    S: Solver<F>,
+{
+    /// Evaluate the OPRF value of `x`. It is meaningful only for `x` which is one of the queries.
+    pub fn eval(&self, x: F) -> Result<F, Error> {
+        let d = self
+            .decoder
+            .decode(x)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+        hash_with_tag(&self.domain_tag, d, x).with_context(|| format!("@{}:{}", file!(), line!()))
+    }
+}
+
+/// Reusable version of [SepOprfSenderWithVole].
+///
+/// In the offline phase, VOLE is shared once for `runs` online phases (the reservoir).
+/// Each online phase draws a fresh part of $`\bm{B}`$ from the reservoir while $`\Delta`$ and parameters are kept.
+pub struct ReusableSepOprfSenderWithVole<F, S, V>
+where
+    F: FF,
+    S: Solver<F>,
+    V: VoleShareForSender<F>,
+{
+    params: S::Params,
+    query_num: usize,
+    delta: F,
+    reservoir_b: Vec<F>,
+    domain_tag: Vec<u8>,
+    vole: V,
+    _p: PhantomData<(F, S, V)>,
+}
+
+impl<F, S, V> ReusableSepOprfSenderWithVole<F, S, V>
+where
+    F: FF,
+    S: Solver<F>,
+    V: VoleShareForSender<F>,
+{
+    /// Precomputation for `runs` online phases. It runned in the offline phase.
+    pub fn precomp<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        rng: &mut RNG,
+        query_num: usize,
+        runs: usize,
+        domain_tag: &[u8],
+        mut vole_share_for_s: V,
+    ) -> Result<Self, Error> {
+        let params = S::calc_params(query_num);
+        let m = params.code_length() * runs;
+
+        let (delta, reservoir_b) = vole_share_for_s
+            .receive(channel, rng, m)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        if reservoir_b.len() != m {
+            bail!(
+                "reservoir_b.len() (={}) != m (={}) @ {}:{}",
+                reservoir_b.len(),
+                m,
+                file!(),
+                line!()
+            );
+        }
+
+        Ok(Self {
+            params,
+            query_num,
+            delta,
+            reservoir_b,
+            domain_tag: domain_tag.to_vec(),
+            vole: vole_share_for_s,
+            _p: PhantomData,
+        })
+    }
+
+    /// The number of online phases which can be run yet.
+    pub fn remaining_runs(&self) -> usize {
+        self.reservoir_b.len() / self.params.code_length()
+    }
+
+    /// Draw a one-shot sender for the next online phase from the reservoir.
+    pub fn draw(&mut self) -> Result<SepOprfSenderWithVole<F, S, V>, Error> {
+        let m = self.params.code_length();
+        if self.reservoir_b.len() < m {
+            bail!("VOLE reservoir is exhausted. @ {}:{}", file!(), line!());
+        }
+
+        let vec_b = self.reservoir_b.drain(..m).collect::<Vec<_>>();
+
+        Ok(SepOprfSenderWithVole {
+            params: self.params,
+            delta: self.delta,
+            vec_b,
+            domain_tag: self.domain_tag.clone(),
+            vole: self.vole,
+            _p: PhantomData,
+        })
+    }
+
+    /// Send protocol using the next part of the reservoir. It runned in the online phase.
+    pub fn send<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        query_num: usize,
+        rng: &mut RNG,
+    ) -> Result<Box<dyn Fn(F) -> Result<F, Error> + Send>, Error> {
+        self.draw()
+            .with_context(|| format!("@{}:{}", file!(), line!()))?
+            .send(channel, query_num, rng)
+    }
+
+    /// Sender side of [receive_windowed](ReusableSepOprfReceiverWithVole::receive_windowed).
+    ///
+    /// One PRF is returned per window, in the order of the windows. The receiver's outputs of the `i`-th window are those of the `i`-th PRF.
+    pub fn send_windowed<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<Vec<Box<dyn Fn(F) -> Result<F, Error> + Send>>, Error> {
+        let windows = channel.read_u64()? as usize;
+        if windows > self.remaining_runs() {
+            bail!(
+                "{} windows are requested, but the reservoir has {} runs. @{}:{}",
+                windows,
+                self.remaining_runs(),
+                file!(),
+                line!()
+            );
+        }
+
+        (0..windows)
+            .map(|_| {
+                self.send(channel, self.query_num, rng)
+                    .with_context(|| format!("@{}:{}", file!(), line!()))
             })
-            .collect::<Result<Vec<_>, Error>>()?;
+            .collect()
+    }
+}
+
+/// Reusable version of [SepOprfReceiverWithVole].
+///
+/// In the offline phase, VOLE is shared once for `runs` online phases (the reservoir).
+/// Each online phase draws a fresh part of $`\bm{A}, \bm{C}`$ from the reservoir while parameters are kept.
+pub struct ReusableSepOprfReceiverWithVole<F, S, V>
+where
+    F: FF,
+    S: Solver<F>,
+    V: VoleShareForReceiver<F>,
+{
+    params: S::Params,
+    query_num: usize,
+    reservoir_a: Vec<F>,
+    reservoir_c: Vec<F>,
+    domain_tag: Vec<u8>,
+    vole: V,
+    _p: PhantomData<(F, S, V)>,
+}
+
+impl<F, S, V> ReusableSepOprfReceiverWithVole<F, S, V>
+where
+    F: FF,
+    S: Solver<F>,
+    V: VoleShareForReceiver<F>,
+{
+    /// Precomputation for `runs` online phases. It runned in the offline phase.
+    pub fn precomp<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        rng: &mut RNG,
+        query_num: usize,
+        runs: usize,
+        domain_tag: &[u8],
+        mut vole_share_for_r: V,
+    ) -> Result<Self, Error> {
+        let params = S::calc_params(query_num);
+        let m = params.code_length() * runs;
+
+        let (reservoir_a, reservoir_c) = vole_share_for_r
+            .receive(channel, rng, m)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        if reservoir_a.len() != m || reservoir_c.len() != m {
+            bail!(
+                "reservoir_a.len() (={}) != m (={}) or reservoir_c.len() (={}) != m @ {}:{}",
+                reservoir_a.len(),
+                m,
+                reservoir_c.len(),
+                file!(),
+                line!()
+            );
+        }
+
+        Ok(Self {
+            params,
+            query_num,
+            reservoir_a,
+            reservoir_c,
+            domain_tag: domain_tag.to_vec(),
+            vole: vole_share_for_r,
+            _p: PhantomData,
+        })
+    }
+
+    /// The number of online phases which can be run yet.
+    pub fn remaining_runs(&self) -> usize {
+        self.reservoir_a.len() / self.params.code_length()
+    }
+
+    /// Draw a one-shot receiver for the next online phase from the reservoir.
+    pub fn draw(&mut self) -> Result<SepOprfReceiverWithVole<F, S, V>, Error> {
+        let m = self.params.code_length();
+        if self.reservoir_a.len() < m {
+            bail!("VOLE reservoir is exhausted. @ {}:{}", file!(), line!());
+        }
+
+        let vec_a = self.reservoir_a.drain(..m).collect::<Vec<_>>();
+        let vec_c = self.reservoir_c.drain(..m).collect::<Vec<_>>();
+
+        Ok(SepOprfReceiverWithVole {
+            params: self.params,
+            vec_a,
+            vec_c,
+            domain_tag: self.domain_tag.clone(),
+            vole: self.vole,
+            _p: PhantomData,
+        })
+    }
+
+    /// Receive protocol using the next part of the reservoir. It runned in the online phase.
+    pub fn receive<C, RNG>(
+        &mut self,
+        channel: &mut C,
+        queries: &[F],
+        rng: &mut RNG,
+    ) -> Result<Vec<(F, F)>, Error>
+    where
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+    {
+        self.draw()
+            .with_context(|| format!("@{}:{}", file!(), line!()))?
+            .receive(channel, queries, rng)
+    }
+
+    /// Receive protocol for more queries than the precomputed `query_num`.
+    ///
+    /// `queries` are split into windows of `query_num` queries, and each window runs one online phase on the next part of the reservoir.
+    /// The results are concatenated in the order of `queries`. The number of windows is sent first, and must not exceed [remaining_runs](Self::remaining_runs).
+    /// The sender calls [send_windowed](ReusableSepOprfSenderWithVole::send_windowed).
+    pub fn receive_windowed<C, RNG>(
+        &mut self,
+        channel: &mut C,
+        queries: &[F],
+        rng: &mut RNG,
+    ) -> Result<Vec<(F, F)>, Error>
+    where
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+    {
+        if self.query_num == 0 {
+            bail!(
+                "the reservoir is made for 0 queries. @{}:{}",
+                file!(),
+                line!()
+            );
+        }
+        let windows = queries.len().div_ceil(self.query_num);
+        if windows > self.remaining_runs() {
+            bail!(
+                "{} queries need {} windows of {}, but the reservoir has {} runs. @{}:{}",
+                queries.len(),
+                windows,
+                self.query_num,
+                self.remaining_runs(),
+                file!(),
+                line!()
+            );
+        }
+
+        channel.write_u64(windows as u64)?;
+        channel.flush()?;
+
+        let mut res = Vec::with_capacity(queries.len());
+        for window in queries.chunks(self.query_num) {
+            res.extend(
+                self.receive(channel, window, rng)
+                    .with_context(|| format!("@{}:{}", file!(), line!()))?,
+            );
+        }
 
         Ok(res)
     }
@@ -439,6 +982,8 @@ where
             params: self.params,
             delta: self.delta,
             vec_b: self.vec_b.clone(),
+            domain_tag: self.domain_tag.clone(),
+            vole: self.vole,
             // fk: None,
             _p: PhantomData,
         }
@@ -459,6 +1004,8 @@ where
             params: self.params,
             vec_a: self.vec_a.clone(),
             vec_c: self.vec_c.clone(),
+            domain_tag: self.domain_tag.clone(),
+            vole: self.vole,
             _p: PhantomData,
         }
     }
@@ -467,6 +1014,8 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::channel_utils::channel_from;
+    use crate::set_utils::{gen_overlapping_sets, FromU128};
     use crate::solver::{PaxosSolver, VandelmondeSolver};
     use crate::vole::{
         LPNVoleReceiver, LPNVoleSender, LPN_EXTEND_MEDIUM, LPN_EXTEND_SMALL, LPN_SETUP_MEDIUM,
@@ -479,38 +1028,12 @@ mod tests {
     use std::io::{BufReader, BufWriter};
     use std::os::unix::net::UnixStream;
 
+    // random sets, not reproducible on purpose.
     fn create_sets<F: FF>(set_size: usize, common_size: usize) -> (Vec<F>, Vec<F>, Vec<F>)
     where
         Standard: Distribution<F>,
     {
-        if set_size < common_size {
-            panic!("set_size (={}) < common_size (={})", set_size, common_size);
-        }
-
-        let mut rng = AesRng::new();
-        let common = (0..common_size).map(|_| rng.gen::<F>()).collect::<Vec<_>>();
-
-        let mut set1 = HashSet::<F>::from_iter(common.clone().into_iter());
-        while set1.len() < set_size {
-            set1.insert(rng.gen::<F>());
-        }
-
-        let mut set2 = HashSet::<F>::from_iter(common.clone().into_iter());
-        while set2.len() < set_size {
-            set2.insert(rng.gen::<F>());
-        }
-
-        let common = set1
-            .iter()
-            .filter_map(|x| if set2.contains(x) { Some(*x) } else { None })
-            .collect::<Vec<_>>();
-
-        let mut set1 = set1.into_iter().collect::<Vec<F>>();
-        set1.shuffle(&mut rng);
-        let mut set2 = set2.into_iter().collect::<Vec<_>>();
-        set2.shuffle(&mut rng);
-
-        (set1, set2, common)
+        gen_overlapping_sets(set_size, common_size, AesRng::new().gen()).unwrap()
     }
 
     fn test_2party_psi_base<S: Solver<F128b>>(set_size: usize, common_size: usize, verbose: bool) {
@@ -553,6 +1076,7 @@ mod tests {
                 &mut channel,
                 &mut rng,
                 sender_set.len(),
+                &[],
                 vole_share_for_s,
             )
             .unwrap();
@@ -592,6 +1116,7 @@ mod tests {
             &mut channel,
             &mut rng,
             receiver_set.len(),
+            &[],
             vole_share_for_r,
         )
         .unwrap();
@@ -659,6 +1184,240 @@ mod tests {
         test_2party_psi_base::<PaxosSolver<F128b>>(1 << 17, 1 << 16, false);
     }
 
+    fn oprf_with_tags(
+        sender_tag: &'static [u8],
+        receiver_tag: &'static [u8],
+        inputs: Vec<F128b>,
+    ) -> (Vec<F128b>, Vec<F128b>) {
+        let inputs_for_s = inputs.clone();
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+
+            let vole_share_for_s = LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL);
+            let oprf_sender = SepOprfSenderWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
+                &mut channel,
+                &mut rng,
+                inputs_for_s.len(),
+                sender_tag,
+                vole_share_for_s,
+            )
+            .unwrap();
+
+            let fk = oprf_sender
+                .send(&mut channel, inputs_for_s.len(), &mut rng)
+                .unwrap();
+            inputs_for_s
+                .iter()
+                .map(|&x| fk(x).unwrap())
+                .collect::<Vec<_>>()
+        });
+
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+
+        let vole_share_for_r = LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL);
+        let oprf_receiver = SepOprfReceiverWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
+            &mut channel,
+            &mut rng,
+            inputs.len(),
+            receiver_tag,
+            vole_share_for_r,
+        )
+        .unwrap();
+
+        let received = oprf_receiver
+            .receive(&mut channel, &inputs, &mut rng)
+            .unwrap()
+            .into_iter()
+            .map(|(_, y)| y)
+            .collect::<Vec<_>>();
+
+        (handle.join().unwrap(), received)
+    }
+
+    #[test]
+    fn test_oprf_domain_tag() {
+        let inputs = (0..10).map(F128b::from_u128).collect::<Vec<_>>();
+
+        // matching tags interoperate
+        let (sender_res, receiver_res) = oprf_with_tags(b"deploy-a", b"deploy-a", inputs.clone());
+        assert_eq!(sender_res, receiver_res);
+
+        // different tags yield different outputs for the same input
+        let (sender_res, receiver_res) = oprf_with_tags(b"deploy-a", b"deploy-b", inputs.clone());
+        for (s, r) in sender_res.iter().zip(receiver_res.iter()) {
+            assert_ne!(s, r);
+        }
+    }
+
+    #[test]
+    fn test_reusable_oprf_windowed() {
+        let query_num = 50;
+        let windows = 3;
+        let (sender_set, receiver_set, intersection) =
+            create_sets::<F128b>(query_num * windows, 20);
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let mut channel = channel_from(sender).unwrap();
+            let mut oprf_sender =
+                ReusableSepOprfSenderWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
+                    &mut channel,
+                    &mut rng,
+                    query_num,
+                    windows,
+                    &[],
+                    LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+                )
+                .unwrap();
+
+            let fks = oprf_sender.send_windowed(&mut channel, &mut rng).unwrap();
+            assert_eq!(fks.len(), windows);
+            assert_eq!(oprf_sender.remaining_runs(), 0);
+
+            // the sender doesn't know which window a query is in, so its set is evaluated by every PRF.
+            for fk in fks.iter() {
+                let fk_set = sender_set
+                    .iter()
+                    .map(|&x| fk(x).unwrap())
+                    .collect::<Vec<_>>();
+                write_vec_f(&mut channel, &fk_set).unwrap();
+            }
+            channel.flush().unwrap();
+        });
+
+        let mut rng = AesRng::new();
+        let mut channel = channel_from(receiver).unwrap();
+        let mut oprf_receiver =
+            ReusableSepOprfReceiverWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
+                &mut channel,
+                &mut rng,
+                query_num,
+                windows,
+                &[],
+                LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+            )
+            .unwrap();
+
+        let received = oprf_receiver
+            .receive_windowed(&mut channel, &receiver_set, &mut rng)
+            .unwrap();
+        assert_eq!(received.len(), receiver_set.len());
+        assert_eq!(oprf_receiver.remaining_runs(), 0);
+
+        let mut res = HashSet::new();
+        for (w, window) in received.chunks(query_num).enumerate() {
+            let sender_fk_set: HashSet<F128b> =
+                HashSet::from_iter(read_vec_f::<F128b, _>(&mut channel).unwrap());
+            for (i, &(x, y)) in window.iter().enumerate() {
+                assert_eq!(x, receiver_set[w * query_num + i]);
+                if sender_fk_set.contains(&y) {
+                    res.insert(x);
+                }
+            }
+        }
+        handle.join().unwrap();
+
+        assert_eq!(res, HashSet::<F128b>::from_iter(intersection));
+
+        // an exhausted reservoir rejects the queries before sending anything.
+        assert!(oprf_receiver
+            .receive_windowed(&mut channel, &receiver_set[..1], &mut rng)
+            .is_err());
+    }
+
+    #[test]
+    fn test_reusable_oprf() {
+        let (sender_set, receiver_set, intersection) = create_sets::<F128b>(10, 5);
+        let runs = 2;
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+
+            let vole_share_for_s = LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL);
+
+            let mut oprf_sender =
+                ReusableSepOprfSenderWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
+                    &mut channel,
+                    &mut rng,
+                    sender_set.len(),
+                    runs,
+                    &[],
+                    vole_share_for_s,
+                )
+                .unwrap();
+
+            for _ in 0..runs {
+                let fk = oprf_sender
+                    .send(&mut channel, sender_set.len(), &mut rng)
+                    .unwrap();
+                let fk_set = sender_set
+                    .iter()
+                    .map(|&x| fk(x).unwrap())
+                    .collect::<Vec<_>>();
+                write_vec_f(&mut channel, &fk_set).unwrap();
+            }
+
+            assert_eq!(oprf_sender.remaining_runs(), 0);
+            assert!(oprf_sender.draw().is_err());
+        });
+
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+
+        let vole_share_for_r = LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL);
+
+        let mut oprf_receiver =
+            ReusableSepOprfReceiverWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
+                &mut channel,
+                &mut rng,
+                receiver_set.len(),
+                runs,
+                &[],
+                vole_share_for_r,
+            )
+            .unwrap();
+
+        let intersection = HashSet::<F128b>::from_iter(intersection.into_iter());
+
+        let mut outputs = Vec::new();
+        for _ in 0..runs {
+            let received = oprf_receiver
+                .receive(&mut channel, &receiver_set, &mut rng)
+                .unwrap();
+            let sender_fk_set: Vec<F128b> = read_vec_f(&mut channel).unwrap();
+
+            let res = received
+                .iter()
+                .filter(|(_, y)| sender_fk_set.contains(y))
+                .map(|(x, _)| *x)
+                .collect::<HashSet<_>>();
+            assert_eq!(res, intersection);
+
+            outputs.push(received);
+        }
+
+        handle.join().unwrap();
+
+        assert_eq!(oprf_receiver.remaining_runs(), 0);
+        // each run draws a fresh part of the reservoir, so the PRF differs.
+        assert_ne!(outputs[0], outputs[1]);
+    }
+
     // If you want to finish below calculation within the expected time (60s), you should consider a more intelligent two-party PSI. there is no problem with PaXoS
     // The filter_map is taking a crazy amount of time.
     /*
@@ -0,0 +1,178 @@
+//! Packing of values from a small output domain (e.g. 8-bit labels) into field elements, to save bandwidth.
+//!
+//! A value of `domain_bits` bits takes a whole field element when sent as it is. [Packing] puts
+//! [capacity](Packing::capacity) values into the low bits of one element instead, and both sides must use the same `domain_bits`.
+//!
+//! **Capacity limits:** one element holds $`\lfloor \min(b_F, 128) / d \rfloor`$ values, where $`b_F`$ is the bit length of the field
+//! and $`d`$ is `domain_bits` (in `1..=64`). Only the low 128 bits are used since elements are made by [FromU128].
+//! e.g. `F128b` holds 16 values of 8 bits, 128 values of 1 bit, or 2 values of 64 bits. Values must be below $`2^d`$.
+//!
+//! Packed elements are not random, so pack only outputs that are public to the peer, or mask them before sending.
+
+use crate::channel_utils::{read_vec_f, write_vec_f};
+use crate::preprocessed::psi::field_bits;
+use crate::set_utils::FromU128;
+use anyhow::{bail, Context, Error};
+use scuttlebutt::field::FiniteField as FF;
+use scuttlebutt::AbstractChannel;
+
+/// Packing of `domain_bits`-bit values into field elements. See the [module document](crate::preprocessed::packing) for the limits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Packing {
+    domain_bits: u32,
+    capacity: usize,
+}
+
+impl Packing {
+    /// Packing of `domain_bits`-bit values into elements of `F`. `domain_bits` must be in `1..=64` and fit in the field.
+    pub fn new<F: FF>(domain_bits: u32) -> Result<Self, Error> {
+        let usable_bits = field_bits::<F>().min(128);
+        if domain_bits == 0 || domain_bits > 64 || domain_bits as usize > usable_bits {
+            bail!(
+                "domain_bits (={}) must be in 1..=64 and at most {} bits of the field. @{}:{}",
+                domain_bits,
+                usable_bits,
+                file!(),
+                line!()
+            );
+        }
+
+        Ok(Self {
+            domain_bits,
+            capacity: usable_bits / domain_bits as usize,
+        })
+    }
+
+    /// Bit length of one value.
+    pub fn domain_bits(&self) -> u32 {
+        self.domain_bits
+    }
+
+    /// Number of values held by one element.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of elements needed for `len` values.
+    pub fn packed_len(&self, len: usize) -> usize {
+        len.div_ceil(self.capacity)
+    }
+
+    /// Pack `values` into elements, [capacity](Packing::capacity) values per element from the lowest bits.
+    pub fn pack<F: FF + FromU128>(&self, values: &[u64]) -> Result<Vec<F>, Error> {
+        let bits = self.domain_bits;
+        if let Some(&v) = values.iter().find(|&&v| bits < 64 && v >> bits != 0) {
+            bail!(
+                "value (={}) doesn't fit in {} bits. @{}:{}",
+                v,
+                bits,
+                file!(),
+                line!()
+            );
+        }
+
+        Ok(values
+            .chunks(self.capacity)
+            .map(|chunk| {
+                let word = chunk.iter().enumerate().fold(0u128, |acc, (i, &v)| {
+                    acc | ((v as u128) << (i as u32 * bits))
+                });
+                F::from_u128(word)
+            })
+            .collect())
+    }
+
+    /// Inverse of [pack](Packing::pack): the first `len` values held by `elements`.
+    pub fn unpack<F: FF>(&self, elements: &[F], len: usize) -> Result<Vec<u64>, Error> {
+        if len > elements.len() * self.capacity {
+            bail!(
+                "{} elements hold at most {} values, but {} are requested. @{}:{}",
+                elements.len(),
+                elements.len() * self.capacity,
+                len,
+                file!(),
+                line!()
+            );
+        }
+
+        let bits = self.domain_bits;
+        let mask = if bits == 64 {
+            u64::MAX
+        } else {
+            (1 << bits) - 1
+        };
+
+        Ok(elements
+            .iter()
+            .flat_map(|x| {
+                let mut low = [0u8; 16];
+                let bytes = x.to_bytes();
+                let n = bytes.len().min(16);
+                low[..n].copy_from_slice(&bytes[..n]);
+                let word = u128::from_le_bytes(low);
+                (0..self.capacity).map(move |i| ((word >> (i as u32 * bits)) as u64) & mask)
+            })
+            .take(len)
+            .collect())
+    }
+
+    /// Send `values` packed: the number of values (`u64`) followed by the packed elements.
+    pub fn send<F: FF + FromU128, C: AbstractChannel>(
+        &self,
+        channel: &mut C,
+        values: &[u64],
+    ) -> Result<(), Error> {
+        let packed = self
+            .pack::<F>(values)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+        channel.write_u64(values.len() as u64)?;
+        write_vec_f(channel, &packed).with_context(|| format!("@{}:{}", file!(), line!()))?;
+        channel.flush()?;
+        Ok(())
+    }
+
+    /// Receive the values sent by [send](Packing::send).
+    pub fn receive<F: FF, C: AbstractChannel>(&self, channel: &mut C) -> Result<Vec<u64>, Error> {
+        let len = channel.read_u64()? as usize;
+        let packed: Vec<F> =
+            read_vec_f(channel).with_context(|| format!("@{}:{}", file!(), line!()))?;
+        self.unpack(&packed, len)
+            .with_context(|| format!("@{}:{}", file!(), line!()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channel_utils::channel_from;
+    use scuttlebutt::field::F128b;
+    use std::os::unix::net::UnixStream;
+
+    #[test]
+    fn test_packing() {
+        let packing = Packing::new::<F128b>(8).unwrap();
+        assert_eq!(packing.capacity(), 16);
+
+        let labels = (0..16).map(|i| i * 15 + 7).collect::<Vec<u64>>();
+        let packed = packing.pack::<F128b>(&labels).unwrap();
+        assert_eq!(packed.len(), 1);
+        assert_eq!(packing.unpack(&packed, 16).unwrap(), labels);
+
+        // a partial last element.
+        let packed = packing.pack::<F128b>(&labels[..5]).unwrap();
+        assert_eq!(packing.unpack(&packed, 5).unwrap(), labels[..5]);
+        assert!(packing.unpack(&packed, 17).is_err());
+
+        assert!(packing.pack::<F128b>(&[256]).is_err());
+        assert!(Packing::new::<F128b>(0).is_err());
+        assert!(Packing::new::<F128b>(65).is_err());
+        assert_eq!(Packing::new::<F128b>(64).unwrap().capacity(), 2);
+
+        let (s, r) = UnixStream::pair().unwrap();
+        let mut sender = channel_from(s).unwrap();
+        let mut receiver = channel_from(r).unwrap();
+        let values = (0..40).collect::<Vec<u64>>();
+        packing.send::<F128b, _>(&mut sender, &values).unwrap();
+        assert_eq!(packing.receive::<F128b, _>(&mut receiver).unwrap(), values);
+    }
+}
@@ -1,17 +1,77 @@
 use crate::channel_utils::ch_arcnize;
+use crate::channel_utils::counting_channel::CountingChannel;
+use crate::channel_utils::throttled_channel::{Throttle, ThrottledChannel};
+use crate::channel_utils::transcript_channel::{Transcript, TranscriptChannel};
 use crate::cli_utils::{
-    self as cli, create_vole_sr, ChannelUnion, MultiThreadOptimization, PrePSIArgs, SolverType,
-    VoleShareForReceiverUnion, VoleShareForSenderUnion,
+    self as cli, create_vole_sr, select_vole_type, ChannelUnion, InputFormat,
+    MultiThreadOptimization, Normalize, OkvsHash, OnDuplicate, OnPartyDrop, PhaseType, PrePSIArgs,
+    PsiCommand, Role, SolverType, VoleShareForReceiverUnion, VoleShareForSenderUnion,
 };
-use crate::preprocessed::psi::{Receiver, Sender};
-use crate::set_utils::create_sets_without_check;
-use crate::solver::{PaxosSolver, VandelmondeSolver};
-use anyhow::{Context, Result};
+use crate::control::{serve as serve_control, Control};
+use crate::error::PsiError;
+use crate::metrics::{append_csv, serve as serve_metrics, Metrics, Phase, RunRecord};
+use crate::preprocessed::psi::cost::estimate_peak_memory_mb;
+use crate::preprocessed::psi::ping::run_ping;
+use crate::preprocessed::psi::warmup::{warmup, WarmupReport};
+use crate::preprocessed::psi::{
+    false_match_probability, field_bits, is_disconnect as is_peer_disconnect, PartyId, Receiver,
+    Sender,
+};
+use crate::preprocessed::state::{read_u64, write_u64};
+use crate::set_utils::{
+    check_duplicates, commit_set, create_sets_without_check, format_intersection, gen_padding,
+    is_gzip_path, load_input_file, sort_canonical, write_intersection_file,
+};
+use crate::solver::paxos::{AesRowHash, Sha256RowHash};
+#[cfg(feature = "okvs-hash")]
+use crate::solver::paxos::{Blake3RowHash, SipRowHash};
+use crate::solver::{HashedPaxosSolver, Solver, VandelmondeSolver};
+use crate::vole::VoleShareForReceiver;
+use anyhow::{anyhow, bail, Context, Result};
+use rand::SeedableRng;
 use scuttlebutt::field::F128b;
-use scuttlebutt::AesRng;
+use scuttlebutt::{AbstractChannel, AesRng, Block};
 use std::collections::HashSet;
-use std::sync::Arc;
-use std::time::Instant;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+// runs `$body` with the type `$h` of the row hash family of `--okvs-hash`, e.g. for `HashedPaxosSolver<F128b, $h>`.
+// The families of the `okvs-hash` feature are rejected by `PrePSIArgs::validate` without it.
+macro_rules! with_row_hash {
+    ( $okvs_hash:expr, $h:ident => $body:expr ) => {
+        match $okvs_hash {
+            OkvsHash::Sha256 => {
+                type $h = Sha256RowHash;
+                $body
+            }
+            OkvsHash::Aes => {
+                type $h = AesRowHash;
+                $body
+            }
+            #[cfg(feature = "okvs-hash")]
+            OkvsHash::Blake3 => {
+                type $h = Blake3RowHash;
+                $body
+            }
+            #[cfg(feature = "okvs-hash")]
+            OkvsHash::Siphash => {
+                type $h = SipRowHash;
+                $body
+            }
+            #[cfg(not(feature = "okvs-hash"))]
+            hash @ (OkvsHash::Blake3 | OkvsHash::Siphash) => {
+                unreachable!(
+                    "--okvs-hash {} is rejected without the okvs-hash feature.",
+                    hash
+                )
+            }
+        }
+    };
+}
 
 fn intersection_prepare(
     rng: &mut AesRng,
@@ -28,112 +88,477 @@ fn intersection_prepare(
     Ok((intersection, sets))
 }
 
+fn validate_inputs(
+    input: &[PathBuf],
+    input_format: InputFormat,
+    normalize: &[Normalize],
+    on_duplicate: OnDuplicate,
+    set_size: usize,
+) -> Result<()> {
+    let mut errors = Vec::new();
+    for path in input.iter() {
+        let report = load_input_file(path, input_format, normalize)?;
+
+        println!(
+            "{}: {} elements ({} duplicates removed)",
+            path.display(),
+            report.elements.len(),
+            report.duplicates
+        );
+
+        match check_duplicates(&report, &path.display().to_string(), on_duplicate) {
+            Ok(Some(warning)) => println!("{}", warning),
+            Ok(None) => {}
+            Err(e) => {
+                println!("{}", e);
+                errors.push(e.to_string());
+            }
+        }
+
+        for (line, e) in report.errors.into_iter() {
+            println!("{}: line {}: {}", path.display(), line, e);
+            errors.push(format!("{}: line {}: {}", path.display(), line, e));
+        }
+
+        if report.elements.len() > set_size {
+            let e = format!(
+                "{}: {} elements exceed set_size (={})",
+                path.display(),
+                report.elements.len(),
+                set_size
+            );
+            println!("{}", e);
+            errors.push(e);
+        }
+    }
+
+    if !errors.is_empty() {
+        bail!("Input validation failed.\n{}", errors.join("\n"));
+    }
+
+    println!("input validation passed.");
+
+    Ok(())
+}
+
+fn input_prepare(
+    rng: &mut AesRng,
+    input: &[PathBuf],
+    input_format: InputFormat,
+    normalize: &[Normalize],
+    on_duplicate: OnDuplicate,
+    num_parties: usize,
+    min_set_size: usize,
+    print_commitments: bool,
+) -> Result<(Vec<F128b>, Vec<Vec<F128b>>, usize)> {
+    if input.len() != num_parties {
+        bail!(
+            "The number of --input (={}) must be equal to num_parties (={}).",
+            input.len(),
+            num_parties
+        );
+    }
+
+    let mut sets = Vec::with_capacity(num_parties);
+    for path in input.iter() {
+        let report = load_input_file(path, input_format, normalize)?;
+        if let Some((line, e)) = report.errors.first() {
+            bail!("{}: line {}: {}", path.display(), line, e);
+        }
+        if let Some(warning) = check_duplicates(&report, &path.display().to_string(), on_duplicate)?
+        {
+            println!("{}", warning);
+        }
+        sets.push(report.elements);
+    }
+
+    // commit to the loaded elements, not to the random padding.
+    if print_commitments {
+        print_set_commitments(input.iter().map(|path| path.display().to_string()), &sets);
+    }
+
+    let intersection = sets[0]
+        .iter()
+        .filter(|x| sets[1..].iter().all(|set| set.contains(x)))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    // pad sets with random elements so that all parties have the same set size.
+    // dummies must not collide with any real element nor with dummies of other parties.
+    let set_size = sets
+        .iter()
+        .map(|set| set.len())
+        .max()
+        .unwrap_or(0)
+        .max(min_set_size);
+    let mut existing: HashSet<F128b> = sets.iter().flatten().cloned().collect();
+    for set in sets.iter_mut() {
+        let padding = gen_padding(&existing, set_size - set.len(), rng);
+        existing.extend(padding.iter().cloned());
+        set.extend(padding);
+    }
+
+    println!("inputs loaded.");
+
+    Ok((intersection, sets, set_size))
+}
+
+fn print_set_commitments(labels: impl Iterator<Item = String>, sets: &[Vec<F128b>]) {
+    for (label, set) in labels.zip(sets.iter()) {
+        let commitment: String = commit_set(set)
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        println!("commitment of {}: {}", label, commitment);
+    }
+}
+
+// the set of party `pid`, which has the `(num_parties - 1 - pid)`-th input. The role of a party is given by its PID,
+// and the input is looked up by the PID too, so that no role is taken from where a set happens to be.
+fn take_set(sets: &mut [Vec<F128b>], pid: PartyId) -> Vec<F128b> {
+    let index = sets.len() - 1 - pid;
+    std::mem::take(&mut sets[index])
+}
+
+// stream of `party_rng` for the random sets and the padding of `--input`. PIDs are the streams of the parties.
+const SETS_RNG_STREAM: u64 = u64::MAX;
+
+// RNG of `stream` derived from the `--deterministic` seed, or from the OS randomness without it.
+fn party_rng(seed: Option<u64>, stream: u64) -> AesRng {
+    match seed {
+        Some(seed) => AesRng::from_seed(Block::from(((seed as u128) << 64) | stream as u128)),
+        None => AesRng::new(),
+    }
+}
+
+// the intersection, and the sets of all parties (the receiver's last).
+type Sets = (Vec<F128b>, Vec<Vec<F128b>>);
+
+// sets prepared before the protocol, or loaded on a separate thread while precomputing (`--overlap-input`).
+enum SetsSource {
+    Ready(Sets),
+    Loading(JoinHandle<Result<Sets>>),
+}
+
+impl SetsSource {
+    // wait for the sets. An error of the loading thread is returned as it is.
+    fn get(self) -> Result<Sets> {
+        match self {
+            SetsSource::Ready(sets) => Ok(sets),
+            SetsSource::Loading(handle) => handle
+                .join()
+                .map_err(|_| anyhow!("The input loading thread panicked."))?
+                .with_context(|| "Failed to load inputs."),
+        }
+    }
+}
+
+// channel ends of `from` to `to` with the transcript of the bytes sent by `from`.
+type Transcripts = Vec<(PartyId, PartyId, Arc<Transcript>)>;
+
+// wrap all channels with TranscriptChannel. transcripts are recorded only if `record` is true, and returned sorted by `(from, to)`.
+// channel of a party before recording and counting: `--throttle-kbps` and `--latency-ms` are applied to it.
+type PartyChannel = ThrottledChannel<ChannelUnion>;
+
+fn record_transcripts(
+    receiver_channels: Vec<(usize, PartyChannel)>,
+    channels: Vec<Vec<(usize, PartyChannel)>>,
+    record: bool,
+) -> (
+    Vec<(usize, TranscriptChannel<PartyChannel>)>,
+    Vec<Vec<(usize, TranscriptChannel<PartyChannel>)>>,
+    Transcripts,
+) {
+    let mut transcripts = Vec::new();
+    let mut wrap = |from: PartyId, chns: Vec<(usize, PartyChannel)>| {
+        chns.into_iter()
+            .map(|(to, c)| {
+                let transcript = record.then(|| Arc::new(Transcript::default()));
+                if let Some(transcript) = &transcript {
+                    transcripts.push((from, to, Arc::clone(transcript)));
+                }
+                (to, TranscriptChannel::new(c, transcript))
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let receiver_channels = wrap(0, receiver_channels);
+    let channels = channels
+        .into_iter()
+        .enumerate()
+        .map(|(i, chns)| wrap(i + 1, chns))
+        .collect();
+    transcripts.sort_by_key(|&(from, to, _)| (from, to));
+
+    (receiver_channels, channels, transcripts)
+}
+
+// transcript file of `--transcript`: for each `(from, to)` in ascending order,
+// `from`, `to` and the length as u64 followed by the bytes sent from `from` to `to`.
+fn save_transcript(path: &Path, transcripts: &Transcripts) -> Result<()> {
+    let mut writer = BufWriter::new(
+        File::create(path).with_context(|| format!("Failed to create {}.", path.display()))?,
+    );
+    for (from, to, transcript) in transcripts.iter() {
+        let bytes = transcript.bytes();
+        write_u64(&mut writer, *from as u64)?;
+        write_u64(&mut writer, *to as u64)?;
+        write_u64(&mut writer, bytes.len() as u64)?;
+        writer.write_all(&bytes)?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+// channels of the receiver (counted for the metrics) and of the senders, and their transcripts.
+type PartyChannels = (
+    Vec<(usize, CountingChannel<TranscriptChannel<PartyChannel>>)>,
+    Vec<Vec<(usize, TranscriptChannel<PartyChannel>)>>,
+    Transcripts,
+);
+
+// remove the parties of PIDs `pids` in the last run from `parties` and their sets from `sets`,
+// and recompute the intersection of the remaining sets.
+fn remove_dropped(parties: &mut Vec<PartyId>, (intersection, sets): &mut Sets, pids: &[PartyId]) {
+    // party `i` has `sets[num_parties - 1 - i]`.
+    let num_parties = parties.len();
+    let mut pid = 0..;
+    parties.retain(|_| !pids.contains(&pid.next().unwrap()));
+    let mut index = 0..;
+    sets.retain(|_| !pids.contains(&(num_parties - 1 - index.next().unwrap())));
+
+    let others = sets[1..]
+        .iter()
+        .map(|set| HashSet::<F128b>::from_iter(set.iter().copied()))
+        .collect::<Vec<_>>();
+    *intersection = sets[0]
+        .iter()
+        .copied()
+        .filter(|x| others.iter().all(|set| set.contains(x)))
+        .collect();
+}
+
+// output of a sender thread of protocol_base: its state in `--phase offline`, or the intersection if it is the result party.
+enum SenderOutput {
+    State(Vec<u8>),
+    Intersection(Option<Vec<F128b>>),
+}
+
+// result of protocol_base. times and bytes are measured on the receiver.
+struct ProtocolOutput {
+    states: Vec<Vec<u8>>,
+    // `None` in the offline phase.
+    intersection: Option<Vec<F128b>>,
+    offline: Duration,
+    online: Option<Duration>,
+    offline_bytes: u64,
+    online_bytes: u64,
+}
+
+// whether `err` is caused by a disconnection of a peer, i.e. the party failed only because another one dropped out.
+// A sender whose set is not given because the receiver failed is also disconnected, and so is one which found dropped peers in the handshake.
+fn is_disconnect(err: &anyhow::Error) -> bool {
+    is_peer_disconnect(err)
+        || err.chain().any(|cause| {
+            cause.is::<mpsc::RecvError>()
+                || matches!(
+                    cause.downcast_ref::<PsiError>(),
+                    Some(PsiError::PartyDropped { .. })
+                )
+        })
+}
+
+// on a failure of the receiver, join all senders to track whether each of them completed.
+// The senders whose own error is not a disconnection are dropped, and reported as [PsiError::PartyDropped].
+fn track_party_drops(
+    err: anyhow::Error,
+    handles: Vec<JoinHandle<Result<SenderOutput>>>,
+) -> anyhow::Error {
+    let mut dropped = Vec::new();
+    for (i, handle) in handles.into_iter().enumerate() {
+        match handle.join().expect("Failed to join a thread.") {
+            Ok(_) => println!("sender {} completed.", i + 1),
+            Err(e) if is_disconnect(&e) => println!("sender {} failed: {:#}", i + 1, e),
+            Err(e) => {
+                println!("sender {} dropped: {:#}", i + 1, e);
+                dropped.push(i + 1);
+            }
+        }
+    }
+
+    if dropped.is_empty() || !is_disconnect(&err) {
+        err
+    } else {
+        err.context(PsiError::PartyDropped { pids: dropped })
+    }
+}
+
+// `states[i]` is the state of party `i` imported instead of the offline phase (`--phase online`).
+// In `--phase offline`, the exported states of all parties (receiver first) are returned instead of running the online phase.
+// The intersection is output by the party `result_party` of `role` (See [Receiver::receive_for]).
 fn protocol_base(
-    intersection: Vec<F128b>,
-    mut sets: Vec<Vec<F128b>>,
-    receiver_channels: Vec<(usize, ChannelUnion)>,
-    channels: Vec<Vec<(usize, ChannelUnion)>>,
+    set_size: usize,
+    sets: SetsSource,
+    receiver_channels: Vec<(usize, CountingChannel<TranscriptChannel<PartyChannel>>)>,
+    channels: Vec<Vec<(usize, TranscriptChannel<PartyChannel>)>>,
     multi_thread: MultiThreadOptimization,
     solver_type: SolverType,
+    okvs_hash: OkvsHash,
     vole_share_for_s: VoleShareForSenderUnion,
     vole_share_for_r: VoleShareForReceiverUnion,
     verbose: bool,
-) -> Result<()> {
-    let r_set = sets.pop().unwrap();
+    compare_bits: usize,
+    verify_encode: bool,
+    metrics: Arc<Metrics>,
+    control: Arc<Control>,
+    phase: PhaseType,
+    mut states: Vec<Option<Vec<u8>>>,
+    seed: Option<u64>,
+    role: Role,
+    result_party: PartyId,
+) -> Result<ProtocolOutput> {
+    let receiver_state = states.first_mut().and_then(|state| state.take());
 
-    if verbose {
-        println!("receiver's set: {:?}", r_set);
-    }
+    // the sets are given to the parties after the offline phase, so that they may still be loading during it.
+    let (set_txs, set_rxs): (Vec<_>, Vec<_>) = channels.iter().map(|_| mpsc::channel()).unzip();
 
+    let control_for_s = Arc::clone(&control);
     let handles = channels
         .into_iter()
+        .zip(set_rxs)
         .enumerate()
-        .map(move |(i, channels)| {
+        .map(move |(i, (channels, set_rx))| {
             // create and fork senders
             let pid = i + 1;
-            let set = sets.pop().unwrap();
+            let state = states.get_mut(pid).and_then(|state| state.take());
             let vole_share_for_s = vole_share_for_s.clone();
             let vole_share_for_r = vole_share_for_r.clone();
+            let control = Arc::clone(&control_for_s);
 
-            if verbose {
-                println!("sender {}'s set: {:?}", pid, set);
-            }
-
-            std::thread::spawn(move || -> Result<()> {
-                let mut rng = AesRng::new();
+            std::thread::spawn(move || -> Result<SenderOutput> {
+                let mut rng = party_rng(seed, pid as u64);
 
                 macro_rules! sender_protocol {
-                    ( $chns:expr, $set:expr, $s:path, $send:ident ) => {{
+                    ( $chns:expr, $set:ident, $arg:expr, $ty:ty, $precomp:ident, $send:ident ) => {{
                         let mut chns = $chns;
 
+                        // pause only at the boundaries of the phases (See [crate::control]).
+                        control.wait_if_paused();
+
                         // offline phase
                         // Sender::<F128b, S, _, _>::precomp(
-                        let sender = $s(
-                            pid,
-                            &mut chns,
-                            &mut rng,
-                            vole_share_for_s,
-                            vole_share_for_r,
-                            set.len(),
-                        )
-                        .with_context(|| format!("Failed to create sender {}.", pid))?;
+                        let sender = match state {
+                            Some(state) => <$ty>::import_state_for(
+                                &mut state.as_slice(),
+                                set_size,
+                                vole_share_for_s,
+                                vole_share_for_r,
+                            )
+                            .with_context(|| format!("Failed to import sender {}.", pid))?,
+                            None => <$ty>::$precomp(
+                                pid,
+                                &mut chns,
+                                &mut rng,
+                                vole_share_for_s,
+                                vole_share_for_r,
+                                set_size,
+                            )
+                            .with_context(|| format!("Failed to create sender {}.", pid))?,
+                        };
+                        let sender = sender
+                            .with_verify_encode(verify_encode)
+                            .with_compare_bits(compare_bits);
+
+                        println!("sender {} prepared. {}", pid, sender.describe());
+
+                        if phase == PhaseType::Offline {
+                            let mut state = Vec::new();
+                            sender
+                                .export_state(&mut state)
+                                .with_context(|| format!("Failed to export sender {}.", pid))?;
+                            return Ok(SenderOutput::State(state));
+                        }
 
-                        println!("sender {} prepared.", pid);
+                        let $set = set_rx
+                            .recv()
+                            .with_context(|| format!("The set of sender {} is not given.", pid))?;
+
+                        control.wait_if_paused();
 
                         // online phase
-                        sender
-                            .$send($set, &mut chns, &mut rng)
+                        let res = sender
+                            .$send($arg, &mut chns, &mut rng, result_party)
                             .with_context(|| format!("Failed to run sender {}.", pid))?;
 
                         println!("sender {} finished.", pid);
+                        res
                     }};
                 }
 
-                match (solver_type, multi_thread) {
+                let res = match (solver_type, multi_thread) {
                     (SolverType::Vandelmonde, MultiThreadOptimization::Off) => {
                         sender_protocol!(
                             channels,
+                            set,
                             &set,
-                            Sender::<F128b, VandelmondeSolver<F128b>, _, _>::precomp,
-                            send
+                            Sender::<F128b, VandelmondeSolver<F128b>, _, _>,
+                            precomp,
+                            send_for
                         )
                     }
                     (SolverType::Paxos, MultiThreadOptimization::Off) => {
-                        sender_protocol!(
+                        with_row_hash!(okvs_hash, H => sender_protocol!(
                             channels,
+                            set,
                             &set,
-                            Sender::<F128b, PaxosSolver<F128b>, _, _>::precomp,
-                            send
-                        )
+                            Sender::<F128b, HashedPaxosSolver<F128b, H>, _, _>,
+                            precomp,
+                            send_for
+                        ))
                     }
                     (SolverType::Vandelmonde, MultiThreadOptimization::On) => {
                         sender_protocol!(
                             ch_arcnize(channels),
+                            set,
                             Arc::new(set),
-                            Sender::<F128b, VandelmondeSolver<F128b>, _, _>::precomp_mt,
-                            send_mt
+                            Sender::<F128b, VandelmondeSolver<F128b>, _, _>,
+                            precomp_mt,
+                            send_mt_for
                         )
                     }
                     (SolverType::Paxos, MultiThreadOptimization::On) => {
-                        sender_protocol!(
+                        with_row_hash!(okvs_hash, H => sender_protocol!(
                             ch_arcnize(channels),
+                            set,
                             Arc::new(set),
-                            Sender::<F128b, PaxosSolver<F128b>, _, _>::precomp_mt,
-                            send_mt
-                        )
+                            Sender::<F128b, HashedPaxosSolver<F128b, H>, _, _>,
+                            precomp_mt,
+                            send_mt_for
+                        ))
                     }
-                }
+                };
 
-                Ok(())
+                Ok(SenderOutput::Intersection(res))
             })
         })
         .collect::<Vec<_>>();
 
-    let mut rng = AesRng::new();
+    let mut rng = party_rng(seed, 0);
+    let mut exported_state = None;
+    let counters = metrics.counters();
+    // bytes counted before this run, e.g. by a run which a party dropped out of.
+    let base_bytes = counters.sent() + counters.received();
+    let mut offline = Duration::ZERO;
+    let mut online = None;
+    let mut offline_bytes = 0;
+    let mut intersection = Vec::new();
 
     macro_rules! receiver_protocol {
-        ( $chns:expr, $set:expr, $r:path, $receive:ident ) => {{
+        ( $chns:expr, $set:ident, $arg:expr, $ty:ty, $precomp:ident, $receive:ident ) => {{
+            control.wait_if_paused();
             println!("offline phase started.");
+            metrics.set_phase(Phase::Offline);
             let start = Instant::now();
 
             let mut chns = $chns;
@@ -141,130 +566,1570 @@ fn protocol_base(
             // create and run receiver
             // offline phase
             // let receiver = Receiver::<F128b, S, _, _>::precomp(
-            let receiver = $r(
-                &mut chns,
-                &mut rng,
-                vole_share_for_s,
-                vole_share_for_r,
-                r_set.len(),
-            )
-            .with_context(|| "Failed to create receiver.")?;
+            let receiver = match receiver_state {
+                Some(state) => <$ty>::import_state_for(
+                    &mut state.as_slice(),
+                    set_size,
+                    vole_share_for_s,
+                    vole_share_for_r,
+                )
+                .with_context(|| "Failed to import receiver.")?,
+                None => <$ty>::$precomp(
+                    &mut chns,
+                    &mut rng,
+                    vole_share_for_s,
+                    vole_share_for_r,
+                    set_size,
+                )
+                .with_context(|| "Failed to create receiver.")?,
+            };
+            let receiver = receiver.with_verify_encode(verify_encode);
 
-            println!("receiver prepared. offline time: {:?}", start.elapsed());
-            println!("online phase started.");
+            offline = start.elapsed();
+            offline_bytes = counters.sent() + counters.received() - base_bytes;
+            println!("receiver prepared. offline time: {:?}", offline);
+            println!("{}", receiver.describe());
 
-            let start = Instant::now();
+            let (loaded, mut sets) = sets.get()?;
+            intersection = loaded;
+            let $set = take_set(&mut sets, 0);
+            if verbose {
+                println!("receiver's set: {:?}", $set);
+            }
+            for (i, tx) in set_txs.into_iter().enumerate() {
+                let set = take_set(&mut sets, i + 1);
+                if verbose {
+                    println!("sender {}'s set: {:?}", i + 1, set);
+                }
+                // a sender which failed has already dropped its end, and its error is returned when joined.
+                let _ = tx.send(set);
+            }
+
+            if phase == PhaseType::Offline {
+                let mut state = Vec::new();
+                receiver
+                    .export_state(&mut state)
+                    .with_context(|| "Failed to export receiver.")?;
+                exported_state = Some(state);
+                None
+            } else {
+                control.wait_if_paused();
+                println!("online phase started.");
+                metrics.set_phase(Phase::Online);
+
+                let start = Instant::now();
 
-            // online phase
-            let res = receiver
-                .$receive($set, &mut chns, &mut rng)
-                .with_context(|| "Failed to run receiver.")?;
+                // online phase
+                let res = receiver
+                    .$receive($arg, &mut chns, &mut rng, compare_bits, result_party)
+                    .with_context(|| "Failed to run receiver.")?;
 
-            println!("receiver finished. online time: {:?}", start.elapsed());
+                online = Some(start.elapsed());
+                println!("receiver finished. online time: {:?}", start.elapsed());
 
-            res
+                Some(res)
+            }
         }};
     }
 
-    let res = match (solver_type, multi_thread) {
-        (SolverType::Vandelmonde, MultiThreadOptimization::Off) => {
-            receiver_protocol!(
-                receiver_channels,
-                &r_set,
-                Receiver::<F128b, VandelmondeSolver<F128b>, _, _>::precomp,
-                receive
-            )
-        }
-        (SolverType::Paxos, MultiThreadOptimization::Off) => {
-            receiver_protocol!(
-                receiver_channels,
-                &r_set,
-                Receiver::<F128b, PaxosSolver<F128b>, _, _>::precomp,
-                receive
-            )
-        }
-        (SolverType::Vandelmonde, MultiThreadOptimization::On) => {
-            receiver_protocol!(
-                ch_arcnize(receiver_channels),
-                Arc::new(r_set),
-                Receiver::<F128b, VandelmondeSolver<F128b>, _, _>::precomp_mt,
-                receive_mt
-            )
+    // the senders are joined even if the receiver fails (See [track_party_drops]).
+    let res = (|| -> Result<Option<Vec<F128b>>> {
+        Ok(match (solver_type, multi_thread) {
+            (SolverType::Vandelmonde, MultiThreadOptimization::Off) => {
+                receiver_protocol!(
+                    receiver_channels,
+                    r_set,
+                    &r_set,
+                    Receiver::<F128b, VandelmondeSolver<F128b>, _, _>,
+                    precomp,
+                    receive_for
+                )
+            }
+            (SolverType::Paxos, MultiThreadOptimization::Off) => {
+                with_row_hash!(okvs_hash, H => receiver_protocol!(
+                    receiver_channels,
+                    r_set,
+                    &r_set,
+                    Receiver::<F128b, HashedPaxosSolver<F128b, H>, _, _>,
+                    precomp,
+                    receive_for
+                ))
+            }
+            (SolverType::Vandelmonde, MultiThreadOptimization::On) => {
+                receiver_protocol!(
+                    ch_arcnize(receiver_channels),
+                    r_set,
+                    Arc::new(r_set),
+                    Receiver::<F128b, VandelmondeSolver<F128b>, _, _>,
+                    precomp_mt,
+                    receive_mt_for
+                )
+            }
+            (SolverType::Paxos, MultiThreadOptimization::On) => {
+                with_row_hash!(okvs_hash, H => receiver_protocol!(
+                    ch_arcnize(receiver_channels),
+                    r_set,
+                    Arc::new(r_set),
+                    Receiver::<F128b, HashedPaxosSolver<F128b, H>, _, _>,
+                    precomp_mt,
+                    receive_mt_for
+                ))
+            }
+        })
+    })();
+    let res = match res {
+        Ok(res) => res,
+        Err(err) => return Err(track_party_drops(err, handles)),
+    };
+
+    let Some(res) = res else {
+        // offline phase only
+        let mut states = Vec::from_iter(exported_state);
+        for handle in handles {
+            let SenderOutput::State(state) = handle.join().expect("Failed to join a thread.")?
+            else {
+                unreachable!("senders export their states in the offline phase.");
+            };
+            states.push(state);
         }
-        (SolverType::Paxos, MultiThreadOptimization::On) => {
-            receiver_protocol!(
-                ch_arcnize(receiver_channels),
-                Arc::new(r_set),
-                Receiver::<F128b, PaxosSolver<F128b>, _, _>::precomp_mt,
-                receive_mt
-            )
+
+        metrics.set_phase(Phase::Finished);
+
+        return Ok(ProtocolOutput {
+            states,
+            intersection: None,
+            offline,
+            online,
+            offline_bytes,
+            online_bytes: 0,
+        });
+    };
+
+    let online_bytes = counters.sent() + counters.received() - base_bytes - offline_bytes;
+
+    let mut sender_outputs = Vec::with_capacity(handles.len());
+    for handle in handles {
+        match handle.join().expect("Failed to join a thread.")? {
+            SenderOutput::Intersection(res) => sender_outputs.push(res),
+            SenderOutput::State(_) => unreachable!("senders export no state in the online phase."),
         }
+    }
+    // only the result party outputs the intersection.
+    let res = match role {
+        Role::Receiver => res,
+        Role::Sender => result_party
+            .checked_sub(1)
+            .and_then(|i| sender_outputs.get_mut(i))
+            .and_then(Option::take),
+    };
+    if let Some(i) = sender_outputs.iter().position(Option::is_some) {
+        bail!(
+            "sender {} output the intersection, but the result party is {}. @{}:{}",
+            i + 1,
+            result_party,
+            file!(),
+            line!()
+        );
+    }
+    let Some(res) = res else {
+        bail!(
+            "the result party {} output no intersection. @{}:{}",
+            result_party,
+            file!(),
+            line!()
+        );
     };
 
-    let res: HashSet<F128b> = HashSet::from_iter(res);
+    let res_set: HashSet<F128b> = HashSet::from_iter(res.iter().copied());
     let intersection: HashSet<F128b> = HashSet::from_iter(intersection);
 
     if verbose {
         println!("intersection: {:?}", intersection);
-        println!("res: {:?}", res);
+        println!("res: {:?}", res_set);
+    }
+
+    // every element of the intersection is output, but below the full width of `compare_bits` an element outside it may
+    // be output too (See [false_match_probability]), so such false matches are only reported.
+    if !intersection.is_subset(&res_set) {
+        bail!(
+            "{} elements of the intersection are not output. @{}:{}",
+            intersection.difference(&res_set).count(),
+            file!(),
+            line!()
+        );
+    }
+    let false_matches = res_set.len() - intersection.len();
+    if false_matches > 0 {
+        println!(
+            "{} false matches are output by comparing {} bits.",
+            false_matches, compare_bits
+        );
     }
 
-    assert_eq!(res, intersection);
+    metrics.set_phase(Phase::Finished);
 
-    for handle in handles {
-        handle.join().expect("Failed to join a thread.")?;
+    Ok(ProtocolOutput {
+        states: Vec::new(),
+        intersection: Some(res),
+        offline,
+        online,
+        offline_bytes,
+        online_bytes,
+    })
+}
+
+// state file of `--phase offline`: num_parties (u64), set_size (u64), then the state of each party
+// (receiver first, see [state](crate::preprocessed::state)) as a u64 length followed by the bytes.
+fn save_states(path: &Path, set_size: usize, states: &[Vec<u8>]) -> Result<()> {
+    let mut writer = BufWriter::new(
+        File::create(path).with_context(|| format!("Failed to create {}.", path.display()))?,
+    );
+    write_u64(&mut writer, states.len() as u64)?;
+    write_u64(&mut writer, set_size as u64)?;
+    for state in states.iter() {
+        write_u64(&mut writer, state.len() as u64)?;
+        writer.write_all(state)?;
     }
+    writer.flush()?;
 
     Ok(())
 }
 
-/// Run the preprocessing mpsi.
-pub fn run(
-    PrePSIArgs {
+// read the file written by `save_states`. returns set_size and the states.
+fn load_states(path: &Path) -> Result<(usize, Vec<Vec<u8>>)> {
+    let mut reader = BufReader::new(
+        File::open(path).with_context(|| format!("Failed to open {}.", path.display()))?,
+    );
+    let num_parties = read_u64(&mut reader)? as usize;
+    let set_size = read_u64(&mut reader)? as usize;
+    let states = (0..num_parties)
+        .map(|_| {
+            let mut state = vec![0u8; read_u64(&mut reader)? as usize];
+            reader.read_exact(&mut state)?;
+            Ok(state)
+        })
+        .collect::<Result<Vec<_>>>()
+        .with_context(|| format!("{} is broken.", path.display()))?;
+
+    Ok((set_size, states))
+}
+
+// run the warmup of all parties on the channels before recording or counting, and return the report of the receiver.
+fn warmup_parties<S: Solver<F128b>, C: AbstractChannel + Send>(
+    receiver_channels: &mut [(PartyId, C)],
+    channels: &mut [Vec<(PartyId, C)>],
+    set_size: usize,
+) -> Result<WarmupReport> {
+    std::thread::scope(|scope| {
+        let handles = channels
+            .iter_mut()
+            .map(|channels| scope.spawn(move || warmup::<F128b, S, _>(channels, set_size)))
+            .collect::<Vec<_>>();
+
+        let report = warmup::<F128b, S, _>(receiver_channels, set_size);
+        for handle in handles {
+            handle
+                .join()
+                .map_err(|_| anyhow!("a sender panicked in the warmup."))??;
+        }
+        report
+    })
+}
+
+/// Where [run_with_sink] delivers the intersection computed by the receiver (or the result party of `--role sender`).
+///
+/// With `--sort-output`, the intersection is sorted by [sort_canonical] before it is delivered to any sink.
+pub enum OutputSink {
+    /// Drop it. The CLI uses this unless `--sort-output` is given, since the intersection is only checked against the plain one.
+    Discard,
+    /// Print it to the standard output after a header line, one rawhex per line (See [format_intersection]).
+    Stdout,
+    /// Write it to a writer (e.g. a file), one rawhex per line.
+    Writer(Box<dyn Write>),
+    /// Write it to the file by [write_intersection_file], compressed by gzip if `compress` is true (`--output`).
+    File { path: PathBuf, compress: bool },
+    /// Call the callback once with the whole intersection.
+    Callback(Box<dyn FnMut(&[F128b])>),
+    /// Return it from [run_with_sink].
+    Vec,
+}
+
+impl OutputSink {
+    /// Sink selected by the CLI flags: [OutputSink::File] with `--output`, [OutputSink::Stdout] with `--sort-output`, and [OutputSink::Discard] otherwise.
+    ///
+    /// The file is compressed with `--output-compress` or if its name ends with `.gz` (See [is_gzip_path]).
+    pub fn from_args(args: &PrePSIArgs) -> Self {
+        if let Some(path) = &args.output {
+            Self::File {
+                path: path.clone(),
+                compress: args.output_compress || is_gzip_path(path),
+            }
+        } else if args.sort_output {
+            Self::Stdout
+        } else {
+            Self::Discard
+        }
+    }
+
+    // deliver `intersection`. returns it only for OutputSink::Vec.
+    fn deliver(self, mut intersection: Vec<F128b>, sort: bool) -> Result<Option<Vec<F128b>>> {
+        if sort {
+            sort_canonical(&mut intersection);
+        }
+
+        match self {
+            Self::Discard => {}
+            Self::Stdout => {
+                println!("intersection{}:", if sort { " (sorted)" } else { "" });
+                print!("{}", format_intersection(intersection, false));
+            }
+            Self::Writer(mut writer) => {
+                writer
+                    .write_all(format_intersection(intersection, false).as_bytes())
+                    .and_then(|_| writer.flush())
+                    .with_context(|| "Failed to write the intersection.")?;
+            }
+            Self::File { path, compress } => {
+                write_intersection_file(&path, intersection, compress)
+                    .with_context(|| "Failed to write the intersection.")?;
+            }
+            Self::Callback(mut callback) => callback(&intersection),
+            Self::Vec => return Ok(Some(intersection)),
+        }
+
+        Ok(None)
+    }
+}
+
+/// Run the CLI with `args`, delivering the intersection to [OutputSink::from_args].
+pub fn run(args: PrePSIArgs) -> Result<()> {
+    let sink = OutputSink::from_args(&args);
+    run_with_sink(args, sink).map(|_| ())
+}
+
+/// Same as [run], but the intersection is delivered to `sink`, so that an embedder can choose the delivery.
+///
+/// Returns the intersection only for [OutputSink::Vec], and nothing when no online phase is run (e.g. `--phase offline`).
+pub fn run_with_sink(args: PrePSIArgs, sink: OutputSink) -> Result<Option<Vec<F128b>>> {
+    run_with_control(args, sink, Arc::new(Control::new()), &cli::create_channels)
+}
+
+// channels of the receiver and of each sender for `(channel_type, num_parties, port)`, as made by [cli::create_channels].
+type CreateChannels =
+    dyn Fn(
+        cli::ChannelType,
+        usize,
+        usize,
+    ) -> Result<(Vec<(usize, ChannelUnion)>, Vec<Vec<(usize, ChannelUnion)>>)>;
+
+// same as run_with_sink, but the parties are paused by `control` (and `--control-socket` serves it), e.g. one paused before the run,
+// and connected by `create_channels`, e.g. with the channels of a party closed as if it dropped out.
+fn run_with_control(
+    args: PrePSIArgs,
+    sink: OutputSink,
+    control: Arc<Control>,
+    create_channels: &CreateChannels,
+) -> Result<Option<Vec<F128b>>> {
+    if let Some(PsiCommand::Ping(ping_args)) = &args.command {
+        let report = run_ping(ping_args).with_context(|| "Ping failed.")?;
+        println!("{}", report);
+        return Ok(None);
+    }
+
+    args.validate()?;
+
+    let PrePSIArgs {
         num_parties,
         set_size,
         common_size,
         vole_type,
         solver_type,
+        okvs_hash,
         channel_type,
         port,
         multi_thread,
         verbose,
-    }: PrePSIArgs,
-) -> Result<()> {
-    let mut rng = AesRng::new();
+        max_memory_mb,
+        max_comm_mb,
+        input,
+        input_format,
+        normalize,
+        on_duplicate,
+        validate_only,
+        compare_bits,
+        sort_output,
+        output: _,
+        output_compress: _,
+        print_commitments,
+        lpn_safety_margin,
+        small_set_threshold,
+        metrics_addr,
+        phase,
+        state,
+        metrics_csv,
+        deterministic,
+        transcript,
+        control_socket,
+        overlap_input,
+        warmup,
+        warmup_only,
+        throttle_kbps,
+        latency_ms,
+        verify_encode,
+        role,
+        result_party,
+        on_party_drop,
+        features: _,
+        command: _,
+    } = args;
+
+    if validate_only {
+        return validate_inputs(&input, input_format, &normalize, on_duplicate, set_size)
+            .map(|_| None);
+    }
+
+    // check memory budget before starting
+    if let Some(budget_mb) = max_memory_mb {
+        let required_mb = match solver_type {
+            SolverType::Vandelmonde => {
+                estimate_peak_memory_mb::<F128b, VandelmondeSolver<F128b>>(num_parties, set_size)
+            }
+            SolverType::Paxos => with_row_hash!(okvs_hash, H => {
+                estimate_peak_memory_mb::<F128b, HashedPaxosSolver<F128b, H>>(num_parties, set_size)
+            }),
+        };
+
+        if required_mb > budget_mb {
+            return Err(PsiError::MemoryBudgetExceeded {
+                required_mb,
+                budget_mb,
+            }
+            .into());
+        }
+    }
+
+    let metrics = Arc::new(Metrics::new());
+    if let Some(addr) = metrics_addr {
+        let addr = serve_metrics(&addr, Arc::clone(&metrics))
+            .with_context(|| "Failed to start the metrics endpoint.")?;
+        println!("metrics served at http://{}/metrics", addr);
+    }
+
+    if let Some(path) = &control_socket {
+        serve_control(path, Arc::clone(&control), Arc::clone(&metrics))
+            .with_context(|| "Failed to start the control socket.")?;
+        println!("control socket listening at {}.", path.display());
+    }
+
+    // load the offline state
+    let (state_set_size, states) = match (phase, &state) {
+        (PhaseType::Online, Some(path)) => {
+            let (state_set_size, states) = load_states(path)?;
+            if states.len() != num_parties {
+                bail!(
+                    "{} is made for {} parties, but --num-parties is {}.",
+                    path.display(),
+                    states.len(),
+                    num_parties
+                );
+            }
+            if input.is_empty() && state_set_size != set_size {
+                bail!(
+                    "{} is made for set size {}, but --set-size is {}.",
+                    path.display(),
+                    state_set_size,
+                    set_size
+                );
+            }
+            println!("offline state loaded from {}.", path.display());
+            (state_set_size, states.into_iter().map(Some).collect())
+        }
+        _ => (0, vec![None; num_parties]),
+    };
+
+    if let Some(seed) = deterministic {
+        println!(
+            "deterministic run from seed {}. Never use it in production.",
+            seed
+        );
+    }
+    let mut rng = party_rng(deterministic, SETS_RNG_STREAM);
 
     // create sets
-    let (intersection, sets) = intersection_prepare(&mut rng, num_parties, set_size, common_size)
-        .with_context(|| "Failed to prepare intersection.")?;
+    let (sets, set_size) = if input.is_empty() {
+        let (intersection, sets) =
+            intersection_prepare(&mut rng, num_parties, set_size, common_size)
+                .with_context(|| "Failed to prepare intersection.")?;
+        if print_commitments {
+            print_set_commitments((0..num_parties).map(|i| format!("set {}", i)), &sets);
+        }
+        println!("sets prepared.");
+        (SetsSource::Ready((intersection, sets)), set_size)
+    } else if overlap_input {
+        // the offline phase is run for --set-size while the inputs are loaded.
+        let handle = std::thread::spawn(move || -> Result<Sets> {
+            let (intersection, sets, loaded_size) = input_prepare(
+                &mut rng,
+                &input,
+                input_format,
+                &normalize,
+                on_duplicate,
+                num_parties,
+                set_size,
+                print_commitments,
+            )?;
+            if loaded_size != set_size {
+                bail!(
+                    "the inputs have up to {} elements, but --overlap-input precomputes for --set-size (={}).",
+                    loaded_size,
+                    set_size
+                );
+            }
+            Ok((intersection, sets))
+        });
+        println!(
+            "loading inputs while precomputing for set size {}.",
+            set_size
+        );
+        (SetsSource::Loading(handle), set_size)
+    } else {
+        let (intersection, sets, set_size) = input_prepare(
+            &mut rng,
+            &input,
+            input_format,
+            &normalize,
+            on_duplicate,
+            num_parties,
+            state_set_size,
+            print_commitments,
+        )
+        .with_context(|| "Failed to load inputs.")?;
+
+        if phase == PhaseType::Online && set_size != state_set_size {
+            bail!(
+                "the inputs have up to {} elements, but the offline state is made for set size {}.",
+                set_size,
+                state_set_size
+            );
+        }
+
+        println!("sets prepared.");
+        (SetsSource::Ready((intersection, sets)), set_size)
+    };
+
+    if role == Role::Sender {
+        println!(
+            "the receiver delivers the intersection to party {}.",
+            result_party
+        );
+    }
+
+    if compare_bits < field_bits::<F128b>() {
+        println!(
+            "compare {} bits. false match probability <= {:e}",
+            compare_bits,
+            false_match_probability(set_size, compare_bits)
+        );
+    }
 
-    println!("sets prepared.");
+    // bytes of --max-comm-mb, checked on the channels of the receiver.
+    let comm_budget = max_comm_mb.map(|mb| (mb * (1u64 << 20) as f64) as u64);
 
-    // create channels
-    let (receiver_channels, channels) = cli::create_channels(channel_type, num_parties, port)
-        .with_context(|| "Failed to create channels.")?;
+    let throttle = Throttle::from_kbps(throttle_kbps, latency_ms);
+    if !throttle.is_unlimited() {
+        println!(
+            "links throttled to {} and {}ms latency per message.",
+            throttle_kbps.map_or("unlimited".to_string(), |kbps| format!("{} kbps", kbps)),
+            latency_ms
+        );
+    }
+    let throttled = |chns: Vec<(usize, ChannelUnion)>| {
+        chns.into_iter()
+            .map(|(i, c)| (i, ThrottledChannel::new(c, throttle)))
+            .collect::<Vec<_>>()
+    };
+
+    // create channels among `num_parties` parties. They are created again for the remaining parties with `--on-party-drop continue`.
+    let connect = |num_parties: usize, warmup: bool| -> Result<PartyChannels> {
+        let (receiver_channels, channels) = create_channels(channel_type, num_parties, port)
+            .with_context(|| "Failed to create channels.")?;
+
+        let mut receiver_channels = throttled(receiver_channels);
+        let mut channels = channels.into_iter().map(throttled).collect::<Vec<_>>();
+
+        if warmup {
+            let report = match solver_type {
+                SolverType::Vandelmonde => warmup_parties::<VandelmondeSolver<F128b>, _>(
+                    &mut receiver_channels,
+                    &mut channels,
+                    set_size,
+                ),
+                SolverType::Paxos => with_row_hash!(okvs_hash, H => {
+                    warmup_parties::<HashedPaxosSolver<F128b, H>, _>(
+                        &mut receiver_channels,
+                        &mut channels,
+                        set_size,
+                    )
+                }),
+            }
+            .with_context(|| "Warmup failed.")?;
+            println!("{}", report);
+        }
+
+        let (receiver_channels, channels, transcripts) =
+            record_transcripts(receiver_channels, channels, transcript.is_some());
+
+        let receiver_channels = receiver_channels
+            .into_iter()
+            .map(|(i, c)| {
+                let c = CountingChannel::new(c, metrics.counters());
+                match comm_budget {
+                    Some(budget) => (i, c.with_budget(budget)),
+                    None => (i, c),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        println!("channels prepared.");
 
-    println!("channels prepared.");
+        Ok((receiver_channels, channels, transcripts))
+    };
+
+    let (receiver_channels, channels, mut transcripts) =
+        connect(num_parties, warmup || warmup_only)?;
+    if warmup_only {
+        return Ok(None);
+    }
 
     // create vole share
+    let selected = select_vole_type(vole_type, set_size, small_set_threshold);
+    if selected != vole_type {
+        println!(
+            "set size {} < --small-set-threshold {}: OT-based VOLE is used.",
+            set_size, small_set_threshold
+        );
+    }
+    let vole_type = selected;
     let (vole_share_for_s, vole_share_for_r) = match solver_type {
-        SolverType::Vandelmonde => create_vole_sr::<VandelmondeSolver<F128b>>(vole_type, set_size),
-        SolverType::Paxos => create_vole_sr::<PaxosSolver<F128b>>(vole_type, set_size),
-    };
+        SolverType::Vandelmonde => {
+            create_vole_sr::<VandelmondeSolver<F128b>>(vole_type, set_size, lpn_safety_margin)
+        }
+        SolverType::Paxos => with_row_hash!(okvs_hash, H => {
+            create_vole_sr::<HashedPaxosSolver<F128b, H>>(vole_type, set_size, lpn_safety_margin)
+        }),
+    }
+    .with_context(|| "Failed to prepare VOLE share.")?;
 
     println!("vole share prepared.");
 
-    protocol_base(
-        intersection,
+    let vole = vole_share_for_r.describe();
+
+    // the sets are kept to run again among the remaining parties.
+    let mut remaining_sets = match (&sets, on_party_drop) {
+        (SetsSource::Ready(sets), OnPartyDrop::Continue) => Some(sets.clone()),
+        _ => None,
+    };
+
+    let mut output = protocol_base(
+        set_size,
         sets,
         receiver_channels,
         channels,
         multi_thread,
         solver_type,
-        vole_share_for_s,
-        vole_share_for_r,
+        okvs_hash,
+        vole_share_for_s.clone(),
+        vole_share_for_r.clone(),
         verbose,
-    )?;
+        compare_bits,
+        verify_encode,
+        Arc::clone(&metrics),
+        Arc::clone(&control),
+        phase,
+        states,
+        deterministic,
+        role,
+        result_party,
+    );
 
-    Ok(())
+    // original PIDs of the parties in the last run, the receiver first.
+    let mut parties = (0..num_parties).collect::<Vec<PartyId>>();
+    while let Some(remaining) = remaining_sets.as_mut() {
+        let pids = match output
+            .as_ref()
+            .err()
+            .and_then(|e| e.downcast_ref::<PsiError>())
+        {
+            Some(PsiError::PartyDropped { pids }) => pids.clone(),
+            _ => break,
+        };
+
+        let dropped = pids.iter().map(|&pid| parties[pid]).collect::<Vec<_>>();
+        remove_dropped(&mut parties, remaining, &pids);
+        if parties.len() < 2 {
+            output = output.with_context(|| "No sender remains to continue.");
+            break;
+        }
+        println!(
+            "parties {:?} dropped out. continue with the remaining parties {:?}.",
+            dropped, parties
+        );
+
+        let (receiver_channels, channels, new_transcripts) = connect(parties.len(), false)?;
+        transcripts = new_transcripts;
+
+        output = protocol_base(
+            set_size,
+            SetsSource::Ready(remaining.clone()),
+            receiver_channels,
+            channels,
+            multi_thread,
+            solver_type,
+            okvs_hash,
+            vole_share_for_s.clone(),
+            vole_share_for_r.clone(),
+            verbose,
+            compare_bits,
+            verify_encode,
+            Arc::clone(&metrics),
+            Arc::clone(&control),
+            phase,
+            vec![None; parties.len()],
+            deterministic,
+            // `--on-party-drop continue` is only with `--role receiver`.
+            Role::Receiver,
+            0,
+        );
+    }
+
+    if let Some(path) = &control_socket {
+        // the socket is left even if the protocol fails.
+        let _ = std::fs::remove_file(path);
+    }
+
+    // the refused I/O error may be wrapped by the protocols, so the counters tell whether the budget is the cause.
+    let output = output.map_err(|err| match comm_budget {
+        Some(budget) if metrics.counters().budget_exceeded() => {
+            err.context(PsiError::CommBudgetExceeded {
+                used: metrics.counters().total(),
+                budget,
+            })
+        }
+        _ => err,
+    });
+    let output = output?;
+
+    if let Some(path) = transcript {
+        save_transcript(&path, &transcripts)?;
+        println!("transcript saved to {}.", path.display());
+    }
+
+    if let (PhaseType::Offline, Some(path)) = (phase, &state) {
+        save_states(path, set_size, &output.states)?;
+        println!("offline state saved to {}.", path.display());
+    }
+
+    if let Some(path) = metrics_csv {
+        let record = RunRecord {
+            num_parties,
+            set_size,
+            solver: match okvs_hash {
+                OkvsHash::Sha256 => solver_type.to_string(),
+                hash => format!("{}-{}", solver_type, hash),
+            },
+            vole,
+            phase: phase.to_string(),
+            offline: output.offline,
+            online: output.online,
+            offline_bytes: output.offline_bytes,
+            online_bytes: output.online_bytes,
+        };
+        append_csv(&path, &record)?;
+        println!("metrics appended to {}.", path.display());
+    }
+
+    match output.intersection {
+        Some(intersection) => sink.deliver(intersection, sort_output),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli_utils::{ChannelType, VoleType};
+    use crate::set_utils::FromU128;
+    use crate::solver::PaxosSolver;
+    use clap::Parser;
+
+    #[test]
+    fn test_run_memory_budget_exceeded() {
+        let args = PrePSIArgs::parse_from([
+            "prep_psi",
+            "-N",
+            "3",
+            "-n",
+            "1048576",
+            "--max-memory-mb",
+            "1",
+        ]);
+
+        let err = run(args).unwrap_err();
+
+        match err.downcast_ref::<PsiError>() {
+            Some(PsiError::MemoryBudgetExceeded {
+                required_mb,
+                budget_mb,
+            }) => {
+                assert_eq!(*budget_mb, 1);
+                assert!(*required_mb > 1);
+            }
+            _ => panic!("unexpected error: {:?}", err),
+        }
+    }
+
+    #[test]
+    fn test_run_comm_budget_exceeded() {
+        let args =
+            PrePSIArgs::parse_from(["prep_psi", "-N", "3", "-n", "1000", "--max-comm-mb", "0.01"]);
+
+        let err = run(args).unwrap_err();
+
+        match err.downcast_ref::<PsiError>() {
+            Some(PsiError::CommBudgetExceeded { used, budget }) => {
+                assert_eq!(*budget, 10485);
+                assert!(*used <= *budget);
+            }
+            _ => panic!("unexpected error: {:#}", err),
+        }
+    }
+
+    #[test]
+    fn test_validate_only_malformed_line() {
+        let path =
+            std::env::temp_dir().join(format!("prep_psi_validate_only_{}.txt", std::process::id()));
+        std::fs::write(&path, "1\n2\n2\nnot a number\n5\n").unwrap();
+
+        let args = PrePSIArgs::parse_from([
+            "prep_psi",
+            "--validate-only",
+            "--input",
+            path.to_str().unwrap(),
+        ]);
+
+        let res = run(args);
+        std::fs::remove_file(&path).unwrap();
+
+        let err = res.unwrap_err();
+        assert!(err.to_string().contains("line 4"), "{}", err);
+    }
+
+    fn validate_err(args: &[&str]) -> String {
+        let args = PrePSIArgs::parse_from(std::iter::once("prep_psi").chain(args.iter().copied()));
+        args.validate().unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_validate_contradictory_flags() {
+        assert!(PrePSIArgs::parse_from(["prep_psi"]).validate().is_ok());
+
+        assert_eq!(
+            validate_err(&["--validate-only"]),
+            "--validate-only requires at least one --input."
+        );
+        assert_eq!(
+            validate_err(&[
+                "--validate-only",
+                "--input",
+                "a.txt",
+                "--metrics-addr",
+                "127.0.0.1:0"
+            ]),
+            "--metrics-addr cannot be used with --validate-only: no protocol is run."
+        );
+        assert_eq!(
+            validate_err(&["--validate-only", "--input", "a.txt", "--sort-output"]),
+            "--sort-output cannot be used with --validate-only: no intersection is computed."
+        );
+        assert_eq!(
+            validate_err(&[
+                "--validate-only",
+                "--input",
+                "a.txt",
+                "--control-socket",
+                "ctl.sock"
+            ]),
+            "--control-socket cannot be used with --validate-only: no protocol is run."
+        );
+        assert_eq!(
+            validate_err(&["-N", "3", "--input", "a.txt", "--input", "b.txt"]),
+            "--input is given 2 times but --num-parties is 3. Give one --input per party."
+        );
+        assert_eq!(
+            validate_err(&["-n", "10", "-m", "11"]),
+            "--common-size (=11) must not exceed --set-size (=10)."
+        );
+        assert_eq!(
+            validate_err(&["--normalize", "lower"]),
+            "--normalize is only applied with --input-format hash, but --input-format is decimal."
+        );
+        assert_eq!(
+            validate_err(&["--compare-bits", "0"]),
+            "--compare-bits (=0) must be in 1..=128."
+        );
+        assert_eq!(
+            validate_err(&["--channel", "tcp", "--port", "65535"]),
+            "--port (=65535) is too large for --channel tcp with --num-parties 3: ports up to port + num_parties are used."
+        );
+        assert_eq!(
+            validate_err(&["-N", "2", "--input", "-", "--input", "-"]),
+            "--input - (the standard input) is given 2 times, but it can be read only once."
+        );
+        assert_eq!(
+            validate_err(&["--print-commitments", "--phase", "offline", "--state", "a.bin"]),
+            "--print-commitments cannot be used with --phase offline: sets are used only in the online phase."
+        );
+        assert_eq!(
+            validate_err(&["-N", "3", "--result-party", "3"]),
+            "--result-party (=3) must be less than --num-parties (=3)."
+        );
+        assert_eq!(
+            validate_err(&["--result-party", "2"]),
+            "--result-party (=2) is a sender, but --role is receiver. Use --role sender."
+        );
+        assert_eq!(
+            validate_err(&[
+                "--result-party",
+                "1",
+                "--phase",
+                "offline",
+                "--state",
+                "a.bin"
+            ]),
+            "--result-party cannot be used with --phase offline: no intersection is computed."
+        );
+        assert_eq!(
+            validate_err(&["--overlap-input"]),
+            "--overlap-input requires --input."
+        );
+        assert_eq!(
+            validate_err(&["--validate-only", "--input", "a.txt", "--warmup-only"]),
+            "--warmup-only cannot be used with --validate-only: no protocol is run."
+        );
+        assert_eq!(
+            validate_err(&["--throttle-kbps", "0"]),
+            "--throttle-kbps must be positive. Omit it for an unlimited link."
+        );
+        assert_eq!(
+            validate_err(&["--verify-encode", "--phase", "offline", "--state", "a.bin"]),
+            "--verify-encode cannot be used with --phase offline: tables are encoded only in the online phase."
+        );
+        assert_eq!(
+            validate_err(&["--phase", "online"]),
+            "--phase online requires --state."
+        );
+        assert_eq!(
+            validate_err(&["--state", "state.bin"]),
+            "--state is only used with --phase offline or --phase online."
+        );
+        assert_eq!(
+            validate_err(&[
+                "--on-party-drop",
+                "continue",
+                "--role",
+                "sender",
+                "--result-party",
+                "1"
+            ]),
+            "--on-party-drop continue requires --role receiver: the receiver decides to continue."
+        );
+        assert_eq!(
+            validate_err(&["--max-comm-mb", "0"]),
+            "--max-comm-mb (=0) must be positive."
+        );
+
+        // rejected by run before any channel is created.
+        let args = PrePSIArgs::parse_from(["prep_psi", "--channel", "tcp", "-n", "1", "-m", "2"]);
+        let err = run(args).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "--common-size (=2) must not exceed --set-size (=1)."
+        );
+    }
+
+    #[test]
+    fn test_run_offline_then_online() {
+        let path = std::env::temp_dir().join(format!("prep_psi_state_{}.bin", std::process::id()));
+        let path_str = path.to_str().unwrap();
+
+        let offline = PrePSIArgs::parse_from([
+            "prep_psi", "-N", "3", "-n", "10", "-m", "5", "--phase", "offline", "--state", path_str,
+        ]);
+        let res_offline = run(offline);
+
+        let online = PrePSIArgs::parse_from([
+            "prep_psi", "-N", "3", "-n", "10", "-m", "5", "--phase", "online", "--state", path_str,
+        ]);
+        let res_online = run(online);
+
+        // a state made for other parameters is rejected.
+        let mismatch = PrePSIArgs::parse_from([
+            "prep_psi", "-N", "3", "-n", "20", "-m", "5", "--phase", "online", "--state", path_str,
+        ]);
+        let res_mismatch = run(mismatch);
+
+        std::fs::remove_file(&path).unwrap();
+
+        res_offline.unwrap();
+        res_online.unwrap();
+        assert!(res_mismatch
+            .unwrap_err()
+            .to_string()
+            .contains("--set-size is 20"));
+    }
+
+    // inputs of three parties whose intersection is {2, 3}.
+    const THREE_INPUTS: [&str; 3] = ["1\n2\n3\n10\n", "2\n3\n4\n11\n", "3\n2\n5\n"];
+
+    // writes `inputs` to temp files named after `name`, runs `f` with their `--input` args and removes the files.
+    fn with_input_files<T>(name: &str, inputs: &[&str], f: impl FnOnce(&[&str]) -> T) -> T {
+        let dir = std::env::temp_dir();
+        let paths = inputs
+            .iter()
+            .enumerate()
+            .map(|(i, input)| {
+                let path = dir.join(format!(
+                    "prep_psi_{}_{}_{}.txt",
+                    name,
+                    std::process::id(),
+                    i
+                ));
+                std::fs::write(&path, input).unwrap();
+                path
+            })
+            .collect::<Vec<_>>();
+        let input_args = paths
+            .iter()
+            .flat_map(|path| ["--input", path.to_str().unwrap()])
+            .collect::<Vec<_>>();
+
+        let res = f(&input_args);
+
+        for path in paths.iter() {
+            std::fs::remove_file(path).unwrap();
+        }
+        res
+    }
+
+    // runs `prep_psi -N 3` with `args` on `inputs` into a Vec.
+    fn run_three_parties(name: &str, inputs: &[&str], args: &[&str]) -> Result<Option<Vec<F128b>>> {
+        with_input_files(name, inputs, |input_args| {
+            let args = ["prep_psi", "-N", "3"]
+                .iter()
+                .chain(args)
+                .chain(input_args)
+                .copied()
+                .collect::<Vec<_>>();
+            run_with_sink(PrePSIArgs::parse_from(args), OutputSink::Vec)
+        })
+    }
+
+    // the intersection of THREE_INPUTS in the canonical order.
+    fn three_inputs_intersection() -> Vec<F128b> {
+        let mut expected = vec![F128b::from_u128(2), F128b::from_u128(3)];
+        sort_canonical(&mut expected);
+        expected
+    }
+
+    #[test]
+    fn test_run_result_party() {
+        // party 2 (the first input) stays a sender, and the receiver (the last input) delivers the intersection to it.
+        // run checks the output of the result party against the plain intersection.
+        let res = run_three_parties(
+            "result_party",
+            &THREE_INPUTS,
+            &["--role", "sender", "--result-party", "2", "--sort-output"],
+        );
+
+        assert_eq!(res.unwrap(), Some(three_inputs_intersection()));
+    }
+
+    #[test]
+    fn test_run_with_sink() {
+        let collected = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let (res_callback, res_vec) = with_input_files("sink", &THREE_INPUTS, |input_args| {
+            let mut args = vec!["prep_psi", "-N", "3", "--sort-output"];
+            args.extend(input_args);
+
+            let collected_for_sink = std::rc::Rc::clone(&collected);
+            let sink = OutputSink::Callback(Box::new(move |res: &[F128b]| {
+                collected_for_sink.borrow_mut().extend_from_slice(res)
+            }));
+            (
+                run_with_sink(PrePSIArgs::parse_from(args.clone()), sink),
+                run_with_sink(PrePSIArgs::parse_from(args), OutputSink::Vec),
+            )
+        });
+
+        let expected = three_inputs_intersection();
+        assert_eq!(res_callback.unwrap(), None);
+        assert_eq!(*collected.borrow(), expected);
+        assert_eq!(res_vec.unwrap(), Some(expected));
+    }
+
+    #[test]
+    fn test_run_narrow_compare_bits() {
+        let receiver_set = (1..=64u128).collect::<Vec<_>>();
+        let lines = receiver_set
+            .iter()
+            .map(|x| format!("{}\n", x))
+            .collect::<String>();
+
+        // with 4 bits, each of the 62 elements of the receiver outside the intersection is a false match with probability 1/16.
+        let res = run_three_parties(
+            "narrow_compare_bits",
+            &[THREE_INPUTS[0], THREE_INPUTS[1], &lines],
+            &["-n", "64", "--compare-bits", "4"],
+        );
+
+        let res = res.unwrap().unwrap();
+        let receiver_set = receiver_set
+            .into_iter()
+            .map(F128b::from_u128)
+            .collect::<HashSet<_>>();
+        assert!(res.contains(&F128b::from_u128(2)));
+        assert!(res.contains(&F128b::from_u128(3)));
+        assert!(res.iter().all(|x| receiver_set.contains(x)));
+    }
+
+    #[test]
+    fn test_run_okvs_hash() {
+        #[allow(unused_mut)]
+        let mut hashes = vec!["sha256", "aes"];
+        #[cfg(feature = "okvs-hash")]
+        hashes.extend(["blake3", "siphash"]);
+
+        for hash in hashes {
+            let res = run_three_parties(
+                "okvs_hash",
+                &THREE_INPUTS,
+                &["--sort-output", "--okvs-hash", hash],
+            );
+            assert_eq!(res.unwrap(), Some(three_inputs_intersection()));
+        }
+
+        assert!(
+            validate_err(&["--solver", "vandelmonde", "--okvs-hash", "aes"])
+                .contains("--okvs-hash aes cannot be used with --solver vandelmonde")
+        );
+        #[cfg(not(feature = "okvs-hash"))]
+        assert!(validate_err(&["--okvs-hash", "blake3"])
+            .contains("--okvs-hash blake3 requires the okvs-hash feature"));
+    }
+
+    #[test]
+    fn test_run_control_socket() {
+        let path =
+            std::env::temp_dir().join(format!("prep_psi_control_run_{}.sock", std::process::id()));
+        let path_for_client = path.clone();
+
+        // paused before the run, so the parties stop at the first boundary until the client resumes them.
+        let control = Arc::new(Control::new());
+        control.pause();
+
+        let client = std::thread::spawn(move || -> std::io::Result<Vec<String>> {
+            use std::io::BufRead;
+
+            // the run can't finish before it is resumed, so the socket is there.
+            let start = Instant::now();
+            let stream = loop {
+                match std::os::unix::net::UnixStream::connect(&path_for_client) {
+                    Ok(stream) => break stream,
+                    Err(_) if start.elapsed() < Duration::from_secs(10) => {
+                        std::thread::sleep(Duration::from_millis(1))
+                    }
+                    Err(e) => return Err(e),
+                }
+            };
+            let mut writer = stream.try_clone()?;
+            let mut lines = BufReader::new(stream).lines();
+            let mut replies = Vec::new();
+            for command in ["status", "status", "resume"] {
+                writeln!(writer, "{}", command)?;
+                replies.push(lines.next().unwrap()?);
+                std::thread::sleep(Duration::from_millis(100));
+            }
+            Ok(replies)
+        });
+
+        let args = PrePSIArgs::parse_from([
+            "prep_psi",
+            "-N",
+            "3",
+            "-n",
+            "20",
+            "-m",
+            "5",
+            "--control-socket",
+            path.to_str().unwrap(),
+        ]);
+        let sink = OutputSink::from_args(&args);
+        let res = run_with_control(args, sink, control, &cli::create_channels);
+        res.unwrap();
+        assert!(!path.exists(), "the socket is removed after the run.");
+
+        // the run is held in the preparing phase while paused, and finishes only after the resume.
+        let replies = client.join().unwrap().unwrap();
+        assert_eq!(
+            replies,
+            vec![
+                "paused phase=preparing".to_string(),
+                "paused phase=preparing".to_string(),
+                "ok running".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_run_warmup_only() {
+        let (mut receiver_channels, mut channels) =
+            cli::create_channels(ChannelType::Unix, 3, 10000).unwrap();
+        let report =
+            warmup_parties::<PaxosSolver<F128b>, _>(&mut receiver_channels, &mut channels, 20)
+                .unwrap();
+        assert_eq!(report.peers, 2);
+        assert_eq!(report.set_size, 20);
+        assert!(
+            report.to_string().starts_with(&format!(
+                "warmup succeeded: 2 peers agree on version {}, field F128b, solver {}, set size 20",
+                crate::version_info().version,
+                PaxosSolver::<F128b>::NAME
+            )),
+            "{}",
+            report
+        );
+
+        run(PrePSIArgs::parse_from([
+            "prep_psi",
+            "-N",
+            "3",
+            "-n",
+            "20",
+            "-m",
+            "5",
+            "--warmup-only",
+        ]))
+        .unwrap();
+    }
+
+    #[test]
+    fn test_run_overlap_input() {
+        let dir = std::env::temp_dir();
+        let (sequential, overlapped, too_small) =
+            with_input_files("overlap", &THREE_INPUTS, |input_args| {
+                let run_with = |overlap: bool, set_size: &str, i: usize| {
+                    let transcript = dir.join(format!(
+                        "prep_psi_overlap_transcript_{}_{}.bin",
+                        std::process::id(),
+                        i
+                    ));
+                    let mut args = vec![
+                        "prep_psi",
+                        "-N",
+                        "3",
+                        "-n",
+                        set_size,
+                        "--deterministic",
+                        "7",
+                        "--transcript",
+                        transcript.to_str().unwrap(),
+                    ];
+                    if overlap {
+                        args.push("--overlap-input");
+                    }
+                    args.extend(input_args);
+                    let res = run(PrePSIArgs::parse_from(args));
+                    let bytes = std::fs::read(&transcript).ok();
+                    let _ = std::fs::remove_file(&transcript);
+                    res.map(|_| bytes.unwrap())
+                };
+
+                // the same sizes and seed: the overlapped run exchanges the same bytes as the sequential one.
+                // an input larger than --set-size fails when the sets are joined.
+                (
+                    run_with(false, "4", 0),
+                    run_with(true, "4", 1),
+                    run_with(true, "3", 2),
+                )
+            });
+
+        assert_eq!(sequential.unwrap(), overlapped.unwrap());
+        assert!(format!("{:#}", too_small.unwrap_err()).contains("--set-size (=3)"));
+    }
+
+    #[test]
+    fn test_run_deterministic_transcript() {
+        let dir = std::env::temp_dir();
+        let run_with = |seed: &str, i: usize| {
+            let path = dir.join(format!(
+                "prep_psi_transcript_{}_{}.bin",
+                std::process::id(),
+                i
+            ));
+            let res = run(PrePSIArgs::parse_from([
+                "prep_psi",
+                "-N",
+                "3",
+                "-n",
+                "20",
+                "-m",
+                "5",
+                "--deterministic",
+                seed,
+                "--transcript",
+                path.to_str().unwrap(),
+            ]));
+            let dump = std::fs::read(&path);
+            let _ = std::fs::remove_file(&path);
+            res.unwrap();
+            dump.unwrap()
+        };
+
+        let first = run_with("42", 0);
+        let second = run_with("42", 1);
+        let other = run_with("43", 2);
+
+        assert!(!first.is_empty());
+        assert_eq!(first, second);
+        assert_ne!(first, other);
+    }
+
+    #[test]
+    fn test_role() {
+        let args = PrePSIArgs::parse_from(["prep_psi", "--role", "sender", "--result-party", "2"]);
+        assert_eq!(args.role, Role::Sender);
+        assert_eq!(args.result_party, 2);
+        args.validate().unwrap();
+
+        let args = PrePSIArgs::parse_from(["prep_psi"]);
+        assert_eq!(args.role, Role::Receiver);
+        assert_eq!(args.result_party, 0);
+        args.validate().unwrap();
+
+        // the result party is not chosen by the role, so a sender must be given.
+        assert_eq!(
+            validate_err(&["--role", "sender"]),
+            "--role sender requires --result-party of a sender (1 to 2): PID 0 is the receiver."
+        );
+
+        // the receiver (PID 0) has the last input, and party 2 has the first one.
+        let inputs = (0..3)
+            .map(|i| vec![F128b::from_u128(i)])
+            .collect::<Vec<_>>();
+        let mut sets = inputs.clone();
+        assert_eq!(take_set(&mut sets, 2), inputs[0]);
+        assert_eq!(take_set(&mut sets, 0), inputs[2]);
+        assert_eq!(take_set(&mut sets, 1), inputs[1]);
+    }
+
+    #[test]
+    fn test_run_on_duplicate() {
+        let dir = std::env::temp_dir();
+        let paths = (0..2)
+            .map(|i| {
+                dir.join(format!(
+                    "prep_psi_on_duplicate_{}_{}.txt",
+                    std::process::id(),
+                    i
+                ))
+            })
+            .collect::<Vec<_>>();
+        std::fs::write(&paths[0], "1\n2\n2\n3\n").unwrap();
+        std::fs::write(&paths[1], "2\n3\n4\n").unwrap();
+
+        let run_with = |policy: &str| {
+            let mut args = vec!["prep_psi", "-N", "2", "--on-duplicate", policy];
+            for path in paths.iter() {
+                args.extend(["--input", path.to_str().unwrap()]);
+            }
+            run(PrePSIArgs::parse_from(args))
+        };
+        let res_dedup = run_with("dedup");
+        let res_warn = run_with("warn");
+        let res_error = run_with("error");
+
+        for path in paths.iter() {
+            std::fs::remove_file(path).unwrap();
+        }
+
+        res_dedup.unwrap();
+        res_warn.unwrap();
+        let err = format!("{:#}", res_error.unwrap_err());
+        assert!(err.contains("1 duplicated elements found"), "{}", err);
+    }
+
+    #[test]
+    fn test_run_small_set_fast_path() {
+        assert_eq!(select_vole_type(VoleType::Lpn, 10, 64), VoleType::Ot);
+        assert_eq!(select_vole_type(VoleType::Lpn, 64, 64), VoleType::Lpn);
+        assert_eq!(select_vole_type(VoleType::Lpn, 10, 0), VoleType::Lpn);
+        assert_eq!(select_vole_type(VoleType::Ot, 100, 64), VoleType::Ot);
+
+        let dir = std::env::temp_dir();
+        let paths = (0..3)
+            .map(|i| {
+                dir.join(format!(
+                    "prep_psi_fast_path_{}_{}.txt",
+                    std::process::id(),
+                    i
+                ))
+            })
+            .collect::<Vec<_>>();
+        std::fs::write(&paths[0], "1\n2\n3\n4\n").unwrap();
+        std::fs::write(&paths[1], "2\n3\n4\n5\n").unwrap();
+        std::fs::write(&paths[2], "3\n4\n5\n6\n").unwrap();
+
+        // run checks the intersection, so both the fast path and the LPN path must output {3, 4}.
+        let run_with = |threshold: &str| {
+            let mut args = vec!["prep_psi", "-N", "3", "--small-set-threshold", threshold];
+            for path in paths.iter() {
+                args.extend(["--input", path.to_str().unwrap()]);
+            }
+            run(PrePSIArgs::parse_from(args))
+        };
+        let res_fast = run_with("64");
+        let res_lpn = run_with("0");
+
+        for path in paths.iter() {
+            std::fs::remove_file(path).unwrap();
+        }
+
+        res_fast.unwrap();
+        res_lpn.unwrap();
+    }
+
+    #[test]
+    fn test_run_metrics_csv() {
+        let path =
+            std::env::temp_dir().join(format!("prep_psi_metrics_{}.csv", std::process::id()));
+        let path_str = path.to_str().unwrap();
+
+        let res = (0..2)
+            .map(|_| {
+                run(PrePSIArgs::parse_from([
+                    "prep_psi",
+                    "-N",
+                    "3",
+                    "-n",
+                    "10",
+                    "-m",
+                    "5",
+                    "--metrics-csv",
+                    path_str,
+                ]))
+            })
+            .collect::<Vec<_>>();
+        let csv = std::fs::read_to_string(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        for res in res {
+            res.unwrap();
+        }
+        let csv = csv.unwrap();
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], crate::metrics::CSV_HEADER);
+        for line in &lines[1..] {
+            let fields = line.split(',').collect::<Vec<_>>();
+            assert_eq!(fields.len(), lines[0].split(',').count());
+            assert_eq!(fields[..5], ["3", "10", "paxos", "OT", "both"]);
+            assert!(fields[7].parse::<u64>().unwrap() > 0);
+            assert!(fields[8].parse::<u64>().unwrap() > 0);
+        }
+    }
+
+    // a channel whose peer has already disconnected.
+    fn closed_channel() -> ChannelUnion {
+        let (stream, _) = std::os::unix::net::UnixStream::pair().unwrap();
+        ChannelUnion::Unix(scuttlebutt::SyncChannel::new(
+            BufReader::new(stream.try_clone().unwrap()),
+            BufWriter::new(stream),
+        ))
+    }
+
+    #[test]
+    fn test_run_on_party_drop() {
+        let dir = std::env::temp_dir();
+        let paths = (0..4)
+            .map(|i| {
+                dir.join(format!(
+                    "prep_psi_on_party_drop_{}_{}.txt",
+                    std::process::id(),
+                    i
+                ))
+            })
+            .collect::<Vec<_>>();
+        // sender 2 has the second input.
+        std::fs::write(&paths[0], "1\n2\n3\n4\n").unwrap();
+        std::fs::write(&paths[1], "1\n2\n").unwrap();
+        std::fs::write(&paths[2], "1\n2\n3\n5\n").unwrap();
+        std::fs::write(&paths[3], "3\n2\n1\n6\n").unwrap();
+
+        let run_with = |policy: &str| {
+            let mut args = vec![
+                "prep_psi",
+                "-N",
+                "4",
+                "--sort-output",
+                "--on-party-drop",
+                policy,
+            ];
+            for path in paths.iter() {
+                args.extend(["--input", path.to_str().unwrap()]);
+            }
+            // sender 2 drops out before the run: its ends of the first channels are closed, and the rerun is connected as usual.
+            let first = std::cell::Cell::new(true);
+            let create_channels = |channel_type: ChannelType, num_parties: usize, port: usize| {
+                cli::create_channels(channel_type, num_parties, port).map(
+                    |(receiver_channels, mut channels)| {
+                        if first.replace(false) {
+                            channels[1] = channels[1]
+                                .iter()
+                                .map(|(peer, _)| (*peer, closed_channel()))
+                                .collect();
+                        }
+                        (receiver_channels, channels)
+                    },
+                )
+            };
+            run_with_control(
+                PrePSIArgs::parse_from(args),
+                OutputSink::Vec,
+                Arc::new(Control::new()),
+                &create_channels,
+            )
+        };
+        let res_abort = run_with("abort");
+        let res_continue = run_with("continue");
+
+        for path in paths.iter() {
+            std::fs::remove_file(path).unwrap();
+        }
+
+        let err = res_abort.err().unwrap();
+        assert_eq!(
+            err.downcast_ref::<PsiError>(),
+            Some(&PsiError::PartyDropped { pids: vec![2] }),
+            "{:#}",
+            err
+        );
+
+        let mut expected = vec![
+            F128b::from_u128(1),
+            F128b::from_u128(2),
+            F128b::from_u128(3),
+        ];
+        sort_canonical(&mut expected);
+        assert_eq!(res_continue.unwrap(), Some(expected));
+    }
 }
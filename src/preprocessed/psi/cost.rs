@@ -0,0 +1,57 @@
+//! Cost estimator for the preprocessing MPSI protocol.
+//!
+//! It is used to check resource budgets before running the protocol.
+//!
+//! # Memory model
+//!
+//! Let $`N`$ be the number of parties, $`n`$ the set size and $`m`$ the code length of the solver for $`n`$.
+//!
+//! - Conditional zero sharing: each pair of parties runs OPPRF in both directions, i.e. $`N(N-1)`$ OPPRF instances.
+//! - Conditional reconstruction: each sender runs OPPRF with the receiver, i.e. $`N-1`$ OPPRF instances.
+//! - One OPPRF instance keeps $`3m`$ field elements generated in the offline phase ($`\bm{B}`$ for the sender, $`\bm{A}`$ and $`\bm{C}`$ for the receiver).
+//! - In the online phase, each instance additionally holds the encoded vector and the temporary values of the solver. We count them as $`3m`$ more elements.
+//!
+//! The estimation is for the case where all parties run in one process (as [run](crate::preprocessed::psi::run) does).
+//! It is a rough upper bound and not an exact value.
+
+use crate::solver::{Solver, SolverParams};
+use generic_array::typenum::Unsigned;
+use scuttlebutt::field::FiniteField as FF;
+
+/// Estimated peak memory of the protocol in bytes.
+pub fn estimate_peak_memory_bytes<F: FF, S: Solver<F>>(
+    num_parties: usize,
+    set_size: usize,
+) -> usize {
+    let field_len = F::ByteReprLen::to_usize();
+    let m = S::calc_params(set_size).code_length();
+
+    let instances = num_parties * num_parties.saturating_sub(1) + num_parties.saturating_sub(1);
+    let per_instance = 6 * m * field_len;
+    let sets = num_parties * set_size * field_len;
+
+    instances.saturating_mul(per_instance).saturating_add(sets)
+}
+
+/// Estimated peak memory of the protocol in MB (rounded up).
+pub fn estimate_peak_memory_mb<F: FF, S: Solver<F>>(num_parties: usize, set_size: usize) -> usize {
+    let bytes = estimate_peak_memory_bytes::<F, S>(num_parties, set_size);
+    (bytes + (1 << 20) - 1) >> 20
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::PaxosSolver;
+    use scuttlebutt::field::F128b;
+
+    #[test]
+    fn test_estimate_is_monotone() {
+        let small = estimate_peak_memory_bytes::<F128b, PaxosSolver<F128b>>(3, 1 << 10);
+        let large = estimate_peak_memory_bytes::<F128b, PaxosSolver<F128b>>(3, 1 << 16);
+        let many = estimate_peak_memory_bytes::<F128b, PaxosSolver<F128b>>(5, 1 << 10);
+
+        assert!(small < large);
+        assert!(small < many);
+    }
+}
@@ -0,0 +1,181 @@
+use super::{PartyId, Receiver, Sender};
+use crate::set_utils::IntoFieldElement;
+use crate::solver::Solver;
+use crate::vole::{VoleShareForReceiver, VoleShareForSender};
+use anyhow::{bail, Context, Error};
+use rand::distributions::{Distribution, Standard};
+use rand::{CryptoRng, Rng};
+use scuttlebutt::channel::AbstractChannel;
+use scuttlebutt::field::FiniteField as FF;
+use std::collections::{HashMap, HashSet};
+
+// map `inputs` into the field. Two inputs mapped to the same element would be indistinguishable in the intersection, so they are rejected.
+fn field_elements<F: FF, T: IntoFieldElement<F>>(inputs: &[T]) -> Result<Vec<F>, Error> {
+    let keys = inputs
+        .iter()
+        .map(|x| x.to_field_element())
+        .collect::<Vec<F>>();
+
+    let mut seen = HashMap::with_capacity(keys.len());
+    for (i, k) in keys.iter().enumerate() {
+        if let Some(j) = seen.insert(*k, i) {
+            bail!(
+                "the inputs #{} and #{} are mapped to the same field element. @{}:{}",
+                j,
+                i,
+                file!(),
+                line!()
+            );
+        }
+    }
+
+    Ok(keys)
+}
+
+impl<F, S, VS, VR> Sender<F, S, VS, VR>
+where
+    F: FF,
+    S: Solver<F>,
+    VS: VoleShareForSender<F>,
+    VR: VoleShareForReceiver<F>,
+    Standard: Distribution<F>,
+{
+    /// Same as [Sender::send], but the inputs are of an application's type mapped into the field by [IntoFieldElement].
+    pub fn send_elements<T, C, RNG>(
+        self,
+        inputs: &[T],
+        channels: &mut [(PartyId, C)],
+        rng: &mut RNG,
+    ) -> Result<(), Error>
+    where
+        T: IntoFieldElement<F>,
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+    {
+        let keys = field_elements(inputs).with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        self.send(&keys, channels, rng)
+            .with_context(|| format!("@{}:{}", file!(), line!()))
+    }
+}
+
+impl<F, S, VS, VR> Receiver<F, S, VS, VR>
+where
+    F: FF,
+    S: Solver<F>,
+    VS: VoleShareForSender<F>,
+    VR: VoleShareForReceiver<F>,
+    Standard: Distribution<F>,
+{
+    /// Same as [Receiver::receive], but the inputs are of an application's type mapped into the field by [IntoFieldElement].
+    ///
+    /// Returns the inputs in the intersection, in input order. Senders must run [Sender::send_elements] with the same mapping.
+    pub fn receive_elements<T, C, RNG>(
+        self,
+        inputs: &[T],
+        channels: &mut [(PartyId, C)],
+        rng: &mut RNG,
+    ) -> Result<Vec<T>, Error>
+    where
+        T: IntoFieldElement<F> + Clone,
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+    {
+        let keys = field_elements(inputs).with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        let intersection = self
+            .receive(&keys, channels, rng)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?
+            .into_iter()
+            .collect::<HashSet<F>>();
+
+        Ok(inputs
+            .iter()
+            .zip(keys)
+            .filter(|(_, k)| intersection.contains(k))
+            .map(|(x, _)| x.clone())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channel_utils::sync_channel::create_unix_channels;
+    use crate::set_utils::FromU128;
+    use crate::solver::PaxosSolver;
+    use crate::vole::{LPNVoleReceiver, LPNVoleSender, LPN_EXTEND_SMALL, LPN_SETUP_SMALL};
+    use scuttlebutt::field::F128b;
+    use scuttlebutt::AesRng;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    struct UserId(u64);
+
+    impl IntoFieldElement<F128b> for UserId {
+        fn to_field_element(&self) -> F128b {
+            F128b::from_u128(self.0 as u128)
+        }
+    }
+
+    #[test]
+    fn test_field_elements_duplicate() {
+        let keys: Vec<F128b> = field_elements(&[UserId(1), UserId(2)]).unwrap();
+        assert_eq!(keys, vec![F128b::from_u128(1), F128b::from_u128(2)]);
+
+        let err = field_elements::<F128b, _>(&[UserId(1), UserId(2), UserId(1)]).unwrap_err();
+        assert!(err.to_string().contains("#0 and #2"), "{}", err);
+    }
+
+    #[test]
+    fn test_user_id_intersection() {
+        let ids = |ids: &[u64]| ids.iter().map(|&x| UserId(x)).collect::<Vec<_>>();
+
+        let receiver_set = ids(&[7, 42, 1000, 13, 99]);
+        let sender_sets = vec![ids(&[42, 5, 99, 13, 8]), ids(&[99, 42, 6, 1000, 13])];
+        let set_size = 5;
+
+        let vole_share_for_s = LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL);
+        let vole_share_for_r = LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL);
+        let (mut receiver_channels, channels) = create_unix_channels(3).unwrap();
+
+        let handles = channels
+            .into_iter()
+            .zip(sender_sets)
+            .enumerate()
+            .map(|(i, (mut channels, set))| {
+                std::thread::spawn(move || {
+                    let mut rng = AesRng::new();
+                    let sender = Sender::<F128b, PaxosSolver<F128b>, _, _>::precomp(
+                        i + 1,
+                        &mut channels,
+                        &mut rng,
+                        vole_share_for_s,
+                        vole_share_for_r,
+                        set_size,
+                    )
+                    .unwrap();
+                    sender.send_elements(&set, &mut channels, &mut rng).unwrap();
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut rng = AesRng::new();
+        let receiver = Receiver::<F128b, PaxosSolver<F128b>, _, _>::precomp(
+            &mut receiver_channels,
+            &mut rng,
+            vole_share_for_s,
+            vole_share_for_r,
+            set_size,
+        )
+        .unwrap();
+        let res = receiver
+            .receive_elements(&receiver_set, &mut receiver_channels, &mut rng)
+            .unwrap();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(res, ids(&[42, 13, 99]));
+    }
+}
@@ -2,10 +2,16 @@ use anyhow::{Context, Result};
 use clap::Parser;
 use preprocessing_mpsi_with_vole::cli_utils::PrePSIArgs;
 use preprocessing_mpsi_with_vole::preprocessed::psi::run;
+use preprocessing_mpsi_with_vole::version_info;
 
 fn main() -> Result<()> {
     let args = PrePSIArgs::parse();
 
+    if args.features {
+        println!("{}", version_info());
+        return Ok(());
+    }
+
     println!("{:?}", args);
 
     run(args).with_context(|| "Failed to run the protocol.")?;
@@ -1,24 +1,137 @@
 //! based on: <https://github.com/GaloisInc/swanky/blob/master/popsicle/src/psi/kmprt.rs>
 
+use crate::channel_utils::{read_vec_f, write_vec_f};
+use crate::error::PsiError;
 use crate::preprocessed::opprf::{
     SepOpprfReceiver, SepOpprfReceiverWithVole, SepOpprfSender, SepOpprfSenderWithVole,
 };
-use crate::solver::Solver;
+use crate::preprocessed::state::{check_query_num, read_header, read_u64, write_header, write_u64};
+use crate::solver::paxos::AesRowHash;
+#[cfg(feature = "okvs-hash")]
+use crate::solver::paxos::{Blake3RowHash, SipRowHash};
+use crate::solver::{HashedPaxosSolver, PaxosSolver, Solver, VandelmondeSolver};
 use crate::vole::{VoleShareForReceiver, VoleShareForSender};
-use anyhow::{bail, Context, Error};
+use anyhow::{anyhow, bail, Context, Error};
 use rand::distributions::{Distribution, Standard};
-use rand::{CryptoRng, Rng};
+use rand::{CryptoRng, Rng, SeedableRng};
 use scuttlebutt::channel::AbstractChannel;
-use scuttlebutt::field::FiniteField as FF;
+use scuttlebutt::field::{F128b, FiniteField as FF};
+use scuttlebutt::{AesRng, Block};
+use sha2::{Digest, Sha256};
 use std::clone::Clone;
+use std::collections::HashMap;
+use std::io::{ErrorKind, Read, Write};
+use std::time::{Duration, Instant};
+use typenum::marker_traits::Unsigned;
 
 mod bin;
+pub mod cost;
+mod elements;
 mod multithread_ver;
-pub use bin::run;
+pub mod ping;
+mod prefix;
+pub mod sweep;
+pub mod warmup;
+pub use bin::{run, run_with_sink, OutputSink};
 
 /// usize is used as a party ID. Receiver's ID is always 0.
 pub type PartyId = usize;
 
+/// Bit length of the canonical byte representation of `F`. It is the default comparison width.
+pub fn field_bits<F: FF>() -> usize {
+    F::ByteReprLen::to_usize() * 8
+}
+
+/// Upper bound of the probability that at least one false match occurs in `comparisons` comparisons of uniformly random values
+/// on `compare_bits` bits. It is $`\min(1, c \cdot 2^{-b})`$ where $`c`$ is `comparisons` and $`b`$ is `compare_bits`.
+///
+/// - The receiver of [Receiver::receive_with_compare_bits] compares the sum of each of its `set_size` elements with zero,
+///   so `comparisons` is `set_size`.
+/// - When each of `receiver_size` outputs is compared with each of `sender_size` truncated outputs (e.g. OPRF outputs sent by
+///   the sender in a two-party PSI), `comparisons` is `sender_size * receiver_size`.
+pub fn false_match_probability(comparisons: usize, compare_bits: usize) -> f64 {
+    (comparisons as f64 * 2f64.powi(-(compare_bits.min(i32::MAX as usize) as i32))).min(1.0)
+}
+
+/// Byte size of the code vector (OKVS) sent for `set_size` points of `F` by the solver of [Solver::ID] `solver_id`,
+/// computed analytically without building any params, e.g. for capacity dashboards.
+///
+/// For [HashedPaxosSolver] of any hash family, the code length is $`(2 + \epsilon) n + (\log n + \lambda)`$ with $`\lambda = 40`$,
+/// and `epsilon` of 0.01 gives the params of [Solver::calc_params]. [VandelmondeSolver] ignores `epsilon`.
+/// It fails for an unknown `solver_id` or an `epsilon` which is negative or not finite.
+pub fn okvs_byte_size<F: FF>(set_size: usize, solver_id: u8, epsilon: f64) -> Result<usize, Error> {
+    if !(epsilon.is_finite() && epsilon >= 0.0) {
+        bail!(
+            "epsilon (={}) must be non-negative and finite. @{}:{}",
+            epsilon,
+            file!(),
+            line!()
+        );
+    }
+
+    let paxos_code_length = || {
+        let l_size = 2 * set_size + (epsilon * set_size as f64) as usize;
+        let logn = set_size.next_power_of_two().trailing_zeros() as usize;
+        l_size + logn + 40
+    };
+
+    let code_length = match solver_id {
+        id if id == PaxosSolver::<F128b>::ID
+            || id == HashedPaxosSolver::<F128b, AesRowHash>::ID =>
+        {
+            paxos_code_length()
+        }
+        #[cfg(feature = "okvs-hash")]
+        id if id == HashedPaxosSolver::<F128b, Blake3RowHash>::ID
+            || id == HashedPaxosSolver::<F128b, SipRowHash>::ID =>
+        {
+            paxos_code_length()
+        }
+        id if id == VandelmondeSolver::<F128b>::ID => set_size,
+        _ => bail!("unknown solver id {}. @{}:{}", solver_id, file!(), line!()),
+    };
+
+    Ok(code_length * F::ByteReprLen::to_usize())
+}
+
+/// Intersection of `sets` computed in plain, with the index of each element in every set, e.g. for downstream joins in tests.
+///
+/// Elements are in the order of `sets[0]`, and `indices[j]` of an element is its position in `sets[j]`.
+/// Nothing is hidden here, so use it only for data which all holders may see. In the protocol, only the receiver learns
+/// its own indices (See [Receiver::receive_with_indices]).
+pub fn intersect_local_with_indices<F: FF>(sets: &[&[F]]) -> Vec<(F, Vec<usize>)> {
+    let Some((first, others)) = sets.split_first() else {
+        return Vec::new();
+    };
+    let others = others
+        .iter()
+        .map(|set| HashMap::<F, usize>::from_iter(set.iter().enumerate().map(|(i, &x)| (x, i))))
+        .collect::<Vec<_>>();
+
+    first
+        .iter()
+        .enumerate()
+        .filter_map(|(i, &x)| {
+            let mut indices = vec![i];
+            for set in others.iter() {
+                indices.push(*set.get(&x)?);
+            }
+            Some((x, indices))
+        })
+        .collect()
+}
+
+/// Check whether the lowest `compare_bits` bits of the canonical byte representation of `s` are zero.
+fn is_zero_in_bits<F: FF>(s: F, compare_bits: usize) -> bool {
+    if compare_bits >= field_bits::<F>() {
+        return s.is_zero();
+    }
+
+    let bytes = s.to_bytes();
+    let (full, rest) = (compare_bits / 8, compare_bits % 8);
+    bytes[..full].iter().all(|&b| b == 0) && (rest == 0 || bytes[full] & ((1 << rest) - 1) == 0)
+}
+
 struct Party<F, S, VS, VR>
 where
     F: FF,
@@ -28,6 +141,8 @@ where
     Standard: Distribution<F>,
 {
     id: PartyId,
+    // set size declared by all the peers in the handshake of precomp.
+    set_size: usize,
     opprf_senders: Vec<(usize, SepOpprfSenderWithVole<F, S, VS>)>,
     opprf_receivers: Vec<(usize, SepOpprfReceiverWithVole<F, S, VR>)>,
 }
@@ -40,7 +155,8 @@ where
 ///
 /// In the optimized version, each party has a separate thread to communicate with each of the other parties.
 ///
-/// On the other hand, in the not optimized version, each party communicates in the same thread with all the other parties.
+/// On the other hand, in the not optimized version, each party communicates in the same thread with all the other parties,
+/// except in the precomputation, which has a thread for each peer since it is dominated by the VOLE extensions with the peers.
 pub struct Sender<F, S, VS, VR>
 where
     F: FF,
@@ -67,15 +183,88 @@ where
         self.id
     }
 
+    /// Describe the field, the solver and the VOLE in use. e.g. `PSI-Sender[field=F128b, solver=Paxos, vole=LPN(medium)]`
+    pub fn describe(&self) -> String {
+        format!(
+            "PSI-Sender[{}]",
+            self.opprf_sender_for_rc.describe_components()
+        )
+    }
+
+    /// Write the offline state in the format of [state](crate::preprocessed::state).
+    ///
+    /// The state contains secret VOLE correlations, so keep it secret and import it only once.
+    pub fn export_state<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        write_header::<F, S, _>(writer, self.party_for_zs.set_size)?;
+        self.party_for_zs.write_state(writer)?;
+        self.opprf_sender_for_rc.write_state(writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Read the state written by [Sender::export_state]. The result can run [Sender::send] without the offline phase.
+    ///
+    /// `vole_share_for_s` and `vole_share_for_r` are not run. They should be the ones used in the offline phase.
+    pub fn import_state<R: Read>(
+        reader: &mut R,
+        vole_share_for_s: VS,
+        vole_share_for_r: VR,
+    ) -> Result<Self, Error> {
+        read_header::<F, S, _>(reader).with_context(|| format!("@{}:{}", file!(), line!()))?;
+        Self::read_body(reader, vole_share_for_s, vole_share_for_r)
+    }
+
+    /// Same as [Sender::import_state], but fails with [PsiError::VoleSizeMismatch] before reading the body
+    /// unless the state is made for `set_size`.
+    pub fn import_state_for<R: Read>(
+        reader: &mut R,
+        set_size: usize,
+        vole_share_for_s: VS,
+        vole_share_for_r: VR,
+    ) -> Result<Self, Error> {
+        let stored =
+            read_header::<F, S, _>(reader).with_context(|| format!("@{}:{}", file!(), line!()))?;
+        check_query_num(stored, set_size)?;
+        Self::read_body(reader, vole_share_for_s, vole_share_for_r)
+    }
+
+    fn read_body<R: Read>(
+        reader: &mut R,
+        vole_share_for_s: VS,
+        vole_share_for_r: VR,
+    ) -> Result<Self, Error> {
+        let party_for_zs = Party::read_state(reader, vole_share_for_s, vole_share_for_r)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+        let opprf_sender_for_rc = SepOpprfSenderWithVole::read_state(reader, vole_share_for_s)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        if party_for_zs.id == 0 {
+            bail!("sender index must not be 0. @{}:{}", file!(), line!());
+        }
+
+        Ok(Self {
+            id: party_for_zs.id,
+            party_for_zs,
+            opprf_sender_for_rc,
+        })
+    }
+
     /// Precomputation for the sender. It runned in the offline phase.
-    pub fn precomp<C: AbstractChannel, RNG: Rng + CryptoRng>(
+    ///
+    /// The precomputation with each peer runs on its own thread, so the VOLE extensions with different peers overlap.
+    pub fn precomp<C: AbstractChannel + Send, RNG: Rng + CryptoRng>(
         me: PartyId,
         channels: &mut [(PartyId, C)],
         rng: &mut RNG,
         vole_share_for_s: VS,
         vole_share_for_r: VR,
         set_size: usize,
-    ) -> Result<Self, Error> {
+    ) -> Result<Self, Error>
+    where
+        S: Send,
+        VS: Send,
+        VR: Send,
+    {
         if me == 0 {
             bail!("sender index must not be 0. @{}:{}", file!(), line!());
         }
@@ -103,6 +292,73 @@ where
         })
     }
 
+    /// Same as [Sender::precomp] for `max_set_size`. The result can be shrunk by [Sender::shrink_to] for any smaller set size.
+    ///
+    /// This is useful for a service which handles requests of various sizes up to a known maximum.
+    pub fn precomp_for_max<C: AbstractChannel + Send, RNG: Rng + CryptoRng>(
+        me: PartyId,
+        channels: &mut [(PartyId, C)],
+        rng: &mut RNG,
+        vole_share_for_s: VS,
+        vole_share_for_r: VR,
+        max_set_size: usize,
+    ) -> Result<Self, Error>
+    where
+        S: Send,
+        VS: Send,
+        VR: Send,
+    {
+        Self::precomp(
+            me,
+            channels,
+            rng,
+            vole_share_for_s,
+            vole_share_for_r,
+            max_set_size,
+        )
+    }
+
+    /// Shrink the precomputation to `set_size` (at most the precomputed size), keeping only the prefix of the VOLE correlations it needs.
+    ///
+    /// The online phase then costs as if precomputed for `set_size`. All parties must shrink to the same `set_size`.
+    pub fn shrink_to(self, set_size: usize) -> Result<Self, Error> {
+        let party_for_zs = self
+            .party_for_zs
+            .shrink_to(set_size)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+        let opprf_sender_for_rc = self
+            .opprf_sender_for_rc
+            .shrink(set_size)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        Ok(Self {
+            id: self.id,
+            party_for_zs,
+            opprf_sender_for_rc,
+        })
+    }
+
+    /// Locally verify every table this party encodes before sending it. See [SepOpprfSenderWithVole::with_verify_encode].
+    pub fn with_verify_encode(self, verify_encode: bool) -> Self {
+        Self {
+            id: self.id,
+            party_for_zs: self.party_for_zs.with_verify_encode(verify_encode),
+            opprf_sender_for_rc: self.opprf_sender_for_rc.with_verify_encode(verify_encode),
+        }
+    }
+
+    /// Send the table of conditional reconstruction truncated to the lowest `compare_bits` bits of each entry.
+    /// The receiver must run [Receiver::receive_with_compare_bits] with the same `compare_bits`. Default: the whole field.
+    ///
+    /// See [SepOpprfSenderWithVole::with_wire_bits] for the solvers and fields in which the table is truncated.
+    pub fn with_compare_bits(self, compare_bits: usize) -> Self {
+        Self {
+            id: self.id,
+            party_for_zs: self.party_for_zs,
+            opprf_sender_for_rc: self.opprf_sender_for_rc.with_wire_bits(compare_bits),
+        }
+    }
+
     /// Send protocol which consists of conditional secret sharing and conditional reconstruction sending.
     /// It runned in the online phase.
     pub fn send<C: AbstractChannel, RNG: CryptoRng + Rng>(
@@ -135,6 +391,34 @@ where
 
         Ok(())
     }
+
+    /// Same as [Sender::send], but returns the intersection sent by the receiver if this sender is `result_party`
+    /// (See [Receiver::receive_for]), and `None` otherwise.
+    ///
+    /// All parties must give the same `result_party`. It fails before the protocol if `result_party` is no party.
+    pub fn send_for<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        self,
+        inputs: &[F],
+        channels: &mut [(PartyId, C)],
+        rng: &mut RNG,
+        result_party: PartyId,
+    ) -> Result<Option<Vec<F>>, Error> {
+        let id = self.id;
+        check_result_party(
+            result_party,
+            std::iter::once(id).chain(channels.iter().map(|(pid, _)| *pid)),
+        )?;
+
+        self.send(inputs, channels, rng)?;
+
+        if result_party != id {
+            return Ok(None);
+        }
+        let intersection =
+            read_vec_f(&mut channels[0].1).with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        Ok(Some(intersection))
+    }
 }
 
 /// A kind of party in the protocol. They play sender and receiver in Conditional Zero Sharing, and play receiver in Conditional Reconstruction.
@@ -145,7 +429,8 @@ where
 ///
 /// In the optimized version, each party has a separate thread to communicate with each of the other parties.
 ///
-/// On the other hand, in the not optimized version, each party communicates in the same thread with all the other parties.
+/// On the other hand, in the not optimized version, each party communicates in the same thread with all the other parties,
+/// except in the precomputation, which has a thread for each peer since it is dominated by the VOLE extensions with the peers.
 pub struct Receiver<F, S, VS, VR>
 where
     F: FF,
@@ -171,14 +456,105 @@ where
         0
     }
 
+    /// Describe the field, the solver and the VOLE in use. e.g. `PSI-Receiver[field=F128b, solver=Paxos, vole=LPN(medium)]`
+    pub fn describe(&self) -> String {
+        let components = self
+            .opprf_receivers_for_rc
+            .first()
+            .map(|(_, r)| r.describe_components())
+            .unwrap_or_default();
+        format!("PSI-Receiver[{}]", components)
+    }
+
+    /// Write the offline state in the format of [state](crate::preprocessed::state).
+    ///
+    /// The state contains secret VOLE correlations, so keep it secret and import it only once.
+    pub fn export_state<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        write_header::<F, S, _>(writer, self.party_for_zs.set_size)?;
+        self.party_for_zs.write_state(writer)?;
+        write_u64(writer, self.opprf_receivers_for_rc.len() as u64)?;
+        for (them, receiver) in self.opprf_receivers_for_rc.iter() {
+            write_u64(writer, *them as u64)?;
+            receiver.write_state(writer)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Read the state written by [Receiver::export_state]. The result can run [Receiver::receive] without the offline phase.
+    ///
+    /// `vole_share_for_s` and `vole_share_for_r` are not run. They should be the ones used in the offline phase.
+    pub fn import_state<R: Read>(
+        reader: &mut R,
+        vole_share_for_s: VS,
+        vole_share_for_r: VR,
+    ) -> Result<Self, Error> {
+        read_header::<F, S, _>(reader).with_context(|| format!("@{}:{}", file!(), line!()))?;
+        Self::read_body(reader, vole_share_for_s, vole_share_for_r)
+    }
+
+    /// Same as [Receiver::import_state], but fails with [PsiError::VoleSizeMismatch] before reading the body
+    /// unless the state is made for `set_size`.
+    pub fn import_state_for<R: Read>(
+        reader: &mut R,
+        set_size: usize,
+        vole_share_for_s: VS,
+        vole_share_for_r: VR,
+    ) -> Result<Self, Error> {
+        let stored =
+            read_header::<F, S, _>(reader).with_context(|| format!("@{}:{}", file!(), line!()))?;
+        check_query_num(stored, set_size)?;
+        Self::read_body(reader, vole_share_for_s, vole_share_for_r)
+    }
+
+    fn read_body<R: Read>(
+        reader: &mut R,
+        vole_share_for_s: VS,
+        vole_share_for_r: VR,
+    ) -> Result<Self, Error> {
+        let party_for_zs = Party::read_state(reader, vole_share_for_s, vole_share_for_r)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        if party_for_zs.id != 0 {
+            bail!(
+                "receiver index must be 0, but the state is of party {}. @{}:{}",
+                party_for_zs.id,
+                file!(),
+                line!()
+            );
+        }
+
+        let len = read_u64(reader)? as usize;
+        let opprf_receivers_for_rc = (0..len)
+            .map(|_| {
+                let them = read_u64(reader)? as usize;
+                let receiver = SepOpprfReceiverWithVole::read_state(reader, vole_share_for_r)
+                    .with_context(|| format!("@{}:{}", file!(), line!()))?;
+                Ok((them, receiver))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        Ok(Self {
+            party_for_zs,
+            opprf_receivers_for_rc,
+        })
+    }
+
     /// Precomputation for the receiver. It runned in the offline phase.
-    pub fn precomp<C: AbstractChannel, RNG: CryptoRng + Rng>(
+    ///
+    /// The precomputation with each sender runs on its own thread, so the VOLE extensions with different senders overlap.
+    pub fn precomp<C: AbstractChannel + Send, RNG: CryptoRng + Rng>(
         channels: &mut [(PartyId, C)],
         rng: &mut RNG,
         vole_share_for_s: VS,
         vole_share_for_r: VR,
         set_size: usize,
-    ) -> Result<Self, Error> {
+    ) -> Result<Self, Error>
+    where
+        S: Send,
+        VS: Send,
+        VR: Send,
+    {
         let party_for_zs = Party::precomp(
             0,
             channels,
@@ -189,15 +565,81 @@ where
         )
         .with_context(|| format!("@{}:{}", file!(), line!()))?;
 
-        let opprf_receivers_for_rc = channels
-            .iter_mut()
-            .map(|(them, channel)| {
-                let rcvr =
-                    SepOpprfReceiverWithVole::precomp(channel, rng, set_size, vole_share_for_r)
+        let seeds = channels.iter().map(|_| rng.gen()).collect::<Vec<Block>>();
+        let opprf_receivers_for_rc = std::thread::scope(|scope| {
+            let handles = channels
+                .iter_mut()
+                .zip(seeds)
+                .map(|((them, channel), seed)| {
+                    let them = *them;
+                    scope.spawn(move || -> Result<_, Error> {
+                        let mut rng = AesRng::from_seed(seed);
+                        let rcvr = SepOpprfReceiverWithVole::precomp(
+                            channel,
+                            &mut rng,
+                            set_size,
+                            vole_share_for_r,
+                        )
                         .with_context(|| format!("@{}:{}", file!(), line!()))?;
-                Ok((*them, rcvr))
-            })
-            .collect::<Result<Vec<_>, Error>>()?;
+                        Ok((them, rcvr))
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            handles
+                .into_iter()
+                .map(|handle| {
+                    handle.join().map_err(|_| {
+                        anyhow!(
+                            "precomputation with a sender panicked. @{}:{}",
+                            file!(),
+                            line!()
+                        )
+                    })?
+                })
+                .collect::<Result<Vec<_>, Error>>()
+        })?;
+
+        Ok(Self {
+            party_for_zs,
+            opprf_receivers_for_rc,
+        })
+    }
+
+    /// Same as [Receiver::precomp] for `max_set_size`. The result can be shrunk by [Receiver::shrink_to] for any smaller set size.
+    pub fn precomp_for_max<C: AbstractChannel + Send, RNG: CryptoRng + Rng>(
+        channels: &mut [(PartyId, C)],
+        rng: &mut RNG,
+        vole_share_for_s: VS,
+        vole_share_for_r: VR,
+        max_set_size: usize,
+    ) -> Result<Self, Error>
+    where
+        S: Send,
+        VS: Send,
+        VR: Send,
+    {
+        Self::precomp(
+            channels,
+            rng,
+            vole_share_for_s,
+            vole_share_for_r,
+            max_set_size,
+        )
+    }
+
+    /// Shrink the precomputation to `set_size`. See [Sender::shrink_to].
+    pub fn shrink_to(self, set_size: usize) -> Result<Self, Error> {
+        let party_for_zs = self
+            .party_for_zs
+            .shrink_to(set_size)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+        let opprf_receivers_for_rc = self
+            .opprf_receivers_for_rc
+            .into_iter()
+            .map(|(them, receiver)| Ok((them, receiver.shrink(set_size)?)))
+            .collect::<Result<Vec<_>, Error>>()
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
 
         Ok(Self {
             party_for_zs,
@@ -205,6 +647,14 @@ where
         })
     }
 
+    /// Locally verify every table this party encodes (in conditional zero sharing) before sending it. See [Sender::with_verify_encode].
+    pub fn with_verify_encode(self, verify_encode: bool) -> Self {
+        Self {
+            party_for_zs: self.party_for_zs.with_verify_encode(verify_encode),
+            opprf_receivers_for_rc: self.opprf_receivers_for_rc,
+        }
+    }
+
     /// Receive protocol which consists of conditional secret sharing and conditional reconstruction receiving.
     /// It runned in the online phase.
     pub fn receive<C: AbstractChannel, RNG: CryptoRng + Rng>(
@@ -213,6 +663,86 @@ where
         channels: &mut [(PartyId, C)],
         rng: &mut RNG,
     ) -> Result<Vec<F>, Error> {
+        self.receive_with_compare_bits(inputs, channels, rng, field_bits::<F>())
+    }
+
+    /// Same as [Receiver::receive], but only the lowest `compare_bits` bits of the reconstructed sums are compared with zero.
+    ///
+    /// Narrower width raises the false match probability (See [false_match_probability]).
+    /// The senders then send the tables of conditional reconstruction truncated to `compare_bits` bits per entry, which must be set by
+    /// [Sender::with_compare_bits] with the same `compare_bits`. So the width cuts the communication of conditional reconstruction
+    /// for [PaxosSolver] in a binary field (See [SepOpprfSenderWithVole::with_wire_bits]).
+    /// The tables of conditional zero sharing carry whole elements.
+    pub fn receive_with_compare_bits<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        self,
+        inputs: &[F],
+        channels: &mut [(PartyId, C)],
+        rng: &mut RNG,
+        compare_bits: usize,
+    ) -> Result<Vec<F>, Error> {
+        let intersection = self
+            .receive_indices_with_compare_bits(inputs, channels, rng, compare_bits)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        Ok(intersection.into_iter().map(|(x, _)| x).collect())
+    }
+
+    /// Same as [Receiver::receive_with_compare_bits], but the intersection is output by the party `result_party` instead of the receiver.
+    ///
+    /// The cryptographic roles are kept: the receiver computes the intersection as usual, sends it only to `result_party`,
+    /// which gets it from [Sender::send_for], and returns `None`. The other senders get nothing.
+    /// The receiver still sees the intersection while computing it, so it must be trusted to discard it.
+    /// `result_party` 0 is the same as [Receiver::receive_with_compare_bits].
+    pub fn receive_for<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        self,
+        inputs: &[F],
+        channels: &mut [(PartyId, C)],
+        rng: &mut RNG,
+        compare_bits: usize,
+        result_party: PartyId,
+    ) -> Result<Option<Vec<F>>, Error> {
+        check_result_party(result_party, channels.iter().map(|(pid, _)| *pid))?;
+
+        let intersection = self
+            .receive_with_compare_bits(inputs, channels, rng, compare_bits)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        if result_party == 0 {
+            return Ok(Some(intersection));
+        }
+        let (_, channel) = channels
+            .iter_mut()
+            .find(|(pid, _)| *pid == result_party)
+            .unwrap();
+        write_vec_f(channel, &intersection).with_context(|| format!("@{}:{}", file!(), line!()))?;
+        channel
+            .flush()
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        Ok(None)
+    }
+
+    /// Same as [Receiver::receive], but each element of the intersection is returned with its index in `inputs`, in input order.
+    ///
+    /// The indices are the receiver's own information, so they reveal nothing more than the intersection to the receiver.
+    /// The indices in the senders' inputs are not available: senders learn nothing, and the receiver learns only whether each of its elements is shared.
+    /// Sending the indices to another party reveals the order of the receiver's input, so treat them as sensitively as the input itself.
+    pub fn receive_with_indices<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        self,
+        inputs: &[F],
+        channels: &mut [(PartyId, C)],
+        rng: &mut RNG,
+    ) -> Result<Vec<(F, usize)>, Error> {
+        self.receive_indices_with_compare_bits(inputs, channels, rng, field_bits::<F>())
+    }
+
+    fn receive_indices_with_compare_bits<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        self,
+        inputs: &[F],
+        channels: &mut [(PartyId, C)],
+        rng: &mut RNG,
+        compare_bits: usize,
+    ) -> Result<Vec<(F, usize)>, Error> {
         let Self {
             party_for_zs,
             opprf_receivers_for_rc,
@@ -228,6 +758,7 @@ where
             assert!(ri == *them);
 
             let shares = receiver
+                .with_wire_bits(compare_bits)
                 .receive(channel, inputs, rng)
                 .with_context(|| format!("@{}:{}", file!(), line!()))?;
             for (i, (_, y)) in shares.into_iter().enumerate() {
@@ -238,11 +769,70 @@ where
         let intersection = inputs
             .iter()
             .zip(s_hat_sum.into_iter())
-            .filter_map(|(&x, s)| if s.is_zero() { Some(x) } else { None })
+            .enumerate()
+            .filter_map(|(i, (&x, s))| {
+                if is_zero_in_bits(s, compare_bits) {
+                    Some((x, i))
+                } else {
+                    None
+                }
+            })
             .collect::<Vec<_>>();
 
         Ok(intersection)
     }
+
+    /// Same as [Receiver::receive], but refuses to output the intersection if it has more than `cap` elements.
+    ///
+    /// In that case [PsiError::RevealCapExceeded] is returned, which contains neither the elements nor the size of the intersection.
+    /// Senders can't tell whether the cap was exceeded since they learn nothing in the protocol anyway.
+    pub fn receive_with_max_reveal<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        self,
+        inputs: &[F],
+        channels: &mut [(PartyId, C)],
+        rng: &mut RNG,
+        cap: usize,
+    ) -> Result<Vec<F>, Error> {
+        let intersection = self
+            .receive(inputs, channels, rng)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        if intersection.len() > cap {
+            return Err(PsiError::RevealCapExceeded { cap }.into());
+        }
+
+        Ok(intersection)
+    }
+
+    /// Same as [Receiver::receive], but the inputs are partitioned into `shards` shards by a hash (See [shard_of]),
+    /// and the matches are returned per shard as `(matched_count, matches)`, for load analysis.
+    ///
+    /// The protocol is run once over all the inputs since the preprocessing is made for one run, so the counts sum to the intersection size.
+    /// Matches keep input order in each shard.
+    pub fn receive_sharded<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        self,
+        inputs: &[F],
+        channels: &mut [(PartyId, C)],
+        rng: &mut RNG,
+        shards: usize,
+    ) -> Result<Vec<(usize, Vec<F>)>, Error> {
+        if shards == 0 {
+            bail!("shards (=0) must be positive. @{}:{}", file!(), line!());
+        }
+
+        let intersection = self
+            .receive(inputs, channels, rng)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        let mut res = vec![(0, Vec::new()); shards];
+        for x in intersection.into_iter() {
+            let (count, matches) = &mut res[shard_of(&x, shards)];
+            *count += 1;
+            matches.push(x);
+        }
+
+        Ok(res)
+    }
 }
 
 impl<F, S, VS, VR> Party<F, S, VS, VR>
@@ -253,44 +843,228 @@ where
     VR: VoleShareForReceiver<F>,
     Standard: Distribution<F>,
 {
-    pub fn precomp<C: AbstractChannel, RNG: CryptoRng + Rng>(
+    pub fn precomp<C: AbstractChannel + Send, RNG: CryptoRng + Rng>(
         me: PartyId,
         channels: &mut [(PartyId, C)],
         rng: &mut RNG,
         vole_share_for_s: VS,
         vole_share_for_r: VR,
         set_size: usize,
-    ) -> Result<Self, Error> {
+    ) -> Result<Self, Error>
+    where
+        S: Send,
+        VS: Send,
+        VR: Send,
+    {
+        let mut peers = channels
+            .iter_mut()
+            .map(|(them, channel)| (*them, channel))
+            .collect::<Vec<_>>();
+        handshake::<F, S, _>(&mut peers, set_size)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
         let mut opprf_senders = Vec::with_capacity(channels.len());
         let mut opprf_receivers = Vec::with_capacity(channels.len());
 
-        for (them, channel) in channels.iter_mut() {
-            // the party with the lowest PID gets to initialize their OPPRF sender first
-            if me < *them {
-                let sndr =
-                    SepOpprfSenderWithVole::precomp(channel, rng, set_size, vole_share_for_s)
-                        .with_context(|| format!("@{}:{}", file!(), line!()))?;
-                opprf_senders.push((*them, sndr));
-
-                let rcvr =
-                    SepOpprfReceiverWithVole::precomp(channel, rng, set_size, vole_share_for_r)
-                        .with_context(|| format!("@{}:{}", file!(), line!()))?;
-                opprf_receivers.push((*them, rcvr));
-            } else {
-                let rcvr =
-                    SepOpprfReceiverWithVole::precomp(channel, rng, set_size, vole_share_for_r)
-                        .with_context(|| format!("@{}:{}", file!(), line!()))?;
-                opprf_receivers.push((*them, rcvr));
-
-                let sndr =
-                    SepOpprfSenderWithVole::precomp(channel, rng, set_size, vole_share_for_s)
-                        .with_context(|| format!("@{}:{}", file!(), line!()))?;
-                opprf_senders.push((*them, sndr));
-            }
+        let pairs = Self::precomp_pairs(
+            me,
+            channels,
+            rng,
+            vole_share_for_s,
+            vole_share_for_r,
+            set_size,
+        )
+        .with_context(|| format!("@{}:{}", file!(), line!()))?;
+        for (them, sndr, rcvr, _elapsed) in pairs {
+            opprf_senders.push((them, sndr));
+            opprf_receivers.push((them, rcvr));
         }
 
         Ok(Self {
             id: me,
+            set_size,
+            opprf_senders,
+            opprf_receivers,
+        })
+    }
+
+    // precompute the OPPRF sender and receiver with every peer, each peer on its own thread.
+    // All the work with a peer is on its channel, so the VOLE extensions with different peers overlap
+    // instead of waiting for each other. The results are in the order of `channels`, with the time spent for each peer.
+    #[allow(clippy::type_complexity)]
+    fn precomp_pairs<C: AbstractChannel + Send, RNG: CryptoRng + Rng>(
+        me: PartyId,
+        channels: &mut [(PartyId, C)],
+        rng: &mut RNG,
+        vole_share_for_s: VS,
+        vole_share_for_r: VR,
+        set_size: usize,
+    ) -> Result<
+        Vec<(
+            PartyId,
+            SepOpprfSenderWithVole<F, S, VS>,
+            SepOpprfReceiverWithVole<F, S, VR>,
+            Duration,
+        )>,
+        Error,
+    >
+    where
+        S: Send,
+        VS: Send,
+        VR: Send,
+    {
+        let seeds = channels.iter().map(|_| rng.gen()).collect::<Vec<Block>>();
+        std::thread::scope(|scope| {
+            let handles = channels
+                .iter_mut()
+                .zip(seeds)
+                .map(|((them, channel), seed)| {
+                    let them = *them;
+                    scope.spawn(move || -> Result<_, Error> {
+                        let start = Instant::now();
+                        let mut rng = AesRng::from_seed(seed);
+                        // the party with the lowest PID gets to initialize their OPPRF sender first
+                        let (sndr, rcvr) = if me < them {
+                            let sndr = SepOpprfSenderWithVole::precomp(
+                                channel,
+                                &mut rng,
+                                set_size,
+                                vole_share_for_s,
+                            )
+                            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+                            let rcvr = SepOpprfReceiverWithVole::precomp(
+                                channel,
+                                &mut rng,
+                                set_size,
+                                vole_share_for_r,
+                            )
+                            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+                            (sndr, rcvr)
+                        } else {
+                            let rcvr = SepOpprfReceiverWithVole::precomp(
+                                channel,
+                                &mut rng,
+                                set_size,
+                                vole_share_for_r,
+                            )
+                            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+                            let sndr = SepOpprfSenderWithVole::precomp(
+                                channel,
+                                &mut rng,
+                                set_size,
+                                vole_share_for_s,
+                            )
+                            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+                            (sndr, rcvr)
+                        };
+                        Ok((them, sndr, rcvr, start.elapsed()))
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            handles
+                .into_iter()
+                .map(|handle| {
+                    handle.join().map_err(|_| {
+                        anyhow!(
+                            "precomputation with a peer panicked. @{}:{}",
+                            file!(),
+                            line!()
+                        )
+                    })?
+                })
+                .collect()
+        })
+    }
+
+    fn with_verify_encode(self, verify_encode: bool) -> Self {
+        let opprf_senders = self
+            .opprf_senders
+            .into_iter()
+            .map(|(them, sender)| (them, sender.with_verify_encode(verify_encode)))
+            .collect();
+
+        Self {
+            opprf_senders,
+            ..self
+        }
+    }
+
+    fn shrink_to(self, set_size: usize) -> Result<Self, Error> {
+        if set_size == 0 || set_size > self.set_size {
+            bail!(
+                "set size (={}) must be in 1..={} (the precomputed size). @{}:{}",
+                set_size,
+                self.set_size,
+                file!(),
+                line!()
+            );
+        }
+
+        let opprf_senders = self
+            .opprf_senders
+            .into_iter()
+            .map(|(them, sender)| Ok((them, sender.shrink(set_size)?)))
+            .collect::<Result<Vec<_>, Error>>()
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+        let opprf_receivers = self
+            .opprf_receivers
+            .into_iter()
+            .map(|(them, receiver)| Ok((them, receiver.shrink(set_size)?)))
+            .collect::<Result<Vec<_>, Error>>()
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        Ok(Self {
+            id: self.id,
+            set_size,
+            opprf_senders,
+            opprf_receivers,
+        })
+    }
+
+    fn write_state<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        write_u64(writer, self.id as u64)?;
+        write_u64(writer, self.set_size as u64)?;
+        write_u64(writer, self.opprf_senders.len() as u64)?;
+        for (them, sender) in self.opprf_senders.iter() {
+            write_u64(writer, *them as u64)?;
+            sender.write_state(writer)?;
+        }
+        for (them, receiver) in self.opprf_receivers.iter() {
+            write_u64(writer, *them as u64)?;
+            receiver.write_state(writer)?;
+        }
+        Ok(())
+    }
+
+    fn read_state<R: Read>(
+        reader: &mut R,
+        vole_share_for_s: VS,
+        vole_share_for_r: VR,
+    ) -> Result<Self, Error> {
+        let id = read_u64(reader)? as usize;
+        let set_size = read_u64(reader)? as usize;
+        let len = read_u64(reader)? as usize;
+        let opprf_senders = (0..len)
+            .map(|_| {
+                let them = read_u64(reader)? as usize;
+                let sender = SepOpprfSenderWithVole::read_state(reader, vole_share_for_s)
+                    .with_context(|| format!("@{}:{}", file!(), line!()))?;
+                Ok((them, sender))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let opprf_receivers = (0..len)
+            .map(|_| {
+                let them = read_u64(reader)? as usize;
+                let receiver = SepOpprfReceiverWithVole::read_state(reader, vole_share_for_r)
+                    .with_context(|| format!("@{}:{}", file!(), line!()))?;
+                Ok((them, receiver))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        Ok(Self {
+            id,
+            set_size,
             opprf_senders,
             opprf_receivers,
         })
@@ -302,6 +1076,8 @@ where
         channels: &mut [(PartyId, C)],
         rng: &mut RNG,
     ) -> Result<Vec<F>, Error> {
+        check_input_size(inputs.len(), self.set_size)?;
+
         let nparties = channels.len() + 1;
         let ninputs = inputs.len();
 
@@ -319,6 +1095,7 @@ where
 
         let Self {
             id: _,
+            set_size: _,
             opprf_senders,
             opprf_receivers,
         } = self;
@@ -364,6 +1141,158 @@ where
     }
 }
 
+// kinds of I/O errors which a party gets when a peer disconnects.
+pub(crate) const DISCONNECT_KINDS: [ErrorKind; 5] = [
+    ErrorKind::UnexpectedEof,
+    ErrorKind::BrokenPipe,
+    ErrorKind::ConnectionReset,
+    ErrorKind::ConnectionAborted,
+    ErrorKind::NotConnected,
+];
+
+// whether `err` is caused by a disconnection of a peer.
+pub(crate) fn is_disconnect(err: &Error) -> bool {
+    err.chain().any(|cause| {
+        matches!(cause.downcast_ref::<std::io::Error>(), Some(e) if DISCONNECT_KINDS.contains(&e.kind()))
+    })
+}
+
+// Handshake of precomp with all the peers (See send_handshake). A disconnected peer doesn't stop the handshake with the others,
+// and all the disconnected peers are returned together as [PsiError::PartyDropped], so that the parties still connected find the same ones.
+pub(crate) fn handshake<F: FF, S: Solver<F>, C: AbstractChannel>(
+    peers: &mut [(PartyId, &mut C)],
+    set_size: usize,
+) -> Result<(), Error> {
+    let mut dropped = Vec::new();
+    for (them, channel) in peers.iter_mut() {
+        match send_handshake::<F, S, _>(&mut **channel, set_size) {
+            Err(e) if is_disconnect(&e) => dropped.push(*them),
+            res => res.with_context(|| format!("@{}:{}", file!(), line!()))?,
+        }
+    }
+    for (them, channel) in peers.iter_mut() {
+        if dropped.contains(them) {
+            continue;
+        }
+        match check_peer_handshake::<F, S, _>(&mut **channel, *them, set_size) {
+            Err(e) if is_disconnect(&e) => dropped.push(*them),
+            res => res.with_context(|| format!("@{}:{}", file!(), line!()))?,
+        }
+    }
+
+    if !dropped.is_empty() {
+        dropped.sort();
+        return Err(PsiError::PartyDropped { pids: dropped }.into());
+    }
+
+    Ok(())
+}
+
+// Handshake at the start of precomp: every party declares its set size, solver and field length to each peer, and all of them must agree.
+// All the declarations are sent before any is received, so that the handshake doesn't depend on the order of peers.
+fn send_handshake<F: FF, S: Solver<F>, C: AbstractChannel>(
+    channel: &mut C,
+    set_size: usize,
+) -> Result<(), Error> {
+    channel.write_u64(set_size as u64)?;
+    channel.write_u8(S::ID)?;
+    channel.write_u64(F::ByteReprLen::to_usize() as u64)?;
+    channel.flush()?;
+    Ok(())
+}
+
+fn check_peer_handshake<F: FF, S: Solver<F>, C: AbstractChannel>(
+    channel: &mut C,
+    them: PartyId,
+    set_size: usize,
+) -> Result<(), Error> {
+    let theirs = channel.read_u64()? as usize;
+    let their_solver = channel.read_u8()?;
+    let their_field_len = channel.read_u64()? as usize;
+
+    // the encodings of different solvers are incompatible, so decoding would silently fail.
+    if their_solver != S::ID {
+        return Err(PsiError::SolverMismatch {
+            peer: them,
+            ours: S::ID,
+            theirs: their_solver,
+        }
+        .into());
+    }
+
+    // checked by both sides, so that neither of them blocks on shares of a different length.
+    if their_field_len != F::ByteReprLen::to_usize() {
+        bail!(
+            "party {} uses a field of {} bits, but ours has {} bits. @{}:{}",
+            them,
+            their_field_len * 8,
+            field_bits::<F>(),
+            file!(),
+            line!()
+        );
+    }
+
+    if set_size == 0 {
+        bail!("set size must not be 0. @{}:{}", file!(), line!());
+    }
+
+    if theirs == 0 {
+        bail!(
+            "party {} declared set size 0. @{}:{}",
+            them,
+            file!(),
+            line!()
+        );
+    }
+
+    if theirs != set_size {
+        return Err(PsiError::SetSizeMismatch {
+            peer: them,
+            ours: set_size,
+            theirs,
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Shard of `x` among `shards` shards used by [Receiver::receive_sharded]: the first 8 bytes of SHA-256 of `x` modulo `shards`.
+pub fn shard_of<F: FF>(x: &F, shards: usize) -> usize {
+    let digest = Sha256::digest(x.to_bytes());
+    (u64::from_le_bytes(digest[0..8].try_into().unwrap()) % shards as u64) as usize
+}
+
+// `result_party` must be the receiver or one of `pids`, so that every party fails before the protocol for an unknown one.
+fn check_result_party(
+    result_party: PartyId,
+    mut pids: impl Iterator<Item = PartyId>,
+) -> Result<(), Error> {
+    if result_party != 0 && !pids.any(|pid| pid == result_party) {
+        bail!(
+            "the result party {} is no party. @{}:{}",
+            result_party,
+            file!(),
+            line!()
+        );
+    }
+
+    Ok(())
+}
+
+fn check_input_size(ninputs: usize, set_size: usize) -> Result<(), Error> {
+    if ninputs > set_size {
+        bail!(
+            "{} inputs are given, but the set size agreed in precomp is {}. @{}:{}",
+            ninputs,
+            set_size,
+            file!(),
+            line!()
+        );
+    }
+    Ok(())
+}
+
 fn secret_sharing_of_zero<F: FF, R: Rng>(nparties: usize, rng: &mut R) -> Vec<F>
 where
     Standard: Distribution<F>,
@@ -395,6 +1324,7 @@ where
     fn clone(&self) -> Self {
         Self {
             id: self.id,
+            set_size: self.set_size,
             opprf_senders: self.opprf_senders.clone(),
             opprf_receivers: self.opprf_receivers.clone(),
         }
@@ -446,7 +1376,7 @@ where
 mod tests {
     use super::*;
     use crate::channel_utils::sync_channel::create_unix_channels;
-    use crate::set_utils::create_sets_without_check;
+    use crate::set_utils::{create_sets_without_check, FromU128};
     use crate::solver::{PaxosSolver, Solver, SolverParams, VandelmondeSolver};
     use crate::vole::{
         LPNVoleReceiver, LPNVoleSender, OtVoleReceiver, OtVoleSender, VoleShareForReceiver,
@@ -456,8 +1386,11 @@ mod tests {
     use ocelot::ot::{AlszReceiver as OtReceiver, AlszSender as OtSender};
     use rand::Rng;
     use scuttlebutt::field::F128b;
-    use scuttlebutt::AesRng;
+    use scuttlebutt::serialization::CanonicalSerialize;
+    use scuttlebutt::{AesRng, SyncChannel};
     use std::collections::HashSet;
+    use std::io::{BufReader, BufWriter};
+    use std::os::unix::net::UnixStream;
 
     #[test]
     fn test_secret_sharing_of_zero() {
@@ -490,6 +1423,103 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_is_zero_in_bits() {
+        let mut bytes = [0u8; 16];
+        bytes[2] = 0b0001_0000;
+        let s = F128b::from_bytes(&bytes.into()).unwrap();
+
+        assert!(is_zero_in_bits(s, 16));
+        assert!(is_zero_in_bits(s, 20));
+        assert!(!is_zero_in_bits(s, 21));
+        assert!(!is_zero_in_bits(s, 128));
+        assert!(is_zero_in_bits(F128b::zero(), 128));
+
+        assert!(false_match_probability(10, 40) < 1e-10);
+        assert_eq!(false_match_probability(10, 1), 1.0);
+    }
+
+    #[test]
+    fn test_okvs_byte_size() {
+        for n in [0, 1, 10, 100, 1000, 12345, 1 << 16] {
+            assert_eq!(
+                okvs_byte_size::<F128b>(n, PaxosSolver::<F128b>::ID, 0.01).unwrap(),
+                PaxosSolver::<F128b>::calc_params(n).code_length() * 16
+            );
+            assert_eq!(
+                okvs_byte_size::<F128b>(n, VandelmondeSolver::<F128b>::ID, 0.01).unwrap(),
+                VandelmondeSolver::<F128b>::calc_params(n).code_length() * 16
+            );
+        }
+
+        assert!(
+            okvs_byte_size::<F128b>(1000, PaxosSolver::<F128b>::ID, 0.4).unwrap()
+                > okvs_byte_size::<F128b>(1000, PaxosSolver::<F128b>::ID, 0.01).unwrap()
+        );
+        assert!(okvs_byte_size::<F128b>(1000, u8::MAX, 0.01).is_err());
+        assert!(okvs_byte_size::<F128b>(1000, PaxosSolver::<F128b>::ID, -0.1).is_err());
+        assert!(okvs_byte_size::<F128b>(1000, PaxosSolver::<F128b>::ID, f64::NAN).is_err());
+    }
+
+    #[test]
+    fn test_false_match_probability_pairwise() {
+        assert_eq!(false_match_probability((1 << 10) * (1 << 10), 20), 1.0);
+        assert!(false_match_probability((1 << 20) * (1 << 20), 128) < 1e-26);
+
+        // higher with narrower outputs and with larger sets, until it reaches 1.
+        for bits in [64, 56, 48, 40] {
+            assert!(
+                false_match_probability(1000 * 1000, bits)
+                    < false_match_probability(1000 * 1000, bits - 1)
+            );
+            assert!(
+                false_match_probability(1000 * 1000, bits)
+                    < false_match_probability(2000 * 1000, bits)
+            );
+        }
+    }
+
+    #[test]
+    fn test_describe() {
+        let (mut receiver_channels, mut channels) = create_unix_channels(2).unwrap();
+        let (vole_share_for_s, vole_share_for_r) = create_lpn_vole_sr::<PaxosSolver<F128b>>(10);
+
+        let mut sender_channels = channels.pop().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let sender = Sender::<F128b, PaxosSolver<F128b>, _, _>::precomp(
+                1,
+                &mut sender_channels,
+                &mut rng,
+                vole_share_for_s,
+                vole_share_for_r,
+                10,
+            )
+            .unwrap();
+            sender.describe()
+        });
+
+        let mut rng = AesRng::new();
+        let receiver = Receiver::<F128b, PaxosSolver<F128b>, _, _>::precomp(
+            &mut receiver_channels,
+            &mut rng,
+            vole_share_for_s,
+            vole_share_for_r,
+            10,
+        )
+        .unwrap();
+
+        let sender_desc = handle.join().unwrap();
+        let receiver_desc = receiver.describe();
+
+        assert_eq!(
+            sender_desc,
+            "PSI-Sender[field=F128b, solver=Paxos, vole=LPN(small)]"
+        );
+        assert!(receiver_desc.contains("solver=Paxos"), "{}", receiver_desc);
+        assert!(receiver_desc.contains("field=F128b"), "{}", receiver_desc);
+    }
+
     fn test_protocol_base<S, VS, VR>(
         nparties: usize,
         set_size: usize,
@@ -497,9 +1527,108 @@ mod tests {
         vole_share_for_s: VS,
         vole_share_for_r: VR,
     ) where
-        S: Solver<F128b>,
+        S: Solver<F128b> + Send,
+        VS: VoleShareForSender<F128b> + 'static + Send,
+        VR: VoleShareForReceiver<F128b> + 'static + Send,
+    {
+        test_protocol_base_with_compare_bits::<S, _, _>(
+            nparties,
+            set_size,
+            common_size,
+            vole_share_for_s,
+            vole_share_for_r,
+            field_bits::<F128b>(),
+        );
+    }
+
+    fn test_protocol_base_with_compare_bits<S, VS, VR>(
+        nparties: usize,
+        set_size: usize,
+        common_size: usize,
+        vole_share_for_s: VS,
+        vole_share_for_r: VR,
+        compare_bits: usize,
+    ) where
+        S: Solver<F128b> + Send,
         VS: VoleShareForSender<F128b> + 'static + Send,
         VR: VoleShareForReceiver<F128b> + 'static + Send,
+    {
+        let (intersection, res) = run_test_protocol_with_compare_bits::<S, _, _, _>(
+            nparties,
+            set_size,
+            common_size,
+            vole_share_for_s,
+            vole_share_for_r,
+            compare_bits,
+            |receiver, set, channels, rng| {
+                receiver.receive_with_compare_bits(set, channels, rng, compare_bits)
+            },
+        );
+        let res = res.unwrap();
+
+        let res: HashSet<F128b> = HashSet::from_iter(res);
+        let intersection: HashSet<F128b> = HashSet::from_iter(intersection);
+
+        assert_eq!(res, intersection);
+    }
+
+    // run senders in threads and the receiver by `receive`. returns the intersection and the result of `receive`.
+    fn run_test_protocol<S, VS, VR, G>(
+        nparties: usize,
+        set_size: usize,
+        common_size: usize,
+        vole_share_for_s: VS,
+        vole_share_for_r: VR,
+        receive: G,
+    ) -> (Vec<F128b>, Result<Vec<F128b>, Error>)
+    where
+        S: Solver<F128b> + Send,
+        VS: VoleShareForSender<F128b> + 'static + Send,
+        VR: VoleShareForReceiver<F128b> + 'static + Send,
+        G: FnOnce(
+            Receiver<F128b, S, VS, VR>,
+            &[F128b],
+            &mut [(
+                PartyId,
+                SyncChannel<BufReader<UnixStream>, BufWriter<UnixStream>>,
+            )],
+            &mut AesRng,
+        ) -> Result<Vec<F128b>, Error>,
+    {
+        run_test_protocol_with_compare_bits(
+            nparties,
+            set_size,
+            common_size,
+            vole_share_for_s,
+            vole_share_for_r,
+            field_bits::<F128b>(),
+            receive,
+        )
+    }
+
+    // same as run_test_protocol, but the senders send the tables of conditional reconstruction in `compare_bits` bits.
+    fn run_test_protocol_with_compare_bits<S, VS, VR, G>(
+        nparties: usize,
+        set_size: usize,
+        common_size: usize,
+        vole_share_for_s: VS,
+        vole_share_for_r: VR,
+        compare_bits: usize,
+        receive: G,
+    ) -> (Vec<F128b>, Result<Vec<F128b>, Error>)
+    where
+        S: Solver<F128b> + Send,
+        VS: VoleShareForSender<F128b> + 'static + Send,
+        VR: VoleShareForReceiver<F128b> + 'static + Send,
+        G: FnOnce(
+            Receiver<F128b, S, VS, VR>,
+            &[F128b],
+            &mut [(
+                PartyId,
+                SyncChannel<BufReader<UnixStream>, BufWriter<UnixStream>>,
+            )],
+            &mut AesRng,
+        ) -> Result<Vec<F128b>, Error>,
     {
         let mut rng = AesRng::new();
 
@@ -529,7 +1658,8 @@ mod tests {
                     vole_share_for_r,
                     set_size,
                 )
-                .unwrap();
+                .unwrap()
+                .with_compare_bits(compare_bits);
 
                 println!("sender {} prepared.", pid);
 
@@ -553,20 +1683,539 @@ mod tests {
         )
         .unwrap();
 
-        println!("receiver prepared.");
+        println!("receiver prepared. {}", receiver.describe());
 
         // online phase
         let set = sets.pop().unwrap();
+        let res = receive(receiver, &set, &mut receiver_channels, &mut rng);
+
+        println!("receiver finished.");
+
+        (intersection, res)
+    }
+
+    #[test]
+    fn test_protocol_result_party() {
+        let nparties = 3;
+        let set_size = 20;
+        let result_party = 2;
+        let (vole_share_for_s, vole_share_for_r) =
+            create_lpn_vole_sr::<PaxosSolver<F128b>>(set_size);
+
+        let mut rng = AesRng::new();
+        let (intersection, mut sets) =
+            create_sets_without_check(nparties, set_size, 5, &mut rng).unwrap();
+        let (mut receiver_channels, channels) = create_unix_channels(nparties).unwrap();
+
+        let handles = channels
+            .into_iter()
+            .enumerate()
+            .map(|(i, mut channels)| {
+                let pid = i + 1;
+                let set = sets.pop().unwrap();
+                let vole_share_for_s = vole_share_for_s.clone();
+                let vole_share_for_r = vole_share_for_r.clone();
+                std::thread::spawn(move || {
+                    let mut rng = AesRng::new();
+                    let sender = Sender::<F128b, PaxosSolver<F128b>, _, _>::precomp(
+                        pid,
+                        &mut channels,
+                        &mut rng,
+                        vole_share_for_s,
+                        vole_share_for_r,
+                        set_size,
+                    )
+                    .unwrap();
+                    sender
+                        .send_for(&set, &mut channels, &mut rng, result_party)
+                        .unwrap()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let receiver = Receiver::<F128b, PaxosSolver<F128b>, _, _>::precomp(
+            &mut receiver_channels,
+            &mut rng,
+            vole_share_for_s,
+            vole_share_for_r,
+            set_size,
+        )
+        .unwrap();
+        let set = sets.pop().unwrap();
         let res = receiver
-            .receive(&set, &mut receiver_channels, &mut rng)
+            .receive_for(
+                &set,
+                &mut receiver_channels,
+                &mut rng,
+                field_bits::<F128b>(),
+                result_party,
+            )
             .unwrap();
 
-        println!("receiver finished.");
+        // only the result party outputs the intersection.
+        assert_eq!(res, None);
+        let outputs = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(outputs[0], None);
+        let res: HashSet<F128b> = HashSet::from_iter(outputs[1].clone().unwrap());
+        assert_eq!(res, HashSet::from_iter(intersection));
 
-        let res: HashSet<F128b> = HashSet::from_iter(res);
-        let intersection: HashSet<F128b> = HashSet::from_iter(intersection);
+        // every party fails before the protocol for an unknown result party.
+        assert!(check_result_party(3, [1, 2].into_iter()).is_err());
+        assert!(check_result_party(0, [1, 2].into_iter()).is_ok());
+    }
 
-        assert_eq!(res, intersection);
+    #[test]
+    fn test_protocol_paxos_max_reveal() {
+        let nparties = 3;
+        let set_size = 20;
+        let common_size = 5;
+
+        for cap in [6, 5, 4] {
+            let (vole_share_for_s, vole_share_for_r) =
+                create_lpn_vole_sr::<PaxosSolver<F128b>>(set_size);
+            let (intersection, res) = run_test_protocol::<PaxosSolver<F128b>, _, _, _>(
+                nparties,
+                set_size,
+                common_size,
+                vole_share_for_s,
+                vole_share_for_r,
+                |receiver, set, channels, rng| {
+                    receiver.receive_with_max_reveal(set, channels, rng, cap)
+                },
+            );
+            assert_eq!(intersection.len(), common_size);
+
+            if cap >= common_size {
+                let res: HashSet<F128b> = HashSet::from_iter(res.unwrap());
+                assert_eq!(res, HashSet::from_iter(intersection));
+            } else {
+                let err = res.unwrap_err();
+                assert_eq!(
+                    err.downcast_ref::<PsiError>(),
+                    Some(&PsiError::RevealCapExceeded { cap })
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_protocol_paxos_sharded() {
+        let shards = 4;
+        let mut sharded = None;
+        let (intersection, res) = run_test_protocol::<PaxosSolver<F128b>, _, _, _>(
+            3,
+            100,
+            30,
+            LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+            LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+            |receiver, set, channels, rng| {
+                let res = receiver.receive_sharded(set, channels, rng, shards)?;
+                let flat = res.iter().flat_map(|(_, m)| m.clone()).collect();
+                sharded = Some(res);
+                Ok(flat)
+            },
+        );
+        let res = res.unwrap();
+        let sharded = sharded.unwrap();
+
+        assert_eq!(sharded.len(), shards);
+        for (i, (count, matches)) in sharded.iter().enumerate() {
+            assert_eq!(*count, matches.len());
+            assert!(matches.iter().all(|x| shard_of(x, shards) == i));
+        }
+        let total: usize = sharded.iter().map(|(count, _)| count).sum();
+        assert_eq!(total, intersection.len());
+        assert_eq!(
+            HashSet::<F128b>::from_iter(res),
+            HashSet::from_iter(intersection)
+        );
+    }
+
+    #[test]
+    fn test_protocol_paxos_with_indices() {
+        let mut indexed = None;
+        let (intersection, res) = run_test_protocol::<PaxosSolver<F128b>, _, _, _>(
+            3,
+            50,
+            10,
+            LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+            LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+            |receiver, set, channels, rng| {
+                let res = receiver.receive_with_indices(set, channels, rng)?;
+                for &(x, i) in res.iter() {
+                    assert_eq!(set[i], x);
+                }
+                assert!(res.windows(2).all(|w| w[0].1 < w[1].1));
+                let flat = res.iter().map(|&(x, _)| x).collect();
+                indexed = Some(res);
+                Ok(flat)
+            },
+        );
+        assert_eq!(indexed.unwrap().len(), intersection.len());
+        assert_eq!(
+            HashSet::<F128b>::from_iter(res.unwrap()),
+            HashSet::from_iter(intersection)
+        );
+
+        let a = [1u128, 2, 3, 4].map(F128b::from_u128);
+        let b = [4u128, 3, 5].map(F128b::from_u128);
+        let c = [3u128, 6, 4].map(F128b::from_u128);
+        assert_eq!(
+            intersect_local_with_indices(&[&a[..], &b[..], &c[..]]),
+            vec![
+                (F128b::from_u128(3), vec![2, 1, 0]),
+                (F128b::from_u128(4), vec![3, 0, 2])
+            ]
+        );
+        assert!(intersect_local_with_indices::<F128b>(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_import_state_vole_size_mismatch() {
+        let (vole_share_for_s, vole_share_for_r) = create_lpn_vole_sr::<PaxosSolver<F128b>>(100);
+        let (mut receiver_channels, channels) = create_unix_channels(2).unwrap();
+
+        let handles = channels
+            .into_iter()
+            .enumerate()
+            .map(|(i, mut channels)| {
+                std::thread::spawn(move || {
+                    let mut rng = AesRng::new();
+                    Sender::<F128b, PaxosSolver<F128b>, _, _>::precomp(
+                        i + 1,
+                        &mut channels,
+                        &mut rng,
+                        vole_share_for_s,
+                        vole_share_for_r,
+                        100,
+                    )
+                    .unwrap();
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut rng = AesRng::new();
+        let receiver = Receiver::<F128b, PaxosSolver<F128b>, _, _>::precomp(
+            &mut receiver_channels,
+            &mut rng,
+            vole_share_for_s,
+            vole_share_for_r,
+            100,
+        )
+        .unwrap();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let mut state = Vec::new();
+        receiver.export_state(&mut state).unwrap();
+
+        let err = Receiver::<F128b, PaxosSolver<F128b>, _, _>::import_state_for(
+            &mut state.as_slice(),
+            200,
+            vole_share_for_s,
+            vole_share_for_r,
+        )
+        .map(|_| ())
+        .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<PsiError>(),
+            Some(&PsiError::VoleSizeMismatch {
+                stored: 100,
+                requested: 200
+            })
+        );
+
+        Receiver::<F128b, PaxosSolver<F128b>, _, _>::import_state_for(
+            &mut state.as_slice(),
+            100,
+            vole_share_for_s,
+            vole_share_for_r,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_precomp_set_size_mismatch() {
+        let (vole_share_for_s, vole_share_for_r) = create_lpn_vole_sr::<PaxosSolver<F128b>>(20);
+        let (mut receiver_channels, channels) = create_unix_channels(2).unwrap();
+
+        let handles = channels
+            .into_iter()
+            .enumerate()
+            .map(|(i, mut channels)| {
+                std::thread::spawn(move || {
+                    let mut rng = AesRng::new();
+                    Sender::<F128b, PaxosSolver<F128b>, _, _>::precomp(
+                        i + 1,
+                        &mut channels,
+                        &mut rng,
+                        vole_share_for_s,
+                        vole_share_for_r,
+                        10,
+                    )
+                    .map(|_| ())
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut rng = AesRng::new();
+        let err = Receiver::<F128b, PaxosSolver<F128b>, _, _>::precomp(
+            &mut receiver_channels,
+            &mut rng,
+            vole_share_for_s,
+            vole_share_for_r,
+            20,
+        )
+        .map(|_| ())
+        .unwrap_err();
+
+        assert_eq!(
+            err.downcast_ref::<PsiError>(),
+            Some(&PsiError::SetSizeMismatch {
+                peer: 1,
+                ours: 20,
+                theirs: 10
+            })
+        );
+
+        for handle in handles {
+            let err = handle.join().unwrap().unwrap_err();
+            assert_eq!(
+                err.downcast_ref::<PsiError>(),
+                Some(&PsiError::SetSizeMismatch {
+                    peer: 0,
+                    ours: 10,
+                    theirs: 20
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn test_precomp_for_max() {
+        let nparties = 3;
+        let max_set_size = 1000;
+        let sizes = [100, 1000];
+        let (vole_share_for_s, vole_share_for_r) =
+            create_lpn_vole_sr::<PaxosSolver<F128b>>(max_set_size);
+
+        let mut rng = AesRng::new();
+        // runs[k] = (intersection, sets) for sizes[k].
+        let mut runs = sizes
+            .iter()
+            .map(|&n| create_sets_without_check(nparties, n, n / 10, &mut rng).unwrap())
+            .collect::<Vec<(Vec<F128b>, Vec<Vec<F128b>>)>>();
+
+        let (mut receiver_channels, channels) = create_unix_channels(nparties).unwrap();
+
+        let handles = channels
+            .into_iter()
+            .enumerate()
+            .map(|(i, mut channels)| {
+                let pid = i + 1;
+                let sets = runs
+                    .iter_mut()
+                    .map(|(_, sets)| sets.pop().unwrap())
+                    .collect::<Vec<_>>();
+                std::thread::spawn(move || {
+                    let mut rng = AesRng::new();
+                    let sender = Sender::<F128b, PaxosSolver<F128b>, _, _>::precomp_for_max(
+                        pid,
+                        &mut channels,
+                        &mut rng,
+                        vole_share_for_s,
+                        vole_share_for_r,
+                        max_set_size,
+                    )
+                    .unwrap();
+
+                    // the precomputation is cloned only to test several sizes. It must be used once in practice.
+                    for (&n, set) in sizes.iter().zip(sets.iter()) {
+                        let sender = sender.clone().shrink_to(n).unwrap();
+                        sender.send(set, &mut channels, &mut rng).unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let receiver = Receiver::<F128b, PaxosSolver<F128b>, _, _>::precomp_for_max(
+            &mut receiver_channels,
+            &mut rng,
+            vole_share_for_s,
+            vole_share_for_r,
+            max_set_size,
+        )
+        .unwrap();
+
+        for (&n, (intersection, sets)) in sizes.iter().zip(runs.iter_mut()) {
+            let set = sets.pop().unwrap();
+            let res = receiver
+                .clone()
+                .shrink_to(n)
+                .unwrap()
+                .receive(&set, &mut receiver_channels, &mut rng)
+                .unwrap();
+
+            assert_eq!(
+                HashSet::<F128b>::from_iter(res),
+                HashSet::from_iter(intersection.iter().cloned())
+            );
+        }
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert!(receiver.shrink_to(max_set_size + 1).is_err());
+    }
+
+    #[test]
+    fn test_precomp_overlaps_peers() {
+        type P = Party<F128b, PaxosSolver<F128b>, LPNVoleSender<F128b>, LPNVoleReceiver<F128b>>;
+
+        let set_size = 1000;
+        let (vole_share_for_s, vole_share_for_r) =
+            create_lpn_vole_sr::<PaxosSolver<F128b>>(set_size);
+        let (mut receiver_channels, channels) = create_unix_channels(3).unwrap();
+        let mut rng = AesRng::new();
+        let (intersection, sets): (Vec<F128b>, Vec<Vec<F128b>>) =
+            create_sets_without_check(2, set_size, set_size / 10, &mut rng).unwrap();
+        let (sender_set, receiver_set) = (sets[0].clone(), sets[1].clone());
+
+        let handles = channels
+            .into_iter()
+            .enumerate()
+            .map(|(i, mut channels)| {
+                let receiver_set = receiver_set.clone();
+                std::thread::spawn(move || {
+                    let mut rng = AesRng::new();
+                    let pairs = P::precomp_pairs(
+                        i + 1,
+                        &mut channels,
+                        &mut rng,
+                        vole_share_for_s,
+                        vole_share_for_r,
+                        set_size,
+                    )
+                    .unwrap();
+
+                    // party 1 queries party 0 with its OPPRF receiver for party 0.
+                    if i == 0 {
+                        let (them, _, rcvr, _) = pairs.into_iter().next().unwrap();
+                        assert_eq!(them, 0);
+                        let channel = &mut channels[0].1;
+                        return rcvr.receive(channel, &receiver_set, &mut rng).unwrap();
+                    }
+                    Vec::new()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let start = Instant::now();
+        let pairs = P::precomp_pairs(
+            0,
+            &mut receiver_channels,
+            &mut rng,
+            vole_share_for_s,
+            vole_share_for_r,
+            set_size,
+        )
+        .unwrap();
+        let total = start.elapsed();
+        let sum = pairs
+            .iter()
+            .map(|(_, _, _, elapsed)| *elapsed)
+            .sum::<Duration>();
+
+        let points = sender_set
+            .iter()
+            .map(|&x| (x, x + F128b::one()))
+            .collect::<Vec<_>>();
+        let (them, sndr, _, _) = pairs.into_iter().next().unwrap();
+        assert_eq!(them, 1);
+        sndr.send(&mut receiver_channels[0].1, &points, set_size, &mut rng)
+            .unwrap();
+
+        let received = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect::<Vec<_>>();
+        for &(x, y) in received[0].iter() {
+            if intersection.contains(&x) {
+                assert_eq!(y, x + F128b::one());
+            }
+        }
+
+        // the precomputations with the two peers overlap, so they take less than one after another.
+        if std::thread::available_parallelism().map_or(1, |n| n.get()) >= 2 {
+            assert!(
+                total < sum,
+                "total (={:?}) >= sum of peers (={:?})",
+                total,
+                sum
+            );
+        }
+    }
+
+    #[test]
+    fn test_precomp_solver_mismatch() {
+        let (vole_share_for_s, vole_share_for_r) = create_lpn_vole_sr::<PaxosSolver<F128b>>(20);
+        let (mut receiver_channels, channels) = create_unix_channels(2).unwrap();
+
+        let handles = channels
+            .into_iter()
+            .enumerate()
+            .map(|(i, mut channels)| {
+                std::thread::spawn(move || {
+                    let mut rng = AesRng::new();
+                    Sender::<F128b, VandelmondeSolver<F128b>, _, _>::precomp(
+                        i + 1,
+                        &mut channels,
+                        &mut rng,
+                        vole_share_for_s,
+                        vole_share_for_r,
+                        20,
+                    )
+                    .map(|_| ())
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut rng = AesRng::new();
+        let err = Receiver::<F128b, PaxosSolver<F128b>, _, _>::precomp(
+            &mut receiver_channels,
+            &mut rng,
+            vole_share_for_s,
+            vole_share_for_r,
+            20,
+        )
+        .map(|_| ())
+        .unwrap_err();
+
+        assert_eq!(
+            err.downcast_ref::<PsiError>(),
+            Some(&PsiError::SolverMismatch {
+                peer: 1,
+                ours: PaxosSolver::<F128b>::ID,
+                theirs: VandelmondeSolver::<F128b>::ID,
+            })
+        );
+
+        for handle in handles {
+            let err = handle.join().unwrap().unwrap_err();
+            assert_eq!(
+                err.downcast_ref::<PsiError>(),
+                Some(&PsiError::SolverMismatch {
+                    peer: 0,
+                    ours: VandelmondeSolver::<F128b>::ID,
+                    theirs: PaxosSolver::<F128b>::ID,
+                })
+            );
+        }
     }
 
     #[test]
@@ -601,6 +2250,27 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_protocol_paxos_narrow_compare_bits() {
+        let nparties = 3;
+        let set_size = 1 << 8;
+        let common_size = 1 << 4;
+        // false match probability is at most 2^8 * 2^-40 = 2^-32.
+        let compare_bits = 40;
+        assert!(false_match_probability(set_size, compare_bits) <= 2f64.powi(-32));
+
+        let (vole_share_for_s, vole_share_for_r) =
+            create_lpn_vole_sr::<PaxosSolver<F128b>>(set_size);
+        test_protocol_base_with_compare_bits::<PaxosSolver<F128b>, _, _>(
+            nparties,
+            set_size,
+            common_size,
+            vole_share_for_s,
+            vole_share_for_r,
+            compare_bits,
+        );
+    }
+
     #[test]
     fn test_protocol_paxos_middle() {
         let nparties = 5;
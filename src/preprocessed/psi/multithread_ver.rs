@@ -1,4 +1,8 @@
-use super::{secret_sharing_of_zero, Party, PartyId, Receiver, Sender};
+use super::{
+    check_input_size, check_result_party, field_bits, handshake, is_zero_in_bits,
+    secret_sharing_of_zero, Party, PartyId, Receiver, Sender,
+};
+use crate::channel_utils::{read_vec_f, write_vec_f};
 use crate::preprocessed::opprf::{
     SepOpprfReceiver, SepOpprfReceiverWithVole, SepOpprfSender, SepOpprfSenderWithVole,
 };
@@ -100,6 +104,36 @@ where
 
         Ok(())
     }
+
+    /// Multi-threaded optimized version of [Sender::send_for].
+    pub fn send_mt_for<C>(
+        self,
+        inputs: Arc<Vec<F>>,
+        channels: &[(PartyId, Arc<Mutex<C>>)],
+        rng: &mut AesRng,
+        result_party: PartyId,
+    ) -> Result<Option<Vec<F>>>
+    where
+        C: AbstractChannel + Sync + Send + 'static,
+    {
+        let id = self.id;
+        check_result_party(
+            result_party,
+            std::iter::once(id).chain(channels.iter().map(|(pid, _)| *pid)),
+        )?;
+
+        self.send_mt(inputs, channels, rng)?;
+
+        if result_party != id {
+            return Ok(None);
+        }
+        let mut ch = channels[0].1.lock().unwrap();
+        let channel: &mut C = &mut ch;
+        let intersection =
+            read_vec_f(channel).with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        Ok(Some(intersection))
+    }
 }
 
 impl<F, S, VS, VR> Receiver<F, S, VS, VR>
@@ -177,6 +211,20 @@ where
         channels: &[(PartyId, Arc<Mutex<C>>)],
         rng: &mut AesRng,
     ) -> Result<Vec<F>>
+    where
+        C: AbstractChannel + Sync + Send + 'static,
+    {
+        self.receive_mt_with_compare_bits(inputs, channels, rng, field_bits::<F>())
+    }
+
+    /// Multi-threaded optimized version of [Receiver::receive_with_compare_bits].
+    pub fn receive_mt_with_compare_bits<C>(
+        self,
+        inputs: Arc<Vec<F>>,
+        channels: &[(PartyId, Arc<Mutex<C>>)],
+        rng: &mut AesRng,
+        compare_bits: usize,
+    ) -> Result<Vec<F>>
     where
         C: AbstractChannel + Sync + Send + 'static,
     {
@@ -207,6 +255,7 @@ where
                 let mut ch = ch.lock().unwrap();
                 let channel: &mut C = &mut ch;
                 let shares = receiver
+                    .with_wire_bits(compare_bits)
                     .receive(channel, &inputs, &mut rng)
                     .with_context(|| format!("@{}:{}", file!(), line!()));
                 s_tx.send(shares).unwrap();
@@ -223,11 +272,52 @@ where
         let intersection = inputs
             .iter()
             .zip(s_hat_sum.into_iter())
-            .filter_map(|(&x, s)| if s.is_zero() { Some(x) } else { None })
+            .filter_map(|(&x, s)| {
+                if is_zero_in_bits(s, compare_bits) {
+                    Some(x)
+                } else {
+                    None
+                }
+            })
             .collect::<Vec<_>>();
 
         Ok(intersection)
     }
+
+    /// Multi-threaded optimized version of [Receiver::receive_for].
+    pub fn receive_mt_for<C>(
+        self,
+        inputs: Arc<Vec<F>>,
+        channels: &[(PartyId, Arc<Mutex<C>>)],
+        rng: &mut AesRng,
+        compare_bits: usize,
+        result_party: PartyId,
+    ) -> Result<Option<Vec<F>>>
+    where
+        C: AbstractChannel + Sync + Send + 'static,
+    {
+        check_result_party(result_party, channels.iter().map(|(pid, _)| *pid))?;
+
+        let intersection = self
+            .receive_mt_with_compare_bits(inputs, channels, rng, compare_bits)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        if result_party == 0 {
+            return Ok(Some(intersection));
+        }
+        let (_, ch) = channels
+            .iter()
+            .find(|(pid, _)| *pid == result_party)
+            .unwrap();
+        let mut ch = ch.lock().unwrap();
+        let channel: &mut C = &mut ch;
+        write_vec_f(channel, &intersection).with_context(|| format!("@{}:{}", file!(), line!()))?;
+        channel
+            .flush()
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        Ok(None)
+    }
 }
 
 impl<F, S, VS, VR> Party<F, S, VS, VR>
@@ -254,6 +344,19 @@ where
 
         channels.sort_by_key(|(them, _)| *them);
 
+        {
+            let mut guards = channels
+                .iter()
+                .map(|(them, channel)| (*them, channel.lock().unwrap()))
+                .collect::<Vec<_>>();
+            let mut peers = guards
+                .iter_mut()
+                .map(|(them, ch)| (*them, &mut **ch))
+                .collect::<Vec<_>>();
+            handshake::<F, S, _>(&mut peers, set_size)
+                .with_context(|| format!("@{}:{}", file!(), line!()))?;
+        }
+
         for (them, channel) in channels.iter_mut() {
             let mut trng = rng.fork();
             let ch = Arc::clone(channel);
@@ -326,6 +429,7 @@ where
 
         Ok(Self {
             id: me,
+            set_size,
             opprf_senders,
             opprf_receivers,
         })
@@ -340,6 +444,8 @@ where
     where
         C: AbstractChannel + Sync + Send + 'static,
     {
+        check_input_size(inputs.len(), self.set_size)?;
+
         let nparties = channels.len() + 1;
         let ninputs = inputs.len();
 
@@ -359,6 +465,7 @@ where
 
         let Self {
             id: _,
+            set_size: _,
             opprf_senders,
             opprf_receivers,
         } = self;
@@ -0,0 +1,199 @@
+//! Connectivity check (`prep_psi ping`): open one TCP connection, exchange a ping and a pong in each direction,
+//! and report the round-trip time. No protocol state is created, so it only tells whether the peer is reachable (e.g. through a firewall).
+//!
+//! Unlike [warmup](crate::preprocessed::psi::warmup), nothing about the configuration is compared.
+//!
+//! A ping is [PING_MAGIC] followed by a random nonce (u64 each), and the pong echoes both.
+//! The connecting side pings first, and the listening side pings after answering, so that both sides measure the round trip.
+
+use crate::channel_utils::channel_from;
+use crate::cli_utils::PingArgs;
+use anyhow::{bail, Context, Error};
+use rand::Rng;
+use scuttlebutt::channel::AbstractChannel;
+use scuttlebutt::AesRng;
+use std::fmt;
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+/// First u64 of a ping, so that a peer which is not `prep_psi ping` is detected.
+pub const PING_MAGIC: u64 = u64::from_le_bytes(*b"prepping");
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Result of [run_ping].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PingReport {
+    /// Address of the peer.
+    pub peer: SocketAddr,
+    /// Time from sending the ping to receiving the pong.
+    pub round_trip: Duration,
+}
+
+impl fmt::Display for PingReport {
+    /// e.g. `pong from 127.0.0.1:10000: round trip 85.2µs`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "pong from {}: round trip {:?}",
+            self.peer, self.round_trip
+        )
+    }
+}
+
+/// Send a ping and wait for the pong. Returns the round-trip time.
+pub fn ping<C: AbstractChannel>(channel: &mut C) -> Result<Duration, Error> {
+    let nonce = AesRng::new().gen::<u64>();
+
+    let start = Instant::now();
+    channel.write_u64(PING_MAGIC)?;
+    channel.write_u64(nonce)?;
+    channel.flush()?;
+
+    let magic = channel
+        .read_u64()
+        .with_context(|| format!("no pong. @{}:{}", file!(), line!()))?;
+    let echoed = channel.read_u64()?;
+    let round_trip = start.elapsed();
+
+    if magic != PING_MAGIC || echoed != nonce {
+        bail!(
+            "the peer answered a wrong pong. Is it prep_psi ping? @{}:{}",
+            file!(),
+            line!()
+        );
+    }
+
+    Ok(round_trip)
+}
+
+/// Wait for a ping and answer the pong.
+pub fn pong<C: AbstractChannel>(channel: &mut C) -> Result<(), Error> {
+    let magic = channel
+        .read_u64()
+        .with_context(|| format!("no ping. @{}:{}", file!(), line!()))?;
+    if magic != PING_MAGIC {
+        bail!(
+            "the peer sent a wrong ping. Is it prep_psi ping? @{}:{}",
+            file!(),
+            line!()
+        );
+    }
+    let nonce = channel.read_u64()?;
+
+    channel.write_u64(PING_MAGIC)?;
+    channel.write_u64(nonce)?;
+    channel.flush()?;
+
+    Ok(())
+}
+
+/// Connect to `addr`, then ping and pong.
+pub fn ping_connect<A: ToSocketAddrs>(addr: A) -> Result<PingReport, Error> {
+    let Some(peer) = addr
+        .to_socket_addrs()
+        .with_context(|| format!("@{}:{}", file!(), line!()))?
+        .next()
+    else {
+        bail!("no address to connect to. @{}:{}", file!(), line!());
+    };
+    let stream = TcpStream::connect_timeout(&peer, CONNECT_TIMEOUT)
+        .with_context(|| format!("Failed to connect to {}. @{}:{}", peer, file!(), line!()))?;
+    let mut channel = channel_from(stream)?;
+
+    let round_trip = ping(&mut channel).with_context(|| format!("@{}:{}", file!(), line!()))?;
+    pong(&mut channel).with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+    Ok(PingReport { peer, round_trip })
+}
+
+/// Accept one connection on `listener`, then pong and ping.
+pub fn ping_accept(listener: &TcpListener) -> Result<PingReport, Error> {
+    let (stream, peer) = listener
+        .accept()
+        .with_context(|| format!("@{}:{}", file!(), line!()))?;
+    let mut channel = channel_from(stream)?;
+
+    pong(&mut channel).with_context(|| format!("@{}:{}", file!(), line!()))?;
+    let round_trip = ping(&mut channel).with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+    Ok(PingReport { peer, round_trip })
+}
+
+/// Run `prep_psi ping` with `--connect` or `--listen`.
+pub fn run_ping(args: &PingArgs) -> Result<PingReport, Error> {
+    match (&args.connect, &args.listen) {
+        (Some(addr), None) => ping_connect(addr.as_str()),
+        (None, Some(addr)) => {
+            let listener = TcpListener::bind(addr.as_str())
+                .with_context(|| format!("Failed to listen on {}.", addr))?;
+            println!("listening on {}.", listener.local_addr()?);
+            ping_accept(&listener)
+        }
+        _ => bail!("give exactly one of --connect and --listen."),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixStream;
+
+    #[test]
+    fn test_ping_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handle = std::thread::spawn(move || ping_accept(&listener).unwrap());
+
+        let report = ping_connect(addr).unwrap();
+        let accepted = handle.join().unwrap();
+
+        assert_eq!(report.peer, addr);
+        assert_eq!(accepted.peer.ip(), addr.ip());
+        assert!(report.round_trip > Duration::ZERO);
+        assert!(accepted.round_trip > Duration::ZERO);
+        assert!(report.to_string().starts_with("pong from 127.0.0.1:"));
+    }
+
+    #[test]
+    fn test_ping_args() {
+        use crate::cli_utils::{PrePSIArgs, PsiCommand};
+        use clap::Parser;
+
+        let args =
+            PrePSIArgs::try_parse_from(["prep_psi", "ping", "--listen", "0.0.0.0:10000"]).unwrap();
+        let Some(PsiCommand::Ping(ping_args)) = args.command else {
+            panic!("ping is not parsed: {:?}", args);
+        };
+        assert_eq!(ping_args.listen.as_deref(), Some("0.0.0.0:10000"));
+        assert_eq!(ping_args.connect, None);
+
+        assert!(PrePSIArgs::try_parse_from(["prep_psi", "ping"]).is_err());
+        assert!(PrePSIArgs::try_parse_from([
+            "prep_psi",
+            "ping",
+            "--listen",
+            "a:1",
+            "--connect",
+            "b:1"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_ping_wrong_peer() {
+        let (s, r) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut channel = channel_from(s).unwrap();
+            channel.read_u64().unwrap();
+            let nonce = channel.read_u64().unwrap();
+            channel.write_u64(PING_MAGIC).unwrap();
+            channel.write_u64(nonce + 1).unwrap();
+            channel.flush().unwrap();
+        });
+
+        let mut channel = channel_from(r).unwrap();
+        assert!(ping(&mut channel).is_err());
+        handle.join().unwrap();
+    }
+}
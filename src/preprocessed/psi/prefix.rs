@@ -0,0 +1,171 @@
+use super::{PartyId, Receiver, Sender};
+use crate::set_utils::{gen_padding, FromU128, PrefixKey};
+use crate::solver::Solver;
+use crate::vole::{VoleShareForReceiver, VoleShareForSender};
+use anyhow::{Context, Error};
+use rand::distributions::{Distribution, Standard};
+use rand::{CryptoRng, Rng};
+use scuttlebutt::channel::AbstractChannel;
+use scuttlebutt::field::FiniteField as FF;
+use std::collections::HashSet;
+
+// reduce `inputs` by `prefix`. Reduced duplicates are replaced by random padding, so the number of elements is kept.
+fn prefix_keys<F, RNG>(prefix: &PrefixKey, inputs: &[u128], rng: &mut RNG) -> Vec<F>
+where
+    F: FF + FromU128,
+    RNG: CryptoRng + Rng,
+    Standard: Distribution<F>,
+{
+    let keys = inputs
+        .iter()
+        .map(|&x| prefix.key::<F>(x))
+        .collect::<HashSet<F>>();
+    let padding = gen_padding(&keys, inputs.len() - keys.len(), rng);
+
+    keys.into_iter().chain(padding).collect()
+}
+
+impl<F, S, VS, VR> Sender<F, S, VS, VR>
+where
+    F: FF + FromU128,
+    S: Solver<F>,
+    VS: VoleShareForSender<F>,
+    VR: VoleShareForReceiver<F>,
+    Standard: Distribution<F>,
+{
+    /// Prefix mode of [Sender::send]. Each input is reduced by `prefix` before the exact-match protocol.
+    ///
+    /// e.g. a sender holding `10.0.0.0` with [PrefixKey::from_cidr]`("10.0.0.0/8")` matches every receiver's address in `10.0.0.0/8`.
+    pub fn send_prefix<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        self,
+        prefix: &PrefixKey,
+        inputs: &[u128],
+        channels: &mut [(PartyId, C)],
+        rng: &mut RNG,
+    ) -> Result<(), Error> {
+        let keys = prefix_keys::<F, _>(prefix, inputs, rng);
+
+        self.send(&keys, channels, rng)
+            .with_context(|| format!("@{}:{}", file!(), line!()))
+    }
+}
+
+impl<F, S, VS, VR> Receiver<F, S, VS, VR>
+where
+    F: FF + FromU128,
+    S: Solver<F>,
+    VS: VoleShareForSender<F>,
+    VR: VoleShareForReceiver<F>,
+    Standard: Distribution<F>,
+{
+    /// Prefix mode of [Receiver::receive]. Each input is reduced by `prefix` before the exact-match protocol.
+    ///
+    /// Returns the inputs whose prefix is held by all the senders, in input order.
+    /// Senders must run [Sender::send_prefix] with the same `prefix`.
+    pub fn receive_prefix<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        self,
+        prefix: &PrefixKey,
+        inputs: &[u128],
+        channels: &mut [(PartyId, C)],
+        rng: &mut RNG,
+    ) -> Result<Vec<u128>, Error> {
+        let keys = prefix_keys::<F, _>(prefix, inputs, rng);
+
+        let intersection = self
+            .receive(&keys, channels, rng)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?
+            .into_iter()
+            .collect::<HashSet<F>>();
+
+        Ok(inputs
+            .iter()
+            .filter(|&&x| intersection.contains(&prefix.key::<F>(x)))
+            .copied()
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channel_utils::sync_channel::create_unix_channels;
+    use crate::set_utils::parse_ipv4;
+    use crate::solver::PaxosSolver;
+    use crate::vole::{LPNVoleReceiver, LPNVoleSender, LPN_EXTEND_SMALL, LPN_SETUP_SMALL};
+    use scuttlebutt::field::F128b;
+    use scuttlebutt::AesRng;
+
+    #[test]
+    fn test_prefix_cidr() {
+        let (network, prefix) = PrefixKey::from_cidr("10.0.0.0/8").unwrap();
+        let ipv4 = |addrs: &[&str]| {
+            addrs
+                .iter()
+                .map(|a| parse_ipv4(a).unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        let receiver_set = ipv4(&[
+            "10.1.2.3",
+            "192.168.1.1",
+            "10.200.0.1",
+            "11.0.0.1",
+            "172.16.0.1",
+        ]);
+        let sender_sets = vec![
+            vec![network, parse_ipv4("192.0.0.0").unwrap()],
+            ipv4(&["10.0.0.0", "172.0.0.0", "8.0.0.0"]),
+        ];
+        let set_size = 5;
+
+        let vole_share_for_s = LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL);
+        let vole_share_for_r = LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL);
+        let (mut receiver_channels, channels) = create_unix_channels(3).unwrap();
+
+        let handles = channels
+            .into_iter()
+            .zip(sender_sets)
+            .enumerate()
+            .map(|(i, (mut channels, set))| {
+                std::thread::spawn(move || {
+                    let mut rng = AesRng::new();
+                    // pad the senders up to set_size with networks the receiver doesn't hold.
+                    let mut set = set;
+                    set.extend((0..set_size - set.len()).map(|j| (100 + j as u128) << 24));
+
+                    let sender = Sender::<F128b, PaxosSolver<F128b>, _, _>::precomp(
+                        i + 1,
+                        &mut channels,
+                        &mut rng,
+                        vole_share_for_s,
+                        vole_share_for_r,
+                        set_size,
+                    )
+                    .unwrap();
+                    sender
+                        .send_prefix(&prefix, &set, &mut channels, &mut rng)
+                        .unwrap();
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut rng = AesRng::new();
+        let receiver = Receiver::<F128b, PaxosSolver<F128b>, _, _>::precomp(
+            &mut receiver_channels,
+            &mut rng,
+            vole_share_for_s,
+            vole_share_for_r,
+            set_size,
+        )
+        .unwrap();
+        let res = receiver
+            .receive_prefix(&prefix, &receiver_set, &mut receiver_channels, &mut rng)
+            .unwrap();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(res, ipv4(&["10.1.2.3", "10.200.0.1"]));
+    }
+}
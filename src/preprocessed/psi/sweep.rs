@@ -0,0 +1,434 @@
+//! Sweep of set sizes, solvers and LPN tiers for benchmarking, used by the `prep_psi_sweep` binary.
+//!
+//! Each point of the sweep runs the whole protocol once, with all parties in this process connected by crossbeam (in-memory) channels.
+//! The times and the communication are measured on the receiver and written as one CSV row (See [CSV_HEADER]).
+//! Communication is the bytes sent and received by the receiver.
+//!
+//! With several trials per point ([run_sweep_trials]), one row per point reports the mean, median and standard deviation of each measurement instead (See [TRIALS_CSV_HEADER]).
+//!
+//! A point whose LPN tier is too small for the code length is skipped.
+
+use crate::channel_utils::counting_channel::{ByteCounters, CountingChannel};
+use crate::cli_utils::{create_channels, ChannelType, SolverType};
+use crate::preprocessed::psi::{Receiver, Sender};
+use crate::set_utils::create_sets_without_check;
+use crate::solver::{PaxosSolver, Solver, SolverParams, VandelmondeSolver};
+use crate::vole::lpn_tier::measure_capacity;
+use crate::vole::{LPNVoleReceiver, LPNVoleSender, LpnTier};
+use anyhow::{bail, Context, Result};
+use scuttlebutt::field::F128b;
+use scuttlebutt::AesRng;
+use std::collections::HashSet;
+use std::io::Write;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Header line of the CSV written by [run_sweep].
+pub const CSV_HEADER: &str =
+    "num_parties,set_size,solver,lpn_tier,offline_ms,online_ms,offline_bytes,online_bytes";
+
+/// Header line of the CSV written by [run_sweep_trials].
+pub const TRIALS_CSV_HEADER: &str = "num_parties,set_size,solver,lpn_tier,trials,\
+offline_ms_mean,offline_ms_median,offline_ms_stddev,\
+online_ms_mean,online_ms_median,online_ms_stddev,\
+offline_bytes_mean,offline_bytes_median,offline_bytes_stddev,\
+online_bytes_mean,online_bytes_median,online_bytes_stddev";
+
+/// One configuration of the sweep.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SweepPoint {
+    /// Number of parties including the receiver.
+    pub num_parties: usize,
+    /// Set size of each party.
+    pub set_size: usize,
+    /// Solver of the OPPRFs.
+    pub solver: SolverType,
+    /// LPN parameter tier of the VOLE.
+    pub tier: LpnTier,
+}
+
+/// Measurement of one [SweepPoint].
+#[derive(Clone, Copy, Debug)]
+pub struct SweepRow {
+    /// The configuration.
+    pub point: SweepPoint,
+    /// Time of the receiver's offline phase.
+    pub offline: Duration,
+    /// Time of the receiver's online phase.
+    pub online: Duration,
+    /// Bytes sent and received by the receiver in the offline phase.
+    pub offline_bytes: u64,
+    /// Bytes sent and received by the receiver in the online phase.
+    pub online_bytes: u64,
+}
+
+impl SweepRow {
+    /// Format as a CSV row in the order of [CSV_HEADER].
+    pub fn to_csv(&self) -> String {
+        format!(
+            "{},{},{},{},{:.3},{:.3},{},{}",
+            self.point.num_parties,
+            self.point.set_size,
+            self.point.solver,
+            self.point.tier,
+            self.offline.as_secs_f64() * 1000.0,
+            self.online.as_secs_f64() * 1000.0,
+            self.offline_bytes,
+            self.online_bytes
+        )
+    }
+}
+
+/// Mean, median and (sample) standard deviation of one measurement over the trials.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Summary {
+    /// Arithmetic mean.
+    pub mean: f64,
+    /// Middle value, or the mean of the two middle values for an even number of trials.
+    pub median: f64,
+    /// 0 for a single trial.
+    pub stddev: f64,
+}
+
+impl Summary {
+    /// Summary of `values`, which must not be empty.
+    pub fn of(values: &[f64]) -> Self {
+        assert!(!values.is_empty(), "no values to summarize.");
+        let n = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / n;
+
+        let mut sorted = values.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let mid = sorted.len() / 2;
+        let median = if sorted.len() % 2 == 0 {
+            (sorted[mid - 1] + sorted[mid]) / 2.0
+        } else {
+            sorted[mid]
+        };
+
+        let stddev = if values.len() > 1 {
+            (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt()
+        } else {
+            0.0
+        };
+
+        Self {
+            mean,
+            median,
+            stddev,
+        }
+    }
+
+    fn to_csv(self, precision: usize) -> String {
+        format!(
+            "{:.*},{:.*},{:.*}",
+            precision, self.mean, precision, self.median, precision, self.stddev
+        )
+    }
+}
+
+/// Aggregate of several trials of one [SweepPoint], each over freshly generated sets.
+#[derive(Clone, Debug)]
+pub struct TrialReport {
+    /// The configuration.
+    pub point: SweepPoint,
+    /// Measurement of each trial, in the order they were run.
+    pub rows: Vec<SweepRow>,
+    /// Offline time in milliseconds.
+    pub offline_ms: Summary,
+    /// Online time in milliseconds.
+    pub online_ms: Summary,
+    /// Offline communication in bytes.
+    pub offline_bytes: Summary,
+    /// Online communication in bytes.
+    pub online_bytes: Summary,
+}
+
+impl TrialReport {
+    /// Aggregate `rows` of `point`. `rows` must not be empty.
+    pub fn from_rows(point: SweepPoint, rows: Vec<SweepRow>) -> Result<Self> {
+        if rows.is_empty() {
+            bail!("no trials of {:?} to aggregate.", point);
+        }
+
+        let summary =
+            |f: fn(&SweepRow) -> f64| Summary::of(&rows.iter().map(f).collect::<Vec<_>>());
+        Ok(Self {
+            point,
+            offline_ms: summary(|r| r.offline.as_secs_f64() * 1000.0),
+            online_ms: summary(|r| r.online.as_secs_f64() * 1000.0),
+            offline_bytes: summary(|r| r.offline_bytes as f64),
+            online_bytes: summary(|r| r.online_bytes as f64),
+            rows,
+        })
+    }
+
+    /// Format as a CSV row in the order of [TRIALS_CSV_HEADER].
+    pub fn to_csv(&self) -> String {
+        format!(
+            "{},{},{},{},{},{},{},{},{}",
+            self.point.num_parties,
+            self.point.set_size,
+            self.point.solver,
+            self.point.tier,
+            self.rows.len(),
+            self.offline_ms.to_csv(3),
+            self.online_ms.to_csv(3),
+            self.offline_bytes.to_csv(1),
+            self.online_bytes.to_csv(1)
+        )
+    }
+}
+
+/// All combinations of set sizes $`2^{\mathrm{log\_size}}`$, solvers and tiers, in this order of nesting.
+pub fn sweep_points(
+    num_parties: usize,
+    log_sizes: impl IntoIterator<Item = u32>,
+    solvers: &[SolverType],
+    tiers: &[LpnTier],
+) -> Vec<SweepPoint> {
+    let mut points = Vec::new();
+    for log_size in log_sizes {
+        for &solver in solvers {
+            for &tier in tiers {
+                points.push(SweepPoint {
+                    num_parties,
+                    set_size: 1 << log_size,
+                    solver,
+                    tier,
+                });
+            }
+        }
+    }
+    points
+}
+
+/// Run all `points` and write the CSV (with [CSV_HEADER]) to `writer`, one row per point as soon as it is measured.
+///
+/// Returns the skipped points, whose LPN tier is too small for the code length.
+pub fn run_sweep<W: Write>(writer: &mut W, points: &[SweepPoint]) -> Result<Vec<SweepPoint>> {
+    writeln!(writer, "{}", CSV_HEADER)?;
+
+    let mut skipped = Vec::new();
+    for &point in points.iter() {
+        match run_point(point).with_context(|| format!("Failed to run {:?}.", point))? {
+            Some(row) => {
+                writeln!(writer, "{}", row.to_csv())?;
+                writer.flush()?;
+            }
+            None => skipped.push(point),
+        }
+    }
+
+    Ok(skipped)
+}
+
+/// Run all `points` `trials` times each and write the CSV (with [TRIALS_CSV_HEADER]) to `writer`, one row per point as soon as all its trials are measured.
+///
+/// Returns the skipped points, whose LPN tier is too small for the code length.
+pub fn run_sweep_trials<W: Write>(
+    writer: &mut W,
+    points: &[SweepPoint],
+    trials: usize,
+) -> Result<Vec<SweepPoint>> {
+    writeln!(writer, "{}", TRIALS_CSV_HEADER)?;
+
+    let mut skipped = Vec::new();
+    for &point in points.iter() {
+        match run_trials(point, trials).with_context(|| format!("Failed to run {:?}.", point))? {
+            Some(report) => {
+                writeln!(writer, "{}", report.to_csv())?;
+                writer.flush()?;
+            }
+            None => skipped.push(point),
+        }
+    }
+
+    Ok(skipped)
+}
+
+/// Run the protocol `trials` times for `point`, each over freshly generated sets, and aggregate the measurements.
+/// Returns `None` if the LPN tier is too small for the code length.
+pub fn run_trials(point: SweepPoint, trials: usize) -> Result<Option<TrialReport>> {
+    if trials == 0 {
+        bail!("trials must be at least 1.");
+    }
+
+    let mut rows = Vec::with_capacity(trials);
+    for trial in 0..trials {
+        match run_point(point).with_context(|| format!("Failed to run the trial {}.", trial))? {
+            Some(row) => rows.push(row),
+            None => return Ok(None),
+        }
+    }
+
+    TrialReport::from_rows(point, rows).map(Some)
+}
+
+/// Run the protocol once for `point`. Returns `None` if the LPN tier is too small for the code length.
+pub fn run_point(point: SweepPoint) -> Result<Option<SweepRow>> {
+    match point.solver {
+        SolverType::Vandelmonde => run_point_with::<VandelmondeSolver<F128b>>(point),
+        SolverType::Paxos => run_point_with::<PaxosSolver<F128b>>(point),
+    }
+}
+
+fn run_point_with<S>(point: SweepPoint) -> Result<Option<SweepRow>>
+where
+    S: Solver<F128b> + Send + 'static,
+{
+    let SweepPoint {
+        num_parties,
+        set_size,
+        tier,
+        ..
+    } = point;
+
+    if num_parties < 2 {
+        bail!("num_parties (={}) must be at least 2.", num_parties);
+    }
+
+    let m = S::calc_params(set_size).code_length();
+    if measure_capacity::<F128b>(tier)? < m {
+        return Ok(None);
+    }
+
+    let (setup_param, extend_param) = tier.params();
+    let vole_share_for_s = LPNVoleSender::new(setup_param, extend_param);
+    let vole_share_for_r = LPNVoleReceiver::new(setup_param, extend_param);
+
+    let mut rng = AesRng::new();
+    let (intersection, mut sets): (Vec<F128b>, Vec<Vec<F128b>>) =
+        create_sets_without_check(num_parties, set_size, set_size / 10, &mut rng)?;
+
+    let (receiver_channels, channels) = create_channels(ChannelType::CrossBeam, num_parties, 0)?;
+    let counters = Arc::new(ByteCounters::default());
+    let mut receiver_channels = receiver_channels
+        .into_iter()
+        .map(|(i, c)| (i, CountingChannel::new(c, Arc::clone(&counters))))
+        .collect::<Vec<_>>();
+
+    let handles = channels
+        .into_iter()
+        .enumerate()
+        .map(|(i, mut channels)| {
+            let pid = i + 1;
+            let set = sets.pop().unwrap();
+            std::thread::spawn(move || -> Result<()> {
+                let mut rng = AesRng::new();
+                let sender = Sender::<F128b, S, _, _>::precomp(
+                    pid,
+                    &mut channels,
+                    &mut rng,
+                    vole_share_for_s,
+                    vole_share_for_r,
+                    set_size,
+                )?;
+                sender.send(&set, &mut channels, &mut rng)
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let start = Instant::now();
+    let receiver = Receiver::<F128b, S, _, _>::precomp(
+        &mut receiver_channels,
+        &mut rng,
+        vole_share_for_s,
+        vole_share_for_r,
+        set_size,
+    )?;
+    let offline = start.elapsed();
+    let offline_bytes = counters.sent() + counters.received();
+
+    let set = sets.pop().unwrap();
+    let start = Instant::now();
+    let res = receiver.receive(&set, &mut receiver_channels, &mut rng)?;
+    let online = start.elapsed();
+    let online_bytes = counters.sent() + counters.received() - offline_bytes;
+
+    for handle in handles {
+        handle.join().expect("Failed to join a thread.")?;
+    }
+
+    if HashSet::<F128b>::from_iter(res) != HashSet::from_iter(intersection) {
+        bail!("wrong intersection for {:?}.", point);
+    }
+
+    Ok(Some(SweepRow {
+        point,
+        offline,
+        online,
+        offline_bytes,
+        online_bytes,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_sweep_smoke() {
+        let points = sweep_points(3, [6], &[SolverType::Paxos], &[LpnTier::Small]);
+        assert_eq!(points.len(), 1);
+
+        let mut out = Vec::new();
+        let skipped = run_sweep(&mut out, &points).unwrap();
+        assert!(skipped.is_empty());
+
+        let out = String::from_utf8(out).unwrap();
+        let lines = out.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], CSV_HEADER);
+
+        let fields = lines[1].split(',').collect::<Vec<_>>();
+        assert_eq!(fields.len(), CSV_HEADER.split(',').count());
+        assert_eq!(fields[..4], ["3", "64", "paxos", "small"]);
+        assert!(fields[6].parse::<u64>().unwrap() > 0);
+        assert!(fields[7].parse::<u64>().unwrap() > 0);
+    }
+
+    #[test]
+    fn test_summary() {
+        let summary = Summary::of(&[3.0, 1.0, 2.0, 6.0]);
+        assert_eq!(summary.mean, 3.0);
+        assert_eq!(summary.median, 2.5);
+        assert!((summary.stddev - (14.0f64 / 3.0).sqrt()).abs() < 1e-9);
+
+        assert_eq!(
+            Summary::of(&[5.0]),
+            Summary {
+                mean: 5.0,
+                median: 5.0,
+                stddev: 0.0
+            }
+        );
+    }
+
+    #[test]
+    fn test_run_trials() {
+        let point = sweep_points(3, [6], &[SolverType::Paxos], &[LpnTier::Small])[0];
+
+        let report = run_trials(point, 3).unwrap().unwrap();
+        assert_eq!(report.rows.len(), 3);
+
+        let offline_ms = report
+            .rows
+            .iter()
+            .map(|r| r.offline.as_secs_f64() * 1000.0)
+            .collect::<Vec<_>>();
+        let min = offline_ms.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max = offline_ms.iter().cloned().fold(0.0, f64::max);
+        assert!((report.offline_ms.mean - offline_ms.iter().sum::<f64>() / 3.0).abs() < 1e-9);
+        assert!(min <= report.offline_ms.mean && report.offline_ms.mean <= max);
+        assert!(min <= report.offline_ms.median && report.offline_ms.median <= max);
+        assert!(report.online_bytes.mean > 0.0);
+
+        let fields = report.to_csv();
+        let fields = fields.split(',').collect::<Vec<_>>();
+        assert_eq!(fields.len(), TRIALS_CSV_HEADER.split(',').count());
+        assert_eq!(fields[..5], ["3", "64", "paxos", "small", "3"]);
+
+        assert!(run_trials(point, 0).is_err());
+    }
+}
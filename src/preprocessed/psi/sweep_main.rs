@@ -0,0 +1,58 @@
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use preprocessing_mpsi_with_vole::cli_utils::SweepArgs;
+use preprocessing_mpsi_with_vole::preprocessed::psi::sweep::{
+    run_sweep, run_sweep_trials, sweep_points,
+};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+fn main() -> Result<()> {
+    let args = SweepArgs::parse();
+
+    eprintln!("{:?}", args);
+
+    if args.min_log_size > args.max_log_size {
+        bail!(
+            "--min-log-size (={}) must not exceed --max-log-size (={}).",
+            args.min_log_size,
+            args.max_log_size
+        );
+    }
+
+    if args.trials == 0 {
+        bail!("--trials must be at least 1.");
+    }
+
+    let points = sweep_points(
+        args.num_parties,
+        args.min_log_size..=args.max_log_size,
+        &args.solvers,
+        &args.tiers,
+    );
+
+    let mut writer: Box<dyn Write> = match &args.output {
+        Some(path) => {
+            Box::new(BufWriter::new(File::create(path).with_context(|| {
+                format!("Failed to create {}.", path.display())
+            })?))
+        }
+        None => Box::new(std::io::stdout()),
+    };
+
+    let skipped = if args.trials > 1 {
+        run_sweep_trials(&mut writer, &points, args.trials)
+    } else {
+        run_sweep(&mut writer, &points)
+    }
+    .with_context(|| "Failed to run the sweep.")?;
+
+    for point in skipped {
+        eprintln!(
+            "skipped: set_size={} solver={} lpn_tier={} (the tier is too small)",
+            point.set_size, point.solver, point.tier
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,231 @@
+//! Preflight of a run (`prep_psi --warmup`): one round trip with every peer confirming that all parties can reach each other
+//! and agree on the configuration, before committing to the offline phase.
+//!
+//! Each party declares to each peer, in this order:
+//!
+//! | Item          | Encoding                                  | Error on mismatch        |
+//! | :------------ | :---------------------------------------- | :----------------------- |
+//! | Version       | length (u64) + UTF-8 bytes                | message                  |
+//! | Field         | length (u64) + name, byte length (u64)    | [PsiError::FieldMismatch] |
+//! | Solver        | [Solver::ID] (u8)                         | [PsiError::SolverMismatch] |
+//! | Set size      | u64                                       | [PsiError::SetSizeMismatch] |
+//! | Params hash   | SHA-256 of [Solver::calc_params] of the set size (32 bytes) | message  |
+//!
+//! Like the handshake of [Party::precomp](crate::preprocessed::psi), all the declarations are sent before any is received.
+//! The handshake of precomp is still run, so the warmup is optional.
+
+use crate::error::PsiError;
+use crate::preprocessed::psi::PartyId;
+use crate::preprocessed::table::{check_field, field_name};
+use crate::solver::{Solver, SolverParams};
+use crate::version_info;
+use anyhow::{bail, Context, Error};
+use scuttlebutt::channel::AbstractChannel;
+use scuttlebutt::field::FiniteField as FF;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::time::{Duration, Instant};
+use typenum::marker_traits::Unsigned;
+
+// upper bound of a declared string, so that a broken peer can't make us allocate much.
+const MAX_DECLARED_LEN: usize = 1 << 10;
+
+/// Configuration confirmed by [warmup].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WarmupReport {
+    /// Number of peers which agreed.
+    pub peers: usize,
+    /// Version of this crate.
+    pub version: &'static str,
+    /// Name of the field.
+    pub field: &'static str,
+    /// Name of the solver.
+    pub solver: &'static str,
+    /// Set size.
+    pub set_size: usize,
+    /// Time from sending the declarations to checking the last peer.
+    pub round_trip: Duration,
+}
+
+impl fmt::Display for WarmupReport {
+    /// e.g. `warmup succeeded: 2 peers agree on version 0.1.0, field F128b, solver Paxos, set size 10 (round trip 1.2ms)`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "warmup succeeded: {} peers agree on version {}, field {}, solver {}, set size {} (round trip {:?})",
+            self.peers, self.version, self.field, self.solver, self.set_size, self.round_trip
+        )
+    }
+}
+
+fn params_hash<F: FF, S: Solver<F>>(set_size: usize) -> [u8; 32] {
+    Sha256::digest(S::calc_params(set_size).to_bytes()).into()
+}
+
+fn write_str<C: AbstractChannel>(channel: &mut C, s: &str) -> Result<(), Error> {
+    channel.write_u64(s.len() as u64)?;
+    channel.write_bytes(s.as_bytes())?;
+    Ok(())
+}
+
+fn read_str<C: AbstractChannel>(channel: &mut C, them: PartyId) -> Result<String, Error> {
+    let len = channel.read_u64()? as usize;
+    if len > MAX_DECLARED_LEN {
+        bail!(
+            "party {} declared a string of {} bytes. @{}:{}",
+            them,
+            len,
+            file!(),
+            line!()
+        );
+    }
+    let mut bytes = vec![0u8; len];
+    channel.read_bytes(&mut bytes)?;
+    String::from_utf8(bytes).with_context(|| format!("party {} @{}:{}", them, file!(), line!()))
+}
+
+fn send_declaration<F: FF, S: Solver<F>, C: AbstractChannel>(
+    channel: &mut C,
+    set_size: usize,
+) -> Result<(), Error> {
+    write_str(channel, version_info().version)?;
+    write_str(channel, field_name::<F>())?;
+    channel.write_u64(F::ByteReprLen::to_usize() as u64)?;
+    channel.write_u8(S::ID)?;
+    channel.write_u64(set_size as u64)?;
+    channel.write_bytes(&params_hash::<F, S>(set_size))?;
+    channel.flush()?;
+    Ok(())
+}
+
+fn check_declaration<F: FF, S: Solver<F>, C: AbstractChannel>(
+    channel: &mut C,
+    them: PartyId,
+    set_size: usize,
+) -> Result<(), Error> {
+    let version = read_str(channel, them)?;
+    if version != version_info().version {
+        bail!(
+            "party {} runs version {}, but ours is {}. @{}:{}",
+            them,
+            version,
+            version_info().version,
+            file!(),
+            line!()
+        );
+    }
+
+    let field = read_str(channel, them)?;
+    let field_len = channel.read_u64()? as usize;
+    check_field::<F>(&field, field_len)
+        .with_context(|| format!("party {} @{}:{}", them, file!(), line!()))?;
+
+    let their_solver = channel.read_u8()?;
+    if their_solver != S::ID {
+        return Err(PsiError::SolverMismatch {
+            peer: them,
+            ours: S::ID,
+            theirs: their_solver,
+        }
+        .into());
+    }
+
+    let theirs = channel.read_u64()? as usize;
+    if theirs != set_size {
+        return Err(PsiError::SetSizeMismatch {
+            peer: them,
+            ours: set_size,
+            theirs,
+        }
+        .into());
+    }
+
+    let mut hash = [0u8; 32];
+    channel.read_bytes(&mut hash)?;
+    if hash != params_hash::<F, S>(set_size) {
+        bail!(
+            "party {} derives different solver params for set size {}. @{}:{}",
+            them,
+            set_size,
+            file!(),
+            line!()
+        );
+    }
+
+    Ok(())
+}
+
+/// Exchange the declarations (See the [module document](crate::preprocessed::psi::warmup)) with all `channels`, and check them.
+///
+/// Every peer must run it at the same point, e.g. right after connecting. It fails with the first mismatch found.
+pub fn warmup<F: FF, S: Solver<F>, C: AbstractChannel>(
+    channels: &mut [(PartyId, C)],
+    set_size: usize,
+) -> Result<WarmupReport, Error> {
+    if set_size == 0 {
+        bail!("set size must not be 0. @{}:{}", file!(), line!());
+    }
+
+    let start = Instant::now();
+    for (_, channel) in channels.iter_mut() {
+        send_declaration::<F, S, _>(channel, set_size)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+    }
+    for (them, channel) in channels.iter_mut() {
+        check_declaration::<F, S, _>(channel, *them, set_size)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+    }
+
+    Ok(WarmupReport {
+        peers: channels.len(),
+        version: version_info().version,
+        field: field_name::<F>(),
+        solver: S::NAME,
+        set_size,
+        round_trip: start.elapsed(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channel_utils::channel_from;
+    use crate::solver::{PaxosSolver, VandelmondeSolver};
+    use scuttlebutt::field::F128b;
+    use std::os::unix::net::UnixStream;
+
+    #[test]
+    fn test_warmup() {
+        let (s, r) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut channels = vec![(0, channel_from(s).unwrap())];
+            warmup::<F128b, PaxosSolver<F128b>, _>(&mut channels, 10).unwrap()
+        });
+
+        let mut channels = vec![(1, channel_from(r).unwrap())];
+        let report = warmup::<F128b, PaxosSolver<F128b>, _>(&mut channels, 10).unwrap();
+        assert_eq!(handle.join().unwrap().peers, 1);
+
+        assert_eq!(report.peers, 1);
+        assert_eq!(report.field, "F128b");
+        assert_eq!(report.set_size, 10);
+        assert!(report
+            .to_string()
+            .starts_with("warmup succeeded: 1 peers agree on version"));
+
+        // a peer with another solver.
+        let (s, r) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut channels = vec![(0, channel_from(s).unwrap())];
+            warmup::<F128b, VandelmondeSolver<F128b>, _>(&mut channels, 10).is_err()
+        });
+
+        let mut channels = vec![(1, channel_from(r).unwrap())];
+        let err = warmup::<F128b, PaxosSolver<F128b>, _>(&mut channels, 10).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<PsiError>(),
+            Some(PsiError::SolverMismatch { peer: 1, .. })
+        ));
+        assert!(handle.join().unwrap());
+    }
+}
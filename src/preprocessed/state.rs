@@ -0,0 +1,171 @@
+//! Serialization of the offline state, so that the offline and online phases can run in separate processes.
+//!
+//! [Sender::export_state](crate::preprocessed::psi::Sender::export_state) and [Receiver::export_state](crate::preprocessed::psi::Receiver::export_state)
+//! write everything shared by VOLE in the offline phase. The `import_state` counterparts read it back, given the VOLE systems (which are not used any more except for [describe](crate::preprocessed::psi::Sender::describe)).
+//!
+//! **The state contains secret VOLE correlations. Keep it as secret as the input sets, and use it only once.**
+//!
+//! # Byte layout
+//!
+//! All integers are little endian. A state starts with the following header.
+//!
+//! | Offset  | Size  | Content                                                |
+//! | :------ | :---- | :----------------------------------------------------- |
+//! | 0       | 4     | Magic bytes `b"PSIS"`                                  |
+//! | 4       | 1     | Format version (currently `2`)                         |
+//! | 5       | 1     | Solver id ([Solver::ID])                               |
+//! | 6       | 2     | Byte length $`b`$ of one field element (`u16`)         |
+//! | 8       | 1     | Byte length $`k`$ of the field name (`u8`)             |
+//! | 9       | $`k`$ | Field name in ASCII (e.g. `F128b`)                     |
+//! | 9 + $`k`$ | 8   | Number of queries (the set size) the VOLE correlations are made for (`u64`) |
+//!
+//! The number of queries is checked by `import_state_for` before the body is read, so a state made for another set size
+//! fails with [PsiError::VoleSizeMismatch] instead of an obscure error in the online phase.
+//!
+//! The body consists of the following items. A *block* is a `u32` length followed by the bytes, and a *vector* is a `u64` number of elements followed by the elements ($`b`$ bytes each).
+//!
+//! - OPRF sender: solver parameters (block), $`\Delta`$ ($`b`$ bytes), $`\bm{B}`$ (vector), domain tag (block).
+//! - OPRF receiver: solver parameters (block), $`\bm{A}`$ (vector), $`\bm{C}`$ (vector), domain tag (block).
+//! - OPPRF sender: solver parameters (block), the number of encode attempts (`u64`), OPRF sender.
+//! - OPPRF receiver: solver parameters (block), OPRF receiver.
+//! - Party of conditional zero sharing: party id (`u64`), the set size agreed in precomp (`u64`), the number $`l`$ of peers (`u64`), $`l`$ pairs of peer id (`u64`) and OPPRF sender, then $`l`$ pairs of peer id (`u64`) and OPPRF receiver.
+//! - Sender: party, OPPRF sender for conditional reconstruction.
+//! - Receiver: party, the number $`l`$ of senders (`u64`), $`l`$ pairs of sender id (`u64`) and OPPRF receiver.
+
+use crate::error::PsiError;
+use crate::preprocessed::table::{check_field, field_name};
+use crate::solver::{Solver, SolverParams};
+use anyhow::{bail, Context, Error};
+use generic_array::{typenum::Unsigned, GenericArray};
+use scuttlebutt::field::FiniteField as FF;
+use std::io::{Read, Write};
+
+/// Magic bytes at the head of the serialized state.
+pub const STATE_MAGIC: [u8; 4] = *b"PSIS";
+/// Format version of the serialized state.
+pub const STATE_VERSION: u8 = 2;
+
+pub(crate) fn write_header<F: FF, S: Solver<F>, W: Write>(
+    writer: &mut W,
+    query_num: usize,
+) -> Result<(), Error> {
+    let name = field_name::<F>();
+    writer.write_all(&STATE_MAGIC)?;
+    writer.write_all(&[STATE_VERSION, S::ID])?;
+    writer.write_all(&(F::ByteReprLen::to_usize() as u16).to_le_bytes())?;
+    writer.write_all(&[name.len() as u8])?;
+    writer.write_all(name.as_bytes())?;
+    write_u64(writer, query_num as u64)?;
+    Ok(())
+}
+
+// returns the number of queries recorded in the header.
+pub(crate) fn read_header<F: FF, S: Solver<F>, R: Read>(reader: &mut R) -> Result<usize, Error> {
+    let mut head = [0u8; 9];
+    reader
+        .read_exact(&mut head)
+        .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+    if head[0..4] != STATE_MAGIC {
+        bail!("invalid magic bytes. @{}:{}", file!(), line!());
+    }
+    if head[4] != STATE_VERSION {
+        bail!(
+            "unsupported state version (={}). @{}:{}",
+            head[4],
+            file!(),
+            line!()
+        );
+    }
+    if head[5] != S::ID {
+        bail!(
+            "solver mismatch: state is made by solver id {}, but {} (id {}) is expected. @{}:{}",
+            head[5],
+            S::NAME,
+            S::ID,
+            file!(),
+            line!()
+        );
+    }
+
+    let field_len = u16::from_le_bytes([head[6], head[7]]) as usize;
+    let mut name = vec![0u8; head[8] as usize];
+    reader
+        .read_exact(&mut name)
+        .with_context(|| format!("@{}:{}", file!(), line!()))?;
+    check_field::<F>(&String::from_utf8_lossy(&name), field_len)
+        .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+    Ok(read_u64(reader).with_context(|| format!("@{}:{}", file!(), line!()))? as usize)
+}
+
+// fails with [PsiError::VoleSizeMismatch] unless the state is made for `requested` queries.
+pub(crate) fn check_query_num(stored: usize, requested: usize) -> Result<(), Error> {
+    if stored != requested {
+        return Err(PsiError::VoleSizeMismatch { stored, requested }.into());
+    }
+    Ok(())
+}
+
+pub(crate) fn write_u64<W: Write>(writer: &mut W, x: u64) -> Result<(), Error> {
+    writer.write_all(&x.to_le_bytes())?;
+    Ok(())
+}
+
+pub(crate) fn read_u64<R: Read>(reader: &mut R) -> Result<u64, Error> {
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+pub(crate) fn write_block<W: Write>(writer: &mut W, bytes: &[u8]) -> Result<(), Error> {
+    writer.write_all(&(bytes.len() as u32).to_le_bytes())?;
+    writer.write_all(bytes)?;
+    Ok(())
+}
+
+pub(crate) fn read_block<R: Read>(reader: &mut R) -> Result<Vec<u8>, Error> {
+    let mut len = [0u8; 4];
+    reader.read_exact(&mut len)?;
+    let mut res = vec![0u8; u32::from_le_bytes(len) as usize];
+    reader.read_exact(&mut res)?;
+    Ok(res)
+}
+
+pub(crate) fn write_f<F: FF, W: Write>(writer: &mut W, x: F) -> Result<(), Error> {
+    writer.write_all(&x.to_bytes())?;
+    Ok(())
+}
+
+pub(crate) fn read_f<F: FF, R: Read>(reader: &mut R) -> Result<F, Error> {
+    let mut buf = GenericArray::<u8, F::ByteReprLen>::default();
+    reader.read_exact(&mut buf)?;
+    Ok(F::from_bytes(&buf)?)
+}
+
+pub(crate) fn write_vec<F: FF, W: Write>(writer: &mut W, v: &[F]) -> Result<(), Error> {
+    write_u64(writer, v.len() as u64)?;
+    for &x in v.iter() {
+        write_f(writer, x)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn read_vec<F: FF, R: Read>(reader: &mut R) -> Result<Vec<F>, Error> {
+    let len = read_u64(reader)? as usize;
+    (0..len).map(|_| read_f(reader)).collect()
+}
+
+pub(crate) fn write_params<F: FF, S: Solver<F>, W: Write>(
+    writer: &mut W,
+    params: S::Params,
+) -> Result<(), Error> {
+    write_block(writer, &params.to_bytes())
+}
+
+pub(crate) fn read_params<F: FF, S: Solver<F>, R: Read>(
+    reader: &mut R,
+) -> Result<S::Params, Error> {
+    let bytes = read_block(reader)?;
+    S::Params::from_bytes(&bytes).with_context(|| format!("@{}:{}", file!(), line!()))
+}
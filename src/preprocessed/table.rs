@@ -0,0 +1,428 @@
+//! Stable serialization format of the programmed table sent in the OPPRF online phase.
+//!
+//! The OPPRF sender encodes its points into code vector $`\bm{p}`$ by a [Solver] and sends $`\bm{p}`$ with the auxillary information.
+//! [ProgrammedTable] bundles them with the solver parameters so that the table can be stored or handed to another implementation.
+//!
+//! # Byte layout
+//!
+//! All integers are little endian.
+//!
+//! | Offset      | Size           | Content                                                                      |
+//! | :---------- | :------------- | :--------------------------------------------------------------------------- |
+//! | 0           | 4              | Magic bytes `b"OPPT"`                                                        |
+//! | 4           | 1              | Format version (currently `1`)                                               |
+//! | 5           | 1              | Solver id ([Solver::ID]; `0`: Vandelmonde, `1` and `3`-`5`: Paxos)           |
+//! | 6           | 2              | Byte length $`b`$ of one field element (`u16`)                               |
+//! | 8           | 1              | Byte length $`k`$ of the field name (`u8`)                                   |
+//! | 9           | $`k`$          | Field name in ASCII (e.g. `F128b`)                                           |
+//! | $`9+k`$     | 4              | Byte length $`s`$ of the solver parameters (`u32`)                           |
+//! | ...         | $`s`$          | Solver parameters                                                            |
+//! | ...         | 4              | Byte length $`t`$ of the auxillary information (`u32`)                       |
+//! | ...         | $`t`$          | Auxillary information                                                        |
+//! | ...         | 8              | The number $`m`$ of elements in $`\bm{p}`$ (`u64`)                           |
+//! | ...         | $`m \cdot b`$  | Elements of $`\bm{p}`$, each one is the canonical byte representation of the field |
+//!
+//! Solver parameters and auxillary information are as follows.
+//!
+//! - Vandelmonde: parameters are $`n`$ (`u64`, 8 bytes). Auxillary information is empty.
+//! - Paxos: parameters are $`|L|`$ and $`|R|`$ (`u64` each, 16 bytes). Auxillary information is three hash keys $`k_1, k_2, k_3`$ (`u64` each, 24 bytes).
+//!
+//! Decoding value at $`x`$ is done by the decode algorithm of the solver (See [crate::solver]) with the above $`\bm{p}`$, parameters and auxillary information.
+//! Note that OPPRF output is the decoded value plus OPRF value of $`x`$.
+//!
+//! # Integrity of cached tables
+//!
+//! When a table is cached between the offline and the online phases, keep [ProgrammedTable::merkle_root] computed at caching time
+//! and load the table by [ProgrammedTable::import_table_verified].
+//! The root is a SHA-256 Merkle tree over the elements of $`\bm{p}`$ (leaf: `H(0x00 || bytes)`, node: `H(0x01 || left || right)`, an odd node is carried up as is),
+//! so any altered element causes [PsiError::TableTampered].
+
+use crate::error::PsiError;
+use crate::solver::{Solver, SolverParams};
+use anyhow::{bail, Context, Error};
+use generic_array::{typenum::Unsigned, GenericArray};
+use scuttlebutt::field::FiniteField as FF;
+use sha2::{Digest, Sha256};
+use std::io::{Read, Write};
+use std::marker::PhantomData;
+
+/// Magic bytes at the head of the serialized table.
+pub const TABLE_MAGIC: [u8; 4] = *b"OPPT";
+/// Format version of the serialized table.
+pub const TABLE_VERSION: u8 = 1;
+
+/// Name of the field used in serialized artifacts. e.g. `F128b`.
+pub fn field_name<F: FF>() -> &'static str {
+    let name = std::any::type_name::<F>();
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+/// Check that the field recorded in an artifact (`name` and byte length `len`) is `F`.
+///
+/// Otherwise [PsiError::FieldMismatch] is returned.
+pub fn check_field<F: FF>(name: &str, len: usize) -> Result<(), Error> {
+    if name != field_name::<F>() || len != F::ByteReprLen::to_usize() {
+        return Err(PsiError::FieldMismatch {
+            expected: field_name::<F>().to_string(),
+            expected_len: F::ByteReprLen::to_usize(),
+            found: name.to_string(),
+            found_len: len,
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Code vector made by OPPRF sender with the information needed to decode it.
+///
+/// Please look the parent document ( [crate::preprocessed::table] ) for the byte layout.
+pub struct ProgrammedTable<F, S>
+where
+    F: FF,
+    S: Solver<F>,
+{
+    params: S::Params,
+    aux: S::AuxInfo,
+    p: Vec<F>,
+    _s: PhantomData<fn() -> S>,
+}
+
+impl<F, S> ProgrammedTable<F, S>
+where
+    F: FF,
+    S: Solver<F>,
+{
+    /// Create the table from code vector and the information used to encode it.
+    pub fn new(params: S::Params, aux: S::AuxInfo, p: Vec<F>) -> Self {
+        Self {
+            params,
+            aux,
+            p,
+            _s: PhantomData,
+        }
+    }
+
+    /// Parameters of the solver.
+    pub fn params(&self) -> S::Params {
+        self.params
+    }
+
+    /// Auxillary information of the solver.
+    pub fn aux(&self) -> S::AuxInfo {
+        self.aux
+    }
+
+    /// Code vector $`\bm{p}`$.
+    pub fn p(&self) -> &[F] {
+        &self.p
+    }
+
+    /// Decode the value programmed at `x`.
+    pub fn decode(&self, x: F) -> Result<F, Error> {
+        S::decode(&self.p, x, self.aux, self.params)
+            .with_context(|| format!("@{}:{}", file!(), line!()))
+    }
+
+    /// Prepare a decoder for many queries. See [Solver::prepare_decoder].
+    pub fn decoder(&self) -> S::Decoder {
+        S::prepare_decoder(&self.p, self.aux, self.params)
+    }
+
+    /// Write the table in the documented format.
+    pub fn export_table<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        let field_len = F::ByteReprLen::to_usize();
+        let name = field_name::<F>();
+        let params = self.params.to_bytes();
+        let aux = S::aux_to_bytes(self.aux);
+
+        writer.write_all(&TABLE_MAGIC)?;
+        writer.write_all(&[TABLE_VERSION, S::ID])?;
+        writer.write_all(&(field_len as u16).to_le_bytes())?;
+        writer.write_all(&[name.len() as u8])?;
+        writer.write_all(name.as_bytes())?;
+        writer.write_all(&(params.len() as u32).to_le_bytes())?;
+        writer.write_all(&params)?;
+        writer.write_all(&(aux.len() as u32).to_le_bytes())?;
+        writer.write_all(&aux)?;
+        writer.write_all(&(self.p.len() as u64).to_le_bytes())?;
+        for x in self.p.iter() {
+            writer.write_all(&x.to_bytes())?;
+        }
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Read the table written by [ProgrammedTable::export_table].
+    ///
+    /// It fails if the field or the solver is different from the one recorded in the table.
+    pub fn import_table<R: Read>(reader: &mut R) -> Result<Self, Error> {
+        let mut head = [0u8; 9];
+        reader
+            .read_exact(&mut head)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        if head[0..4] != TABLE_MAGIC {
+            bail!("invalid magic bytes. @{}:{}", file!(), line!());
+        }
+        if head[4] != TABLE_VERSION {
+            bail!(
+                "unsupported table version (={}). @{}:{}",
+                head[4],
+                file!(),
+                line!()
+            );
+        }
+        if head[5] != S::ID {
+            bail!(
+                "solver mismatch: table is made by solver id {}, but {} (id {}) is expected. @{}:{}",
+                head[5],
+                S::NAME,
+                S::ID,
+                file!(),
+                line!()
+            );
+        }
+
+        let field_len = u16::from_le_bytes([head[6], head[7]]) as usize;
+        let mut name = vec![0u8; head[8] as usize];
+        reader
+            .read_exact(&mut name)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+        check_field::<F>(&String::from_utf8_lossy(&name), field_len)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        let params = read_block(reader).with_context(|| format!("@{}:{}", file!(), line!()))?;
+        let params =
+            S::Params::from_bytes(&params).with_context(|| format!("@{}:{}", file!(), line!()))?;
+        let aux = read_block(reader).with_context(|| format!("@{}:{}", file!(), line!()))?;
+        let aux = S::aux_from_bytes(&aux).with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        let mut len = [0u8; 8];
+        reader
+            .read_exact(&mut len)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+        let len = u64::from_le_bytes(len) as usize;
+
+        let mut buf = vec![0u8; field_len];
+        let p = (0..len)
+            .map(|_| {
+                reader.read_exact(&mut buf)?;
+                let x = F::from_bytes(GenericArray::from_slice(&buf))?;
+                Ok(x)
+            })
+            .collect::<Result<Vec<_>, Error>>()
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+        Ok(Self::new(params, aux, p))
+    }
+
+    /// Merkle root over the elements of $`\bm{p}`$. See the parent document ( [crate::preprocessed::table] ).
+    pub fn merkle_root(&self) -> [u8; 32] {
+        let mut level = self
+            .p
+            .iter()
+            .map(|x| {
+                let mut hasher = Sha256::new();
+                hasher.update([0x00]);
+                hasher.update(x.to_bytes());
+                <[u8; 32]>::from(hasher.finalize())
+            })
+            .collect::<Vec<_>>();
+
+        if level.is_empty() {
+            return Sha256::digest([0x00]).into();
+        }
+
+        while level.len() > 1 {
+            level = level
+                .chunks(2)
+                .map(|pair| match pair {
+                    [l, r] => {
+                        let mut hasher = Sha256::new();
+                        hasher.update([0x01]);
+                        hasher.update(l);
+                        hasher.update(r);
+                        hasher.finalize().into()
+                    }
+                    [odd] => *odd,
+                    _ => unreachable!(),
+                })
+                .collect();
+        }
+
+        level[0]
+    }
+
+    /// Check that the Merkle root of the table is `root`. Otherwise [PsiError::TableTampered] is returned.
+    pub fn verify_merkle_root(&self, root: &[u8; 32]) -> Result<(), Error> {
+        if &self.merkle_root() != root {
+            return Err(PsiError::TableTampered.into());
+        }
+        Ok(())
+    }
+
+    /// [import_table](ProgrammedTable::import_table) followed by [verify_merkle_root](ProgrammedTable::verify_merkle_root) with `root`.
+    pub fn import_table_verified<R: Read>(reader: &mut R, root: &[u8; 32]) -> Result<Self, Error> {
+        let table =
+            Self::import_table(reader).with_context(|| format!("@{}:{}", file!(), line!()))?;
+        table
+            .verify_merkle_root(root)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+        Ok(table)
+    }
+}
+
+fn read_block<R: Read>(reader: &mut R) -> Result<Vec<u8>, Error> {
+    let mut len = [0u8; 4];
+    reader.read_exact(&mut len)?;
+    let mut res = vec![0u8; u32::from_le_bytes(len) as usize];
+    reader.read_exact(&mut res)?;
+    Ok(res)
+}
+
+impl<F, S> Clone for ProgrammedTable<F, S>
+where
+    F: FF,
+    S: Solver<F>,
+{
+    fn clone(&self) -> Self {
+        Self::new(self.params, self.aux, self.p.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::set_utils::FromU128;
+    use crate::solver::{PaxosSolver, VandelmondeSolver};
+    use rand::Rng;
+    use scuttlebutt::{field::F128b, AesRng};
+
+    fn round_trip<S: Solver<F128b>>() {
+        let mut rng = AesRng::new();
+        let points = (0..20)
+            .map(|i| (F128b::from_u128(i), rng.gen::<F128b>()))
+            .collect::<Vec<_>>();
+
+        let params = S::calc_params(points.len());
+        let aux = S::gen_aux(&mut rng).unwrap();
+        let p = S::encode(&mut rng, &points, aux, params).unwrap();
+        let table = ProgrammedTable::<F128b, S>::new(params, aux, p);
+
+        let mut bytes = Vec::new();
+        table.export_table(&mut bytes).unwrap();
+
+        // check the head of the documented layout
+        assert_eq!(&bytes[0..4], b"OPPT");
+        assert_eq!(bytes[4], TABLE_VERSION);
+        assert_eq!(bytes[5], S::ID);
+        assert_eq!(u16::from_le_bytes([bytes[6], bytes[7]]), 16);
+        assert_eq!(&bytes[9..9 + bytes[8] as usize], b"F128b");
+
+        let imported = ProgrammedTable::<F128b, S>::import_table(&mut bytes.as_slice()).unwrap();
+
+        assert_eq!(table.p(), imported.p());
+        for &(x, y) in points.iter() {
+            assert_eq!(imported.decode(x).unwrap(), y);
+        }
+    }
+
+    #[test]
+    fn test_table_round_trip_paxos() {
+        round_trip::<PaxosSolver<F128b>>();
+    }
+
+    #[test]
+    fn test_table_round_trip_vandelmonde() {
+        round_trip::<VandelmondeSolver<F128b>>();
+    }
+
+    #[test]
+    fn test_table_solver_mismatch() {
+        let mut rng = AesRng::new();
+        let points = (0..5)
+            .map(|i| (F128b::from_u128(i), rng.gen::<F128b>()))
+            .collect::<Vec<_>>();
+        let params = VandelmondeSolver::<F128b>::calc_params(points.len());
+        let p = VandelmondeSolver::encode(&mut rng, &points, (), params).unwrap();
+        let table = ProgrammedTable::<F128b, VandelmondeSolver<F128b>>::new(params, (), p);
+
+        let mut bytes = Vec::new();
+        table.export_table(&mut bytes).unwrap();
+
+        let res = ProgrammedTable::<F128b, PaxosSolver<F128b>>::import_table(&mut bytes.as_slice());
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_table_merkle_tampered() {
+        let mut rng = AesRng::new();
+        let points = (0..20)
+            .map(|i| (F128b::from_u128(i), rng.gen::<F128b>()))
+            .collect::<Vec<_>>();
+        let params = PaxosSolver::<F128b>::calc_params(points.len());
+        let aux = PaxosSolver::<F128b>::gen_aux(&mut rng).unwrap();
+        let p = PaxosSolver::encode(&mut rng, &points, aux, params).unwrap();
+        let table = ProgrammedTable::<F128b, PaxosSolver<F128b>>::new(params, aux, p);
+
+        // offline: cache the table with its root.
+        let root = table.merkle_root();
+        let mut bytes = Vec::new();
+        table.export_table(&mut bytes).unwrap();
+
+        // online: the untouched cache verifies.
+        let imported = ProgrammedTable::<F128b, PaxosSolver<F128b>>::import_table_verified(
+            &mut bytes.as_slice(),
+            &root,
+        )
+        .unwrap();
+        assert_eq!(imported.p(), table.p());
+
+        // alter one byte of the last element of p.
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        let err = ProgrammedTable::<F128b, PaxosSolver<F128b>>::import_table_verified(
+            &mut bytes.as_slice(),
+            &root,
+        )
+        .err()
+        .unwrap();
+        assert_eq!(
+            err.downcast_ref::<PsiError>(),
+            Some(&PsiError::TableTampered)
+        );
+    }
+
+    #[test]
+    fn test_table_field_mismatch() {
+        let mut rng = AesRng::new();
+        let points = (0..5)
+            .map(|i| (F128b::from_u128(i), rng.gen::<F128b>()))
+            .collect::<Vec<_>>();
+        let params = VandelmondeSolver::<F128b>::calc_params(points.len());
+        let p = VandelmondeSolver::encode(&mut rng, &points, (), params).unwrap();
+        let table = ProgrammedTable::<F128b, VandelmondeSolver<F128b>>::new(params, (), p);
+
+        let mut bytes = Vec::new();
+        table.export_table(&mut bytes).unwrap();
+
+        // tag the table for another field with the same name length.
+        bytes[9..14].copy_from_slice(b"F64b_");
+
+        let err =
+            ProgrammedTable::<F128b, VandelmondeSolver<F128b>>::import_table(&mut bytes.as_slice())
+                .err()
+                .unwrap();
+        match err.downcast_ref::<PsiError>() {
+            Some(PsiError::FieldMismatch {
+                expected, found, ..
+            }) => {
+                assert_eq!(expected, "F128b");
+                assert_eq!(found, "F64b_");
+            }
+            _ => panic!("unexpected error: {:?}", err),
+        }
+    }
+}
@@ -1,18 +1,119 @@
 //! Utility functions for creating sets for the set intersection protocol.
 
-use anyhow::{bail, Result};
+use crate::preprocessed::table::{check_field, field_name};
+use anyhow::{anyhow, bail, Context, Result};
+use clap::ValueEnum;
+use flate2::bufread::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use generic_array::GenericArray;
 use rand::distributions::{Distribution, Standard};
 use rand::seq::SliceRandom;
-use rand::{CryptoRng, Rng};
-use scuttlebutt::field::F128b;
+use rand::{CryptoRng, Rng, SeedableRng};
+use scuttlebutt::field::{F128b, F64b, FiniteField as FF};
 use scuttlebutt::serialization::CanonicalSerialize;
-use scuttlebutt::Block;
+use scuttlebutt::{AesRng, Block};
+use sha2::{Digest, Sha256};
 use std::collections::HashSet;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use typenum::marker_traits::Unsigned;
+use unicode_normalization::UnicodeNormalization;
+
+/// Formats of input files. Each line of the file is one element of the set.
+#[derive(Clone, Copy, ValueEnum, Debug, PartialEq, Eq)]
+pub enum InputFormat {
+    /// Decimal number less than $`2^{128}`$. It is converted by [FromU128].
+    Decimal,
+    /// Arbitrary string. It is hashed by SHA-256 and truncated to the field size.
+    Hash,
+    /// Exactly 32 hex characters. The 16 bytes are placed into the field as they are (no reduction or re-hashing).
+    #[value(name = "rawhex")]
+    RawHex,
+    /// Binary file: the number of elements (u32, little endian) followed by the canonical bytes of each element.
+    /// It is not line-based. See [read_binary_set_for].
+    Bin,
+}
+
+impl Display for InputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InputFormat::Decimal => write!(f, "decimal"),
+            InputFormat::Hash => write!(f, "hash"),
+            InputFormat::RawHex => write!(f, "rawhex"),
+            InputFormat::Bin => write!(f, "bin"),
+        }
+    }
+}
+
+/// Normalizations of string inputs applied before hashing ([InputFormat::Hash] only).
+///
+/// All parties must use the same normalizations, otherwise equal strings may not match.
+#[derive(Clone, Copy, ValueEnum, Debug, PartialEq, Eq)]
+pub enum Normalize {
+    /// No normalization.
+    None,
+    /// Convert to lowercase.
+    Lower,
+    /// Unicode Normalization Form C.
+    Nfc,
+    /// Remove leading and trailing whitespaces.
+    Trim,
+    /// Canonicalize a numeric string (e.g. `007` and `+7` to `7`, `000` to `0`), so that equal numbers match.
+    /// The leading zeros are stripped from the text, so decimals of any length are accepted. Other strings are left as they are.
+    Numeric,
+}
+
+impl Display for Normalize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Normalize::None => write!(f, "none"),
+            Normalize::Lower => write!(f, "lower"),
+            Normalize::Nfc => write!(f, "nfc"),
+            Normalize::Trim => write!(f, "trim"),
+            Normalize::Numeric => write!(f, "numeric"),
+        }
+    }
+}
+
+/// What to do with duplicated elements in input files.
+#[derive(Clone, Copy, ValueEnum, Debug, PartialEq, Eq)]
+pub enum OnDuplicate {
+    /// Remove duplicates silently.
+    Dedup,
+    /// Abort with the number of duplicates.
+    Error,
+    /// Print a warning with the number of duplicates, and remove them.
+    Warn,
+}
+
+impl Display for OnDuplicate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OnDuplicate::Dedup => write!(f, "dedup"),
+            OnDuplicate::Error => write!(f, "error"),
+            OnDuplicate::Warn => write!(f, "warn"),
+        }
+    }
+}
 
 /// Trait for converting u128 to a type.
 pub trait FromU128 {
     /// Convert u128 to a type.
     fn from_u128(x: u128) -> Self;
+
+    /// Convert u128 to a type, or fail with the value if it is out of range (e.g. `x >= 2^64` for [F64b]).
+    ///
+    /// By default it is [FromU128::from_u128], for the types which every u128 fits in. A type with a narrower range overrides it,
+    /// and then its `from_u128` panics out of range, so use `try_from_u128` for user inputs.
+    fn try_from_u128(x: u128) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(Self::from_u128(x))
+    }
 }
 
 impl FromU128 for F128b {
@@ -22,12 +123,72 @@ impl FromU128 for F128b {
     }
 }
 
+impl FromU128 for F64b {
+    fn from_u128(x: u128) -> Self {
+        Self::try_from_u128(x).unwrap()
+    }
+
+    fn try_from_u128(x: u128) -> Result<Self> {
+        let Ok(x) = u64::try_from(x) else {
+            bail!("{} is out of the range of F64b (must be below 2^64)", x);
+        };
+        Ok(F64b::from_bytes(&x.to_le_bytes().into())?)
+    }
+}
+
 impl FromU128 for Block {
     fn from_u128(x: u128) -> Self {
         Block::from(x)
     }
 }
 
+/// Mapping of an application's element type (e.g. a user ID) into the field `F`, so that the protocol can take the elements as they are.
+/// See [Sender::send_elements](crate::preprocessed::psi::Sender::send_elements) and [Receiver::receive_elements](crate::preprocessed::psi::Receiver::receive_elements).
+///
+/// All parties must use the same mapping, and it must be injective on each party's inputs.
+/// It doesn't have to be invertible, since the receiver maps the intersection back through its own inputs.
+pub trait IntoFieldElement<F> {
+    /// Field element representing `self`.
+    fn to_field_element(&self) -> F;
+}
+
+impl<F: FF> IntoFieldElement<F> for F {
+    fn to_field_element(&self) -> F {
+        *self
+    }
+}
+
+// set of party `i` made of `common`, the IDs of the other parties (so that only `common` is shared by all), and random elements.
+// Elements are kept in insertion order, not in the order of a HashSet, so that the result depends only on `rng`.
+fn create_set<T, RNG>(
+    i: usize,
+    nparties: usize,
+    set_size: usize,
+    common: &[T],
+    rng: &mut RNG,
+) -> Vec<T>
+where
+    T: FromU128 + Clone + Copy + Eq + std::hash::Hash,
+    RNG: CryptoRng + Rng,
+    Standard: Distribution<T>,
+{
+    let mut seen = HashSet::with_capacity(set_size);
+    let mut set = Vec::with_capacity(set_size);
+    let ids = (0..nparties)
+        .filter(|&counter| counter != i)
+        .map(|counter| T::from_u128(counter as u128));
+    let mut candidates = common.iter().copied().chain(ids);
+
+    while set.len() < set_size {
+        let x = candidates.next().unwrap_or_else(|| rng.gen::<T>());
+        if seen.insert(x) {
+            set.push(x);
+        }
+    }
+
+    set
+}
+
 /// Create sets for the set intersection protocol with a check that intersection size is common_size.
 pub fn create_sets_with_check<T, RNG>(
     nparties: usize,
@@ -57,33 +218,8 @@ where
     let common = (0..common_size).map(|_| rng.gen::<T>()).collect::<Vec<_>>();
 
     let mut sets = (0..nparties)
-        .map(|i| {
-            let mut set = HashSet::<T>::from_iter(common.clone().into_iter());
-
-            let mut counter: usize = 0;
-            while set.len() < set_size {
-                if counter >= nparties {
-                    break;
-                }
-
-                if i == counter {
-                    counter += 1;
-                    continue;
-                }
-
-                let x = T::from_u128(counter as u128);
-
-                set.insert(x);
-
-                counter += 1;
-            }
-
-            while set.len() < set_size {
-                set.insert(rng.gen::<T>());
-            }
-            Ok(set.into_iter().collect::<Vec<_>>())
-        })
-        .collect::<Result<Vec<_>>>()?;
+        .map(|i| create_set(i, nparties, set_size, &common, rng))
+        .collect::<Vec<_>>();
 
     let set0 = sets[0].clone();
     let common = sets.iter().skip(1).fold(set0, |acc, set| {
@@ -129,33 +265,8 @@ where
     let common = (0..common_size).map(|_| rng.gen::<T>()).collect::<Vec<_>>();
 
     let mut sets = (0..nparties)
-        .map(|i| {
-            let mut set = HashSet::<T>::from_iter(common.clone().into_iter());
-
-            let mut counter: usize = 0;
-            while set.len() < set_size {
-                if counter >= nparties {
-                    break;
-                }
-
-                if i == counter {
-                    counter += 1;
-                    continue;
-                }
-
-                let x = T::from_u128(counter as u128);
-
-                set.insert(x);
-
-                counter += 1;
-            }
-
-            while set.len() < set_size {
-                set.insert(rng.gen::<T>());
-            }
-            Ok(set.into_iter().collect::<Vec<_>>())
-        })
-        .collect::<Result<Vec<_>>>()?;
+        .map(|i| create_set(i, nparties, set_size, &common, rng))
+        .collect::<Vec<_>>();
 
     for set in sets.iter_mut() {
         set.shuffle(rng);
@@ -180,10 +291,1003 @@ where
     create_sets_without_check(nparties, set_size, common_size, rng)
 }
 
+/// Create two sets of `set_size` elements sharing exactly `common_size` elements, reproducibly from `seed`.
+///
+/// Returns `(set1, set2, intersection)`, where the sets are shuffled. The same seed gives the same sets, so it is useful for
+/// integration tests and benchmarks outside this crate. The RNG is [AesRng] seeded like `prep_psi --deterministic`, so never use the sets as secrets.
+pub fn gen_overlapping_sets<T>(
+    set_size: usize,
+    common_size: usize,
+    seed: u64,
+) -> Result<(Vec<T>, Vec<T>, Vec<T>)>
+where
+    T: Clone + Copy + Eq + std::hash::Hash,
+    Standard: Distribution<T>,
+{
+    if set_size < common_size {
+        bail!(
+            "set_size (={}) < common_size (={}) @{}:{}",
+            set_size,
+            common_size,
+            file!(),
+            line!()
+        );
+    }
+
+    let mut rng = AesRng::from_seed(Block::from(seed as u128));
+    let common = gen_padding(&HashSet::new(), common_size, &mut rng);
+
+    // the private parts are drawn outside all the elements so far, so the intersection is exactly `common`.
+    let mut existing = HashSet::<T>::from_iter(common.iter().cloned());
+    let private1 = gen_padding(&existing, set_size - common_size, &mut rng);
+    existing.extend(private1.iter().cloned());
+    let private2 = gen_padding(&existing, set_size - common_size, &mut rng);
+
+    let mut set1 = common.iter().cloned().chain(private1).collect::<Vec<_>>();
+    set1.shuffle(&mut rng);
+    let mut set2 = common.iter().cloned().chain(private2).collect::<Vec<_>>();
+    set2.shuffle(&mut rng);
+
+    Ok((set1, set2, common))
+}
+
+/// Draw `count` random elements which are neither in `existing` nor duplicated with each other.
+///
+/// Colliding draws are regenerated, so the result is determined by the state of `rng`.
+/// It never returns if fewer than `count` values are left outside `existing` (irrelevant for large fields such as [F128b]).
+pub fn gen_padding<T, RNG>(existing: &HashSet<T>, count: usize, rng: &mut RNG) -> Vec<T>
+where
+    T: Clone + Copy + Eq + std::hash::Hash,
+    RNG: CryptoRng + Rng,
+    Standard: Distribution<T>,
+{
+    let mut fresh = HashSet::with_capacity(count);
+    let mut res = Vec::with_capacity(count);
+    while res.len() < count {
+        let x = rng.gen::<T>();
+        if !existing.contains(&x) && fresh.insert(x) {
+            res.push(x);
+        }
+    }
+
+    res
+}
+
+/// Reduces keys of `width` bits to their highest `bits` bits, so that keys sharing a prefix become the same element.
+///
+/// The reduced keys can be passed to the exact-match protocols as they are. e.g. with [PrefixKey::from_cidr]`("10.0.0.0/8")`,
+/// every IPv4 address in `10.0.0.0/8` reduces to the key of `10.0.0.0`. All parties must use the same `width` and `bits`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PrefixKey {
+    width: u32,
+    bits: u32,
+}
+
+impl PrefixKey {
+    /// Create a prefix reduction. `width` must be in `1..=128` and `bits` must not exceed `width`.
+    pub fn new(width: u32, bits: u32) -> Result<Self> {
+        if width == 0 || width > 128 {
+            bail!("prefix key width (={}) must be in 1..=128.", width);
+        }
+        if bits > width {
+            bail!(
+                "prefix length (={}) must not exceed the key width (={}).",
+                bits,
+                width
+            );
+        }
+        Ok(Self { width, bits })
+    }
+
+    /// Parse an IPv4 CIDR block such as `10.0.0.0/8`. Returns the network address and the prefix reduction of 32-bit keys.
+    pub fn from_cidr(cidr: &str) -> Result<(u128, Self)> {
+        let (addr, bits) = cidr
+            .split_once('/')
+            .ok_or_else(|| anyhow!("{:?} is not a CIDR block: missing '/'.", cidr))?;
+        let addr = parse_ipv4(addr)?;
+        let bits = bits
+            .parse::<u32>()
+            .with_context(|| format!("{:?} has an invalid prefix length.", cidr))?;
+        let prefix = Self::new(32, bits).with_context(|| format!("{:?}", cidr))?;
+
+        Ok((prefix.reduce(addr), prefix))
+    }
+
+    /// Prefix length in bits.
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    /// Width of the keys in bits.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Clear all but the highest `bits` bits of the `width`-bit key `x`. Bits above `width` are cleared too.
+    pub fn reduce(&self, x: u128) -> u128 {
+        let ones = |n: u32| {
+            if n == 128 {
+                u128::MAX
+            } else {
+                (1u128 << n) - 1
+            }
+        };
+        x & ones(self.width) & !ones(self.width - self.bits)
+    }
+
+    /// Reduce `x` and convert it to an element.
+    pub fn key<T: FromU128>(&self, x: u128) -> T {
+        T::from_u128(self.reduce(x))
+    }
+
+    /// Same as [PrefixKey::key], but the prefix length is encoded too (a `1` bit above the `bits` prefix bits),
+    /// so that keys of different prefix lengths never collide. Used by [decompose_range]. `width` must be below 128.
+    pub fn tagged_key<T: FromU128>(&self, x: u128) -> T {
+        debug_assert!(self.width < 128);
+        T::from_u128((1 << self.bits) | (self.reduce(x) >> (self.width - self.bits)))
+    }
+}
+
+/// Decompose the range `[lo, hi]` of `width`-bit keys into the fewest prefix blocks `(network, prefix)` covering it exactly.
+///
+/// At most `2 * width` blocks are returned. `x` is in the range iff `prefix.reduce(x) == network` for some block,
+/// so range membership becomes exact membership of the [tagged keys](PrefixKey::tagged_key) of `x` for every prefix length.
+/// `width` must be in `1..=127` so that tagged keys fit in 128 bits.
+pub fn decompose_range(lo: u128, hi: u128, width: u32) -> Result<Vec<(u128, PrefixKey)>> {
+    if width == 0 || width > 127 {
+        bail!("range key width (={}) must be in 1..=127.", width);
+    }
+    if lo > hi || hi >> width != 0 {
+        bail!(
+            "range [{}, {}] must be non-empty and within {} bits.",
+            lo,
+            hi,
+            width
+        );
+    }
+
+    let mut blocks = Vec::new();
+    let mut lo = lo;
+    loop {
+        // the largest aligned block starting at lo and ending at hi or below.
+        let mut k = if lo == 0 {
+            width
+        } else {
+            lo.trailing_zeros().min(width)
+        };
+        while k > 0 && hi - lo < (1 << k) - 1 {
+            k -= 1;
+        }
+        blocks.push((lo, PrefixKey::new(width, width - k)?));
+
+        let end = lo + ((1 << k) - 1);
+        if end >= hi {
+            break;
+        }
+        lo = end + 1;
+    }
+
+    Ok(blocks)
+}
+
+/// Parse a dotted IPv4 address such as `10.1.2.3` into a 32-bit key.
+pub fn parse_ipv4(addr: &str) -> Result<u128> {
+    let addr = addr
+        .parse::<std::net::Ipv4Addr>()
+        .with_context(|| format!("{:?} is not an IPv4 address.", addr))?;
+    Ok(u32::from(addr) as u128)
+}
+
+// domain separator of [commit_set].
+const COMMIT_DOMAIN: &[u8] = b"preprocessing_mpsi_with_vole/commit_set/v1";
+
+/// Order-independent commitment to a set: SHA-256 of a domain separator, [input_header] and the sorted, deduplicated canonical bytes.
+///
+/// Publish it before the protocol, so that a later dispute can verify the inputs weren't swapped.
+/// It is binding but not hiding: anyone who can guess the whole set can check the guess against it.
+pub fn commit_set(set: &[F128b]) -> [u8; 32] {
+    let mut set = set.to_vec();
+    sort_canonical(&mut set);
+    set.dedup();
+
+    let mut hasher = Sha256::new();
+    hasher.update(COMMIT_DOMAIN);
+    hasher.update(input_header().as_bytes());
+    hasher.update((set.len() as u64).to_le_bytes());
+    for x in set.iter() {
+        hasher.update(x.to_bytes());
+    }
+
+    hasher.finalize().into()
+}
+
+/// Parse one line of input files into a field element.
+pub fn parse_element(line: &str, format: InputFormat) -> Result<F128b> {
+    parse_element_for::<F128b>(line, format)
+}
+
+/// Same as [parse_element], but into an element of `F`.
+///
+/// A decimal out of the range of `F` is an error (See [FromU128::try_from_u128]), not reduced.
+/// [InputFormat::Hash] takes the first bytes of SHA-256 as many as the byte length of `F`,
+/// and [InputFormat::RawHex] takes exactly twice as many hex characters as the byte length.
+pub fn parse_element_for<F: FF + FromU128>(line: &str, format: InputFormat) -> Result<F> {
+    let len = F::ByteReprLen::to_usize();
+    match format {
+        InputFormat::Decimal => {
+            let x = line
+                .parse::<u128>()
+                .map_err(|e| anyhow!("invalid decimal {:?}: {}", line, e))?;
+            F::try_from_u128(x)
+        }
+        InputFormat::Hash => {
+            let digest = Sha256::digest(line.as_bytes());
+            if len > digest.len() {
+                bail!(
+                    "{} bytes of {} are longer than SHA-256",
+                    len,
+                    field_name::<F>()
+                );
+            }
+            Ok(F::from_bytes(GenericArray::from_slice(&digest[..len]))?)
+        }
+        InputFormat::RawHex => {
+            if line.len() != 2 * len || !line.is_ascii() {
+                bail!(
+                    "rawhex must be exactly {} hex characters, but got {} characters",
+                    2 * len,
+                    line.chars().count()
+                );
+            }
+
+            let mut b = vec![0u8; len];
+            for (i, byte) in b.iter_mut().enumerate() {
+                *byte = u8::from_str_radix(&line[2 * i..2 * i + 2], 16)
+                    .map_err(|e| anyhow!("invalid hex {:?}: {}", line, e))?;
+            }
+            Ok(F::from_bytes(GenericArray::from_slice(&b))?)
+        }
+        InputFormat::Bin => bail!("bin inputs are not line-based. Use read_binary_set_for."),
+    }
+}
+
+/// Format a field element as its canonical byte representation in hex. It can be parsed by [InputFormat::RawHex].
+pub fn to_rawhex(x: F128b) -> String {
+    x.to_bytes().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Sort elements by their canonical byte representation, so that the order doesn't depend on the inputs.
+pub fn sort_canonical(set: &mut [F128b]) {
+    set.sort_by_cached_key(|x| x.to_bytes());
+}
+
+/// Format an intersection, one element per line in rawhex (See [to_rawhex]).
+///
+/// If `sort` is true, elements are sorted by [sort_canonical] before formatting and the output is deterministic.
+pub fn format_intersection(mut intersection: Vec<F128b>, sort: bool) -> String {
+    if sort {
+        sort_canonical(&mut intersection);
+    }
+
+    intersection
+        .into_iter()
+        .map(|x| to_rawhex(x) + "\n")
+        .collect()
+}
+
+/// Extension of gzip-compressed input and output files, e.g. `--output intersection.txt.gz`.
+pub const GZIP_EXTENSION: &str = "gz";
+
+// the first bytes of a gzip stream.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Whether `path` ends with [GZIP_EXTENSION].
+pub fn is_gzip_path<P: AsRef<Path>>(path: P) -> bool {
+    path.as_ref()
+        .extension()
+        .is_some_and(|ext| ext == GZIP_EXTENSION)
+}
+
+/// Write an intersection to the file `path` in the format of [format_intersection], compressed by gzip if `compress` is true.
+///
+/// The file can be read back by [load_input_file] with [InputFormat::RawHex], which decompresses it.
+pub fn write_intersection_file<P: AsRef<Path>>(
+    path: P,
+    intersection: Vec<F128b>,
+    compress: bool,
+) -> Result<()> {
+    let path = path.as_ref();
+    let file =
+        File::create(path).with_context(|| format!("Failed to create {}.", path.display()))?;
+    let text = format_intersection(intersection, false);
+
+    let mut writer = BufWriter::new(file);
+    let res = if compress {
+        let mut encoder = GzEncoder::new(writer, Compression::default());
+        encoder
+            .write_all(text.as_bytes())
+            .and_then(|_| encoder.finish())
+            .and_then(|mut writer| writer.flush())
+    } else {
+        writer
+            .write_all(text.as_bytes())
+            .and_then(|_| writer.flush())
+    };
+    res.with_context(|| format!("Failed to write {}.", path.display()))
+}
+
+/// Apply normalizations to a string input in the given order.
+pub fn normalize_line(line: &str, normalize: &[Normalize]) -> String {
+    normalize.iter().fold(line.to_string(), |line, n| match n {
+        Normalize::None => line,
+        Normalize::Lower => line.to_lowercase(),
+        Normalize::Nfc => line.nfc().collect(),
+        Normalize::Trim => line.trim().to_string(),
+        Normalize::Numeric => canonical_numeric(&line).unwrap_or(line),
+    })
+}
+
+// `line` without the sign `+` and the leading zeros if it is a decimal, keeping a single `0` for zero. None for other strings.
+fn canonical_numeric(line: &str) -> Option<String> {
+    let digits = line.strip_prefix('+').unwrap_or(line);
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    match digits.trim_start_matches('0') {
+        "" => Some("0".to_string()),
+        trimmed => Some(trimmed.to_string()),
+    }
+}
+
+/// Result of loading an input set.
+#[derive(Debug, Clone)]
+pub struct InputReport<F = F128b> {
+    /// Deduplicated elements in order of first appearance.
+    pub elements: Vec<F>,
+    /// The number of removed duplicated lines.
+    pub duplicates: usize,
+    /// Parse errors with line numbers (1-origin). For [InputFormat::Bin], the number is the position of the element.
+    pub errors: Vec<(usize, String)>,
+}
+
+/// Prefix of the optional header line of input sets. See [input_header].
+pub const INPUT_HEADER_PREFIX: &str = "#field=";
+
+/// Header line which records the field of an input set. e.g. `#field=F128b/16` (name and byte length).
+pub fn input_header() -> String {
+    format!(
+        "{}{}/{}",
+        INPUT_HEADER_PREFIX,
+        field_name::<F128b>(),
+        <F128b as CanonicalSerialize>::ByteReprLen::to_usize()
+    )
+}
+
+fn check_input_header<F: FF>(line: &str) -> Result<()> {
+    let tag = &line[INPUT_HEADER_PREFIX.len()..];
+    let Some((name, len)) = tag.split_once('/') else {
+        bail!("invalid input header {:?}. @{}:{}", line, file!(), line!());
+    };
+    let len = len
+        .parse::<usize>()
+        .with_context(|| format!("invalid input header {:?}. @{}:{}", line, file!(), line!()))?;
+
+    check_field::<F>(name, len)
+}
+
+/// Load an input set line by line. Lines with only whitespaces are skipped.
+///
+/// If the first line is a header made by [input_header], the field recorded in it is checked,
+/// and [PsiError::FieldMismatch](crate::error::PsiError::FieldMismatch) is returned if it is not [F128b].
+/// Input sets without the header are accepted.
+///
+/// For [InputFormat::Hash], `normalize` is applied to the line before hashing (See [normalize_line]).
+/// The other formats ignore `normalize` and trim the line.
+///
+/// Parse errors don't stop the loading. They are collected into [InputReport::errors].
+/// [InputFormat::Bin] is read by [read_binary_set_for] instead.
+pub fn read_input_set<R: BufRead>(
+    reader: R,
+    format: InputFormat,
+    normalize: &[Normalize],
+) -> Result<InputReport> {
+    read_input_set_for::<F128b, R>(reader, format, normalize)
+}
+
+/// Same as [read_input_set], but into elements of `F` (See [parse_element_for]).
+///
+/// The header must record `F`, and decimals out of the range of `F` are collected into [InputReport::errors] with their line numbers.
+pub fn read_input_set_for<F, R>(
+    reader: R,
+    format: InputFormat,
+    normalize: &[Normalize],
+) -> Result<InputReport<F>>
+where
+    F: FF + FromU128,
+    R: BufRead,
+{
+    if format == InputFormat::Bin {
+        return read_binary_set_for::<F, R>(reader);
+    }
+
+    let mut seen = HashSet::new();
+    let mut report = InputReport {
+        elements: Vec::new(),
+        duplicates: 0,
+        errors: Vec::new(),
+    };
+
+    for (i, line) in reader.lines().enumerate() {
+        let line = line.with_context(|| format!("@{}:{}", file!(), line!()))?;
+        if i == 0 && line.starts_with(INPUT_HEADER_PREFIX) {
+            check_input_header::<F>(line.trim_end())
+                .with_context(|| format!("@{}:{}", file!(), line!()))?;
+            continue;
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let line = match format {
+            InputFormat::Hash => normalize_line(&line, normalize),
+            _ => line.trim().to_string(),
+        };
+
+        match parse_element_for::<F>(&line, format) {
+            Ok(x) => {
+                if seen.insert(x) {
+                    report.elements.push(x);
+                } else {
+                    report.duplicates += 1;
+                }
+            }
+            Err(e) => report.errors.push((i + 1, e.to_string())),
+        }
+    }
+
+    Ok(report)
+}
+
+/// Load an input set of [InputFormat::Bin]: the number of elements `count` (u32, little endian) followed by `count` elements of `F`
+/// in their canonical bytes, parsed by `from_bytes`.
+///
+/// The file size must be exactly `4 + count * byte length of F`, otherwise the whole input is rejected.
+/// Bytes which are not an element are collected into [InputReport::errors] with the position of the element (1-origin).
+pub fn read_binary_set_for<F: FF, R: Read>(mut reader: R) -> Result<InputReport<F>> {
+    let mut bytes = Vec::new();
+    reader
+        .read_to_end(&mut bytes)
+        .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+    if bytes.len() < 4 {
+        bail!(
+            "a bin input must start with the number of elements (u32), but it has {} bytes. @{}:{}",
+            bytes.len(),
+            file!(),
+            line!()
+        );
+    }
+    let count = u32::from_le_bytes(bytes[..4].try_into().unwrap()) as usize;
+    let len = F::ByteReprLen::to_usize();
+    let body = &bytes[4..];
+    if count.checked_mul(len) != Some(body.len()) {
+        bail!(
+            "the bin input declares {} elements of {} bytes, but has {} bytes after the count. @{}:{}",
+            count,
+            len,
+            body.len(),
+            file!(),
+            line!()
+        );
+    }
+
+    let mut seen = HashSet::new();
+    let mut report = InputReport {
+        elements: Vec::with_capacity(count),
+        duplicates: 0,
+        errors: Vec::new(),
+    };
+    for (i, chunk) in body.chunks_exact(len).enumerate() {
+        match F::from_bytes(GenericArray::from_slice(chunk)) {
+            Ok(x) => {
+                if seen.insert(x) {
+                    report.elements.push(x);
+                } else {
+                    report.duplicates += 1;
+                }
+            }
+            Err(e) => report.errors.push((i + 1, e.to_string())),
+        }
+    }
+
+    Ok(report)
+}
+
+/// Apply `policy` to the duplicates counted in `report`. `label` names the input in messages, e.g. the path.
+///
+/// [OnDuplicate::Error] fails if there are duplicates, and [OnDuplicate::Warn] returns the warning to be printed.
+/// The elements of `report` are already deduplicated in any case.
+pub fn check_duplicates(
+    report: &InputReport,
+    label: &str,
+    policy: OnDuplicate,
+) -> Result<Option<String>> {
+    if report.duplicates == 0 {
+        return Ok(None);
+    }
+
+    match policy {
+        OnDuplicate::Dedup => Ok(None),
+        OnDuplicate::Error => bail!(
+            "{}: {} duplicated elements found (--on-duplicate error).",
+            label,
+            report.duplicates
+        ),
+        OnDuplicate::Warn => Ok(Some(format!(
+            "warning: {}: {} duplicated elements removed.",
+            label, report.duplicates
+        ))),
+    }
+}
+
+/// Input path which means the standard input, e.g. `--input -`.
+pub const STDIN_PATH: &str = "-";
+
+/// Whether `path` means the standard input ([STDIN_PATH]).
+pub fn is_stdin<P: AsRef<Path>>(path: P) -> bool {
+    path.as_ref() == Path::new(STDIN_PATH)
+}
+
+/// Load an input set from the file, or from the standard input if `path` is [STDIN_PATH]. See [read_input_set].
+pub fn load_input_file<P: AsRef<Path>>(
+    path: P,
+    format: InputFormat,
+    normalize: &[Normalize],
+) -> Result<InputReport> {
+    load_input_with_stdin(path, std::io::stdin().lock(), format, normalize)
+}
+
+/// Same as [load_input_file], but `stdin` is read instead of the standard input.
+///
+/// The standard input can't be rewound, so it is read only once, parsing and checking each line in a single pass.
+///
+/// A file is decompressed by gzip if its name ends with [GZIP_EXTENSION], or if it starts with the gzip magic bytes
+/// (text formats only, since a count of [InputFormat::Bin] can start with them). The standard input is never decompressed.
+pub fn load_input_with_stdin<P: AsRef<Path>, R: BufRead>(
+    path: P,
+    stdin: R,
+    format: InputFormat,
+    normalize: &[Normalize],
+) -> Result<InputReport> {
+    let path = path.as_ref();
+    if is_stdin(path) {
+        return read_input_set(stdin, format, normalize)
+            .with_context(|| "Failed to read the standard input.");
+    }
+
+    let file = File::open(path).with_context(|| format!("Failed to open {}.", path.display()))?;
+    let mut reader = BufReader::new(file);
+    let gzip = is_gzip_path(path)
+        || (format != InputFormat::Bin
+            && reader
+                .fill_buf()
+                .with_context(|| format!("Failed to read {}.", path.display()))?
+                .starts_with(&GZIP_MAGIC));
+
+    let res = if gzip {
+        read_input_set(
+            BufReader::new(MultiGzDecoder::new(reader)),
+            format,
+            normalize,
+        )
+    } else {
+        read_input_set(reader, format, normalize)
+    };
+    res.with_context(|| format!("Failed to read {}.", path.display()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use scuttlebutt::AesRng;
+
+    #[test]
+    fn test_check_duplicates() {
+        let report =
+            read_input_set("1\n2\n2\n3\n1\n".as_bytes(), InputFormat::Decimal, &[]).unwrap();
+        assert_eq!(report.elements.len(), 3);
+        assert_eq!(report.duplicates, 2);
+
+        assert_eq!(
+            check_duplicates(&report, "a.txt", OnDuplicate::Dedup).unwrap(),
+            None
+        );
+        assert_eq!(
+            check_duplicates(&report, "a.txt", OnDuplicate::Warn).unwrap(),
+            Some("warning: a.txt: 2 duplicated elements removed.".to_string())
+        );
+        assert_eq!(
+            check_duplicates(&report, "a.txt", OnDuplicate::Error)
+                .unwrap_err()
+                .to_string(),
+            "a.txt: 2 duplicated elements found (--on-duplicate error)."
+        );
+
+        // no duplicates pass with any policy.
+        let report = read_input_set("1\n2\n".as_bytes(), InputFormat::Decimal, &[]).unwrap();
+        for policy in [OnDuplicate::Dedup, OnDuplicate::Error, OnDuplicate::Warn] {
+            assert_eq!(check_duplicates(&report, "b.txt", policy).unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn test_gen_padding() {
+        let mut rng = AesRng::from_seed(Block::from(7u128));
+        // dense set: only 6 values of u8 are left.
+        let existing: HashSet<u8> = (0..250).collect();
+
+        let padding = gen_padding(&existing, 6, &mut rng);
+        assert_eq!(padding.len(), 6);
+        assert!(padding.iter().all(|x| !existing.contains(x)));
+        let padding: HashSet<u8> = HashSet::from_iter(padding);
+        assert_eq!(padding, (250..=255).collect());
+
+        let mut rng1 = AesRng::from_seed(Block::from(8u128));
+        let mut rng2 = AesRng::from_seed(Block::from(8u128));
+        assert_eq!(
+            gen_padding(&existing, 3, &mut rng1),
+            gen_padding(&existing, 3, &mut rng2)
+        );
+    }
+
+    #[test]
+    fn test_gen_overlapping_sets() {
+        let (set1, set2, common) = gen_overlapping_sets::<F128b>(100, 30, 11).unwrap();
+        assert_eq!(set1.len(), 100);
+        assert_eq!(set2.len(), 100);
+
+        let set1_hash: HashSet<F128b> = HashSet::from_iter(set1.iter().cloned());
+        let set2_hash: HashSet<F128b> = HashSet::from_iter(set2.iter().cloned());
+        assert_eq!(set1_hash.len(), 100);
+        assert_eq!(set2_hash.len(), 100);
+        let expected: HashSet<F128b> = set1_hash.intersection(&set2_hash).cloned().collect();
+        assert_eq!(HashSet::from_iter(common.iter().cloned()), expected);
+        assert_eq!(common.len(), 30);
+
+        // the same seed reproduces the same sets in the same order.
+        assert_eq!(
+            gen_overlapping_sets::<F128b>(100, 30, 11).unwrap(),
+            (set1, set2, common)
+        );
+        assert_ne!(
+            gen_overlapping_sets::<F128b>(100, 30, 12).unwrap().0,
+            gen_overlapping_sets::<F128b>(100, 30, 11).unwrap().0
+        );
+
+        assert!(gen_overlapping_sets::<F128b>(10, 11, 11).is_err());
+    }
+
+    #[test]
+    fn test_prefix_key() {
+        let (network, prefix) = PrefixKey::from_cidr("10.0.0.0/8").unwrap();
+        assert_eq!(prefix, PrefixKey::new(32, 8).unwrap());
+        assert_eq!(network, 10 << 24);
+
+        for addr in ["10.0.0.0", "10.1.2.3", "10.255.255.255"] {
+            assert_eq!(prefix.reduce(parse_ipv4(addr).unwrap()), network);
+        }
+        for addr in ["11.0.0.0", "9.255.255.255", "192.168.1.1"] {
+            assert_ne!(prefix.reduce(parse_ipv4(addr).unwrap()), network);
+        }
+
+        assert_eq!(
+            PrefixKey::new(128, 128).unwrap().reduce(u128::MAX),
+            u128::MAX
+        );
+        assert_eq!(PrefixKey::new(128, 0).unwrap().reduce(u128::MAX), 0);
+        assert_eq!(
+            prefix.key::<F128b>(parse_ipv4("10.9.8.7").unwrap()),
+            F128b::from_u128(network)
+        );
+
+        assert!(PrefixKey::new(32, 33).is_err());
+        assert!(PrefixKey::new(0, 0).is_err());
+        assert!(PrefixKey::from_cidr("10.0.0.0").is_err());
+        assert!(PrefixKey::from_cidr("10.0.0/8").is_err());
+        assert!(PrefixKey::from_cidr("10.0.0.0/40").is_err());
+    }
+
+    #[test]
+    fn test_decompose_range() {
+        let blocks = decompose_range(100, 200, 32).unwrap();
+        assert!(blocks.len() <= 64);
+        for x in 0..300u128 {
+            let covered = blocks
+                .iter()
+                .any(|(network, prefix)| prefix.reduce(x) == *network);
+            assert_eq!(covered, (100..=200).contains(&x), "{}", x);
+        }
+
+        assert_eq!(
+            decompose_range(0, (1 << 16) - 1, 16).unwrap(),
+            vec![(0, PrefixKey::new(16, 0).unwrap())]
+        );
+        assert_eq!(decompose_range(7, 7, 16).unwrap().len(), 1);
+
+        // keys of different prefix lengths don't collide.
+        let a: F128b = PrefixKey::new(32, 8).unwrap().tagged_key(10 << 24);
+        let b: F128b = PrefixKey::new(32, 32).unwrap().tagged_key(10 << 24);
+        assert_ne!(a, b);
+
+        assert!(decompose_range(200, 100, 32).is_err());
+        assert!(decompose_range(0, 1 << 32, 32).is_err());
+        assert!(decompose_range(0, 1, 128).is_err());
+    }
+
+    #[test]
+    fn test_read_input_set() {
+        let input = "1\n2\n\n2\nabc\n3\n";
+        let report = read_input_set(input.as_bytes(), InputFormat::Decimal, &[]).unwrap();
+
+        assert_eq!(
+            report.elements,
+            vec![
+                F128b::from_u128(1),
+                F128b::from_u128(2),
+                F128b::from_u128(3)
+            ]
+        );
+        assert_eq!(report.duplicates, 1);
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.errors[0].0, 5);
+
+        let report =
+            read_input_set(input.as_bytes(), InputFormat::Hash, &[Normalize::Trim]).unwrap();
+        assert_eq!(report.elements.len(), 4);
+        assert!(report.errors.is_empty());
+    }
+
+    #[test]
+    fn test_read_binary_set() {
+        let text = "5\n1\n340282366920938463463374607431768211455\n1\n42\n";
+        let expected = read_input_set(text.as_bytes(), InputFormat::Decimal, &[]).unwrap();
+
+        let values: [u128; 5] = [5, 1, u128::MAX, 1, 42];
+        let mut bin = (values.len() as u32).to_le_bytes().to_vec();
+        for &v in values.iter() {
+            bin.extend_from_slice(&F128b::from_u128(v).to_bytes());
+        }
+        let path = std::env::temp_dir().join(format!("prep_psi_bin_{}.bin", std::process::id()));
+        std::fs::write(&path, &bin).unwrap();
+        let report = load_input_file(&path, InputFormat::Bin, &[]);
+        std::fs::remove_file(&path).unwrap();
+
+        let report = report.unwrap();
+        assert_eq!(report.elements, expected.elements);
+        assert_eq!(report.duplicates, 1);
+        assert!(report.errors.is_empty());
+
+        // the declared count must match the file size.
+        let mut truncated = bin.clone();
+        truncated.pop();
+        assert!(read_input_set(truncated.as_slice(), InputFormat::Bin, &[]).is_err());
+        let mut longer = bin.clone();
+        longer[0] = 4;
+        assert!(read_input_set(longer.as_slice(), InputFormat::Bin, &[]).is_err());
+        assert!(read_input_set(&bin[..2], InputFormat::Bin, &[]).is_err());
+    }
+
+    #[test]
+    fn test_load_input_stdin() {
+        let stdin = std::io::Cursor::new("3\n1\n\n3\nx\n2\n");
+        let report = load_input_with_stdin(STDIN_PATH, stdin, InputFormat::Decimal, &[]).unwrap();
+        assert_eq!(
+            report.elements,
+            vec![
+                F128b::from_u128(3),
+                F128b::from_u128(1),
+                F128b::from_u128(2)
+            ]
+        );
+        assert_eq!(report.duplicates, 1);
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.errors[0].0, 5);
+
+        // other paths are opened as files, and `stdin` is not touched.
+        assert!(is_stdin("-"));
+        assert!(!is_stdin("./-"));
+        let path = std::env::temp_dir().join(format!("prep_psi_stdin_{}.txt", std::process::id()));
+        std::fs::write(&path, "7\n").unwrap();
+        let report = load_input_with_stdin(
+            &path,
+            std::io::Cursor::new("8\n"),
+            InputFormat::Decimal,
+            &[],
+        );
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(report.unwrap().elements, vec![F128b::from_u128(7)]);
+    }
+
+    #[test]
+    fn test_commit_set() {
+        let mut rng = AesRng::from_seed(Block::from(9u128));
+        let set = (0..50).map(|_| rng.gen::<F128b>()).collect::<Vec<_>>();
+        let mut shuffled = set.clone();
+        shuffled.shuffle(&mut rng);
+        assert_ne!(set, shuffled);
+
+        assert_eq!(commit_set(&set), commit_set(&shuffled));
+
+        // duplicates don't change the set.
+        let mut duplicated = shuffled.clone();
+        duplicated.extend_from_slice(&set[..5]);
+        assert_eq!(commit_set(&set), commit_set(&duplicated));
+
+        // a swapped element changes the commitment.
+        shuffled[0] = rng.gen::<F128b>();
+        assert_ne!(commit_set(&set), commit_set(&shuffled));
+        assert_ne!(commit_set(&set), commit_set(&set[1..]));
+    }
+
+    #[test]
+    fn test_parse_rawhex() {
+        let line = "000102030405060708090a0b0c0d0eFF";
+        let x = parse_element(line, InputFormat::RawHex).unwrap();
+
+        let mut expected = (0u8..15).collect::<Vec<_>>();
+        expected.push(0xff);
+        assert_eq!(x.to_bytes().to_vec(), expected);
+
+        let input = format!("{}\n{}\n", line, "00ff");
+        let report = read_input_set(input.as_bytes(), InputFormat::RawHex, &[]).unwrap();
+        assert_eq!(report.elements, vec![x]);
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.errors[0].0, 2);
+
+        assert!(parse_element("zz0102030405060708090a0b0c0d0e0f", InputFormat::RawHex).is_err());
+    }
+
+    #[test]
+    fn test_input_header() {
+        let input = format!("{}\n1\n2\n", input_header());
+        assert_eq!(input_header(), "#field=F128b/16");
+        let report = read_input_set(input.as_bytes(), InputFormat::Decimal, &[]).unwrap();
+        assert_eq!(report.elements.len(), 2);
+        assert!(report.errors.is_empty());
+
+        let input = "#field=F64b/8\n1\n2\n";
+        let err = read_input_set(input.as_bytes(), InputFormat::Decimal, &[]).unwrap_err();
+        match err.downcast_ref::<crate::error::PsiError>() {
+            Some(crate::error::PsiError::FieldMismatch {
+                found, found_len, ..
+            }) => {
+                assert_eq!(found, "F64b");
+                assert_eq!(*found_len, 8);
+            }
+            _ => panic!("unexpected error: {:?}", err),
+        }
+    }
+
+    #[test]
+    fn test_read_input_set_out_of_range() {
+        let over = (u64::MAX as u128) + 1;
+        let input = format!("#field=F64b/8\n1\n{}\n{}\n", u64::MAX, over);
+        let report =
+            read_input_set_for::<F64b, _>(input.as_bytes(), InputFormat::Decimal, &[]).unwrap();
+
+        // the over-modulus decimal is reported, not reduced to 0.
+        assert_eq!(
+            report.elements,
+            vec![F64b::from_u128(1), F64b::from_u128(u64::MAX as u128)]
+        );
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.errors[0].0, 4);
+        assert!(report.errors[0].1.contains(&over.to_string()));
+
+        assert!(F64b::try_from_u128(over).is_err());
+        assert!(F128b::try_from_u128(u128::MAX).is_ok());
+        assert!(parse_element_for::<F64b>("00000000000000ff", InputFormat::RawHex).is_ok());
+        assert!(parse_element_for::<F64b>(&"0".repeat(32), InputFormat::RawHex).is_err());
+    }
+
+    #[test]
+    fn test_normalize() {
+        let load = |input: &str, normalize: &[Normalize]| {
+            read_input_set(input.as_bytes(), InputFormat::Hash, normalize)
+                .unwrap()
+                .elements
+        };
+
+        let alice = load("Alice\n", &[Normalize::Lower]);
+        assert_eq!(alice, load("alice\n", &[Normalize::Lower]));
+        assert_ne!(
+            load("Alice\n", &[Normalize::None]),
+            load("alice\n", &[Normalize::None])
+        );
+
+        // "é" as one code point and as "e" + combining acute accent.
+        assert_eq!(
+            load("caf\u{e9}\n", &[Normalize::Nfc]),
+            load("cafe\u{301}\n", &[Normalize::Nfc])
+        );
+        assert_eq!(
+            load("  Alice \n", &[Normalize::Trim, Normalize::Lower]),
+            alice
+        );
+        assert_ne!(load("  alice \n", &[Normalize::None]), alice);
+
+        // numeric IDs with leading zeros.
+        let seven = load("7\n", &[Normalize::Trim, Normalize::Numeric]);
+        assert_eq!(
+            load(" 007\n", &[Normalize::Trim, Normalize::Numeric]),
+            seven
+        );
+        assert_eq!(load("+7\n", &[Normalize::Numeric]), seven);
+        assert_ne!(load("007\n", &[Normalize::Trim]), seven);
+        assert_eq!(
+            load("007a\n", &[Normalize::Numeric]),
+            load("007a\n", &[Normalize::None])
+        );
+
+        // the text is canonicalized, so zero and decimals beyond 2^128 match too.
+        let big = "1".repeat(50);
+        assert_eq!(
+            normalize_line(&format!("000{}", big), &[Normalize::Numeric]),
+            big
+        );
+        assert_eq!(normalize_line("000", &[Normalize::Numeric]), "0");
+        assert_eq!(normalize_line("+0", &[Normalize::Numeric]), "0");
+        assert_eq!(normalize_line("+", &[Normalize::Numeric]), "+");
+        assert_eq!(normalize_line("-7", &[Normalize::Numeric]), "-7");
+    }
+
+    #[test]
+    fn test_format_intersection_sorted() {
+        let mut rng = AesRng::new();
+        let intersection = (0..50).map(|_| rng.gen::<F128b>()).collect::<Vec<_>>();
+        let mut shuffled = intersection.clone();
+        shuffled.shuffle(&mut rng);
+
+        let a = format_intersection(intersection.clone(), true);
+        let b = format_intersection(shuffled, true);
+        assert_eq!(a.as_bytes(), b.as_bytes());
+
+        // the output can be read back as rawhex.
+        let report = read_input_set(a.as_bytes(), InputFormat::RawHex, &[]).unwrap();
+        assert!(report.errors.is_empty());
+        let mut sorted = intersection;
+        sort_canonical(&mut sorted);
+        assert_eq!(report.elements, sorted);
+    }
+
+    #[test]
+    fn test_write_intersection_file_gzip() {
+        let mut rng = AesRng::new();
+        let intersection = (0..1000).map(|_| rng.gen::<F128b>()).collect::<Vec<_>>();
+        let dir = std::env::temp_dir();
+        let gz_path = dir.join(format!("prep_psi_output_{}.txt.gz", std::process::id()));
+        let compressed_path = dir.join(format!("prep_psi_output_{}.txt", std::process::id()));
+        assert!(is_gzip_path(&gz_path));
+        assert!(!is_gzip_path(&compressed_path));
+
+        // by the suffix, and by the magic bytes for `--output-compress` without the suffix.
+        write_intersection_file(&gz_path, intersection.clone(), true).unwrap();
+        write_intersection_file(&compressed_path, intersection.clone(), true).unwrap();
+        let bytes = std::fs::read(&gz_path).unwrap();
+        let from_gz = load_input_file(&gz_path, InputFormat::RawHex, &[]);
+        let from_compressed = load_input_file(&compressed_path, InputFormat::RawHex, &[]);
+        std::fs::remove_file(&gz_path).unwrap();
+        std::fs::remove_file(&compressed_path).unwrap();
+
+        assert!(bytes.starts_with(&GZIP_MAGIC));
+        assert!(bytes.len() < format_intersection(intersection.clone(), false).len());
+        for report in [from_gz.unwrap(), from_compressed.unwrap()] {
+            assert!(report.errors.is_empty());
+            assert_eq!(
+                report.elements.into_iter().collect::<HashSet<_>>(),
+                intersection.iter().cloned().collect::<HashSet<_>>()
+            );
+        }
+    }
 
     #[test]
     fn test_small() {
@@ -228,4 +1332,15 @@ mod tests {
         let (_common, _sets): (Vec<F128b>, Vec<Vec<F128b>>) =
             create_sets_without_check(5, 1 << 20, 1 << 10, &mut rng).unwrap();
     }
+
+    #[test]
+    fn test_create_sets_deterministic() {
+        let create = |seed: u128| {
+            let mut rng = AesRng::from_seed(Block::from(seed));
+            create_sets_without_check::<F128b, _>(3, 100, 10, &mut rng).unwrap()
+        };
+
+        assert_eq!(create(1), create(1));
+        assert_ne!(create(1).1, create(2).1);
+    }
 }
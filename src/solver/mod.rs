@@ -13,15 +13,24 @@
 //!
 //! Or, the implementation source code of [PaxosSolver].
 
-use anyhow::Error;
-use rand::{CryptoRng, Rng};
+use crate::error::PsiError;
+use anyhow::{anyhow, Error};
+use rand::{CryptoRng, Rng, SeedableRng};
 use scuttlebutt::field::FiniteField;
-use scuttlebutt::AbstractChannel;
+use scuttlebutt::{AbstractChannel, AesRng, Block};
+use std::collections::HashMap;
+use std::marker::PhantomData;
 pub mod vandelmonde;
 pub use vandelmonde::VandelmondeSolver;
 mod gaussian_eliminations;
+#[cfg(feature = "rayon")]
+pub mod parallel;
 pub mod paxos;
-pub use paxos::PaxosSolver;
+pub use paxos::{HashedPaxosSolver, PaxosSolver};
+#[cfg(feature = "bench")]
+pub mod noop;
+#[cfg(feature = "bench")]
+pub use noop::NoopSolver;
 // mod lu_decomp;
 
 /// Trait for solver parameters.
@@ -29,20 +38,50 @@ pub use paxos::PaxosSolver;
 pub trait SolverParams {
     /// return code length of the solver.
     fn code_length(&self) -> usize;
+
+    /// Serialize parameters into bytes (little endian). See [crate::preprocessed::table] for the concrete layouts.
+    fn to_bytes(&self) -> Vec<u8>;
+
+    /// Deserialize parameters from bytes made by [SolverParams::to_bytes].
+    fn from_bytes(bytes: &[u8]) -> Result<Self, Error>
+    where
+        Self: Sized;
 }
 
 /// Trait for the solver.
 pub trait Solver<FF: FiniteField> {
+    /// Identifier of the solver. It is used to check that both parties use the same solver.
+    ///
+    /// `0` is [VandelmondeSolver], `1` is [PaxosSolver] and `2` is `NoopSolver` (`bench` feature only).
+    /// `3` to `5` are [HashedPaxosSolver] with the other hash families (See [paxos::row_hash]; `4` and `5` are `okvs-hash` feature only).
+    const ID: u8;
+    /// Human readable name of the solver.
+    const NAME: &'static str;
+    /// Whether [Solver::decode] is a sum of entries of the code vector, i.e. their linear combination with coefficients 0 or 1. Default: false.
+    ///
+    /// Then in a field of characteristic 2, the lowest bits of a decoded value depend only on the lowest bits of the entries,
+    /// so the code vector can be sent truncated (See [SepOpprfSenderWithVole::with_wire_bits](crate::preprocessed::opprf::SepOpprfSenderWithVole::with_wire_bits)).
+    const SUM_DECODE: bool = false;
+
     /// Auxillary information for the solver. e.g. shared seeds to create random matrix used in [PaxosSolver].
     /// Auxillary information is decided according to set size.
     type AuxInfo: 'static + Clone + Copy + Send;
     /// Parameters for the solver. e.g. left part length and right part length in code vectors used in [PaxosSolver].
     /// Parameters are decided by the solver on runtime.
     type Params: 'static + Clone + Copy + Send + SolverParams;
+    /// Decoder made by [Solver::prepare_decoder].
+    type Decoder: Decoder<FF>;
 
     /// Generate auxillary information for the solver.
     fn gen_aux<RNG: CryptoRng + Rng>(rng: &mut RNG) -> Result<Self::AuxInfo, Error>;
 
+    /// Derive auxillary information from `seed` shared by both parties, so that neither has to send it by [Solver::aux_send].
+    ///
+    /// The default implementation runs [Solver::gen_aux] with an [AesRng] seeded by `seed`, so the same seed gives the same auxillary information.
+    fn gen_aux_from_seed(seed: Block) -> Result<Self::AuxInfo, Error> {
+        Self::gen_aux(&mut AesRng::from_seed(seed))
+    }
+
     /// Send auxillary information for another party.
     fn aux_send<C: AbstractChannel, RNG: CryptoRng + Rng>(
         channel: &mut C,
@@ -56,6 +95,12 @@ pub trait Solver<FF: FiniteField> {
         rng: &mut RNG,
     ) -> Result<Self::AuxInfo, Error>;
 
+    /// Serialize auxillary information into bytes (little endian).
+    fn aux_to_bytes(aux: Self::AuxInfo) -> Vec<u8>;
+
+    /// Deserialize auxillary information from bytes made by [Solver::aux_to_bytes].
+    fn aux_from_bytes(bytes: &[u8]) -> Result<Self::AuxInfo, Error>;
+
     /// Calculate parameters for the solver according to set size.
     fn calc_params(n: usize) -> Self::Params;
 
@@ -67,6 +112,223 @@ pub trait Solver<FF: FiniteField> {
         params: Self::Params,
     ) -> Result<Vec<FF>, Error>;
 
+    /// Same as [Solver::encode], but the work which can be split is done in `pool` (the global pool of rayon if `None`).
+    ///
+    /// The result is the same as [Solver::encode] with the same `rng`. The default implementation is sequential.
+    #[cfg(feature = "rayon")]
+    fn encode_in<RNG: CryptoRng + Rng>(
+        _pool: Option<&rayon::ThreadPool>,
+        rng: &mut RNG,
+        points: &[(FF, FF)],
+        aux: Self::AuxInfo,
+        params: Self::Params,
+    ) -> Result<Vec<FF>, Error> {
+        Self::encode(rng, points, aux, params)
+    }
+
     /// Decode code vector $`P`$ and value $`x \in \mathbb{F}`$ into value $`y \in \mathbb{F}`$ which corresponds to $`x`$.
     fn decode(p: &[FF], x: FF, aux: Self::AuxInfo, params: Self::Params) -> Result<FF, Error>;
+
+    /// Prepare a decoder of code vector $`P`$ for repeated queries.
+    ///
+    /// The decoder returns the same values as [Solver::decode] with the same arguments, reusing the state computed here.
+    fn prepare_decoder(p: &[FF], aux: Self::AuxInfo, params: Self::Params) -> Self::Decoder;
+
+    /// Find pairs of keys which can't be encoded together under `aux` whatever the code vector is.
+    ///
+    /// The default implementation finds only the same keys with different values.
+    fn find_collisions(
+        points: &[(FF, FF)],
+        _aux: Self::AuxInfo,
+        _params: Self::Params,
+    ) -> Vec<(FF, FF)> {
+        let mut seen = HashMap::new();
+        let mut res = Vec::new();
+        for &(x, y) in points.iter() {
+            match seen.get(&x) {
+                Some(&y_dash) if y_dash != y => res.push((x, x)),
+                Some(_) => {}
+                None => {
+                    seen.insert(x, y);
+                }
+            }
+        }
+        res
+    }
+}
+
+/// Decoder of a fixed code vector. See [Solver::prepare_decoder].
+pub trait Decoder<FF: FiniteField>: Send {
+    /// Decode value $`x \in \mathbb{F}`$ into value $`y \in \mathbb{F}`$ which corresponds to $`x`$.
+    fn decode(&self, x: FF) -> Result<FF, Error>;
+}
+
+/// Decoder which just keeps the arguments of [Solver::decode]. It is for solvers without state worth precomputing.
+pub struct StatelessDecoder<FF: FiniteField, S: Solver<FF>> {
+    p: Vec<FF>,
+    aux: S::AuxInfo,
+    params: S::Params,
+    _s: PhantomData<fn() -> S>,
+}
+
+impl<FF: FiniteField, S: Solver<FF>> StatelessDecoder<FF, S> {
+    /// Keep (a copy of) the arguments.
+    pub fn new(p: &[FF], aux: S::AuxInfo, params: S::Params) -> Self {
+        Self {
+            p: p.to_vec(),
+            aux,
+            params,
+            _s: PhantomData,
+        }
+    }
+}
+
+impl<FF: FiniteField, S: Solver<FF>> Decoder<FF> for StatelessDecoder<FF, S> {
+    fn decode(&self, x: FF) -> Result<FF, Error> {
+        S::decode(&self.p, x, self.aux, self.params)
+    }
+}
+
+/// Encode points with a fresh auxillary information, regenerating it up to `attempts` times on failure.
+///
+/// If all attempts fail and [Solver::find_collisions] finds colliding keys under the last auxillary information,
+/// [PsiError::KeyHashCollision] is returned. Otherwise the last encode error is returned, which means bad luck and it is retryable.
+pub fn encode_with_retry<FF, S, RNG>(
+    rng: &mut RNG,
+    points: &[(FF, FF)],
+    params: S::Params,
+    attempts: usize,
+) -> Result<(Vec<FF>, S::AuxInfo), Error>
+where
+    FF: FiniteField,
+    S: Solver<FF>,
+    RNG: CryptoRng + Rng,
+{
+    encode_with_retry_hook::<FF, S, _>(rng, points, params, attempts, None)
+}
+
+/// Same as [encode_with_retry], but `on_retry` is called with the attempt number (`1` for the first retry) before each retry.
+pub fn encode_with_retry_hook<FF, S, RNG>(
+    rng: &mut RNG,
+    points: &[(FF, FF)],
+    params: S::Params,
+    attempts: usize,
+    on_retry: Option<&dyn Fn(u32)>,
+) -> Result<(Vec<FF>, S::AuxInfo), Error>
+where
+    FF: FiniteField,
+    S: Solver<FF>,
+    RNG: CryptoRng + Rng,
+{
+    encode_with_retry_by::<FF, S, _, _>(rng, points, params, attempts, on_retry, |rng, aux| {
+        S::encode(rng, points, aux, params)
+    })
+}
+
+// retry loop of encode_with_retry_hook, with the encode function replaceable (e.g. by Solver::encode_in).
+pub(crate) fn encode_with_retry_by<FF, S, RNG, E>(
+    rng: &mut RNG,
+    points: &[(FF, FF)],
+    params: S::Params,
+    attempts: usize,
+    on_retry: Option<&dyn Fn(u32)>,
+    mut encode: E,
+) -> Result<(Vec<FF>, S::AuxInfo), Error>
+where
+    FF: FiniteField,
+    S: Solver<FF>,
+    RNG: CryptoRng + Rng,
+    E: FnMut(&mut RNG, S::AuxInfo) -> Result<Vec<FF>, Error>,
+{
+    let mut last_err = anyhow!("no attempt to encode.");
+    let mut last_aux = None;
+    for attempt in 0..attempts {
+        if attempt > 0 {
+            if let Some(on_retry) = on_retry {
+                on_retry(attempt as u32);
+            }
+        }
+        let aux = S::gen_aux(rng)?;
+        match encode(rng, aux) {
+            Ok(p) => return Ok((p, aux)),
+            Err(e) => {
+                last_err = e;
+                last_aux = Some(aux);
+            }
+        }
+    }
+
+    if let Some(aux) = last_aux {
+        let collisions = S::find_collisions(points, aux, params);
+        if !collisions.is_empty() {
+            let keys = collisions
+                .into_iter()
+                .map(|(x1, x2)| (format!("{:?}", x1), format!("{:?}", x2)))
+                .collect();
+            return Err(PsiError::KeyHashCollision { keys }.into());
+        }
+    }
+
+    Err(last_err.context(format!(
+        "encoding failed {} times by bad luck (retryable). @{}:{}",
+        attempts,
+        file!(),
+        line!()
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use paxos::PaxosSolverParams;
+    use rand::SeedableRng;
+    use scuttlebutt::{field::F128b, AesRng, Block};
+
+    #[test]
+    fn test_encode_with_retry_reports_collision() {
+        let mut rng = AesRng::from_seed(Block::from(42u128));
+        let points = (0..20)
+            .map(|_| (rng.gen::<F128b>(), rng.gen::<F128b>()))
+            .collect::<Vec<_>>();
+
+        let params = PaxosSolverParams::new(3, 1);
+        let err = encode_with_retry::<F128b, PaxosSolver<F128b>, _>(&mut rng, &points, params, 2)
+            .unwrap_err();
+
+        let Some(PsiError::KeyHashCollision { keys }) = err.downcast_ref::<PsiError>() else {
+            panic!("unexpected error: {:?}", err);
+        };
+        assert!(!keys.is_empty());
+        let names = points
+            .iter()
+            .map(|(x, _)| format!("{:?}", x))
+            .collect::<Vec<_>>();
+        for (x1, x2) in keys.iter() {
+            assert!(names.contains(x1));
+            assert!(names.contains(x2));
+        }
+
+        let params = PaxosSolver::<F128b>::calc_params(points.len());
+        assert!(
+            encode_with_retry::<F128b, PaxosSolver<F128b>, _>(&mut rng, &points, params, 2).is_ok()
+        );
+    }
+
+    #[test]
+    fn test_default_find_collisions() {
+        let mut rng = AesRng::new();
+        let x = rng.gen::<F128b>();
+        let points = vec![
+            (x, F128b::zero()),
+            (rng.gen(), F128b::zero()),
+            (x, F128b::one()),
+        ];
+
+        let collisions = VandelmondeSolver::<F128b>::find_collisions(
+            &points,
+            (),
+            VandelmondeSolver::<F128b>::calc_params(3),
+        );
+        assert_eq!(collisions, vec![(x, x)]);
+    }
 }
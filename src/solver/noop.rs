@@ -0,0 +1,184 @@
+//! A solver which encodes nothing, for OPRF-only benchmarking. Enabled by the `bench` feature.
+//!
+//! # WARNING: IT PRODUCES WRONG INTERSECTIONS
+//!
+//! [NoopSolver] doesn't encode the points at all: [encode](Solver::encode) returns a code vector of zeros and
+//! [decode](Solver::decode) returns zero for any value. Its code length is the same as that of [PaxosSolver] for the set size,
+//! so the VOLE is as long and every message (the OPRF message and the programmed table) is as large as with [PaxosSolver].
+//!
+//! What is removed is the computation of the OKVS: the encode and the decode, both in the OPPRF and in the OPRF,
+//! whose evaluation decodes the VOLE correlation by the solver. So the time measured with it is the VOLE, the communication
+//! and the hashing of the OPRF. Subtract it from the time with [PaxosSolver] to get the solver cost.
+//! **The outputs are meaningless, so never use it for anything but timing.**
+
+use super::paxos::PaxosSolverParams;
+use super::*;
+use anyhow::{bail, Error};
+use rand::{CryptoRng, Rng};
+use scuttlebutt::field::FiniteField;
+use scuttlebutt::AbstractChannel;
+use std::marker::PhantomData;
+
+/// Solver which encodes nothing. **It produces wrong results.** See the [module document](crate::solver::noop).
+pub struct NoopSolver<FF: FiniteField>(PhantomData<FF>);
+
+/// Parameters for NoopSolver. It keeps the set size, and the code length is that of [PaxosSolver] for it.
+#[derive(Clone, Copy)]
+pub struct NoopSolverParams(usize);
+
+impl SolverParams for NoopSolverParams {
+    fn code_length(&self) -> usize {
+        PaxosSolverParams::for_set_size(self.0).code_length()
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        (self.0 as u64).to_le_bytes().to_vec()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let Ok(b) = <[u8; 8]>::try_from(bytes) else {
+            bail!(
+                "invalid length of params bytes (={}) @{}:{}",
+                bytes.len(),
+                file!(),
+                line!()
+            );
+        };
+
+        Ok(NoopSolverParams(u64::from_le_bytes(b) as usize))
+    }
+}
+
+impl<FF: FiniteField> Solver<FF> for NoopSolver<FF> {
+    const ID: u8 = 2;
+    const NAME: &'static str = "Noop";
+    /// The decoded value is the empty sum.
+    const SUM_DECODE: bool = true;
+
+    type AuxInfo = ();
+    type Params = NoopSolverParams;
+    type Decoder = StatelessDecoder<FF, Self>;
+
+    fn gen_aux<RNG: CryptoRng + Rng>(_rng: &mut RNG) -> Result<Self::AuxInfo, Error> {
+        Ok(())
+    }
+
+    fn aux_send<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        _channel: &mut C,
+        _rng: &mut RNG,
+        _aux: Self::AuxInfo,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn aux_receive<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        _channel: &mut C,
+        _rng: &mut RNG,
+    ) -> Result<Self::AuxInfo, Error> {
+        Ok(())
+    }
+
+    fn aux_to_bytes(_aux: Self::AuxInfo) -> Vec<u8> {
+        Vec::new()
+    }
+
+    fn aux_from_bytes(bytes: &[u8]) -> Result<Self::AuxInfo, Error> {
+        if !bytes.is_empty() {
+            bail!(
+                "invalid length of aux bytes (={}) @{}:{}",
+                bytes.len(),
+                file!(),
+                line!()
+            );
+        }
+
+        Ok(())
+    }
+
+    fn calc_params(n: usize) -> NoopSolverParams {
+        NoopSolverParams(n)
+    }
+
+    /// Return a code vector of zeros. The points are ignored.
+    fn encode<RNG: CryptoRng + Rng>(
+        _rng: &mut RNG,
+        _points: &[(FF, FF)],
+        _aux: (),
+        params: Self::Params,
+    ) -> Result<Vec<FF>, Error> {
+        Ok(vec![FF::zero(); params.code_length()])
+    }
+
+    /// Return zero whatever `x` is.
+    fn decode(_p: &[FF], _x: FF, _aux: (), _params: Self::Params) -> Result<FF, Error> {
+        Ok(FF::zero())
+    }
+
+    fn prepare_decoder(p: &[FF], aux: (), params: Self::Params) -> Self::Decoder {
+        StatelessDecoder::new(p, aux, params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channel_utils::channel_from;
+    use crate::preprocessed::opprf::{
+        SepOpprfReceiver, SepOpprfReceiverWithVole, SepOpprfSender, SepOpprfSenderWithVole,
+    };
+    use crate::vole::{LPNVoleReceiver, LPNVoleSender, LPN_EXTEND_SMALL, LPN_SETUP_SMALL};
+    use scuttlebutt::field::F128b;
+    use scuttlebutt::AesRng;
+    use std::collections::HashMap;
+    use std::os::unix::net::UnixStream;
+
+    #[test]
+    fn test_noop_solver_opprf() {
+        let mut rng = AesRng::new();
+        let set = (0..20).map(|_| rng.gen()).collect::<Vec<F128b>>();
+        let points = set
+            .iter()
+            .map(|&x| (x, rng.gen()))
+            .collect::<Vec<(F128b, F128b)>>();
+        let programmed: HashMap<F128b, F128b> = HashMap::from_iter(points.iter().cloned());
+
+        let (s, r) = UnixStream::pair().unwrap();
+        let points_for_s = points.clone();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let mut channel = channel_from(s).unwrap();
+            let sender = SepOpprfSenderWithVole::<F128b, NoopSolver<F128b>, _>::precomp(
+                &mut channel,
+                &mut rng,
+                points_for_s.len(),
+                LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+            )
+            .unwrap();
+            // the VOLE is as long as for PaxosSolver.
+            assert_eq!(
+                sender.vole_length(),
+                PaxosSolver::<F128b>::calc_params(points_for_s.len()).code_length()
+            );
+            sender
+                .send(&mut channel, &points_for_s, points_for_s.len(), &mut rng)
+                .unwrap();
+        });
+
+        let mut channel = channel_from(r).unwrap();
+        let receiver = SepOpprfReceiverWithVole::<F128b, NoopSolver<F128b>, _>::precomp(
+            &mut channel,
+            &mut rng,
+            set.len(),
+            LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+        )
+        .unwrap();
+
+        // the round trip runs...
+        let received = receiver.receive(&mut channel, &set, &mut rng).unwrap();
+        handle.join().unwrap();
+        assert_eq!(received.len(), set.len());
+
+        // ...but the whole set is programmed and none of the programmed values is recovered.
+        assert!(received.iter().all(|(x, y)| programmed[x] != *y));
+    }
+}
@@ -0,0 +1,146 @@
+//! Parallel paths of solvers using [rayon] (enabled by the `rayon` feature).
+//!
+//! Every function takes an optional [ThreadPool]. If `None` is given, the global pool of rayon is used.
+//! When this library is embedded in an application which also uses rayon, pass a dedicated pool to isolate the PSI work
+//! from the global pool.
+
+use super::Solver;
+use anyhow::{Context, Error};
+use rand::{CryptoRng, Rng};
+use rayon::prelude::*;
+use rayon::ThreadPool;
+use scuttlebutt::field::FiniteField;
+
+pub(crate) fn install<R, OP>(pool: Option<&ThreadPool>, op: OP) -> R
+where
+    R: Send,
+    OP: FnOnce() -> R + Send,
+{
+    match pool {
+        Some(pool) => pool.install(op),
+        None => op(),
+    }
+}
+
+/// Encode points in `pool`. See [Solver::encode_in].
+pub fn encode_in<FF, S, RNG>(
+    pool: Option<&ThreadPool>,
+    rng: &mut RNG,
+    points: &[(FF, FF)],
+    aux: S::AuxInfo,
+    params: S::Params,
+) -> Result<Vec<FF>, Error>
+where
+    FF: FiniteField,
+    S: Solver<FF>,
+    RNG: CryptoRng + Rng,
+{
+    S::encode_in(pool, rng, points, aux, params)
+        .with_context(|| format!("@{}:{}", file!(), line!()))
+}
+
+/// Same as [encode_with_retry_hook](super::encode_with_retry_hook), but each attempt is encoded in `pool`.
+pub fn encode_with_retry_in<FF, S, RNG>(
+    pool: Option<&ThreadPool>,
+    rng: &mut RNG,
+    points: &[(FF, FF)],
+    params: S::Params,
+    attempts: usize,
+    on_retry: Option<&dyn Fn(u32)>,
+) -> Result<(Vec<FF>, S::AuxInfo), Error>
+where
+    FF: FiniteField,
+    S: Solver<FF>,
+    RNG: CryptoRng + Rng,
+{
+    super::encode_with_retry_by::<FF, S, _, _>(
+        rng,
+        points,
+        params,
+        attempts,
+        on_retry,
+        |rng, aux| S::encode_in(pool, rng, points, aux, params),
+    )
+}
+
+/// Decode values of all `xs` in parallel in `pool`. See [Solver::decode].
+pub fn decode_batch<FF, S>(
+    pool: Option<&ThreadPool>,
+    p: &[FF],
+    xs: &[FF],
+    aux: S::AuxInfo,
+    params: S::Params,
+) -> Result<Vec<FF>, Error>
+where
+    FF: FiniteField,
+    S: Solver<FF>,
+    S::AuxInfo: Sync,
+    S::Params: Sync,
+{
+    install(pool, move || {
+        xs.par_iter()
+            .map(|&x| S::decode(p, x, aux, params))
+            .collect::<Result<Vec<_>, Error>>()
+    })
+    .with_context(|| format!("@{}:{}", file!(), line!()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::PaxosSolver;
+    use rand::SeedableRng;
+    use rayon::ThreadPoolBuilder;
+    use scuttlebutt::{field::F128b, AesRng, Block};
+
+    #[test]
+    fn test_encode_decode_in_custom_pool() {
+        let pool = ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+
+        let mut rng = AesRng::new();
+        let points = (0..1000)
+            .map(|_| (rng.gen::<F128b>(), rng.gen::<F128b>()))
+            .collect::<Vec<_>>();
+        let xs = points.iter().map(|(x, _)| *x).collect::<Vec<_>>();
+
+        let params = PaxosSolver::<F128b>::calc_params(points.len());
+        let aux = PaxosSolver::<F128b>::gen_aux(&mut rng).unwrap();
+
+        let p = encode_in::<_, PaxosSolver<F128b>, _>(Some(&pool), &mut rng, &points, aux, params)
+            .unwrap();
+        let ys = decode_batch::<_, PaxosSolver<F128b>>(Some(&pool), &p, &xs, aux, params).unwrap();
+        let ys_global = decode_batch::<_, PaxosSolver<F128b>>(None, &p, &xs, aux, params).unwrap();
+
+        let expected = points.iter().map(|(_, y)| *y).collect::<Vec<_>>();
+        assert_eq!(ys, expected);
+        assert_eq!(ys_global, expected);
+    }
+
+    #[test]
+    fn test_encode_in_matches_sequential_encode() {
+        let pool = ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+
+        let mut rng = AesRng::new();
+        let points = (0..1000)
+            .map(|_| (rng.gen::<F128b>(), rng.gen::<F128b>()))
+            .collect::<Vec<_>>();
+
+        let params = PaxosSolver::<F128b>::calc_params(points.len());
+        let aux = PaxosSolver::<F128b>::gen_aux(&mut rng).unwrap();
+
+        let seed = rng.gen::<Block>();
+        let p_seq =
+            PaxosSolver::<F128b>::encode(&mut AesRng::from_seed(seed), &points, aux, params)
+                .unwrap();
+        let p_par = encode_in::<_, PaxosSolver<F128b>, _>(
+            Some(&pool),
+            &mut AesRng::from_seed(seed),
+            &points,
+            aux,
+            params,
+        )
+        .unwrap();
+
+        assert_eq!(p_par, p_seq);
+    }
+}
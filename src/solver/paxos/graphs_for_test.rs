@@ -203,6 +203,7 @@ fn new_edge_with_point(
 ) -> Rc<RefCell<Edge<F128b>>> {
     let res = Rc::new(RefCell::new(Edge {
         point,
+        bits: None,
         visit_status: NotVisited,
         back_edge: false,
     }));
@@ -250,6 +251,7 @@ pub(crate) fn create_specific_graph_self_loop(
 
             let edge = Rc::new(RefCell::new(Edge {
                 point: (x, y),
+                bits: None,
                 visit_status: NotVisited,
                 back_edge: false,
             }));
@@ -322,6 +324,7 @@ pub(crate) fn create_specific_graph_straight_with_self_loop(
 
     let self_loop_edge = Rc::new(RefCell::new(Edge {
         point: (x, y),
+        bits: None,
         visit_status: NotVisited,
         back_edge: false,
     }));
@@ -341,6 +344,7 @@ pub(crate) fn create_specific_graph_straight_with_self_loop(
 
         let self_loop_edge = Rc::new(RefCell::new(Edge {
             point: (x, y),
+            bits: None,
             visit_status: NotVisited,
             back_edge: false,
         }));
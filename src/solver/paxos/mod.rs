@@ -38,6 +38,8 @@
 //!
 //! This solver is DFS based one. So we use $`m = (2.01 \cdot n) + (\log n + 40)`$
 //!
+//! The rows of the keys are hashed by keyed SHA-256 in [PaxosSolver]. [HashedPaxosSolver] takes the other hash families in [row_hash].
+//!
 //! See the appendix B and figure 7 in full version of "PSI from PaXoS: Fast, Malicious Private Set Intersection"
 //! @ <https://eprint.iacr.org/2020/193>
 //!
@@ -96,41 +98,45 @@ use rand::distributions::{Distribution, Standard};
 use rand::{CryptoRng, Rng};
 use scuttlebutt::field::FiniteField as FF;
 use scuttlebutt::AbstractChannel;
-use sha2::{Digest, Sha256};
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::marker::PhantomData;
 use std::rc::{Rc, Weak};
 
-// H_i: key x F -> [m]
+pub mod row_hash;
+pub use row_hash::{AesRowHash, RowHash, Sha256RowHash};
+#[cfg(feature = "okvs-hash")]
+pub use row_hash::{Blake3RowHash, SipRowHash};
+
+// hashers of the family `H` which have already absorbed the keys.
+#[inline]
+fn keyed_hashers<H: RowHash>(keys: (u64, u64, u64)) -> [H::Keyed; 3] {
+    let (k1, k2, k3) = keys;
+    [H::keyed(k1), H::keyed(k2), H::keyed(k3)]
+}
+
 #[inline]
-fn hash2index<F: FF>(k: u64, x: F, max: usize) -> usize {
-    let mut hasher = Sha256::new();
-    hasher.update(k.to_be_bytes());
-    hasher.update(x.to_bytes());
-    let res = hasher.finalize();
-    let res = res.as_slice();
-    let res = res[0..8].try_into().unwrap();
+fn digest2index(digest: &[u8], max: usize) -> usize {
+    let res = digest[0..8].try_into().unwrap();
     let res = u64::from_be_bytes(res);
     (res as usize) % max
 }
 
 // r: key x F -> {0, 1}^r_size
 #[inline]
-fn r<F: FF>(k: u64, x: F, m: usize) -> Vec<bool> {
-    let mut hasher = Sha256::new();
-    hasher.update(k.to_be_bytes());
-    hasher.update(x.to_bytes());
-    let res = hasher.finalize();
+fn r<H: RowHash, F: FF>(k: u64, x: F, m: usize) -> Vec<bool> {
+    digest2bits(&H::digest(&H::keyed(k), &x.to_bytes()), m).collect()
+}
 
-    res.iter()
+#[inline]
+fn digest2bits(digest: &[u8], m: usize) -> impl Iterator<Item = bool> + '_ {
+    digest
+        .iter()
         .flat_map(|&byte| (0..8).map(move |i| byte & (1 << i) != 0))
         .take(m)
-        .collect()
 }
 
-fn calc_r_inner_product<F: FF>(x: F, vec_r: &[F], k3: u64, r_size: usize) -> F {
-    let bits = r(k3, x, r_size);
-
+fn bits_inner_product<F: FF>(bits: &[bool], vec_r: &[F]) -> F {
     let mut sum = F::zero();
     for (i, b) in bits.iter().enumerate() {
         if *b {
@@ -141,13 +147,35 @@ fn calc_r_inner_product<F: FF>(x: F, vec_r: &[F], k3: u64, r_size: usize) -> F {
     sum
 }
 
+// row of a point: the two positions of L and the bits for R. Rows are independent of each other, so they can be hashed in parallel.
+struct Row {
+    i: usize,
+    j: usize,
+    bits: Vec<bool>,
+}
+
+// H_1, H_2: key x F -> [|L|] and r: key x F -> {0, 1}^r_size by the hashers of `keyed_hashers`.
+fn calc_row<H: RowHash, F: FF>(x: F, hashers: &[H::Keyed; 3], params: PaxosSolverParams) -> Row {
+    let x = x.to_bytes();
+    let [h1, h2, h3] = hashers;
+    Row {
+        i: digest2index(&H::digest(h1, &x), params.l_size),
+        j: digest2index(&H::digest(h2, &x), params.l_size),
+        bits: digest2bits(&H::digest(h3, &x), params.r_size).collect(),
+    }
+}
+
 /// Solver for PaXoS algorithm.
 ///
 /// Please look the parent document ( [crate::solver::paxos] ) for usage example.
-pub struct PaxosSolver<F>(PhantomData<F>)
+pub type PaxosSolver<F> = HashedPaxosSolver<F, Sha256RowHash>;
+
+/// [PaxosSolver] whose rows are hashed by the family `H`. See [row_hash] for the families.
+pub struct HashedPaxosSolver<F, H>(PhantomData<(F, H)>)
 where
     F: FF,
-    Standard: Distribution<F>;
+    Standard: Distribution<F>,
+    H: RowHash;
 
 /// Parameters for PaXoS solver. It contains $`|L|`$ and $`|R|`$.
 #[derive(Clone, Copy)]
@@ -156,21 +184,67 @@ pub struct PaxosSolverParams {
     r_size: usize,
 }
 
+impl PaxosSolverParams {
+    /// Create parameters with explicit $`|L|`$ and $`|R|`$.
+    pub fn new(l_size: usize, r_size: usize) -> Self {
+        Self { l_size, r_size }
+    }
+
+    /// Parameters for `n` points, the same as [calc_params](Solver::calc_params) of [PaxosSolver], which is independent of the field.
+    pub fn for_set_size(n: usize) -> Self {
+        let l_size = 2 * n + n / 100;
+        let logn = n.next_power_of_two().trailing_zeros() as usize;
+        let r_size = logn + 40;
+
+        Self { l_size, r_size }
+    }
+}
+
 impl SolverParams for PaxosSolverParams {
     fn code_length(&self) -> usize {
         self.l_size + self.r_size
     }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut res = (self.l_size as u64).to_le_bytes().to_vec();
+        res.extend((self.r_size as u64).to_le_bytes());
+        res
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != 16 {
+            bail!(
+                "invalid length of params bytes (={}) @{}:{}",
+                bytes.len(),
+                file!(),
+                line!()
+            );
+        }
+
+        let l_size = u64::from_le_bytes(bytes[0..8].try_into().unwrap()) as usize;
+        let r_size = u64::from_le_bytes(bytes[8..16].try_into().unwrap()) as usize;
+
+        Ok(PaxosSolverParams { l_size, r_size })
+    }
 }
 
-impl<F> Solver<F> for PaxosSolver<F>
+impl<F, H> Solver<F> for HashedPaxosSolver<F, H>
 where
     F: FF,
     Standard: Distribution<F>,
+    H: RowHash,
 {
+    const ID: u8 = H::SOLVER_ID;
+    const NAME: &'static str = H::SOLVER_NAME;
+    /// $`P(x)`$ is $`L_{h_1(x)} + L_{h_2(x)} + \langle r(x), R \rangle`$ with the bits $`r(x)`$.
+    const SUM_DECODE: bool = true;
+
     /// Keys for hash functions. Keys are generated randomly by [gen_aux](PaxosSolver::gen_aux).
     type AuxInfo = (u64, u64, u64);
     /// PaxosSolver Parameters consists of $`|L|`$ and $`|R|`$.
     type Params = PaxosSolverParams;
+    /// Keeps the hashers keyed by the auxillary information and $`P`$ split into $`L`$ and $`R`$.
+    type Decoder = PaxosDecoder<F, H>;
 
     fn gen_aux<RNG: CryptoRng + Rng>(rng: &mut RNG) -> Result<Self::AuxInfo> {
         let k1 = rng.gen::<u64>();
@@ -216,12 +290,33 @@ where
         Ok((k1, k2, k3))
     }
 
-    fn calc_params(n: usize) -> PaxosSolverParams {
-        let l_size = 2 * n + n / 100;
-        let logn = n.next_power_of_two().trailing_zeros() as usize;
-        let r_size = logn + 40;
+    fn aux_to_bytes(aux: Self::AuxInfo) -> Vec<u8> {
+        let (k1, k2, k3) = aux;
+        let mut res = k1.to_le_bytes().to_vec();
+        res.extend(k2.to_le_bytes());
+        res.extend(k3.to_le_bytes());
+        res
+    }
+
+    fn aux_from_bytes(bytes: &[u8]) -> Result<Self::AuxInfo> {
+        if bytes.len() != 24 {
+            bail!(
+                "invalid length of aux bytes (={}) @{}:{}",
+                bytes.len(),
+                file!(),
+                line!()
+            );
+        }
+
+        let k1 = u64::from_le_bytes(bytes[0..8].try_into().unwrap());
+        let k2 = u64::from_le_bytes(bytes[8..16].try_into().unwrap());
+        let k3 = u64::from_le_bytes(bytes[16..24].try_into().unwrap());
 
-        PaxosSolverParams { l_size, r_size }
+        Ok((k1, k2, k3))
+    }
+
+    fn calc_params(n: usize) -> PaxosSolverParams {
+        PaxosSolverParams::for_set_size(n)
     }
 
     /// Encode points to a code vector.
@@ -233,53 +328,108 @@ where
         aux: (u64, u64, u64),
         params: Self::Params,
     ) -> Result<Vec<F>> {
-        // 1. Construct the Cuckoo graph $G_{h_1, h_2, X}$ for $X = \{x_1, \ldots, x_n\}$.
-        let graph = construct_cuckoo_graph(points, aux, params);
+        let hashers = keyed_hashers::<H>(aux);
+        let rows = points
+            .iter()
+            .map(|&(x, _)| calc_row::<H, F>(x, &hashers, params))
+            .collect::<Vec<_>>();
+        encode_with_rows::<H, F, RNG>(rng, points, rows, aux, params)
+    }
 
-        // 2. Initialize variables $L$ and $R$ and an initialliy empty set of linear constraints S.
-        let mut vec_l: Vec<F> = (0..params.l_size).map(|_| rng.gen()).collect::<Vec<_>>();
-        let mut vec_r: Vec<F> = (0..params.r_size).map(|_| rng.gen()).collect::<Vec<_>>();
+    /// Same as [encode](Solver::encode), but the rows of the points (the hashing, which dominates the encode) are computed in parallel in `pool`.
+    /// The DFS and the linear equations stay sequential.
+    #[cfg(feature = "rayon")]
+    fn encode_in<RNG: CryptoRng + Rng>(
+        pool: Option<&rayon::ThreadPool>,
+        rng: &mut RNG,
+        points: &[(F, F)],
+        aux: (u64, u64, u64),
+        params: Self::Params,
+    ) -> Result<Vec<F>> {
+        use rayon::prelude::*;
+
+        let hashers = keyed_hashers::<H>(aux);
+        let rows = super::parallel::install(pool, || {
+            points
+                .par_iter()
+                .map(|&(x, _)| calc_row::<H, F>(x, &hashers, params))
+                .collect::<Vec<_>>()
+        });
+        encode_with_rows::<H, F, RNG>(rng, points, rows, aux, params)
+    }
 
-        // 3. Perform a DFS on $G_{h_1, h_2, X}$.
-        let (constraints, graph) = dfs_to_find_constraints(&graph, aux, params);
+    fn decode(p: &[F], x: F, aux: (u64, u64, u64), params: Self::Params) -> Result<F> {
+        let Row { i, j, bits } = calc_row::<H, F>(x, &keyed_hashers::<H>(aux), params);
+        let l1 = p[i];
+        let l2 = p[j];
+        let vec_r = &p[params.l_size..];
+        let inner_product = bits_inner_product(&bits, vec_r);
 
-        if constraints.len() > params.r_size {
-            bail!("too many constraints");
-        }
+        Ok(l1 + l2 + inner_product)
+    }
 
-        if constraints.len() > 0 {
-            // 4. Solve for variables R satisfying the constraints of system S.
-            let equations_w = gaussian_elimination(constraints).with_context(|| {
-                format!("error in gaussian_elimination at @{}:{}", file!(), line!())
-            })?;
-            let Some(equations) = equations_w else {
-                bail!("failed to solve linear equations");
-            };
+    fn prepare_decoder(p: &[F], aux: (u64, u64, u64), params: Self::Params) -> Self::Decoder {
+        let (vec_l, vec_r) = p.split_at(params.l_size);
+        PaxosDecoder {
+            hashers: keyed_hashers::<H>(aux),
+            vec_l: vec_l.to_vec(),
+            vec_r: vec_r.to_vec(),
+            r_size: params.r_size,
+        }
+    }
 
-            // 4'. Adjust vec_r by equations.
-            adjust_vec_r(&equations, &mut vec_r);
+    /// Find pairs of keys whose rows (two positions of $`L`$ and bits for $`R`$) are identical while their values differ.
+    fn find_collisions(
+        points: &[(F, F)],
+        aux: (u64, u64, u64),
+        params: Self::Params,
+    ) -> Vec<(F, F)> {
+        let hashers = keyed_hashers::<H>(aux);
+
+        let mut rows = HashMap::new();
+        let mut res = Vec::new();
+        for &(x, y) in points.iter() {
+            let Row { i, j, bits } = calc_row::<H, F>(x, &hashers, params);
+            let row = (i.min(j), i.max(j), bits);
+
+            match rows.get(&row) {
+                Some(&(x_dash, y_dash)) if y_dash != y => res.push((x_dash, x)),
+                Some(_) => {}
+                None => {
+                    rows.insert(row, (x, y));
+                }
+            }
         }
 
-        // 5. Perform another DFS on $G_{h_1, h_2, X}$ to compute $L$.
-        dfs_to_calc_vec_l(&graph, aux, params, &vec_r, &mut vec_l);
+        res
+    }
+}
 
-        // 6. Output D = L || R
-        let mut result = vec_l;
-        result.extend(vec_r);
+/// Decoder made by [PaxosSolver::prepare_decoder](Solver::prepare_decoder).
+///
+/// The hash keys are absorbed once, so each query only hashes itself.
+pub struct PaxosDecoder<F: FF, H: RowHash> {
+    hashers: [H::Keyed; 3],
+    vec_l: Vec<F>,
+    vec_r: Vec<F>,
+    r_size: usize,
+}
 
-        Ok(result)
-    }
+impl<F: FF, H: RowHash> Decoder<F> for PaxosDecoder<F, H> {
+    fn decode(&self, x: F) -> Result<F> {
+        let x = x.to_bytes();
+        let [h1, h2, h3] = &self.hashers;
 
-    fn decode(p: &[F], x: F, aux: (u64, u64, u64), params: Self::Params) -> Result<F> {
-        let (k1, k2, k3) = aux;
-        let PaxosSolverParams { l_size, r_size } = params;
+        let l_size = self.vec_l.len();
+        let l1 = self.vec_l[digest2index(&H::digest(h1, &x), l_size)];
+        let l2 = self.vec_l[digest2index(&H::digest(h2, &x), l_size)];
 
-        let i = hash2index(k1, x, l_size);
-        let j = hash2index(k2, x, l_size);
-        let l1 = p[i];
-        let l2 = p[j];
-        let vec_r = &p[l_size..];
-        let inner_product = calc_r_inner_product(x, vec_r, k3, r_size);
+        let mut inner_product = F::zero();
+        for (i, b) in digest2bits(&H::digest(h3, &x), self.r_size).enumerate() {
+            if b {
+                inner_product += self.vec_r[i];
+            }
+        }
 
         Ok(l1 + l2 + inner_product)
     }
@@ -333,11 +483,22 @@ use VisitStatus::*;
 #[derive(Debug)]
 pub(crate) struct Edge<F: FF> {
     point: (F, F),
+    // bits for R of the point if already hashed (See Row), or None to hash it in the DFS by the family of the DFS.
+    // Encoding always hashes the rows first, so None is only for the graphs made by tests.
+    bits: Option<Vec<bool>>,
     visit_status: VisitStatus,
     back_edge: bool,
 }
 
 impl<F: FF> Edge<F> {
+    // bits for R of the point.
+    fn bits<H: RowHash>(&self, k3: u64, r_size: usize) -> Vec<bool> {
+        match &self.bits {
+            Some(bits) => bits.clone(),
+            None => r::<H, F>(k3, self.point.0, r_size),
+        }
+    }
+
     pub(crate) fn is_visited(&self, mode: Mode) -> bool {
         match mode {
             FindConstraints => self.visit_status != NotVisited,
@@ -396,12 +557,76 @@ impl<F: FF> Node<F> {
     }
 }
 
-fn construct_cuckoo_graph<F: FF>(
+// encode `points` whose rows are already computed, in the order of `points`.
+fn encode_with_rows<H, F, RNG>(
+    rng: &mut RNG,
+    points: &[(F, F)],
+    rows: Vec<Row>,
+    aux: (u64, u64, u64),
+    params: PaxosSolverParams,
+) -> Result<Vec<F>>
+where
+    H: RowHash,
+    F: FF,
+    RNG: CryptoRng + Rng,
+    Standard: Distribution<F>,
+{
+    // 1. Construct the Cuckoo graph $G_{h_1, h_2, X}$ for $X = \{x_1, \ldots, x_n\}$.
+    let graph = construct_cuckoo_graph_from_rows(points, rows, params);
+
+    // 2. Initialize variables $L$ and $R$ and an initialliy empty set of linear constraints S.
+    let mut vec_l: Vec<F> = (0..params.l_size).map(|_| rng.gen()).collect::<Vec<_>>();
+    let mut vec_r: Vec<F> = (0..params.r_size).map(|_| rng.gen()).collect::<Vec<_>>();
+
+    // 3. Perform a DFS on $G_{h_1, h_2, X}$.
+    let (constraints, graph) = dfs_to_find_constraints::<H, F>(&graph, aux, params);
+
+    if constraints.len() > params.r_size {
+        bail!("too many constraints");
+    }
+
+    if constraints.len() > 0 {
+        // 4. Solve for variables R satisfying the constraints of system S.
+        let equations_w = gaussian_elimination(constraints).with_context(|| {
+            format!("error in gaussian_elimination at @{}:{}", file!(), line!())
+        })?;
+        let Some(equations) = equations_w else {
+            bail!("failed to solve linear equations");
+        };
+
+        // 4'. Adjust vec_r by equations.
+        adjust_vec_r(&equations, &mut vec_r);
+    }
+
+    // 5. Perform another DFS on $G_{h_1, h_2, X}$ to compute $L$.
+    dfs_to_calc_vec_l::<H, F>(&graph, aux, params, &vec_r, &mut vec_l);
+
+    // 6. Output D = L || R
+    let mut result = vec_l;
+    result.extend(vec_r);
+
+    Ok(result)
+}
+
+#[cfg(test)]
+fn construct_cuckoo_graph<H: RowHash, F: FF>(
     points: &[(F, F)],
     keys: (u64, u64, u64),
     params: PaxosSolverParams,
 ) -> Vec<Rc<RefCell<Node<F>>>> {
-    let (k1, k2, _) = keys;
+    let hashers = keyed_hashers::<H>(keys);
+    let rows = points
+        .iter()
+        .map(|&(x, _)| calc_row::<H, F>(x, &hashers, params))
+        .collect::<Vec<_>>();
+    construct_cuckoo_graph_from_rows(points, rows, params)
+}
+
+fn construct_cuckoo_graph_from_rows<F: FF>(
+    points: &[(F, F)],
+    rows: Vec<Row>,
+    params: PaxosSolverParams,
+) -> Vec<Rc<RefCell<Node<F>>>> {
     let m = params.l_size; // m = |L| = 2.01 * set.len()
     let r_size = params.r_size;
     let mut nodes: Vec<Option<Rc<RefCell<Node<F>>>>> = vec![None; m]; // random accessable table for nodes. using it for upsert_node.
@@ -422,16 +647,13 @@ fn construct_cuckoo_graph<F: FF>(
         }
     };
 
-    for &point in points {
-        let x = point.0;
-        let i = hash2index(k1, x, m);
-        let j = hash2index(k2, x, m);
-
+    for (&point, Row { i, j, bits }) in points.iter().zip(rows) {
         let node_i = upsert_node(i);
         let node_j = upsert_node(j);
 
         let edge = Rc::new(RefCell::new(Edge {
             point,
+            bits: Some(bits),
             visit_status: NotVisited,
             back_edge: false,
         }));
@@ -455,7 +677,7 @@ enum TofcRecRes<F: FF> {
     BackEdge(CP<F>),
 }
 
-fn dfs_to_find_constraints<F: FF>(
+fn dfs_to_find_constraints<H: RowHash, F: FF>(
     graph: &[Rc<RefCell<Node<F>>>],
     keys: (u64, u64, u64),
     params: PaxosSolverParams,
@@ -473,7 +695,7 @@ fn dfs_to_find_constraints<F: FF>(
         new_graph.push(Rc::clone(node));
 
         let total = CP::zero(r_size);
-        let _ = dfs_tofc_rec(Rc::clone(node), total, k3, r_size, &mut constraints);
+        let _ = dfs_tofc_rec::<H, F>(Rc::clone(node), total, k3, r_size, &mut constraints);
     }
 
     (constraints, new_graph)
@@ -498,7 +720,7 @@ n3.acc + (n4.acc + e4.cp)
 since same cps' xoring is 0.
 */
 
-fn dfs_tofc_rec<F: FF>(
+fn dfs_tofc_rec<H: RowHash, F: FF>(
     node: Rc<RefCell<Node<F>>>,
     total: CP<F>,
     k3: u64,
@@ -525,13 +747,12 @@ fn dfs_tofc_rec<F: FF>(
         next_edge.borrow_mut().visit_status = VisitedOnce;
 
         let cp = {
-            let x = next_edge.borrow().point.0;
-            let v = r(k3, x, r_size);
+            let v = next_edge.borrow().bits::<H>(k3, r_size);
             let f = next_edge.borrow().point.1;
             CP::new(v, f)
         };
         let next_total = total.add_other(&cp);
-        let res = dfs_tofc_rec(next_node, next_total.clone(), k3, r_size, result);
+        let res = dfs_tofc_rec::<H, F>(next_node, next_total.clone(), k3, r_size, result);
 
         if let BackEdge(cp) = res {
             next_edge.borrow_mut().back_edge = true;
@@ -560,7 +781,7 @@ fn adjust_vec_r<F: FF>(equations: &[(usize, Vec<bool>, F)], vec_r: &mut [F]) {
     }
 }
 
-fn dfs_to_calc_vec_l<F: FF>(
+fn dfs_to_calc_vec_l<H: RowHash, F: FF>(
     graph: &[Rc<RefCell<Node<F>>>],
     keys: (u64, u64, u64),
     params: PaxosSolverParams,
@@ -575,11 +796,11 @@ fn dfs_to_calc_vec_l<F: FF>(
             continue;
         }
 
-        dfs_tocvl_rec(Rc::clone(node), k3, r_size, vec_r, vec_l);
+        dfs_tocvl_rec::<H, F>(Rc::clone(node), k3, r_size, vec_r, vec_l);
     }
 }
 
-fn dfs_tocvl_rec<F: FF>(
+fn dfs_tocvl_rec<H: RowHash, F: FF>(
     node: Rc<RefCell<Node<F>>>,
     k3: u64,
     r_size: usize,
@@ -602,13 +823,12 @@ fn dfs_tocvl_rec<F: FF>(
         next_edge.borrow_mut().visit_status = VisitedTwice;
         let v = next_node.borrow().id;
 
-        let x = next_edge.borrow().point.0;
-        let inner_product = calc_r_inner_product(x, vec_r, k3, r_size);
+        let inner_product = bits_inner_product(&next_edge.borrow().bits::<H>(k3, r_size), vec_r);
         let y = next_edge.borrow().point.1;
 
         vec_l[v] = vec_l[u] + inner_product + y;
 
-        dfs_tocvl_rec(next_node, k3, r_size, vec_r, vec_l);
+        dfs_tocvl_rec::<H, F>(next_node, k3, r_size, vec_r, vec_l);
     }
 }
 
@@ -660,6 +880,31 @@ mod tests {
         assert_eq!(ys, reconstructed_ys);
     }
 
+    #[test]
+    fn test_paxos_prepared_decoder() {
+        let set = create_set::<F128b>(1000);
+
+        let mut rng = AesRng::new();
+        let aux = PaxosSolver::<F128b>::gen_aux(&mut rng).unwrap();
+        let params = PaxosSolver::<F128b>::calc_params(set.len());
+
+        let points = set
+            .iter()
+            .map(|x| (*x, hash_f(*x).unwrap()))
+            .collect::<Vec<_>>();
+
+        let p = PaxosSolver::encode(&mut rng, &points, aux, params).unwrap();
+        let decoder = PaxosSolver::prepare_decoder(&p, aux, params);
+
+        // encoded keys and keys not encoded
+        for x in set.iter().cloned().chain(create_set::<F128b>(100)) {
+            assert_eq!(
+                decoder.decode(x).unwrap(),
+                PaxosSolver::decode(&p, x, aux, params).unwrap()
+            );
+        }
+    }
+
     #[test]
     fn test_paxos_small() {
         for n in 1..=10 {
@@ -705,7 +950,7 @@ mod detail_tests {
             .map(|i| (F128b::from_u128(i as _), F128b::from_u128(i as _)))
             .collect::<Vec<(F128b, F128b)>>();
 
-        let graph = construct_cuckoo_graph(&points, aux, params);
+        let graph = construct_cuckoo_graph::<Sha256RowHash, _>(&points, aux, params);
 
         let (analyzed_result, _new_graph) = graph_analyze(&graph, verbose, verbose);
 
@@ -765,7 +1010,8 @@ mod detail_tests {
 
             let graph = func(params);
 
-            let (constraints, _new_graph) = dfs_to_find_constraints(&graph, aux, params);
+            let (constraints, _new_graph) =
+                dfs_to_find_constraints::<Sha256RowHash, _>(&graph, aux, params);
 
             println!("constraints ({}):", constraints.len());
 
@@ -800,7 +1046,8 @@ mod detail_tests {
 
             let graph = func(params, n);
 
-            let (constraints, _new_graph) = dfs_to_find_constraints(&graph, aux, params);
+            let (constraints, _new_graph) =
+                dfs_to_find_constraints::<Sha256RowHash, _>(&graph, aux, params);
 
             println!("constraints ({}):", constraints.len());
 
@@ -845,9 +1092,10 @@ mod detail_tests {
             .map(|i| (F128b::from_u128(i as _), F128b::from_u128(i as _)))
             .collect::<Vec<(F128b, F128b)>>();
 
-        let graph = construct_cuckoo_graph(&points, aux, params);
+        let graph = construct_cuckoo_graph::<Sha256RowHash, _>(&points, aux, params);
 
-        let (constraints, _new_graph) = dfs_to_find_constraints(&graph, aux, params);
+        let (constraints, _new_graph) =
+            dfs_to_find_constraints::<Sha256RowHash, _>(&graph, aux, params);
 
         println!("constraints ({}):", constraints.len());
 
@@ -915,7 +1163,8 @@ mod detail_tests {
 
             let graph = func(params);
 
-            let (constraints, _new_graph) = dfs_to_find_constraints(&graph, aux, params);
+            let (constraints, _new_graph) =
+                dfs_to_find_constraints::<Sha256RowHash, _>(&graph, aux, params);
 
             println!("constraints ({}):", constraints.len());
 
@@ -948,7 +1197,7 @@ mod detail_tests {
             }
 
             // 5. Perform another DFS on $G_{h_1, h_2, X}$ to compute $L$.
-            dfs_to_calc_vec_l(&graph, aux, params, &vec_r, &mut vec_l);
+            dfs_to_calc_vec_l::<Sha256RowHash, _>(&graph, aux, params, &vec_r, &mut vec_l);
 
             // 6. Output D = L || R
             let mut result = vec_l;
@@ -987,7 +1236,8 @@ mod detail_tests {
 
             let graph = func(params, n);
 
-            let (constraints, _new_graph) = dfs_to_find_constraints(&graph, aux, params);
+            let (constraints, _new_graph) =
+                dfs_to_find_constraints::<Sha256RowHash, _>(&graph, aux, params);
 
             println!("constraints ({}):", constraints.len());
 
@@ -1022,7 +1272,7 @@ mod detail_tests {
             }
 
             // 5. Perform another DFS on $G_{h_1, h_2, X}$ to compute $L$.
-            dfs_to_calc_vec_l(&graph, aux, params, &vec_r, &mut vec_l);
+            dfs_to_calc_vec_l::<Sha256RowHash, _>(&graph, aux, params, &vec_r, &mut vec_l);
 
             // 6. Output D = L || R
             let mut result = vec_l;
@@ -1058,9 +1308,10 @@ mod detail_tests {
             .map(|i| (F128b::from_u128(i as _), F128b::from_u128(i as _)))
             .collect::<Vec<(F128b, F128b)>>();
 
-        let graph = construct_cuckoo_graph(&points, aux, params);
+        let graph = construct_cuckoo_graph::<Sha256RowHash, _>(&points, aux, params);
 
-        let (constraints, _new_graph) = dfs_to_find_constraints(&graph, aux, params);
+        let (constraints, _new_graph) =
+            dfs_to_find_constraints::<Sha256RowHash, _>(&graph, aux, params);
 
         println!("constraints ({}):", constraints.len());
 
@@ -1088,7 +1339,7 @@ mod detail_tests {
         }
 
         // 5. Perform another DFS on $G_{h_1, h_2, X}$ to compute $L$.
-        dfs_to_calc_vec_l(&graph, aux, params, &vec_r, &mut vec_l);
+        dfs_to_calc_vec_l::<Sha256RowHash, _>(&graph, aux, params, &vec_r, &mut vec_l);
 
         // 6. Output D = L || R
         let mut result = vec_l;
@@ -1128,4 +1379,35 @@ mod detail_tests {
     fn test_dfs_to_calc_vec_l_2e20() {
         test_dfs_to_calc_vec_l_base(1 << 20, false);
     }
+
+    #[test]
+    fn test_paxos_find_collisions() {
+        let mut rng = AesRng::new();
+        let aux = PaxosSolver::<F128b>::gen_aux(&mut rng).unwrap();
+
+        // 20 keys can't have distinct rows when there are only 12 (= (3 choose 2 + 3) * 2^1) of them.
+        let params = PaxosSolverParams::new(3, 1);
+        let points = create_set::<F128b>(20)
+            .into_iter()
+            .map(|x| (x, rng.gen()))
+            .collect::<Vec<_>>();
+
+        let collisions = PaxosSolver::find_collisions(&points, aux, params);
+        assert!(!collisions.is_empty());
+
+        let hashers = keyed_hashers::<Sha256RowHash>(aux);
+        for (x1, x2) in collisions {
+            let row1 = calc_row::<Sha256RowHash, F128b>(x1, &hashers, params);
+            let row2 = calc_row::<Sha256RowHash, F128b>(x2, &hashers, params);
+            assert_eq!(
+                (row1.i.min(row1.j), row1.i.max(row1.j)),
+                (row2.i.min(row2.j), row2.i.max(row2.j))
+            );
+            assert_eq!(row1.bits, row2.bits);
+        }
+
+        // usual parameters have no collision.
+        let params = PaxosSolver::<F128b>::calc_params(points.len());
+        assert!(PaxosSolver::find_collisions(&points, aux, params).is_empty());
+    }
 }
@@ -0,0 +1,211 @@
+//! Keyed hash families of [PaxosSolver], which derive the row of each key: the two positions of $`L`$ and the bits for $`R`$.
+//!
+//! The family is a type parameter of [HashedPaxosSolver], and [PaxosSolver] uses [Sha256RowHash]. Each family gives a distinct [Solver::ID],
+//! so the handshake of the protocol fails when the parties use different families, and the saved tables and states of one
+//! family can't be loaded by another.
+//!
+//! | Family          | [Solver::ID] | [Solver::NAME]  |
+//! | --------------- | ------------ | --------------- |
+//! | [Sha256RowHash] | 1            | `Paxos`         |
+//! | [AesRowHash]    | 3            | `Paxos-AES`     |
+//! | [Blake3RowHash] | 4            | `Paxos-BLAKE3`  |
+//! | [SipRowHash]    | 5            | `Paxos-SipHash` |
+//!
+//! [Blake3RowHash] and [SipRowHash] are `okvs-hash` feature only.
+
+use super::*;
+use scuttlebutt::{Aes128, Block};
+use sha2::{Digest, Sha256};
+#[cfg(feature = "okvs-hash")]
+use siphasher::sip128::{Hasher128, SipHasher24};
+#[cfg(feature = "okvs-hash")]
+use std::hash::Hasher;
+
+/// Keyed hash family of [HashedPaxosSolver]. See the [module document](crate::solver::paxos::row_hash).
+///
+/// A digest has 32 bytes: 8 bytes for a position of $`L`$, or up to 256 bits for $`R`$.
+pub trait RowHash: 'static + Send + Sync {
+    /// [Solver::ID] of [HashedPaxosSolver] with this family.
+    const SOLVER_ID: u8;
+    /// [Solver::NAME] of [HashedPaxosSolver] with this family.
+    const SOLVER_NAME: &'static str;
+
+    /// Hasher which has already absorbed the key.
+    type Keyed: Clone + Send + Sync;
+
+    /// Absorb the key `k`, which is a part of the auxillary information of [HashedPaxosSolver].
+    fn keyed(k: u64) -> Self::Keyed;
+
+    /// Digest of `x` under the absorbed key.
+    fn digest(keyed: &Self::Keyed, x: &[u8]) -> [u8; 32];
+}
+
+/// SHA-256 of the key (big endian) followed by `x`. The family of [PaxosSolver].
+pub struct Sha256RowHash;
+
+impl RowHash for Sha256RowHash {
+    const SOLVER_ID: u8 = 1;
+    const SOLVER_NAME: &'static str = "Paxos";
+
+    type Keyed = Sha256;
+
+    fn keyed(k: u64) -> Sha256 {
+        let mut hasher = Sha256::new();
+        hasher.update(k.to_be_bytes());
+        hasher
+    }
+
+    fn digest(keyed: &Sha256, x: &[u8]) -> [u8; 32] {
+        let mut hasher = keyed.clone();
+        hasher.update(x);
+        hasher.finalize().into()
+    }
+}
+
+/// CBC-MAC of `x` (zero padded to blocks) by AES-128 keyed by the key, encrypted again with two tweaks for 32 bytes.
+///
+/// The zero padding is fine since all keys of a solver are of the same field and so of the same length.
+pub struct AesRowHash;
+
+impl RowHash for AesRowHash {
+    const SOLVER_ID: u8 = 3;
+    const SOLVER_NAME: &'static str = "Paxos-AES";
+
+    type Keyed = Aes128;
+
+    fn keyed(k: u64) -> Aes128 {
+        Aes128::new(Block::from(k as u128))
+    }
+
+    fn digest(keyed: &Aes128, x: &[u8]) -> [u8; 32] {
+        let mac = x.chunks(16).fold(Block::default(), |mac, chunk| {
+            let mut bytes = [0u8; 16];
+            bytes[..chunk.len()].copy_from_slice(chunk);
+            keyed.encrypt(mac ^ Block::from(u128::from_le_bytes(bytes)))
+        });
+
+        let mut res = [0u8; 32];
+        res[..16]
+            .copy_from_slice(&u128::from(keyed.encrypt(mac ^ Block::from(1u128))).to_le_bytes());
+        res[16..]
+            .copy_from_slice(&u128::from(keyed.encrypt(mac ^ Block::from(2u128))).to_le_bytes());
+        res
+    }
+}
+
+/// BLAKE3 in the keyed mode, whose 32 bytes key is the key (little endian) padded by zeros.
+#[cfg(feature = "okvs-hash")]
+pub struct Blake3RowHash;
+
+#[cfg(feature = "okvs-hash")]
+impl RowHash for Blake3RowHash {
+    const SOLVER_ID: u8 = 4;
+    const SOLVER_NAME: &'static str = "Paxos-BLAKE3";
+
+    type Keyed = blake3::Hasher;
+
+    fn keyed(k: u64) -> blake3::Hasher {
+        let mut key = [0u8; 32];
+        key[..8].copy_from_slice(&k.to_le_bytes());
+        blake3::Hasher::new_keyed(&key)
+    }
+
+    fn digest(keyed: &blake3::Hasher, x: &[u8]) -> [u8; 32] {
+        let mut hasher = keyed.clone();
+        hasher.update(x);
+        *hasher.finalize().as_bytes()
+    }
+}
+
+/// Two SipHash-2-4 with 128 bits output, keyed by `(k, 0)` and `(k, 1)` where `k` is the key.
+#[cfg(feature = "okvs-hash")]
+pub struct SipRowHash;
+
+#[cfg(feature = "okvs-hash")]
+impl RowHash for SipRowHash {
+    const SOLVER_ID: u8 = 5;
+    const SOLVER_NAME: &'static str = "Paxos-SipHash";
+
+    type Keyed = [SipHasher24; 2];
+
+    fn keyed(k: u64) -> [SipHasher24; 2] {
+        [
+            SipHasher24::new_with_keys(k, 0),
+            SipHasher24::new_with_keys(k, 1),
+        ]
+    }
+
+    fn digest(keyed: &[SipHasher24; 2], x: &[u8]) -> [u8; 32] {
+        let mut res = [0u8; 32];
+        for (hasher, out) in keyed.iter().zip(res.chunks_mut(16)) {
+            let mut hasher = *hasher;
+            hasher.write(x);
+            out.copy_from_slice(&hasher.finish128().as_bytes());
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::VandelmondeSolver;
+    use scuttlebutt::field::F128b;
+    use scuttlebutt::AesRng;
+
+    fn test_row_hash_base<H: RowHash>() {
+        let mut rng = AesRng::new();
+        let points = (0..1000)
+            .map(|_| (rng.gen::<F128b>(), rng.gen::<F128b>()))
+            .collect::<Vec<_>>();
+        let aux = HashedPaxosSolver::<F128b, H>::gen_aux(&mut rng).unwrap();
+        let params = HashedPaxosSolver::<F128b, H>::calc_params(points.len());
+
+        let p = HashedPaxosSolver::<F128b, H>::encode(&mut rng, &points, aux, params).unwrap();
+        let decoder = HashedPaxosSolver::<F128b, H>::prepare_decoder(&p, aux, params);
+        for &(x, y) in points.iter() {
+            assert_eq!(
+                HashedPaxosSolver::<F128b, H>::decode(&p, x, aux, params).unwrap(),
+                y
+            );
+            assert_eq!(decoder.decode(x).unwrap(), y);
+        }
+
+        // the digest depends on the key.
+        let x = rng.gen::<F128b>().to_bytes();
+        assert_ne!(H::digest(&H::keyed(1), &x), H::digest(&H::keyed(2), &x));
+    }
+
+    #[test]
+    fn test_row_hash_families() {
+        test_row_hash_base::<Sha256RowHash>();
+        test_row_hash_base::<AesRowHash>();
+        #[cfg(feature = "okvs-hash")]
+        test_row_hash_base::<Blake3RowHash>();
+        #[cfg(feature = "okvs-hash")]
+        test_row_hash_base::<SipRowHash>();
+    }
+
+    #[test]
+    fn test_row_hash_solver_ids() {
+        #[allow(unused_mut)]
+        let mut ids = vec![
+            VandelmondeSolver::<F128b>::ID,
+            HashedPaxosSolver::<F128b, Sha256RowHash>::ID,
+            HashedPaxosSolver::<F128b, AesRowHash>::ID,
+        ];
+        #[cfg(feature = "okvs-hash")]
+        ids.extend([
+            HashedPaxosSolver::<F128b, Blake3RowHash>::ID,
+            HashedPaxosSolver::<F128b, SipRowHash>::ID,
+        ]);
+        #[cfg(feature = "bench")]
+        ids.push(crate::solver::NoopSolver::<F128b>::ID);
+
+        let n = ids.len();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), n);
+        assert_eq!(PaxosSolver::<F128b>::ID, Sha256RowHash::SOLVER_ID);
+    }
+}
@@ -49,7 +49,7 @@
 //! The Vandelmonde solver uses a polynomial interpolation algorithm.
 
 use super::*;
-use anyhow::Error;
+use anyhow::{bail, Error};
 use rand::{CryptoRng, Rng};
 use scuttlebutt::field::{polynomial::Polynomial, FiniteField};
 use scuttlebutt::AbstractChannel;
@@ -68,11 +68,33 @@ impl SolverParams for VandelmondeSolverParams {
     fn code_length(&self) -> usize {
         self.0
     }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        (self.0 as u64).to_le_bytes().to_vec()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let Ok(b) = <[u8; 8]>::try_from(bytes) else {
+            bail!(
+                "invalid length of params bytes (={}) @{}:{}",
+                bytes.len(),
+                file!(),
+                line!()
+            );
+        };
+
+        Ok(VandelmondeSolverParams(u64::from_le_bytes(b) as usize))
+    }
 }
 
 impl<FF: FiniteField> Solver<FF> for VandelmondeSolver<FF> {
+    const ID: u8 = 0;
+    const NAME: &'static str = "Vandelmonde";
+
     type AuxInfo = ();
     type Params = VandelmondeSolverParams;
+    /// Evaluation of the polynomial has nothing to precompute.
+    type Decoder = StatelessDecoder<FF, Self>;
 
     fn gen_aux<RNG: CryptoRng + Rng>(_rng: &mut RNG) -> Result<Self::AuxInfo, Error> {
         Ok(())
@@ -93,6 +115,23 @@ impl<FF: FiniteField> Solver<FF> for VandelmondeSolver<FF> {
         Ok(())
     }
 
+    fn aux_to_bytes(_aux: Self::AuxInfo) -> Vec<u8> {
+        Vec::new()
+    }
+
+    fn aux_from_bytes(bytes: &[u8]) -> Result<Self::AuxInfo, Error> {
+        if !bytes.is_empty() {
+            bail!(
+                "invalid length of aux bytes (={}) @{}:{}",
+                bytes.len(),
+                file!(),
+                line!()
+            );
+        }
+
+        Ok(())
+    }
+
     fn calc_params(n: usize) -> VandelmondeSolverParams {
         VandelmondeSolverParams(n)
     }
@@ -128,6 +167,10 @@ impl<FF: FiniteField> Solver<FF> for VandelmondeSolver<FF> {
 
         Ok(sum)
     }
+
+    fn prepare_decoder(p: &[FF], aux: (), params: Self::Params) -> Self::Decoder {
+        StatelessDecoder::new(p, aux, params)
+    }
 }
 
 #[cfg(test)]
@@ -173,5 +216,13 @@ mod tests {
         let ys = points.iter().map(|(_, y)| *y).collect::<Vec<_>>();
 
         assert_eq!(ys, reconstructed_ys);
+
+        let decoder = VandelmondeSolver::prepare_decoder(&p, aux, params);
+        for x in set.iter() {
+            assert_eq!(
+                decoder.decode(*x).unwrap(),
+                VandelmondeSolver::decode(&p, *x, aux, params).unwrap()
+            );
+        }
     }
 }
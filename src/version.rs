@@ -0,0 +1,91 @@
+//! Version and build features of this library, for support and compatibility checks.
+
+use std::fmt;
+
+// optional features of Cargo.toml with whether each one is compiled in.
+const FEATURES: &[(&str, bool)] = &[
+    ("rayon", cfg!(feature = "rayon")),
+    ("tokio", cfg!(feature = "tokio")),
+    ("bench", cfg!(feature = "bench")),
+    ("okvs-hash", cfg!(feature = "okvs-hash")),
+];
+
+/// Version of this crate and its optional features compiled in. Made by [version_info].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VersionInfo {
+    /// Version of this crate. e.g. `0.1.0`
+    pub version: &'static str,
+    /// Names of the optional features compiled in, in the order of `Cargo.toml`.
+    pub features: Vec<&'static str>,
+}
+
+impl VersionInfo {
+    /// Whether the optional feature `name` is compiled in.
+    pub fn has_feature(&self, name: &str) -> bool {
+        self.features.contains(&name)
+    }
+}
+
+impl fmt::Display for VersionInfo {
+    /// e.g. `preprocessing_mpsi_with_vole 0.1.0 (features: rayon)`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let features = if self.features.is_empty() {
+            "none".to_string()
+        } else {
+            self.features.join(", ")
+        };
+        write!(
+            f,
+            "{} {} (features: {})",
+            env!("CARGO_PKG_NAME"),
+            self.version,
+            features
+        )
+    }
+}
+
+/// Get the version of this crate and the optional features compiled in.
+///
+/// The optional features are `rayon` (parallel paths of solvers in `solver::parallel`),
+/// `tokio` (cancel-safe async OPPRF in `preprocessed::async_opprf`),
+/// `bench` (`solver::NoopSolver` for OPRF-only benchmarking)
+/// and `okvs-hash` (the BLAKE3 and SipHash row hash families in `solver::paxos::row_hash`).
+pub fn version_info() -> VersionInfo {
+    VersionInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        features: FEATURES
+            .iter()
+            .filter(|(_, enabled)| *enabled)
+            .map(|(name, _)| *name)
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_info() {
+        let info = version_info();
+
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(info.has_feature("rayon"), cfg!(feature = "rayon"));
+        assert!(!info.has_feature("compression"));
+
+        let s = info.to_string();
+        assert!(s.starts_with("preprocessing_mpsi_with_vole "), "{}", s);
+        let enabled = FEATURES
+            .iter()
+            .filter(|(_, on)| *on)
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>();
+        assert_eq!(info.features, enabled);
+        let expected = if enabled.is_empty() {
+            "(features: none)".to_string()
+        } else {
+            format!("(features: {})", enabled.join(", "))
+        };
+        assert!(s.ends_with(&expected), "{}", s);
+    }
+}
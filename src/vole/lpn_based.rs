@@ -89,6 +89,9 @@
 //!
 //! So communications will take longer time than you expect.
 //! (e.g. You specify the length as 100 and specify `LPN_SETUP_SMALL` and `LPN_EXTEND_SMALL`, then the length of the internal vector is larger than 100.)
+//!
+//! Both setup and extension of [ocelot::svole::wykw] are interactive, so there is no CPU-bound work independent of the peer to overlap with channel reads in this wrapper.
+//! The VOLE sharings with different peers are independent, so `precomp` of [psi](crate::preprocessed::psi) runs the one with each peer on its own thread.
 use super::{VoleShareForReceiver, VoleShareForSender};
 use anyhow::{bail, Context, Error};
 use ocelot::svole::wykw::Receiver as SVoleReceiverStruct;
@@ -104,6 +107,20 @@ use scuttlebutt::channel::AbstractChannel;
 use scuttlebutt::field::FiniteField as FF;
 use std::marker::PhantomData;
 
+// name of the predefined parameter set which `setup_param` is. Parameters are compared by their `Debug` output.
+fn lpn_size_name(setup_param: LpnParams) -> &'static str {
+    let p = format!("{:?}", setup_param);
+    if p == format!("{:?}", LPN_SETUP_SMALL) {
+        "small"
+    } else if p == format!("{:?}", LPN_SETUP_MEDIUM) {
+        "medium"
+    } else if p == format!("{:?}", LPN_SETUP_LARGE) {
+        "large"
+    } else {
+        "custom"
+    }
+}
+
 /// VOLE sender based on LPN.
 ///
 /// Please look the parent document ( [crate::vole::lpn_based] ) for usage example.
@@ -155,9 +172,13 @@ impl<F: FF> VoleShareForSender<F> for LPNVoleSender<F> {
             );
         }
 
-        let b_vec = out[..m].to_vec();
+        out.truncate(m);
 
-        Ok((vole.delta(), b_vec))
+        Ok((vole.delta(), out))
+    }
+
+    fn describe(&self) -> String {
+        format!("LPN({})", lpn_size_name(self.setup_param))
     }
 }
 
@@ -202,30 +223,26 @@ impl<F: FF> VoleShareForReceiver<F> for LPNVoleReceiver<F> {
         vole.send(channel, rng, &mut out)
             .with_context(|| format!("@{}:{}", file!(), line!()))?;
 
-        let mut a_vec = Vec::with_capacity(out.len());
-        let mut c_vec = Vec::with_capacity(out.len());
-
-        for (a, c) in out {
-            a_vec.push(a.into());
-            c_vec.push(c);
-        }
-
-        if a_vec.len() < m || c_vec.len() < m {
+        if out.len() < m {
             bail!(
-                "TOO BIG M!\nvec.len() (={} or {}) < m (={}) @ {}:{}",
-                a_vec.len(),
-                c_vec.len(),
+                "TOO BIG M!\nout.len() (={}) < m (={}) @ {}:{}",
+                out.len(),
                 m,
                 file!(),
                 line!()
             );
         }
 
-        let a_vec = a_vec[..m].to_vec();
-        let c_vec = c_vec[..m].to_vec();
+        // only first m elements are converted. the rest are dropped.
+        let (a_vec, c_vec): (Vec<F>, Vec<F>) =
+            out.into_iter().take(m).map(|(a, c)| (a.into(), c)).unzip();
 
         Ok((a_vec, c_vec))
     }
+
+    fn describe(&self) -> String {
+        format!("LPN({})", lpn_size_name(self.setup_param))
+    }
 }
 
 #[cfg(test)]
@@ -233,6 +250,7 @@ mod tests {
     use crate::channel_utils::{read_vec_f, write_vec_f};
 
     use super::*;
+    use crate::vole::verify_correlation;
     use scuttlebutt::{field::F128b, AesRng, Channel};
     use std::io::{BufReader, BufWriter};
     use std::os::unix::net::UnixStream;
@@ -282,6 +300,15 @@ mod tests {
 
         dbg!(b_vec.len());
 
+        assert!(verify_correlation(&b_vec, (&a_vec, &c_vec), delta));
+        if vole_size > 0 {
+            // a perturbed value breaks the relation.
+            let mut perturbed = c_vec.clone();
+            perturbed[vole_size / 2] += F128b::one();
+            assert!(!verify_correlation(&b_vec, (&a_vec, &perturbed), delta));
+            assert!(!verify_correlation(&b_vec[1..], (&a_vec, &c_vec), delta));
+        }
+
         for ((a, b), c) in a_vec
             .into_iter()
             .zip(b_vec.into_iter())
@@ -0,0 +1,181 @@
+//! Runtime selection of LPN parameter tiers for [LPNVoleSender](super::LPNVoleSender) and [LPNVoleReceiver](super::LPNVoleReceiver).
+//!
+//! One extension of [ocelot::svole::wykw] outputs a fixed number of VOLE correlations for each tier (small, medium and large).
+//! [measure_capacity] measures it once per field and tier by running the setup and one extension locally, and caches the result.
+//! [select_tier] picks the smallest tier whose capacity exceeds the required length with a safety margin.
+
+use super::lpn_based::{
+    LpnParams, LPN_EXTEND_LARGE, LPN_EXTEND_MEDIUM, LPN_EXTEND_SMALL, LPN_SETUP_LARGE,
+    LPN_SETUP_MEDIUM, LPN_SETUP_SMALL,
+};
+use crate::channel_utils::channel_from;
+use anyhow::{bail, Context, Error};
+use clap::ValueEnum;
+use ocelot::svole::wykw::Receiver as SVoleReceiverStruct;
+use ocelot::svole::wykw::Sender as SVoleSenderStruct;
+use ocelot::svole::SVoleReceiver as _;
+use ocelot::svole::SVoleSender as _;
+use scuttlebutt::field::FiniteField as FF;
+use scuttlebutt::AesRng;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::os::unix::net::UnixStream;
+use std::sync::{Mutex, OnceLock};
+
+/// Tiers of LPN parameters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ValueEnum)]
+pub enum LpnTier {
+    /// `LPN_SETUP_SMALL` and `LPN_EXTEND_SMALL`.
+    Small,
+    /// `LPN_SETUP_MEDIUM` and `LPN_EXTEND_MEDIUM`.
+    Medium,
+    /// `LPN_SETUP_LARGE` and `LPN_EXTEND_LARGE`.
+    Large,
+}
+
+impl LpnTier {
+    /// All tiers in ascending order of capacity.
+    pub const ALL: [LpnTier; 3] = [LpnTier::Small, LpnTier::Medium, LpnTier::Large];
+
+    /// Setup and extension parameters of the tier.
+    pub fn params(self) -> (LpnParams, LpnParams) {
+        match self {
+            LpnTier::Small => (LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+            LpnTier::Medium => (LPN_SETUP_MEDIUM, LPN_EXTEND_MEDIUM),
+            LpnTier::Large => (LPN_SETUP_LARGE, LPN_EXTEND_LARGE),
+        }
+    }
+}
+
+impl Display for LpnTier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LpnTier::Small => write!(f, "small"),
+            LpnTier::Medium => write!(f, "medium"),
+            LpnTier::Large => write!(f, "large"),
+        }
+    }
+}
+
+type CapacityCache = Mutex<HashMap<(&'static str, LpnTier), usize>>;
+
+fn capacity_cache() -> &'static CapacityCache {
+    static CACHE: OnceLock<CapacityCache> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// The number of VOLE correlations over `F` output by one extension of `tier`.
+///
+/// The first call for each field and tier runs the setup and one extension locally, so it takes as long as one VOLE sharing of the tier.
+pub fn measure_capacity<F: FF>(tier: LpnTier) -> Result<usize, Error> {
+    let key = (std::any::type_name::<F>(), tier);
+    if let Some(&capacity) = capacity_cache().lock().unwrap().get(&key) {
+        return Ok(capacity);
+    }
+
+    let (setup_param, extend_param) = tier.params();
+    let (s, r) = UnixStream::pair().with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+    let handle = std::thread::spawn(move || -> Result<(), Error> {
+        let mut rng = AesRng::new();
+        let mut channel = channel_from(s)?;
+        let mut vole = SVoleReceiverStruct::init(&mut channel, &mut rng, setup_param, extend_param)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+        let mut out: Vec<F> = Vec::new();
+        vole.receive(&mut channel, &mut rng, &mut out)
+            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+        Ok(())
+    });
+
+    let mut rng = AesRng::new();
+    let mut channel = channel_from(r)?;
+    let mut vole = SVoleSenderStruct::init(&mut channel, &mut rng, setup_param, extend_param)
+        .with_context(|| format!("@{}:{}", file!(), line!()))?;
+    let mut out: Vec<(F::PrimeField, F)> = Vec::new();
+    vole.send(&mut channel, &mut rng, &mut out)
+        .with_context(|| format!("@{}:{}", file!(), line!()))?;
+
+    handle
+        .join()
+        .map_err(|_| anyhow::anyhow!("calibration thread panicked. @{}:{}", file!(), line!()))??;
+
+    let capacity = out.len();
+    capacity_cache().lock().unwrap().insert(key, capacity);
+
+    Ok(capacity)
+}
+
+/// Select the smallest tier whose capacity is at least $`m \cdot (1 + \mathrm{margin})`$.
+///
+/// `m` is the required length of VOLE, i.e. `code_length()` of the solver parameters.
+pub fn select_tier<F: FF>(m: usize, safety_margin: f64) -> Result<LpnTier, Error> {
+    if safety_margin.is_nan() || safety_margin < 0.0 {
+        bail!(
+            "safety_margin (={}) must be non-negative. @{}:{}",
+            safety_margin,
+            file!(),
+            line!()
+        );
+    }
+
+    let required = (m as f64 * (1.0 + safety_margin)).ceil() as usize;
+    for tier in LpnTier::ALL {
+        let capacity =
+            measure_capacity::<F>(tier).with_context(|| format!("@{}:{}", file!(), line!()))?;
+        if capacity >= required {
+            return Ok(tier);
+        }
+    }
+
+    bail!(
+        "no LPN tier has capacity for {} (= {} with margin {}). @{}:{}",
+        m,
+        required,
+        safety_margin,
+        file!(),
+        line!()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vole::{LPNVoleReceiver, LPNVoleSender, VoleShareForReceiver, VoleShareForSender};
+    use scuttlebutt::field::F128b;
+
+    #[test]
+    fn test_select_tier_near_old_boundary() {
+        let small = measure_capacity::<F128b>(LpnTier::Small).unwrap();
+        // cached
+        assert_eq!(measure_capacity::<F128b>(LpnTier::Small).unwrap(), small);
+
+        for m in [(1 << 17) - 1, 1 << 17, (1 << 17) + 1] {
+            let tier = select_tier::<F128b>(m, 0.1).unwrap();
+            let capacity = measure_capacity::<F128b>(tier).unwrap();
+            assert!(capacity as f64 >= m as f64 * 1.1);
+            for smaller in LpnTier::ALL.into_iter().filter(|t| *t < tier) {
+                assert!((measure_capacity::<F128b>(smaller).unwrap() as f64) < m as f64 * 1.1);
+            }
+        }
+
+        // the chosen tier can actually share VOLE of the length.
+        let m = (1 << 17) + 1;
+        let (setup_param, extend_param) = select_tier::<F128b>(m, 0.1).unwrap().params();
+        let (s, r) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let mut channel = channel_from(s).unwrap();
+            let mut vole_sender = LPNVoleSender::<F128b>::new(setup_param, extend_param);
+            let (_, b_vec) = vole_sender.receive(&mut channel, &mut rng, m).unwrap();
+            b_vec.len()
+        });
+
+        let mut rng = AesRng::new();
+        let mut channel = channel_from(r).unwrap();
+        let mut vole_receiver = LPNVoleReceiver::<F128b>::new(setup_param, extend_param);
+        let (a_vec, _) = vole_receiver.receive(&mut channel, &mut rng, m).unwrap();
+
+        assert_eq!(a_vec.len(), m);
+        assert_eq!(handle.join().unwrap(), m);
+    }
+}
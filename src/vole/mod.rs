@@ -28,6 +28,8 @@ use scuttlebutt::field::FiniteField as FF;
 
 pub mod lpn_based;
 pub use lpn_based::{LPNVoleReceiver, LPNVoleSender};
+pub mod lpn_tier;
+pub use lpn_tier::{select_tier, LpnTier};
 pub mod ot_based;
 pub use ot_based::{OtVoleReceiver, OtVoleSender};
 
@@ -40,6 +42,11 @@ pub trait VoleShareForSender<F: FF>: Clone + Copy {
         rng: &mut RNG,
         m: usize,
     ) -> Result<(F, Vec<F>), Error>;
+
+    /// Human readable description of the VOLE. e.g. `LPN(medium)`
+    fn describe(&self) -> String {
+        type_name_tail::<Self>().to_string()
+    }
 }
 
 /// Trait for VOLE receiver.
@@ -51,4 +58,32 @@ pub trait VoleShareForReceiver<F: FF>: Clone + Copy {
         rng: &mut RNG,
         m: usize,
     ) -> Result<(Vec<F>, Vec<F>), Error>;
+
+    /// Human readable description of the VOLE. e.g. `LPN(medium)`
+    fn describe(&self) -> String {
+        type_name_tail::<Self>().to_string()
+    }
+}
+
+/// Check the VOLE relation $`\bm{C} = \bm{A} \Delta + \bm{B}`$ of a correlation produced by a VOLE backend.
+///
+/// `sender_out` is $`\bm{B}`$ of [VoleShareForSender::receive] (with `delta`), and `receiver_out` is $`(\bm{A}, \bm{C})`$ of [VoleShareForReceiver::receive].
+/// Vectors of different lengths never satisfy it. It is meant for testing backends, since no party holds all of them in the protocol.
+pub fn verify_correlation<F: FF>(sender_out: &[F], receiver_out: (&[F], &[F]), delta: F) -> bool {
+    let (a_vec, c_vec) = receiver_out;
+    if a_vec.len() != sender_out.len() || c_vec.len() != sender_out.len() {
+        return false;
+    }
+
+    a_vec
+        .iter()
+        .zip(sender_out.iter())
+        .zip(c_vec.iter())
+        .all(|((&a, &b), &c)| a * delta + b == c)
+}
+
+fn type_name_tail<T: ?Sized>() -> &'static str {
+    let name = std::any::type_name::<T>();
+    let name = name.split('<').next().unwrap_or(name);
+    name.rsplit("::").next().unwrap_or(name)
 }
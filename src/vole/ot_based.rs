@@ -82,7 +82,7 @@
 //!
 use super::{VoleShareForReceiver, VoleShareForSender};
 use crate::set_utils::FromU128;
-use anyhow::{Context, Error, Result};
+use anyhow::{bail, Context, Error, Result};
 use generic_array::GenericArray;
 use itertools::Itertools;
 use ocelot::ot::{Receiver as OtReceiver, Sender as OtSender};
@@ -94,6 +94,48 @@ use scuttlebutt::serialization::CanonicalSerialize;
 use scuttlebutt::Block;
 use std::marker::PhantomData;
 
+/// Number of base OTs of one OT extension instance of ocelot, which is also the computational security parameter in bits.
+///
+/// A base-OT count given to [OtVoleSender::with_base_ots] / [OtVoleReceiver::with_base_ots] must be a positive multiple of it.
+/// With this count (the default of `new`), the messages are the same as a plain OT extension, so it doesn't change the protocol.
+pub const BASE_OTS_PER_INSTANCE: usize = 128;
+
+fn check_base_ots(base_ots: usize) -> Result<()> {
+    if base_ots < BASE_OTS_PER_INSTANCE {
+        bail!(
+            "base OT count (={}) is below the security floor of {}. @{}:{}",
+            base_ots,
+            BASE_OTS_PER_INSTANCE,
+            file!(),
+            line!()
+        );
+    }
+    if base_ots % BASE_OTS_PER_INSTANCE != 0 {
+        bail!(
+            "base OT count (={}) must be a multiple of {}. @{}:{}",
+            base_ots,
+            BASE_OTS_PER_INSTANCE,
+            file!(),
+            line!()
+        );
+    }
+    Ok(())
+}
+
+// split `len` extended OTs into the chunks of the OT extension instances.
+fn chunk_len(len: usize, base_ots: usize) -> usize {
+    let instances = base_ots / BASE_OTS_PER_INSTANCE;
+    len.div_ceil(instances).max(1)
+}
+
+fn describe_base_ots(base_ots: usize) -> String {
+    if base_ots == BASE_OTS_PER_INSTANCE {
+        "OT".to_string()
+    } else {
+        format!("OT(base_ots={})", base_ots)
+    }
+}
+
 fn bytes2block(bytes: &[u8]) -> Block {
     let mut b = [0u8; 16];
     b.copy_from_slice(bytes);
@@ -105,11 +147,15 @@ fn bytes2block(bytes: &[u8]) -> Block {
 /// Please set the field `F_LENGTH` to the byte length of type `F`. e.g. `F_LENGTH = 128` for `F128b`.
 ///
 /// Please look the parent document ( [crate::vole::ot_based] ) for usage example.
-pub struct OtVoleSender<F, const F_LENGTH: usize, OT>(PhantomData<(F, OT)>)
+pub struct OtVoleSender<F, const F_LENGTH: usize, OT>
 where
     F: FF + FromU128 + CanonicalSerialize,
     OT: OtSender,
-    Standard: Distribution<F>;
+    Standard: Distribution<F>,
+{
+    base_ots: usize,
+    _p: PhantomData<(F, OT)>,
+}
 
 impl<F, const F_LENGTH: usize, OT> Clone for OtVoleSender<F, F_LENGTH, OT>
 where
@@ -118,7 +164,10 @@ where
     Standard: Distribution<F>,
 {
     fn clone(&self) -> Self {
-        Self(PhantomData)
+        Self {
+            base_ots: self.base_ots,
+            _p: PhantomData,
+        }
     }
 }
 impl<F, const F_LENGTH: usize, OT> Copy for OtVoleSender<F, F_LENGTH, OT>
@@ -135,9 +184,33 @@ where
     OT: OtSender,
     Standard: Distribution<F>,
 {
-    /// Create new OT VOLE sender.
+    /// Create new OT VOLE sender with [BASE_OTS_PER_INSTANCE] base OTs.
     pub fn new() -> Self {
-        Self(PhantomData)
+        Self {
+            base_ots: BASE_OTS_PER_INSTANCE,
+            _p: PhantomData,
+        }
+    }
+
+    /// Create new OT VOLE sender with `base_ots` base OTs in total.
+    ///
+    /// The extended OTs are split among `base_ots / BASE_OTS_PER_INSTANCE` independent OT extension instances, each with its own base OTs.
+    /// It doesn't raise the security level of one OT, which is fixed by [BASE_OTS_PER_INSTANCE]. It limits how many OTs are extended from one set of base OTs
+    /// and the size of the bit matrix transposed by one instance, at the cost of one more base OT setup per instance.
+    ///
+    /// `base_ots` must be a positive multiple of [BASE_OTS_PER_INSTANCE], and the receiver must use the same count.
+    /// A non-default count is checked against the peer by one more message before the OTs, so a peer using `new` can't detect the mismatch.
+    pub fn with_base_ots(base_ots: usize) -> Result<Self> {
+        check_base_ots(base_ots)?;
+        Ok(Self {
+            base_ots,
+            _p: PhantomData,
+        })
+    }
+
+    /// Number of base OTs in total.
+    pub fn base_ots(&self) -> usize {
+        self.base_ots
     }
 }
 
@@ -178,12 +251,30 @@ where
             b_vec.push(b);
         }
 
-        let mut ot = OT::init(channel, rng).with_context(|| format!("@{}:{}", file!(), line!()))?;
-        ot.send(channel, &inputs, rng)
-            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+        if self.base_ots == BASE_OTS_PER_INSTANCE {
+            let mut ot =
+                OT::init(channel, rng).with_context(|| format!("@{}:{}", file!(), line!()))?;
+            ot.send(channel, &inputs, rng)
+                .with_context(|| format!("@{}:{}", file!(), line!()))?;
+        } else {
+            // the receiver checks that both sides split the OTs in the same way.
+            channel.write_u64(self.base_ots as u64)?;
+            channel.flush()?;
+
+            for chunk in inputs.chunks(chunk_len(inputs.len(), self.base_ots)) {
+                let mut ot =
+                    OT::init(channel, rng).with_context(|| format!("@{}:{}", file!(), line!()))?;
+                ot.send(channel, chunk, rng)
+                    .with_context(|| format!("@{}:{}", file!(), line!()))?;
+            }
+        }
 
         Ok((delta, b_vec))
     }
+
+    fn describe(&self) -> String {
+        describe_base_ots(self.base_ots)
+    }
 }
 
 /// VOLE receiver based on OT.
@@ -191,11 +282,15 @@ where
 /// Please set the field `F_LENGTH` to the byte length of type `F`. e.g. `F_LENGTH = 128` for `F128b`.
 ///
 /// Please look the parent document ( [crate::vole::ot_based] ) for usage example.
-pub struct OtVoleReceiver<F, const F_LENGTH: usize, OT>(PhantomData<(F, OT)>)
+pub struct OtVoleReceiver<F, const F_LENGTH: usize, OT>
 where
     F: FF + FromU128 + CanonicalSerialize,
     OT: OtReceiver,
-    Standard: Distribution<F>;
+    Standard: Distribution<F>,
+{
+    base_ots: usize,
+    _p: PhantomData<(F, OT)>,
+}
 
 impl<F, const F_LENGTH: usize, OT> Clone for OtVoleReceiver<F, F_LENGTH, OT>
 where
@@ -204,7 +299,10 @@ where
     Standard: Distribution<F>,
 {
     fn clone(&self) -> Self {
-        Self(PhantomData)
+        Self {
+            base_ots: self.base_ots,
+            _p: PhantomData,
+        }
     }
 }
 
@@ -222,9 +320,33 @@ where
     OT: OtReceiver,
     Standard: Distribution<F>,
 {
-    /// Create new OT VOLE receiver.
+    /// Create new OT VOLE receiver with [BASE_OTS_PER_INSTANCE] base OTs.
     pub fn new() -> Self {
-        Self(PhantomData)
+        Self {
+            base_ots: BASE_OTS_PER_INSTANCE,
+            _p: PhantomData,
+        }
+    }
+
+    /// Create new OT VOLE receiver with `base_ots` base OTs in total.
+    ///
+    /// The extended OTs are split among `base_ots / BASE_OTS_PER_INSTANCE` independent OT extension instances, each with its own base OTs.
+    /// It doesn't raise the security level of one OT, which is fixed by [BASE_OTS_PER_INSTANCE]. It limits how many OTs are extended from one set of base OTs
+    /// and the size of the bit matrix transposed by one instance, at the cost of one more base OT setup per instance.
+    ///
+    /// `base_ots` must be a positive multiple of [BASE_OTS_PER_INSTANCE], and the sender must use the same count.
+    /// A non-default count is checked against the peer by one more message before the OTs, so a peer using `new` can't detect the mismatch.
+    pub fn with_base_ots(base_ots: usize) -> Result<Self> {
+        check_base_ots(base_ots)?;
+        Ok(Self {
+            base_ots,
+            _p: PhantomData,
+        })
+    }
+
+    /// Number of base OTs in total.
+    pub fn base_ots(&self) -> usize {
+        self.base_ots
     }
 }
 
@@ -259,10 +381,34 @@ where
             a_vec.push(F::from_u128(a));
         }
 
-        let mut ot = OT::init(channel, rng).with_context(|| format!("@{}:{}", file!(), line!()))?;
-        let rhos = ot
-            .receive(channel, &inputs, rng)
-            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+        let rhos = if self.base_ots == BASE_OTS_PER_INSTANCE {
+            let mut ot =
+                OT::init(channel, rng).with_context(|| format!("@{}:{}", file!(), line!()))?;
+            ot.receive(channel, &inputs, rng)
+                .with_context(|| format!("@{}:{}", file!(), line!()))?
+        } else {
+            let theirs = channel.read_u64()? as usize;
+            if theirs != self.base_ots {
+                bail!(
+                    "the sender uses {} base OTs, but ours is {}. @{}:{}",
+                    theirs,
+                    self.base_ots,
+                    file!(),
+                    line!()
+                );
+            }
+
+            let mut rhos = Vec::with_capacity(inputs.len());
+            for chunk in inputs.chunks(chunk_len(inputs.len(), self.base_ots)) {
+                let mut ot =
+                    OT::init(channel, rng).with_context(|| format!("@{}:{}", file!(), line!()))?;
+                rhos.extend(
+                    ot.receive(channel, chunk, rng)
+                        .with_context(|| format!("@{}:{}", file!(), line!()))?,
+                );
+            }
+            rhos
+        };
 
         let c_vec = rhos
             .into_iter()
@@ -292,6 +438,10 @@ where
 
         Ok((a_vec, c_vec))
     }
+
+    fn describe(&self) -> String {
+        describe_base_ots(self.base_ots)
+    }
 }
 
 #[cfg(test)]
@@ -305,6 +455,10 @@ mod tests {
     use std::os::unix::net::UnixStream;
 
     fn test_vole_share_base(vole_size: usize) {
+        test_vole_share_with_base_ots(vole_size, BASE_OTS_PER_INSTANCE);
+    }
+
+    fn test_vole_share_with_base_ots(vole_size: usize, base_ots: usize) {
         let (sender, receiver) = UnixStream::pair().unwrap();
         let handle = std::thread::spawn(move || {
             let mut rng = AesRng::new();
@@ -312,7 +466,8 @@ mod tests {
             let writer = BufWriter::new(sender);
             let mut channel = Channel::new(reader, writer);
 
-            let mut vole_sender = OtVoleSender::<F128b, 128, OtSender>::new();
+            let mut vole_sender =
+                OtVoleSender::<F128b, 128, OtSender>::with_base_ots(base_ots).unwrap();
             let (delta, out) = vole_sender
                 .receive(&mut channel, &mut rng, vole_size)
                 .with_context(|| format!("@{}:{}", file!(), line!()))
@@ -333,11 +488,13 @@ mod tests {
         let writer = BufWriter::new(receiver);
         let mut channel = Channel::new(reader, writer);
 
-        let mut vole_receiver = OtVoleReceiver::<F128b, 128, OtReceiver>::new();
+        let mut vole_receiver =
+            OtVoleReceiver::<F128b, 128, OtReceiver>::with_base_ots(base_ots).unwrap();
         let (a_vec, c_vec) = vole_receiver
             .receive(&mut channel, &mut rng, vole_size)
             .with_context(|| format!("@{}:{}", file!(), line!()))
             .unwrap();
+        assert_eq!(a_vec.len(), vole_size);
 
         let delta: F128b = channel.read_serializable().unwrap();
 
@@ -363,6 +520,21 @@ mod tests {
         test_vole_share_base(100);
     }
 
+    #[test]
+    fn test_vole_share_custom_base_ots() {
+        test_vole_share_with_base_ots(100, 3 * BASE_OTS_PER_INSTANCE);
+
+        let vole_sender = OtVoleSender::<F128b, 128, OtSender>::with_base_ots(256).unwrap();
+        assert_eq!(vole_sender.base_ots(), 256);
+        assert_eq!(vole_sender.describe(), "OT(base_ots=256)");
+        assert_eq!(OtVoleSender::<F128b, 128, OtSender>::new().describe(), "OT");
+
+        // below the security floor, or not a whole number of instances.
+        assert!(OtVoleSender::<F128b, 128, OtSender>::with_base_ots(64).is_err());
+        assert!(OtVoleReceiver::<F128b, 128, OtReceiver>::with_base_ots(0).is_err());
+        assert!(OtVoleReceiver::<F128b, 128, OtReceiver>::with_base_ots(200).is_err());
+    }
+
     #[test]
     fn test_vole_share_middle() {
         for e in 1..=17 {